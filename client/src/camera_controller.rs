@@ -66,9 +66,10 @@ impl CameraController {
         }
     }
 
+    // several mouse events can arrive between two simulation steps, so they add up
     pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
-        self.rotate_horizontal = mouse_dx as f32;
-        self.rotate_vertical = mouse_dy as f32;
+        self.rotate_horizontal += mouse_dx as f32;
+        self.rotate_vertical += mouse_dy as f32;
     }
 
     pub fn update_camera(&mut self, camera: &mut Camera, _world: &mut World, cam_sensitivity: f32, dt: f32) {
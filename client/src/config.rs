@@ -0,0 +1,57 @@
+use std::path::PathBuf;
+
+use common::settings::Settings;
+
+const APP_DIRECTORY: &str = "willekeurig";
+const SETTINGS_FILE: &str = "settings.toml";
+
+/// Where the user's settings are kept, a `willekeurig` folder in the platform's config directory.
+/// None if the platform doesn't have one.
+pub fn get_config_directory() -> Option<PathBuf> {
+    dirs::config_dir().map(|directory| directory.join(APP_DIRECTORY))
+}
+
+pub fn get_settings_path() -> Option<PathBuf> {
+    get_config_directory().map(|directory| directory.join(SETTINGS_FILE))
+}
+
+/// The saved settings, or the defaults if they can't be read. Problems are logged rather than returned,
+/// a broken settings file shouldn't keep the game from starting.
+pub fn load_settings() -> Settings {
+    let path = match get_settings_path() {
+        Some(path) => path,
+        None => {
+            eprintln!("[LOG] Using the default settings, there's no config directory to load them from");
+            return Settings::default();
+        }
+    };
+
+    match Settings::load(&path) {
+        Ok((settings, warnings)) => {
+            for warning in warnings {
+                eprintln!("[LOG] Ignoring part of {}: {}", path.display(), warning);
+            }
+
+            settings
+        },
+        Err(err) => {
+            eprintln!("[LOG] Using the default settings, {}", err);
+            Settings::default()
+        }
+    }
+}
+
+pub fn save_settings(settings: &Settings) {
+    let path = match get_settings_path() {
+        Some(path) => path,
+        None => {
+            eprintln!("[LOG] Couldn't save settings, there's no config directory to save them in");
+            return;
+        }
+    };
+
+    match settings.save(&path) {
+        Ok(_) => eprintln!("[LOG] Saved settings to {}", path.display()),
+        Err(err) => eprintln!("[LOG] Couldn't save settings, {}", err)
+    }
+}
@@ -0,0 +1,68 @@
+use anyhow::{Result, Error};
+
+use world::commands::{CommandHistory, CommandRegistry};
+
+// older lines are forgotten
+const MAX_LOG_LINES: usize = 200;
+
+/// What the console overlay and the game share: the commands, what was typed and what they printed.
+/// The overlay only queues lines up, the game runs them on its next update.
+pub struct Console {
+    commands: CommandRegistry,
+    history: CommandHistory,
+    log: Vec<String>,
+
+    // submitted but not run yet
+    pending: Vec<String>,
+}
+
+impl Console {
+    pub fn new() -> Result<Self, Error> {
+        let mut commands = CommandRegistry::new();
+        commands.register_builtins()?;
+
+        Ok(Self {
+            commands,
+            history: CommandHistory::new(),
+            log: Vec::new(),
+
+            pending: Vec::new(),
+        })
+    }
+
+    /// Queues `line` to be run, echoing it to the log
+    pub fn submit(&mut self, line: &str) {
+        let line = line.trim();
+
+        if line.is_empty() {
+            return;
+        }
+
+        self.history.push(line);
+        self.print(format!("> {}", line));
+        self.pending.push(line.to_string());
+    }
+
+    pub fn take_pending(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.pending)
+    }
+
+    pub fn print(&mut self, line: impl Into<String>) {
+        self.log.push(line.into());
+
+        if self.log.len() > MAX_LOG_LINES {
+            let excess = self.log.len() - MAX_LOG_LINES;
+            self.log.drain(..excess);
+        }
+    }
+}
+
+// getters
+impl Console {
+    pub fn get_commands(&self) -> &CommandRegistry { &self.commands }
+
+    pub fn get_history_mut(&mut self) -> &mut CommandHistory { &mut self.history }
+
+    /// Oldest first
+    pub fn get_log(&self) -> &[String] { &self.log }
+}
@@ -0,0 +1,79 @@
+use std::collections::BTreeMap;
+
+use winit::event::VirtualKeyCode;
+
+use renderer::input_manager::InputManager;
+use common::settings::{Action, KeyBindings};
+
+// every key that can be bound, named like in the settings file
+macro_rules! bindable_keys {
+    ($($key:ident),* $(,)?) => {
+        const KEYS: &[(&str, VirtualKeyCode)] = &[$((stringify!($key), VirtualKeyCode::$key)),*];
+    };
+}
+
+bindable_keys!(
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+    Escape, Space, Tab, Return, Back, Capital,
+    LShift, RShift, LControl, RControl, LAlt, RAlt,
+    Up, Down, Left, Right, Insert, Delete, Home, End, PageUp, PageDown,
+    Grave, Minus, Equals, LBracket, RBracket, Semicolon, Apostrophe, Comma, Period, Slash, Backslash,
+);
+
+/// The key with this name, ignoring case
+pub fn key_from_name(name: &str) -> Option<VirtualKeyCode> {
+    KEYS.iter()
+        .find(|(key_name, _)| key_name.eq_ignore_ascii_case(name.trim()))
+        .map(|(_, key)| *key)
+}
+
+/// `KeyBindings` looked up into actual keys
+#[derive(Debug, Clone)]
+pub struct Controls {
+    keys: BTreeMap<Action, VirtualKeyCode>,
+}
+
+impl Controls {
+    /// Keys that don't exist are left on their defaults, with a warning for each
+    pub fn from_bindings(bindings: &KeyBindings) -> (Self, Vec<String>) {
+        let mut keys = BTreeMap::new();
+        let mut warnings = Vec::new();
+
+        for action in Action::ALL.iter() {
+            let name = bindings.get_key(*action);
+
+            let key = match key_from_name(name) {
+                Some(key) => key,
+                None => {
+                    warnings.push(format!("there's no key called '{}' to {}, using {}", name, action.get_name(), action.get_default_key()));
+                    key_from_name(action.get_default_key()).unwrap_or(VirtualKeyCode::Escape)
+                }
+            };
+
+            keys.insert(*action, key);
+        }
+
+        (Self { keys }, warnings)
+    }
+
+    pub fn get_key(&self, action: Action) -> VirtualKeyCode {
+        self.keys[&action]
+    }
+
+    pub fn is_down(&self, input_manager: &InputManager, action: Action) -> bool {
+        input_manager.key_down(self.get_key(action))
+    }
+
+    pub fn just_pressed(&self, input_manager: &InputManager, action: Action) -> bool {
+        input_manager.key_just_pressed(self.get_key(action))
+    }
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Self::from_bindings(&KeyBindings::default()).0
+    }
+}
@@ -1,6 +1,10 @@
 pub mod states;
 pub mod camera_controller;
 pub mod player;
+pub mod saves;
+pub mod console;
+pub mod config;
+pub mod controls;
 
 use std::time::Instant;
 
@@ -11,7 +15,7 @@ use winit::{event::*, event_loop::{EventLoop, ControlFlow}, window::{Window, Win
 use renderer::{RenderableState, Renderer, RenderingError};
 
 
-use states::{willekeuirig_state::WillekeuirigState, err_screen_state::ErrScreenState};
+use states::{title_state::TitleState, err_screen_state::ErrScreenState};
 
 enum RunError {
     FatalError(Error),
@@ -31,19 +35,19 @@ fn main() {
 
     let mut renderer = block_on(Renderer::new(&window));
 
-    match WillekeuirigState::new(&mut renderer, &window) {
+    match TitleState::new(&mut renderer, &window) {
         Ok(state) => {
             match renderer.push_state(&window, state) {
                 Ok(_) => { },
                 Err(err) => {
-                    eprintln!("An error occurred while pushing game state: {}", err);
+                    eprintln!("An error occurred while pushing title state: {}", err);
                     std::process::exit(1);
                 }
             }
         },
         Err(err) => {
             /* TODO: Error handling */
-            eprintln!("An error occurred while creating game state: {}", err);
+            eprintln!("An error occurred while creating title state: {}", err);
             std::process::exit(1);
         }
     }
@@ -67,6 +71,7 @@ fn main() {
                     },
                     RunError::UnhandledError(err) => { eprintln!("[UNHANDLED ERROR] {}", err); },
                     RunError::UpdateError(err) => {
+                        // shown over the state that failed, which stops updating underneath it
                         match ErrScreenState::new(&mut renderer, &window) {
                             Ok(mut state) => {
                                 state.set_error(err);
@@ -99,6 +104,8 @@ fn run_loop(event: Event<()>, control_flow: &mut ControlFlow, window: &Window, r
     match event {
         Event::WindowEvent { ref event, window_id }
          if window_id == window.id() => {
+            renderer.window_input(event);
+
             match event {
                 WindowEvent::Focused(is_focused) => *focused = *is_focused,
                 WindowEvent::CloseRequested => {
@@ -194,6 +201,15 @@ fn run_loop(event: Event<()>, control_flow: &mut ControlFlow, window: &Window, r
         _ => {}
     }
 
+    // states ask to be swapped out while handling input or drawing, which can't be done from inside them
+    match renderer.process_transitions(window) {
+        Ok(true) => { },
+        Ok(false) => *control_flow = ControlFlow::Exit,
+        Err(err) => {
+            return Err(RunError::UpdateError(anyhow!(format!("An error occurred while changing states: {}", err))))
+        }
+    }
+
     if let Some(err) = unhandled_error {
         Err(err)
     } else {
@@ -1,20 +1,21 @@
 use cgmath::{InnerSpace, Point3, Vector3, Zero};
-use physics::box_collider::BoxCollider;
 use renderer::camera::Camera;
-use winit::event::VirtualKeyCode;
-use world::World;
+use world::{World, commands::GameMode, entity::{self, Entity, EntityBody, EntityId}};
+use common::settings::Action;
 
-use crate::camera_controller::CameraController;
+use crate::{camera_controller::CameraController, controls::Controls};
 
 pub struct Player {
     //rotation: Vector3<f32>,
-    velocity: Vector3<f32>, // temporary vel vector
+    move_input: Vector3<f32>, // x = strafe, y = jump/fly up & down, z = forward
 
     speed: f32,
     cam_sensitivity: f32,
+    game_mode: GameMode,
     is_flying: bool,
 
-    collider: BoxCollider, 
+    // the player's body lives in the world like any other entity, the world moves it
+    entity_id: EntityId,
 
     //aabb: AABB,
     
@@ -22,26 +23,39 @@ pub struct Player {
     camera_controller: CameraController,
 }
 
-const PLAYER_COLLIDER_SIZE: Vector3<f32> = Vector3::new(1., 2., 1.);
-const GRAVITY: f32 = -2.;
+const CAMERA_OFFSET: Vector3<f32> = Vector3::new(0.3, 1.6, 0.3);
+
+// in blocks per second
+const JUMP_VELOCITY: f32 = 9.;
+
+// walk up single block ledges without jumping
+const STEP_HEIGHT: f32 = 1.;
 
 impl Player {
-    pub fn new(position: Vector3<f32>, /*rotation: Vector3<f32>,*/ cam_sensitivity: f32, speed: f32) -> Self {
+    pub fn new(world: &mut World, position: Vector3<f32>, /*rotation: Vector3<f32>,*/ cam_sensitivity: f32, speed: f32) -> Self {
         let camera = Camera::new(
-            (position.x, position.y + 1.0, position.z),
+            (position.x + CAMERA_OFFSET.x, position.y + CAMERA_OFFSET.y, position.z + CAMERA_OFFSET.z),
             cgmath::Deg(-90.0),
             cgmath::Deg(-20.0)
         );
 
+        let mut body = EntityBody::new(position, entity::PLAYER_SIZE);
+        body.step_height = STEP_HEIGHT;
+
+        // players are saved by whoever owns them, not with the chunk they're standing in
+        let entity = Entity::new(entity::player_kind(), body)
+            .with_persistence(false);
+
         Self {
             //rotation,
-            velocity: Vector3::zero(),
+            move_input: Vector3::zero(),
 
+            game_mode: GameMode::Creative,
             is_flying: false,
             cam_sensitivity,
             speed,
 
-            collider: BoxCollider::new(position, PLAYER_COLLIDER_SIZE, Vector3::zero()),
+            entity_id: world.spawn_entity(entity),
 
             /*aabb: AABB::new(
                 position,
@@ -55,15 +69,70 @@ impl Player {
     }
 
     //pub fn position(&self) -> Vector3<f32> { self.aabb.position }
+
+    /// Only creative players can fly, switching to survival lands them
+    pub fn set_game_mode(&mut self, game_mode: GameMode) {
+        self.game_mode = game_mode;
+
+        if game_mode == GameMode::Survival {
+            self.is_flying = false;
+        }
+    }
+
+    pub fn set_cam_sensitivity(&mut self, cam_sensitivity: f32) {
+        self.cam_sensitivity = cam_sensitivity;
+    }
+
+    /// In blocks per second
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    /// Moves the player's entity straight to `position`, stopping it
+    pub fn teleport(&mut self, world: &mut World, position: Vector3<f32>) {
+        if let Some(entity) = world.get_entity_mut(self.entity_id) {
+            entity.body.set_position(position);
+            entity.body.velocity = Vector3::zero();
+        }
+    }
+
+    /// Lets go of every movement key, for when something else takes the keyboard
+    pub fn clear_input(&mut self) {
+        self.move_input = Vector3::zero();
+    }
 }
 
 impl Player {
     pub fn is_flying(&self) -> bool { self.is_flying }
 
+    pub fn get_game_mode(&self) -> GameMode { self.game_mode }
+
+    pub fn is_grounded(&self, world: &World) -> bool {
+        world.get_entity(self.entity_id).is_some_and(|entity| entity.body.on_ground)
+    }
+
+    pub fn get_entity_id(&self) -> EntityId { self.entity_id }
+
+    pub fn get_position(&self, world: &World) -> Option<Vector3<f32>> {
+        world.get_entity(self.entity_id).map(|entity| entity.body.get_position())
+    }
+
     pub fn get_camera(&self) -> &Camera {
         &self.camera
     }
 
+    /// The camera as it should be drawn `alpha` of the way between the previous update and the current one
+    pub fn get_interpolated_camera(&self, world: &World, alpha: f32) -> Camera {
+        let mut camera = self.camera;
+
+        if let Some(entity) = world.get_entity(self.entity_id) {
+            let position = entity.body.get_interpolated_position(alpha) + CAMERA_OFFSET;
+            camera.position = Point3::new(position.x, position.y, position.z);
+        }
+
+        camera
+    }
+
     pub fn get_camera_controller(&self) -> &CameraController {
         &self.camera_controller
     } 
@@ -72,56 +141,43 @@ impl Player {
         &mut self.camera_controller
     } 
 
-    pub fn process_keyboard(&mut self, input_manager: &renderer::input_manager::InputManager) -> bool {
+    pub fn process_keyboard(&mut self, input_manager: &renderer::input_manager::InputManager, controls: &Controls) -> bool {
         let amount = 1.0;
 
         let mut key_pressed = false;
 
-        if input_manager.key_down(VirtualKeyCode::W) {
-            self.velocity.z = amount;
+        if controls.is_down(input_manager, Action::MoveForward) {
+            self.move_input.z = amount;
             key_pressed = true;
-        } else if input_manager.key_down(VirtualKeyCode::S) {
-            self.velocity.z = -amount;
+        } else if controls.is_down(input_manager, Action::MoveBack) {
+            self.move_input.z = -amount;
             key_pressed = true;
         } else {
-            self.velocity.z = 0.0;
+            self.move_input.z = 0.0;
         }
 
-        if input_manager.key_down(VirtualKeyCode::A) {
-            self.velocity.x = -amount;
+        if controls.is_down(input_manager, Action::MoveLeft) {
+            self.move_input.x = -amount;
             key_pressed = true;
-        } else if input_manager.key_down(VirtualKeyCode::D) {
-            self.velocity.x = amount;
+        } else if controls.is_down(input_manager, Action::MoveRight) {
+            self.move_input.x = amount;
             key_pressed = true;
         } else {
-            self.velocity.x = 0.0;
+            self.move_input.x = 0.0;
         }
 
-        if !self.is_flying {
-            if input_manager.key_just_pressed(VirtualKeyCode::Space) {
-                self.velocity.y = amount * 1.25;
-                
-                key_pressed = true;
-            } else {
-                self.velocity.y = 0.;
-            }
+        // while walking, holding space keeps jumping whenever we land
+        if controls.is_down(input_manager, Action::Jump) {
+            self.move_input.y = amount;
+            key_pressed = true;
+        } else if self.is_flying && controls.is_down(input_manager, Action::Descend) {
+            self.move_input.y = -amount;
+            key_pressed = true;
         } else {
-            if input_manager.key_down(VirtualKeyCode::Space) {
-                self.velocity.y = amount * 1.25;
-                
-                key_pressed = true;
-            } else if input_manager.key_down(VirtualKeyCode::LShift) {
-                if self.is_flying {
-                    self.velocity.y = -(amount * 1.25);
-                }
-                
-                key_pressed = true;
-            } else {
-                self.velocity.y = 0.0;
-            }
+            self.move_input.y = 0.0;
         }
 
-        if input_manager.key_just_pressed(VirtualKeyCode::P) {
+        if self.game_mode == GameMode::Creative && controls.just_pressed(input_manager, Action::ToggleFlying) {
             self.is_flying = !self.is_flying;
             
             key_pressed = true;
@@ -130,49 +186,46 @@ impl Player {
         key_pressed
     }
 
+    /// Steers the player's entity, which the world moves on its next update.
+    /// `delta_time` is the fixed simulation step, not the frame time
     pub fn update(&mut self, world: &mut World, delta_time: f32) {
         let mut camera = self.camera.clone();
-        self.handle_movement(&camera, world, delta_time);
+        self.handle_movement(&camera, world);
 
         // adjust camera
-        let camera_position = Point3::new(self.collider.position.x, self.collider.position.y, self.collider.position.z);
+        if let Some(entity) = world.get_entity(self.entity_id) {
+            let camera_position = entity.body.get_position() + CAMERA_OFFSET;
                 
-        camera.position = camera_position;
-        camera.position.y += 1.;
+            camera.position = Point3::new(camera_position.x, camera_position.y, camera_position.z);
+        }
 
         self.camera = camera;
 
         self.camera_controller.update_camera(&mut self.camera, world, self.cam_sensitivity, delta_time);
     }
 
-    // TODO: cleanup?
-    fn handle_movement(&mut self, camera: &Camera, world: &mut World, delta_time: f32) {
+    fn handle_movement(&mut self, camera: &Camera, world: &mut World) {
+        let body = match world.get_entity_mut(self.entity_id) {
+            Some(entity) => &mut entity.body,
+            None => return
+        };
+
         // Move forward/backward and left/right
         let (yaw_sin, yaw_cos) = camera.yaw.0.sin_cos();
         let forward = Vector3::new(yaw_cos, 0.0, yaw_sin).normalize();
         let right = Vector3::new(-yaw_sin, 0.0, yaw_cos).normalize();
         
-        let mut velocity = Vector3::zero();
-        velocity += forward * self.velocity.z * self.speed * delta_time;
-        velocity += right * self.velocity.x * self.speed * delta_time;
-        velocity += Vector3::unit_y() * self.velocity.y * self.speed * delta_time;
-
-        self.collider.velocity = velocity;
-
-        // do some collision detection
-        if let Some(chunk) = world.get_chunk_from_world(&self.collider.position) {
-            if let Some(collider) = chunk.has_collision(&self.collider) {
-                let swept_result = self.collider.swept_aabb(collider);
-
-                self.collider.position += self.collider.velocity * swept_result.time;
-            } else {
-                if !self.is_flying {
-                    // no collision, assume we can fall
-                    self.collider.velocity += Vector3::unit_y() * GRAVITY * delta_time;
-                }
-
-                self.collider.position += self.collider.velocity;
-            }
+        let horizontal = forward * self.move_input.z + right * self.move_input.x;
+        body.velocity.x = horizontal.x * self.speed;
+        body.velocity.z = horizontal.z * self.speed;
+
+        // gravity and collision are up to the world
+        body.has_gravity = !self.is_flying;
+
+        if self.is_flying {
+            body.velocity.y = self.move_input.y * self.speed;
+        } else if body.on_ground && self.move_input.y > 0. {
+            body.velocity.y = JUMP_VELOCITY;
         }
     }
 }
\ No newline at end of file
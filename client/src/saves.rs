@@ -0,0 +1,266 @@
+use std::{fs, path::{Path, PathBuf}, time::{SystemTime, UNIX_EPOCH}};
+
+use anyhow::{Result, Error, anyhow};
+use common::serialization::{ByteReader, ByteWriter};
+
+/// Where single player worlds go, next to the working directory like screenshots
+pub const SAVES_DIRECTORY: &str = "saves";
+
+const INFO_FILE: &str = "world.info";
+// bumped whenever what's in the info file changes
+const INFO_VERSION: u32 = 1;
+
+/// A single player world in the saves directory, each in its own folder
+#[derive(Debug, Clone)]
+pub struct WorldInfo {
+    name: String,
+    seed: u32,
+    // seconds since the unix epoch, 0 if it was never played
+    last_played: u64,
+
+    directory: PathBuf,
+}
+
+impl WorldInfo {
+    /// Makes a folder for a new world in `saves`, named after it
+    pub fn create(saves: &Path, name: &str, seed: u32) -> Result<Self, Error> {
+        let name = name.trim();
+
+        if name.is_empty() {
+            return Err(anyhow!("worlds need a name"));
+        }
+
+        let folder = get_folder_name(name);
+        let mut directory = saves.join(&folder);
+        let mut suffix = 2;
+
+        // worlds with the same name get their own folders
+        while directory.exists() {
+            directory = saves.join(format!("{}_{}", folder, suffix));
+            suffix += 1;
+        }
+
+        if let Err(err) = fs::create_dir_all(&directory) {
+            return Err(anyhow!(format!("couldn't create world folder '{}': {}", directory.display(), err)));
+        }
+
+        let info = Self {
+            name: name.to_string(),
+            seed,
+            last_played: 0,
+
+            directory,
+        };
+
+        info.save()?;
+        Ok(info)
+    }
+
+    pub fn load(directory: &Path) -> Result<Self, Error> {
+        let path = directory.join(INFO_FILE);
+
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(err) => return Err(anyhow!(format!("couldn't read '{}': {}", path.display(), err)))
+        };
+
+        let mut reader = ByteReader::new(&bytes);
+        let version = reader.read_u32()?;
+
+        if version != INFO_VERSION {
+            return Err(anyhow!(format!("'{}' has unknown version {}", path.display(), version)));
+        }
+
+        Ok(Self {
+            name: reader.read_string()?,
+            seed: reader.read_u32()?,
+            last_played: reader.read_u64()?,
+
+            directory: directory.to_path_buf(),
+        })
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let mut writer = ByteWriter::new();
+        writer.write_u32(INFO_VERSION);
+        writer.write_string(&self.name);
+        writer.write_u32(self.seed);
+        writer.write_u64(self.last_played);
+
+        let path = self.directory.join(INFO_FILE);
+
+        match fs::write(&path, writer.into_bytes()) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(anyhow!(format!("couldn't save '{}': {}", path.display(), err)))
+        }
+    }
+
+    /// Remembers that the world was just opened, so it's listed first
+    pub fn mark_played(&mut self) -> Result<(), Error> {
+        self.last_played = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or(0);
+
+        self.save()
+    }
+
+    /// Deletes the world's folder along with every chunk in it
+    pub fn delete(self) -> Result<(), Error> {
+        match fs::remove_dir_all(&self.directory) {
+            Ok(_) => {
+                eprintln!("[LOG] Deleted world '{}'", self.name);
+                Ok(())
+            },
+            Err(err) => Err(anyhow!(format!("couldn't delete '{}': {}", self.directory.display(), err)))
+        }
+    }
+}
+
+// getters
+impl WorldInfo {
+    pub fn get_name(&self) -> &str { &self.name }
+
+    pub fn get_seed(&self) -> u32 { self.seed }
+
+    pub fn get_last_played(&self) -> u64 { self.last_played }
+
+    /// What the server saves chunks into
+    pub fn get_directory(&self) -> &Path { &self.directory }
+}
+
+/// Every world in `saves`, most recently played first. Folders that aren't worlds are skipped.
+pub fn list_worlds(saves: &Path) -> Result<Vec<WorldInfo>, Error> {
+    if !saves.exists() {
+        return Ok(Vec::new());
+    }
+
+    let entries = match fs::read_dir(saves) {
+        Ok(entries) => entries,
+        Err(err) => return Err(anyhow!(format!("couldn't list worlds in '{}': {}", saves.display(), err)))
+    };
+
+    let mut worlds = Vec::new();
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        if !path.join(INFO_FILE).exists() {
+            continue;
+        }
+
+        match WorldInfo::load(&path) {
+            Ok(info) => worlds.push(info),
+            Err(err) => eprintln!("[LOG] Skipping world in '{}': {}", path.display(), err)
+        }
+    }
+
+    worlds.sort_by(|a, b| b.last_played.cmp(&a.last_played).then_with(|| a.name.cmp(&b.name)));
+
+    Ok(worlds)
+}
+
+/// A seed from what was typed when creating a world: random when empty,
+/// the number itself for numbers, otherwise a hash of the text
+pub fn parse_seed(text: &str) -> u32 {
+    let text = text.trim();
+
+    if text.is_empty() {
+        return rand::random();
+    }
+
+    match text.parse::<i64>() {
+        Ok(number) => number as u32,
+        // the same text always makes the same world
+        Err(_) => text.bytes().fold(0u32, |hash, byte| hash.wrapping_mul(31).wrapping_add(byte as u32))
+    }
+}
+
+// a folder name safe on every platform
+fn get_folder_name(name: &str) -> String {
+    let folder: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+
+    if folder.trim_matches('_').is_empty() {
+        String::from("world")
+    } else {
+        folder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use common::serialization::ByteWriter;
+
+    use super::{INFO_FILE, INFO_VERSION, WorldInfo, list_worlds, parse_seed};
+
+    fn saves_directory(test: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("saves_test_{}_{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+
+        directory
+    }
+
+    #[test]
+    fn worlds_with_the_same_name_get_their_own_folders() {
+        let saves = saves_directory("names");
+
+        let first = WorldInfo::create(&saves, " My World! ", 7).unwrap();
+        let second = WorldInfo::create(&saves, "My World!", 8).unwrap();
+        let unnamed = WorldInfo::create(&saves, "???", 9).unwrap();
+
+        assert_eq!(first.get_directory(), saves.join("My_World_"));
+        assert_eq!(second.get_directory(), saves.join("My_World__2"));
+        assert_eq!(unnamed.get_directory(), saves.join("world"));
+        assert!(WorldInfo::create(&saves, "  ", 10).is_err());
+
+        let loaded = WorldInfo::load(second.get_directory()).unwrap();
+        assert_eq!(loaded.get_name(), "My World!");
+        assert_eq!(loaded.get_seed(), 8);
+        assert_eq!(loaded.get_last_played(), 0);
+
+        fs::remove_dir_all(&saves).unwrap();
+    }
+
+    #[test]
+    fn worlds_are_listed_most_recently_played_first() {
+        let saves = saves_directory("list");
+        assert!(list_worlds(&saves).unwrap().is_empty());
+
+        for (name, last_played) in [("old", 10), ("new", 30), ("never", 0), ("also new", 30)] {
+            let mut info = WorldInfo::create(&saves, name, 0).unwrap();
+            info.last_played = last_played;
+            info.save().unwrap();
+        }
+
+        // not worlds, or not ones this version can read
+        fs::create_dir_all(saves.join("screenshots")).unwrap();
+
+        let mut writer = ByteWriter::new();
+        writer.write_u32(INFO_VERSION + 1);
+        fs::create_dir_all(saves.join("future")).unwrap();
+        fs::write(saves.join("future").join(INFO_FILE), writer.into_bytes()).unwrap();
+        assert!(WorldInfo::load(&saves.join("future")).is_err());
+
+        let names: Vec<String> = list_worlds(&saves).unwrap().iter()
+            .map(|info| info.get_name().to_string())
+            .collect();
+
+        assert_eq!(names, vec!["also new", "new", "old", "never"]);
+
+        fs::remove_dir_all(&saves).unwrap();
+    }
+
+    #[test]
+    fn numbers_are_their_own_seed() {
+        assert_eq!(parse_seed(" 1234 "), 1234);
+        assert_eq!(parse_seed("-1"), u32::MAX);
+
+        // anything else is hashed, the same way every time
+        assert_eq!(parse_seed("hello"), parse_seed("hello"));
+        assert_ne!(parse_seed("hello"), parse_seed("world"));
+        assert_eq!(parse_seed("a"), 97);
+    }
+}
@@ -0,0 +1,214 @@
+use std::{cell::RefCell, rc::Rc};
+
+use anyhow::{Result, Error};
+use winit::{event::VirtualKeyCode, window::Window};
+
+use renderer::{Renderer, RenderableState, Transition, input_manager::InputManager, render_graph::RenderGraph, ui::Rect};
+
+use common::settings::Action;
+
+use crate::{console::Console, controls::Controls};
+
+const CONSOLE_HEIGHT: f32 = 360.0;
+const INPUT_HEIGHT: f32 = 36.0;
+const PADDING: f32 = 10.0;
+
+const HINT_COLOR: [f32; 4] = [0.7, 0.7, 0.7, 1.0];
+
+/// A strip along the top of the screen to type commands into. The game keeps running underneath.
+pub struct ConsoleState {
+    // shared with the game, which runs what's submitted on its next update
+    console: Rc<RefCell<Console>>,
+    // whatever opened the console closes it again
+    toggle_key: VirtualKeyCode,
+    input: String,
+    // what else tab could have completed to
+    suggestions: Vec<String>,
+    // the input was replaced, so the caret goes back to its end
+    move_caret: bool,
+
+    transition: Option<Transition>,
+}
+
+impl ConsoleState {
+    pub fn open(console: Rc<RefCell<Console>>, toggle_key: VirtualKeyCode) -> Box<Self> {
+        Box::new(Self {
+            console,
+            toggle_key,
+            input: String::new(),
+            suggestions: Vec::new(),
+            move_caret: false,
+
+            transition: None,
+        })
+    }
+
+    fn set_input(&mut self, input: &str) {
+        self.input = input.to_string();
+        self.move_caret = true;
+    }
+
+    // finishes the word being typed as far as it's the same for every completion
+    fn complete(&mut self) {
+        let completions = self.console.borrow().get_commands().complete(&self.input);
+        self.suggestions.clear();
+
+        match completions.as_slice() {
+            [] => { },
+            [completion] => self.set_input(&format!("{} ", completion)),
+            [first, rest @ ..] => {
+                let mut common = first.len();
+
+                for completion in rest {
+                    let same: usize = first.chars().zip(completion.chars())
+                        .take_while(|(a, b)| a == b)
+                        .map(|(a, _)| a.len_utf8())
+                        .sum();
+
+                    common = common.min(same);
+                }
+
+                // only the last word differs between them
+                let word_start = self.input.rfind(' ').map_or(0, |space| space + 1);
+                self.suggestions = completions.iter()
+                    .map(|completion| completion[word_start..].trim_start_matches('/').to_string())
+                    .collect();
+
+                let common = first[..common].to_string();
+                self.set_input(&common);
+            }
+        }
+    }
+}
+
+impl RenderableState for ConsoleState {
+    fn get_state_id(&self) -> u32 { 5 }
+
+    fn is_cursor_visible(&self) -> bool { true }
+
+    fn is_opaque(&self) -> bool { false }
+
+    // the world keeps going while typing, but the player doesn't move
+    fn blocks_update(&self) -> bool { false }
+
+    fn take_transition(&mut self) -> Option<Transition> { self.transition.take() }
+
+    fn new(_renderer: &mut Renderer, _window: &Window) -> Result<Box<Self>, Error> {
+        Ok(Self::open(Rc::new(RefCell::new(Console::new()?)), Controls::default().get_key(Action::Console)))
+    }
+
+    fn update(&mut self, _renderer: &Renderer, _delta_time: f32) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn on_resize(&mut self, _new_size: winit::dpi::PhysicalSize<u32>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn handle_keys(&mut self, input_manager: &InputManager) -> Result<bool, Error> {
+        if input_manager.key_just_pressed(VirtualKeyCode::Escape) || input_manager.key_just_pressed(self.toggle_key) {
+            self.transition = Some(Transition::Pop);
+        }
+
+        if input_manager.key_just_pressed(VirtualKeyCode::Up) {
+            let line = self.console.borrow_mut().get_history_mut().back().map(|line| line.to_string());
+
+            if let Some(line) = line {
+                self.set_input(&line);
+            }
+        }
+
+        if input_manager.key_just_pressed(VirtualKeyCode::Down) {
+            let line = self.console.borrow_mut().get_history_mut().forward().map(|line| line.to_string());
+
+            if let Some(line) = line {
+                self.set_input(&line);
+            }
+        }
+
+        if input_manager.key_just_pressed(VirtualKeyCode::Tab) {
+            self.complete();
+        }
+
+        Ok(true)
+    }
+
+    fn handle_mouse(&mut self, _mouse_dx: f64, _mouse_dy: f64) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    fn prepare_frame(&mut self, renderer: &Renderer, _encoder: &mut wgpu::CommandEncoder) -> Result<(), Error> {
+        let mut ui = renderer.get_ui();
+        let screen = ui.get_screen_rect();
+        let size = ui.get_style().text_size;
+        let line_height = size * 1.25;
+
+        let panel = Rect::new(0.0, 0.0, screen.width, CONSOLE_HEIGHT.min(screen.height));
+        ui.panel("console", panel);
+
+        let inner = panel.shrink(PADDING);
+        let input_rect = Rect::new(inner.x, inner.y + inner.height - INPUT_HEIGHT, inner.width, INPUT_HEIGHT);
+        let mut log_bottom = input_rect.y - PADDING;
+
+        if !self.suggestions.is_empty() {
+            log_bottom -= line_height;
+            ui.text(inner.x, log_bottom, &self.suggestions.join("  "), size, HINT_COLOR);
+        }
+
+        // newest at the bottom, whatever doesn't fit scrolls off the top
+        let log = Rect::new(inner.x, inner.y, inner.width, log_bottom - inner.y);
+        ui.push_clip(log);
+
+        let console = self.console.borrow();
+        let text_color = ui.get_style().text_color;
+
+        for (i, line) in console.get_log().iter().rev().enumerate() {
+            let y = log_bottom - (i + 1) as f32 * line_height;
+
+            if y + line_height < log.y {
+                break;
+            }
+
+            ui.text(log.x, y, line, size, text_color);
+        }
+
+        drop(console);
+        ui.pop_clip();
+
+        // the input always has the keyboard
+        let input_id = ui.make_id("input");
+        ui.focus(input_id);
+
+        if self.move_caret {
+            ui.move_caret_to_end();
+            self.move_caret = false;
+        }
+
+        let response = ui.text_field("input", input_rect, &mut self.input);
+
+        // the key that opens and closes the console types one too
+        if self.toggle_key == VirtualKeyCode::Grave {
+            self.input.retain(|c| c != '`');
+        }
+
+        if response.changed {
+            self.suggestions.clear();
+        }
+
+        if response.submitted {
+            self.console.borrow_mut().submit(&self.input);
+            self.input.clear();
+            self.suggestions.clear();
+        }
+
+        Ok(())
+    }
+
+    fn build_graph(&mut self, _renderer: &Renderer, _graph: &mut RenderGraph<'_>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn exit(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
@@ -1,9 +1,19 @@
 use anyhow::Error;
 
-use renderer::{RenderableState, Renderer};
+use renderer::{RenderableState, Renderer, Transition, render_graph::RenderGraph, ui::{self, Anchor, Layout, Rect}};
 
+use super::{builder, title_state::TitleState};
+
+const DIALOG_WIDTH: f32 = 640.0;
+const DIALOG_HEIGHT: f32 = 240.0;
+const BUTTON_HEIGHT: f32 = 44.0;
+
+const ERROR_COLOR: [f32; 4] = [1.0, 0.4, 0.4, 1.0];
+
+/// A dialog over whatever state failed, which stays paused underneath it
 pub struct ErrScreenState {
-    error: Option<anyhow::Error>
+    error: Option<anyhow::Error>,
+    transition: Option<Transition>,
 }
 
 impl ErrScreenState {
@@ -17,8 +27,12 @@ impl RenderableState for ErrScreenState {
 
     fn is_cursor_visible(&self) -> bool { true }
 
+    fn is_opaque(&self) -> bool { false }
+
+    fn take_transition(&mut self) -> Option<Transition> { self.transition.take() }
+
     fn new(_renderer: &mut Renderer, _window: &winit::window::Window) -> anyhow::Result<Box<Self>, anyhow::Error> where Self: Sized {
-        Ok(Box::new(Self { error: None }))
+        Ok(Box::new(Self { error: None, transition: None }))
     }
 
     fn update(&mut self, _renderer: &Renderer, _delta_time: f32) -> Result<(), Error> {
@@ -37,20 +51,74 @@ impl RenderableState for ErrScreenState {
         Ok(true)
     }
 
-    fn render<'a>(&'a mut self, renderer: &'a Renderer, _render_pass: &mut wgpu::RenderPass<'a>, _delta_time: f32) -> anyhow::Result<(), anyhow::Error> {
-        
+    fn prepare_frame(&mut self, renderer: &Renderer, _encoder: &mut wgpu::CommandEncoder) -> anyhow::Result<(), anyhow::Error> {
         let text = if let Some(err) = &self.error {
             format!("{}", err)
         } else {
             format!("An unknown error occurred!")
         };
 
-        renderer.queue_string(&text, (5.0, 150.0), [1.0, 1.0, 1.0, 1.0], 25.0);
+        let mut ui = renderer.get_ui();
+        let dialog = ui.get_screen_rect().anchored(Anchor::Center, DIALOG_WIDTH, DIALOG_HEIGHT);
+
+        ui.panel("error", dialog);
+
+        let mut column = Layout::column(dialog.shrink(20.0), 10.0);
+        ui.label(column.next(30.0), "Something went wrong", Anchor::Left);
+
+        // errors too long for the dialog are cut off at the bottom
+        let message = column.next(DIALOG_HEIGHT - 40.0 - 30.0 - BUTTON_HEIGHT - 20.0);
+        let size = ui.get_style().text_size;
+
+        ui.push_clip(message);
+
+        for (i, line) in wrap_text(&text, message.width, size).iter().enumerate() {
+            ui.text(message.x, message.y + i as f32 * size * 1.25, line, size, ERROR_COLOR);
+        }
+
+        ui.pop_clip();
+
+        // with nothing underneath there's nothing to go back to
+        let buttons: Vec<Rect> = column.next(BUTTON_HEIGHT).columns(2, 10.0);
+
+        if renderer.get_state_count() > 1 && ui.button("dismiss", buttons[0], "Dismiss").clicked {
+            self.transition = Some(Transition::Pop);
+        }
+
+        if ui.button("title", buttons[1], "Back to title").clicked {
+            self.transition = Some(Transition::Reset(builder::<TitleState>()));
+        }
 
         Ok(())
     }
 
+    fn build_graph(&mut self, _renderer: &Renderer, _graph: &mut RenderGraph<'_>) -> anyhow::Result<(), anyhow::Error> {
+        Ok(())
+    }
+
     fn exit(&mut self) -> anyhow::Result<(), anyhow::Error> {
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+// splits `text` into lines no wider than `width` where it can, between words
+fn wrap_text(text: &str, width: f32, size: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+
+    for word in text.split_whitespace() {
+        let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+
+        if !line.is_empty() && ui::text_width(&candidate, size) > width {
+            lines.push(std::mem::replace(&mut line, word.to_string()));
+        } else {
+            line = candidate;
+        }
+    }
+
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
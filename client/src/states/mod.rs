@@ -1,2 +1,16 @@
 pub mod willekeuirig_state;
-pub mod err_screen_state;
\ No newline at end of file
+pub mod err_screen_state;
+pub mod title_state;
+pub mod world_list_state;
+pub mod pause_state;
+pub mod console_state;
+
+use renderer::{RenderableState, StateBuilder};
+
+/// Builds a `S` with its `RenderableState::new`, for `renderer::Transition`s
+pub fn builder<S: RenderableState + 'static>() -> StateBuilder {
+    Box::new(|renderer, window| {
+        let state: Box<dyn RenderableState> = S::new(renderer, window)?;
+        Ok(state)
+    })
+}
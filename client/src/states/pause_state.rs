@@ -0,0 +1,229 @@
+use std::{cell::RefCell, rc::Rc};
+
+use anyhow::{Result, Error};
+use winit::{event::VirtualKeyCode, window::Window};
+
+use renderer::{Renderer, RenderableState, Transition, input_manager::InputManager, render_graph::RenderGraph, ui::{Anchor, Layout}};
+use common::settings::{GraphicsQuality, MAX_DRAW_DISTANCE, PresentMode, Settings, ShadowQuality, TextureFiltering};
+
+use crate::config;
+use super::{builder, title_state::TitleState};
+
+const PANEL_WIDTH: f32 = 360.0;
+const BUTTON_HEIGHT: f32 = 44.0;
+// the settings page has a lot more rows to fit
+const ROW_HEIGHT: f32 = 36.0;
+const SPACING: f32 = 10.0;
+
+// darkens the game underneath
+const DIM_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Page {
+    Main,
+    Settings,
+}
+
+/// Drawn over the game, which keeps being drawn underneath
+pub struct PauseState {
+    // shared with the game, which picks changes up on its next update
+    settings: Rc<RefCell<Settings>>,
+    page: Page,
+
+    transition: Option<Transition>,
+}
+
+impl PauseState {
+    /// Pauses a game playing with `settings`
+    pub fn open(settings: Rc<RefCell<Settings>>) -> Box<Self> {
+        Box::new(Self {
+            settings,
+            page: Page::Main,
+
+            transition: None,
+        })
+    }
+
+    fn back(&mut self) {
+        match self.page {
+            Page::Main => self.transition = Some(Transition::Pop),
+            Page::Settings => self.close_settings(),
+        }
+    }
+
+    // changes are saved once we're done with them
+    fn close_settings(&mut self) {
+        config::save_settings(&self.settings.borrow());
+        self.page = Page::Main;
+    }
+}
+
+impl RenderableState for PauseState {
+    fn get_state_id(&self) -> u32 { 4 }
+
+    fn is_cursor_visible(&self) -> bool { true }
+
+    fn is_opaque(&self) -> bool { false }
+
+    fn take_transition(&mut self) -> Option<Transition> { self.transition.take() }
+
+    fn new(_renderer: &mut Renderer, _window: &Window) -> Result<Box<Self>, Error> {
+        Ok(Self::open(Rc::new(RefCell::new(Settings::default()))))
+    }
+
+    fn update(&mut self, _renderer: &Renderer, _delta_time: f32) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn on_resize(&mut self, _new_size: winit::dpi::PhysicalSize<u32>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn handle_keys(&mut self, input_manager: &InputManager) -> Result<bool, Error> {
+        if input_manager.key_just_pressed(VirtualKeyCode::Escape) {
+            self.back();
+        }
+
+        Ok(false)
+    }
+
+    fn handle_mouse(&mut self, _mouse_dx: f64, _mouse_dy: f64) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    fn prepare_frame(&mut self, renderer: &Renderer, _encoder: &mut wgpu::CommandEncoder) -> Result<(), Error> {
+        let mut ui = renderer.get_ui();
+        let screen = ui.get_screen_rect();
+
+        ui.rect(screen, DIM_COLOR);
+
+        match self.page {
+            Page::Main => {
+                let height = BUTTON_HEIGHT * 3.0 + SPACING * 2.0;
+                let mut buttons = Layout::column(screen.anchored(Anchor::Center, PANEL_WIDTH, height), SPACING);
+
+                if ui.button("resume", buttons.next(BUTTON_HEIGHT), "Resume").clicked {
+                    self.transition = Some(Transition::Pop);
+                }
+
+                if ui.button("settings", buttons.next(BUTTON_HEIGHT), "Settings").clicked {
+                    self.page = Page::Settings;
+                }
+
+                // the game saves and stops in its exit
+                if ui.button("quit", buttons.next(BUTTON_HEIGHT), "Quit to title").clicked {
+                    self.transition = Some(Transition::Reset(builder::<TitleState>()));
+                }
+            },
+            Page::Settings => {
+                let height = ROW_HEIGHT * 10.0 + SPACING * 9.0;
+                let panel = screen.anchored(Anchor::Center, PANEL_WIDTH, height).shrink(-20.0);
+
+                ui.panel("settings", panel);
+
+                let mut settings = self.settings.borrow_mut();
+                let mut rows = Layout::column(panel.shrink(20.0), SPACING);
+
+                let mut draw_distance = settings.get_draw_distance() as f32;
+                let text = format!("Render distance: {} chunks", settings.get_draw_distance());
+
+                if ui.slider("draw_distance", rows.next(ROW_HEIGHT), &text, &mut draw_distance, 1.0..=MAX_DRAW_DISTANCE as f32).changed {
+                    settings.set_draw_distance(draw_distance.round() as usize);
+                }
+
+                let mut fov = settings.get_fov();
+                let text = format!("Field of view: {:.0}", fov);
+
+                if ui.slider("fov", rows.next(ROW_HEIGHT), &text, &mut fov, 30.0..=110.0).changed {
+                    settings.set_fov(fov.round());
+                }
+
+                let mut sensitivity = settings.get_mouse_sensitivity();
+                let text = format!("Mouse sensitivity: {:.2}", sensitivity);
+
+                if ui.slider("sensitivity", rows.next(ROW_HEIGHT), &text, &mut sensitivity, 0.05..=2.0).changed {
+                    settings.set_mouse_sensitivity(sensitivity);
+                }
+
+                let present_mode = match settings.get_present_mode() {
+                    PresentMode::Fifo => "VSync: On",
+                    PresentMode::Mailbox => "VSync: Off (mailbox)",
+                    PresentMode::Immediate => "VSync: Off (immediate)",
+                };
+
+                if ui.button("present_mode", rows.next(ROW_HEIGHT), present_mode).clicked {
+                    let present_mode = match settings.get_present_mode() {
+                        PresentMode::Fifo => PresentMode::Mailbox,
+                        PresentMode::Mailbox => PresentMode::Immediate,
+                        PresentMode::Immediate => PresentMode::Fifo,
+                    };
+
+                    settings.set_present_mode(present_mode);
+                }
+
+                let quality = format!("Graphics: {:?}", settings.get_graphics_quality());
+
+                if ui.button("quality", rows.next(ROW_HEIGHT), &quality).clicked {
+                    let quality = match settings.get_graphics_quality() {
+                        GraphicsQuality::Low => GraphicsQuality::Medium,
+                        GraphicsQuality::Medium => GraphicsQuality::High,
+                        GraphicsQuality::High | GraphicsQuality::Custom => GraphicsQuality::Low,
+                    };
+
+                    settings.set_graphics_quality(quality);
+                }
+
+                let mut post_processing = *settings.get_post_processing();
+                let fxaa = ui.checkbox("fxaa", rows.next(ROW_HEIGHT), "FXAA", &mut post_processing.fxaa);
+                let vignette = ui.slider("vignette", rows.next(ROW_HEIGHT), "Vignette", &mut post_processing.vignette, 0.0..=1.0);
+
+                if fxaa.changed || vignette.changed {
+                    settings.set_post_processing(post_processing);
+                }
+
+                let shadows = format!("Shadows: {:?}", settings.get_shadow_quality());
+
+                if ui.button("shadows", rows.next(ROW_HEIGHT), &shadows).clicked {
+                    let quality = match settings.get_shadow_quality() {
+                        ShadowQuality::Off => ShadowQuality::Low,
+                        ShadowQuality::Low => ShadowQuality::Medium,
+                        ShadowQuality::Medium => ShadowQuality::High,
+                        ShadowQuality::High => ShadowQuality::Off,
+                    };
+
+                    settings.set_shadow_quality(quality);
+                }
+
+                let filtering = format!("Textures: {:?}", settings.get_texture_filtering());
+
+                if ui.button("filtering", rows.next(ROW_HEIGHT), &filtering).clicked {
+                    let filtering = match settings.get_texture_filtering() {
+                        TextureFiltering::Nearest => TextureFiltering::Bilinear,
+                        TextureFiltering::Bilinear => TextureFiltering::Trilinear,
+                        TextureFiltering::Trilinear => TextureFiltering::Nearest,
+                    };
+
+                    settings.set_texture_filtering(filtering);
+                }
+
+                let back = ui.button("back", rows.next(ROW_HEIGHT), "Back").clicked;
+                drop(settings);
+
+                if back {
+                    self.close_settings();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn build_graph(&mut self, _renderer: &Renderer, _graph: &mut RenderGraph<'_>) -> Result<(), Error> {
+        // only the UI, which the renderer draws on top of everything anyway
+        Ok(())
+    }
+
+    fn exit(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
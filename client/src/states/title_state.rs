@@ -0,0 +1,69 @@
+use anyhow::{Result, Error};
+use winit::window::Window;
+
+use renderer::{Renderer, RenderableState, Transition, input_manager::InputManager, ui::{self, Anchor, Layout}};
+
+use super::{builder, world_list_state::WorldListState};
+
+const TITLE: &str = "Willekeurig";
+const TITLE_SIZE: f32 = 72.0;
+
+const BUTTON_WIDTH: f32 = 300.0;
+const BUTTON_HEIGHT: f32 = 50.0;
+
+/// What the game opens on
+pub struct TitleState {
+    transition: Option<Transition>,
+}
+
+impl RenderableState for TitleState {
+    fn get_state_id(&self) -> u32 { 2 }
+
+    fn is_cursor_visible(&self) -> bool { true }
+
+    fn take_transition(&mut self) -> Option<Transition> { self.transition.take() }
+
+    fn new(_renderer: &mut Renderer, _window: &Window) -> Result<Box<Self>, Error> {
+        Ok(Box::new(Self { transition: None }))
+    }
+
+    fn update(&mut self, _renderer: &Renderer, _delta_time: f32) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn on_resize(&mut self, _new_size: winit::dpi::PhysicalSize<u32>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn handle_keys(&mut self, _input_manager: &InputManager) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    fn handle_mouse(&mut self, _mouse_dx: f64, _mouse_dy: f64) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    fn prepare_frame(&mut self, renderer: &Renderer, _encoder: &mut wgpu::CommandEncoder) -> Result<(), Error> {
+        let mut ui = renderer.get_ui();
+        let screen = ui.get_screen_rect();
+
+        let title = screen.anchored(Anchor::Top, ui::text_width(TITLE, TITLE_SIZE), TITLE_SIZE).offset(0.0, screen.height * 0.2);
+        ui.text(title.x, title.y, TITLE, TITLE_SIZE, [1.0, 1.0, 1.0, 1.0]);
+
+        let mut buttons = Layout::column(screen.anchored(Anchor::Center, BUTTON_WIDTH, BUTTON_HEIGHT * 2.0 + 10.0), 10.0);
+
+        if ui.button("singleplayer", buttons.next(BUTTON_HEIGHT), "Singleplayer").clicked {
+            self.transition = Some(Transition::Push(builder::<WorldListState>()));
+        }
+
+        if ui.button("quit", buttons.next(BUTTON_HEIGHT), "Quit").clicked {
+            self.transition = Some(Transition::Quit);
+        }
+
+        Ok(())
+    }
+
+    fn exit(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
@@ -1,272 +1,489 @@
+use std::{cell::RefCell, path::PathBuf, rc::Rc};
+
 use anyhow::{Result, Error, anyhow};
 use cgmath::Vector3;
-use winit::{event::VirtualKeyCode, window::Window};
+use winit::window::Window;
 
 use rand::Rng;
-use wgpu::util::DeviceExt;
 
-use renderer::{Renderer, RenderableState, camera, texture, camera_uniform, vertex::Vertex};
-use common::{block::Block, identifier::Identifier, registry::Registry};
-use world;
+use renderer::{Renderer, RenderableState, Transition, camera, texture, camera_uniform, chunk_mesh_cache::{self, ChunkMeshCache}, pipeline::{Material, PipelineDescriptor}, render_graph::{self, PassDesc, PassInfo, RenderGraph}, shadow::{self, ShadowMaps}, sky::Sky, vertex::{ChunkVertex, VertexLayout}};
+use net::client::ClientSession;
+use server::{Server, ServerConfig};
+use common::{block::TextureCoords, registry::Registry, settings::{Action, KeyBindings, Settings, ShadowQuality, TextureFiltering}};
+use world::{blocks, commands::{CommandContext, CommandEffect}};
+
+use crate::{config, console::Console, controls::Controls, player, saves::WorldInfo};
+use super::{console_state::ConsoleState, pause_state::PauseState};
+
+// shadows further away than this wouldn't be worth the shadow map resolution they'd take
+const MAX_SHADOW_DISTANCE: f32 = 96.0;
+const UNDERWATER_TINT: [f32; 4] = [0.2, 0.45, 0.9, 0.55];
 
-use crate::player;
+const SKY_PASS: &str = "sky";
+const SHADOW_PASS: &str = "shadows";
+const TERRAIN_PASS: &str = "terrain";
 
 pub struct WillekeuirigState {
     //registry: Arc<Registry>,
 
-    render_pipeline: wgpu::RenderPipeline,
+    block_material: Material,
 
     block_texture: texture::Texture,
+    // shared with the pause menu, changes are picked up on the next update
+    settings: Rc<RefCell<Settings>>,
+    // the keys the settings' bindings were last looked up into
+    key_bindings: KeyBindings,
+    controls: Controls,
+    // what the block sampler was last built with
+    sampler_options: texture::SamplerOptions,
+    // shared with the console overlay, which queues up commands for us to run
+    console: Rc<RefCell<Console>>,
     
-    world: world::World,
+    // single player is our own server, talked to over a loopback connection like any other
+    server: Server,
+    session: ClientSession,
+    // the world only builds meshes, uploading them is up to us
+    chunk_meshes: ChunkMeshCache,
+
+    sky: Sky,
+    shadows: ShadowMaps,
     
     //camera: camera::Camera,
     player: player::Player,
     projection: camera::Projection,
     //camera_controller: camera_controller::CameraController,
-    
-    camera_uniform: camera_uniform::CameraUniform,
-    camera_buffer: wgpu::Buffer,
-    camera_bind_group: wgpu::BindGroup,
 
-    cursor_visible: bool,
+    transition: Option<Transition>,
 }
 
 impl RenderableState for WillekeuirigState {
     fn get_state_id(&self) -> u32 { 0 }
 
-    fn is_cursor_visible(&self) -> bool { self.cursor_visible }
+    fn is_cursor_visible(&self) -> bool { false }
 
-    fn new(renderer: &mut Renderer, window: &Window) -> Result<Box<Self>, Error> {
-        let dev_clone = renderer.get_device().clone();
-        let device = dev_clone.read().unwrap();
+    fn take_transition(&mut self) -> Option<Transition> { self.transition.take() }
 
+    fn new(renderer: &mut Renderer, window: &Window) -> Result<Box<Self>, Error> {
         let seed = rand::thread_rng().gen::<u32>();
 
-        let texture_bytes = include_bytes!("../../../res/textures/block_atlas.png");
-        let block_texture = texture::Texture::from_bytes(&device, &renderer.get_queue(),
-            texture_bytes, "block_texture").unwrap();
+        Self::open(renderer, window, seed, None)
+    }
+
+    fn on_resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) -> Result<(), Error> {
+        self.projection.resize(new_size.width, new_size.height);
+
+        Ok(())
+    }
+
+    fn handle_keys(&mut self, input_manager: &renderer::input_manager::InputManager)  -> Result<bool, Error> {
+        if self.controls.just_pressed(input_manager, Action::Pause) {
+            self.transition = Some(Transition::Push(Box::new({
+                let settings = self.settings.clone();
+
+                move |_renderer, _window| {
+                    let state: Box<dyn RenderableState> = PauseState::open(settings);
+                    Ok(state)
+                }
+            })));
+        }
+
+        if self.controls.just_pressed(input_manager, Action::Console) {
+            self.transition = Some(Transition::Push(Box::new({
+                let console = self.console.clone();
+                let toggle_key = self.controls.get_key(Action::Console);
+
+                move |_renderer, _window| {
+                    let state: Box<dyn RenderableState> = ConsoleState::open(console, toggle_key);
+                    Ok(state)
+                }
+            })));
+
+            // the game keeps updating under the console, so nothing should stay held down
+            self.player.clear_input();
+            return Ok(true);
+        }
+
+        let mut settings = self.settings.borrow_mut();
+
+        if self.controls.just_pressed(input_manager, Action::CycleTextureFiltering) {
+            let filtering = match settings.get_texture_filtering() {
+                TextureFiltering::Nearest => TextureFiltering::Bilinear,
+                TextureFiltering::Bilinear => TextureFiltering::Trilinear,
+                TextureFiltering::Trilinear => TextureFiltering::Nearest,
+            };
+
+            settings.set_texture_filtering(filtering);
+        }
+
+        if self.controls.just_pressed(input_manager, Action::ToggleAnisotropy) {
+            let anisotropy = if settings.get_anisotropy() > 1 { 1 } else { 16 };
+
+            settings.set_anisotropy(anisotropy);
+        }
+
+        if self.controls.just_pressed(input_manager, Action::CycleShadows) {
+            let quality = match settings.get_shadow_quality() {
+                ShadowQuality::Off => ShadowQuality::Low,
+                ShadowQuality::Low => ShadowQuality::Medium,
+                ShadowQuality::Medium => ShadowQuality::High,
+                ShadowQuality::High => ShadowQuality::Off,
+            };
+
+            settings.set_shadow_quality(quality);
+        }
+
+        if self.controls.just_pressed(input_manager, Action::ToggleFxaa) {
+            let mut post_processing = *settings.get_post_processing();
+            post_processing.fxaa = !post_processing.fxaa;
+
+            settings.set_post_processing(post_processing);
+        }
+
+        Ok(self.player.process_keyboard(input_manager, &self.controls))
+    }
+
+    fn handle_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) -> Result<bool, Error> {
+        self.player.get_camera_controller_mut().process_mouse(mouse_dx, mouse_dy);
+
+        Ok(true)
+    }
+
+    fn update(&mut self, renderer: &Renderer, delta_time: f32) -> Result<(), Error> {
+        let settings = self.settings.borrow();
+        let sampler_options = block_sampler_options(renderer, &settings);
+
+        if sampler_options != self.sampler_options {
+            self.block_texture.set_sampler_options(renderer.get_device(), sampler_options);
+            self.sampler_options = sampler_options;
+        }
+
+        self.shadows.set_quality(renderer.get_device(), settings.get_shadow_quality(), self.sky.get_buffer());
+
+        renderer.set_present_mode(settings.get_present_mode());
+        self.projection.set_fovy(cgmath::Deg(settings.get_fov()));
+        self.player.set_cam_sensitivity(settings.get_mouse_sensitivity());
+        self.player.set_speed(settings.get_movement_speed());
+
+        if *settings.get_key_bindings() != self.key_bindings {
+            self.key_bindings = settings.get_key_bindings().clone();
+            self.controls = get_controls(&self.key_bindings);
+        }
+
+        let draw_distance = settings.get_draw_distance();
+        drop(settings);
+
+        // the server only sends us chunks it has loaded, so it has to load further too
+        if draw_distance != self.session.get_world().get_render_distance() {
+            self.server.set_render_distance(draw_distance);
+            self.session.set_view_distance(draw_distance)?;
+        }
+
+        self.run_commands();
+        self.server.step()?;
+
+        // steer the player first, the world update moves it along with every other entity
+        self.player.update(self.session.get_world_mut(), delta_time);
+        self.session.update(self.player.get_camera().pos_as_vec3(), delta_time)?;
+
+        if let Some(position) = self.session.take_teleport() {
+            self.player.teleport(self.session.get_world_mut(), position);
+        }
+
+        if let Some(position) = self.player.get_position(self.session.get_world()) {
+            self.session.send_position(position)?;
+        }
+
+        self.upload_chunk_meshes(renderer);
+
+        Ok(())
+    }
+
+    fn prepare_frame(&mut self, renderer: &Renderer, _encoder: &mut wgpu::CommandEncoder) -> Result<(), Error> {
+        let (camera, projection) = match renderer.get_view_override() {
+            Some(view) => (view.camera, view.projection),
+            // the simulation runs at a fixed rate, so draw the camera between its last two positions
+            None => (
+                self.player.get_interpolated_camera(self.session.get_world(), renderer.get_interpolation_alpha()),
+                self.projection
+            )
+        };
+
+        renderer.update_camera(&camera, &projection);
+
+        let settings = self.settings.borrow();
+
+        // blocks are gone in the fog by the edge of the render distance
+        let fog_distance = (settings.get_draw_distance() * world::chunk::CHUNK_SIZE) as f32;
+        let time_of_day = self.session.get_world().get_time_of_day();
+        self.sky.update(renderer.get_queue(), &camera, &projection, time_of_day, fog_distance);
+
+        let sun_direction = self.sky.get_colors().sun_direction;
+        self.shadows.update(renderer.get_queue(), &camera, &projection, sun_direction, fog_distance.min(MAX_SHADOW_DISTANCE));
+
+        renderer.set_post_processing(*settings.get_post_processing());
+
+        // everything looks blue from inside water
+        let eye = Vector3::new(camera.position.x.floor() as i32, camera.position.y.floor() as i32, camera.position.z.floor() as i32);
+        let underwater = self.session.get_world().get_block(eye)
+            .is_some_and(|block| block.is_fluid());
+
+        renderer.set_screen_tint(if underwater { Some(UNDERWATER_TINT) } else { None });
+
+        Ok(())
+    }
+
+    fn reload_shaders(&mut self, renderer: &Renderer, _changed: &[String]) -> Result<(), Error> {
+        // the renderer already rebuilt whatever it could, this only picks the new pipelines up
+        self.block_material.refresh(renderer)?;
+        self.sky.refresh(renderer)?;
+        self.shadows.refresh(renderer)
+    }
+
+    fn build_graph(&mut self, _renderer: &Renderer, graph: &mut RenderGraph<'_>) -> Result<(), Error> {
+        let shadow_maps = graph.declare("shadow_maps");
+        let scene_color = graph.get_scene_color();
+        let scene_depth = graph.get_scene_depth();
+
+        graph.add_pass(PassDesc::render(SKY_PASS)
+            .with_color_attachment(scene_color, Some(render_graph::CLEAR_COLOR))
+            .with_depth_attachment(scene_depth, Some(1.0)));
+
+        graph.add_pass(PassDesc::render(TERRAIN_PASS)
+            .with_read(shadow_maps)
+            .with_color_attachment(scene_color, None)
+            .with_depth_attachment(scene_depth, None));
+
+        // the terrain reads the shadow maps, so this runs before it anyway
+        graph.add_pass(PassDesc::encoder(SHADOW_PASS)
+            .with_write(shadow_maps));
+
+        Ok(())
+    }
+
+    fn encode_pass(&mut self, renderer: &Renderer, pass: &PassInfo<'_>, encoder: &mut wgpu::CommandEncoder) -> Result<(), Error> {
+        match pass.get_name() {
+            SHADOW_PASS => self.shadows.render(encoder, renderer.get_camera_bind_group(), &self.chunk_meshes),
+            name => return Err(anyhow!(format!("no encoder pass called '{}'", name)))
+        }
+
+        Ok(())
+    }
+
+    fn render_pass<'a>(&'a mut self, renderer: &'a Renderer, pass: &PassInfo<'a>, render_pass: &mut wgpu::RenderPass<'a>) -> Result<(), Error> {
+        match pass.get_name() {
+            SKY_PASS => self.sky.draw(render_pass),
+            TERRAIN_PASS => {
+                self.block_material.bind(render_pass, &[
+                    (0, &self.block_texture.bind_group),
+                    (1, renderer.get_camera_bind_group()),
+                    (3, self.shadows.get_lighting_bind_group()),
+                ]);
+
+                self.chunk_meshes.draw(render_pass, 2);
 
-        register_blocks()?;
+                self.render_text(renderer, pass.get_delta_time());
+            },
+            name => return Err(anyhow!(format!("no render pass called '{}'", name)))
+        }
+
+        Ok(())
+    }
+
+    fn exit(&mut self) -> Result<(), Error> {
+        /*if self.world.get_chunks_loading() > 0 {
+            println!("Waiting for {} background threads to close...", self.world.get_chunks_loading());
+
+            while self.world.get_chunks_loading() > 0 { self.world.fetch_chunks(); }
+        }*/
 
-        let world = world::World::new(seed, 5);
+        config::save_settings(&self.settings.borrow());
 
-        let player_position = Vector3::unit_y() * 64.0;
+        self.session.disconnect("Quit");
+        self.server.shutdown()
+    }
+}
+
+impl WillekeuirigState {
+    /// Plays a world from the saves directory
+    pub fn load(renderer: &mut Renderer, window: &Window, mut world: WorldInfo) -> Result<Box<Self>, Error> {
+        world.mark_played()?;
+
+        eprintln!("[LOG] Loading world '{}'", world.get_name());
+        Self::open(renderer, window, world.get_seed(), Some(world.get_directory().to_path_buf()))
+    }
+
+    // a game on `seed`, saving chunks into `world_directory` if there is one
+    fn open(renderer: &mut Renderer, window: &Window, seed: u32, world_directory: Option<PathBuf>) -> Result<Box<Self>, Error> {
+        let device = renderer.get_device();
+
+        let settings = config::load_settings();
+        let key_bindings = settings.get_key_bindings().clone();
+        let controls = get_controls(&key_bindings);
+
+        // the block texture array has a layer for every texture the registered blocks use
+        blocks::register_blocks()?;
+
+        let sampler_options = block_sampler_options(renderer, &settings);
+
+        let texture_bytes = include_bytes!("../../../res/textures/block_atlas.png");
+        let block_texture = texture::Texture::array_from_atlas_bytes(device, renderer.get_queue(),
+            texture_bytes, Registry::current().get_block_textures(), TextureCoords::TEX_WIDTH_HEIGHT as u32,
+            sampler_options, "block_texture")?;
+
+        let mut server = Server::new(ServerConfig {
+            seed,
+            render_distance: settings.get_draw_distance(),
+            world_directory,
+            ..Default::default()
+        })?;
+
+        let transport = server.connect_loopback();
+        let mut session = ClientSession::connect(Box::new(transport), "Player", settings.get_draw_distance())?;
+
+        // the server answers the handshake on its next step
+        server.step()?;
+        session.update(server.get_spawn_point(), 0.0)?;
+
+        if !session.is_playing() {
+            return Err(anyhow!("the single player server didn't accept our connection"));
+        }
+
+        let chunk_meshes = ChunkMeshCache::new(device);
+        let sky = Sky::new(renderer)?;
+
+        let player_position = session.get_spawn_point();
 
         let player = player::Player::new(
+            session.get_world_mut(),
             player_position,
             //Vector3::zero(), // rotation
-            0.4,
-            4.0,
+            settings.get_mouse_sensitivity(),
+            settings.get_movement_speed(),
         );
 
         let projection = camera::Projection::new(
             renderer.get_surface_config().width,
             renderer.get_surface_config().height,
-            cgmath::Deg(45.0),
+            cgmath::Deg(settings.get_fov()),
             0.1,
             1000.0
         );
         //let camera_controller = camera_controller::CameraController::new(4.0, 0.4);
-        let mut camera_uniform = camera_uniform::CameraUniform::new();
+        renderer.update_camera(player.get_camera(), &projection);
+        renderer.set_present_mode(settings.get_present_mode());
 
-        camera_uniform.update_view_proj(player.get_camera(), &projection);
-
-        let camera_buffer = device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Uniform Buffer"),
-                contents: bytemuck::cast_slice(&[camera_uniform]),
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
-            }
-        );
+        let shadows = ShadowMaps::new(renderer, settings.get_shadow_quality(), sky.get_buffer())?;
 
-        let camera_bind_group_layout = device.create_bind_group_layout(
-            &wgpu::BindGroupLayoutDescriptor {
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::VERTEX,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    }
-                ],
-                label: Some("uniform_bind_group_layout"),
-            }
-        );
-
-        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &camera_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: camera_buffer.as_entire_binding(),
-                }
-            ],
-            label: Some("uniform_bind_group"),
-        });
-
-
-        let shader_src = include_str!("../../../res/shaders/shader.wgsl");
-
-        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(shader_src.into())
-        });
-
-        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[
-                &block_texture.bind_group_layout,
-                &camera_bind_group_layout
-            ],
-            push_constant_ranges: &[]
-        });
-
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "main",
-                buffers: &[ Vertex::desc()/*, instance::InstanceRaw::desc()*/ ]
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "main",
-                targets: &[wgpu::ColorTargetState {
-                    format: renderer.get_surface_config().format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL
-                }]
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-                polygon_mode: wgpu::PolygonMode::Fill,
-                clamp_depth: false,
-                conservative: false
-            },
-            depth_stencil: Some(
-                wgpu::DepthStencilState {
-                    format: texture::Texture::DEPTH_FORMAT,
-                    depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::Less,
-                    stencil: wgpu::StencilState::default(),
-                    bias: wgpu::DepthBiasState::default(),
-                }
-            ),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false
-            }
-        });
+        let block_material = Material::new(renderer, PipelineDescriptor::new("Block Pipeline", "shader.wgsl")
+            .with_vertex_layout(ChunkVertex::desc())
+            .with_bind_group(texture::bind_group_layout_entries(wgpu::TextureViewDimension::D2Array, false))
+            .with_bind_group(camera_uniform::bind_group_layout_entries())
+            .with_bind_group(chunk_mesh_cache::origin_layout_entries())
+            .with_bind_group(shadow::lighting_layout_entries())
+            .with_color_format(renderer.get_scene_format()))?;
 
         match renderer.update_cursor_visibility(window) {
             Ok(_) => {
                 Ok(Box::new(Self {
                     //registry: Arc::new(registry),
-                    render_pipeline,
+                    block_material,
 
                     block_texture,
+                    settings: Rc::new(RefCell::new(settings)),
+                    key_bindings,
+                    controls,
+                    sampler_options,
+                    console: Rc::new(RefCell::new(Console::new()?)),
         
-                    world,
+                    server,
+                    session,
+                    chunk_meshes,
+
+                    sky,
+                    shadows,
                     
                     //camera,
                     //camera_controller,
                     player,
                     projection,
         
-                    camera_uniform,
-                    camera_buffer,
-                    camera_bind_group,
-        
-                    cursor_visible: false, 
+                    transition: None,
                 }))
             },
             Err(err) => Err(anyhow!(err))
         }
     }
 
-    fn on_resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) -> Result<(), Error> {
-        self.projection.resize(new_size.width, new_size.height);
+    // runs what was typed into the console against the server's world, from where the player is
+    fn run_commands(&mut self) {
+        let lines = self.console.borrow_mut().take_pending();
 
-        Ok(())
-    }
-
-    fn handle_keys(&mut self, input_manager: &renderer::input_manager::InputManager)  -> Result<bool, Error> {
-        if input_manager.key_just_pressed(VirtualKeyCode::Escape) {
-            self.cursor_visible = !self.cursor_visible;
+        if lines.is_empty() {
+            return;
         }
 
-        Ok(
-            if self.cursor_visible {
-                false
-            } else {
-                self.player.process_keyboard(input_manager)
+        let origin = self.player.get_position(self.session.get_world())
+            .unwrap_or_else(|| self.server.get_spawn_point());
+
+        for line in lines {
+            let mut context = CommandContext::new(self.server.get_world_mut(), origin);
+            let result = self.console.borrow().get_commands().execute(&line, &mut context);
+
+            let output = context.take_output();
+            let effects = context.take_effects();
+
+            let mut console = self.console.borrow_mut();
+
+            for line in output {
+                console.print(line);
             }
-        )
-    }
 
-    fn handle_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) -> Result<bool, Error> {
-        if !self.cursor_visible {
-            self.player.get_camera_controller_mut().process_mouse(mouse_dx, mouse_dy);
-        }
-        
-        Ok(true)
-    }
+            if let Err(err) = result {
+                console.print(format!("Error: {}", err));
+            }
 
-    fn update(&mut self, renderer: &Renderer, delta_time: f32) -> Result<(), Error> {
-        self.world.create_or_destroy_chunks(&self.player.get_camera().pos_as_vec3());
-        self.world.update(renderer.get_device().clone(), self.player.get_camera().pos_as_vec3());
-        
-        self.player.update(&mut self.world, delta_time);
-        self.camera_uniform.update_view_proj(&self.player.get_camera(), &self.projection);
-        renderer.get_queue().write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
+            drop(console);
 
-        Ok(())
+            for effect in effects {
+                self.apply_command_effect(effect);
+            }
+        }
     }
 
-    fn render<'a>(&'a mut self, renderer: &'a Renderer, render_pass: &mut wgpu::RenderPass<'a>,
-       delta_time: f32) -> Result<(), Error> {
-        render_pass.set_pipeline(&self.render_pipeline);
-        render_pass.set_bind_group(0, &self.block_texture.bind_group, &[]);
-        render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
-
-        for (_, chunk) in self.world.get_renderable_chunks() {
-            if let Some((vertex_buffer, index_buffer, indicies)) = chunk.get_buffers() {
-                render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-                //render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-                render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-                                                
-                render_pass.draw_indexed(0..indicies, 0, 0..1);
-            }
+    fn apply_command_effect(&mut self, effect: CommandEffect) {
+        match effect {
+            CommandEffect::Teleport(position) => {
+                // the server has to know, or it'd put us right back
+                self.server.teleport_player(self.session.get_player_id(), position);
+                self.player.teleport(self.session.get_world_mut(), position);
+            },
+            CommandEffect::SetGameMode(game_mode) => self.player.set_game_mode(game_mode),
+            // the world follows the setting on the next update
+            CommandEffect::SetRenderDistance(render_distance) => self.settings.borrow_mut().set_draw_distance(render_distance),
         }
+    }
 
-        self.render_text(renderer, delta_time);
+    // bring the GPU copies of chunk meshes in line with what the world built or unloaded this update
+    fn upload_chunk_meshes(&mut self, renderer: &Renderer) {
+        let world = self.session.get_world_mut();
 
-        Ok(())
-    }
+        for chunk_pos in world.take_unloaded_chunks() {
+            self.chunk_meshes.free(chunk_pos);
+        }
 
-    fn exit(&mut self) -> Result<(), Error> {
-        /*if self.world.get_chunks_loading() > 0 {
-            println!("Waiting for {} background threads to close...", self.world.get_chunks_loading());
+        for chunk_pos in world.take_remeshed_chunks() {
+            let mesh = world.get_renderable_chunks().get(&chunk_pos)
+                .and_then(|chunk| chunk.get_mesh());
 
-            while self.world.get_chunks_loading() > 0 { self.world.fetch_chunks(); }
-        }*/
+            match mesh {
+                Some(vertices) => self.chunk_meshes.upload(renderer.get_device(), renderer.get_queue(), chunk_pos, vertices),
+                None => { self.chunk_meshes.free(chunk_pos); }
+            }
+        }
 
-        Ok(())
+        self.chunk_meshes.prepare(renderer.get_device(), renderer.get_queue());
     }
-}
 
-impl WillekeuirigState {
     fn render_text<'a>(&'a self, renderer: &'a Renderer, delta_time: f32) {
         let backend_str = match &renderer.get_adapter().get_info().backend {
             wgpu::Backend::BrowserWebGpu => "BrowserWebGpu",
@@ -309,7 +526,7 @@ impl WillekeuirigState {
         );
 
         renderer.queue_string(
-            &format!("Player flying: {}", self.player.is_flying()),
+            &format!("Game mode: {}, flying: {}", self.player.get_game_mode().get_name(), self.player.is_flying()),
             (5.0, 130.0), [1.0, 1.0, 1.0, 1.0], 20.0
         );
 
@@ -319,7 +536,7 @@ impl WillekeuirigState {
             self.player.get_camera().position.z,
         );
 
-        let chunk_pos = match self.world.get_chunk_from_world(&p_pos) {
+        let chunk_pos = match self.session.get_world().get_chunk_from_world(&p_pos) {
             Some(chunk) => {
                 let chunk_pos = chunk.get_pos();
                 format!("[{},{},{}]", chunk_pos.x, chunk_pos.y, chunk_pos.z)
@@ -331,26 +548,44 @@ impl WillekeuirigState {
             &format!("Chunk: {}", chunk_pos),
             (5.0, 155.0), [1.0, 1.0, 1.0, 1.0], 20.0
         );
-    }
-}
 
-fn register_blocks() -> Result<(), Error> {
-    let mut grass_block = Block::new(Identifier::from_str("willekeurig:grass_block")?,0.0, 0.0);
-    grass_block.set_texture_bottom(64.0, 0.0); 
-    grass_block.set_side_textures(32.0, 0.0);
+        let time = self.session.get_world().get_time();
 
-    let dirt = Block::new(Identifier::from_str("willekeurig:dirt")?, 64.0, 0.0);
-    let stone = Block::new(Identifier::from_str("willekeurig:stone")?, 0.0, 32.0);
+        renderer.queue_string(
+            &format!("Day {}, time {:.3}{}", time.get_day(), time.get_time_of_day(), if time.is_frozen() { " (frozen)" } else { "" }),
+            (5.0, 180.0), [1.0, 1.0, 1.0, 1.0], 20.0
+        );
 
-    let mut registry = Registry::new();
+        renderer.queue_string(
+            &format!("Shadows: {:?}", self.shadows.get_quality()),
+            (5.0, 205.0), [1.0, 1.0, 1.0, 1.0], 20.0
+        );
 
-    registry.register_block(grass_block)?;
+        let settings = self.settings.borrow();
+        let post_processing = settings.get_post_processing();
 
-    registry.register_block(dirt)?;
+        renderer.queue_string(
+            &format!("Tonemapping: {:?}, FXAA: {}", post_processing.tonemapping, post_processing.fxaa),
+            (5.0, 230.0), [1.0, 1.0, 1.0, 1.0], 20.0
+        );
+    }
+}
 
-    registry.register_block(stone)?;
+// bindings to keys that don't exist fall back to the defaults
+fn get_controls(key_bindings: &KeyBindings) -> Controls {
+    let (controls, warnings) = Controls::from_bindings(key_bindings);
 
-    registry.make_current();
+    for warning in warnings {
+        eprintln!("[LOG] {}", warning);
+    }
+
+    controls
+}
 
-    Ok(())
-}
\ No newline at end of file
+// anisotropy only where the adapter can do it
+fn block_sampler_options(renderer: &Renderer, settings: &Settings) -> texture::SamplerOptions {
+    texture::SamplerOptions {
+        filtering: settings.get_texture_filtering(),
+        anisotropy: if renderer.supports_anisotropy() { settings.get_anisotropy() } else { 1 },
+    }
+}
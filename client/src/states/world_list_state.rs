@@ -0,0 +1,214 @@
+use std::path::Path;
+
+use anyhow::{Result, Error};
+use winit::{event::VirtualKeyCode, window::Window};
+
+use renderer::{Renderer, RenderableState, Transition, input_manager::InputManager, ui::{Anchor, Layout, Rect}};
+
+use crate::saves::{self, WorldInfo};
+use super::willekeuirig_state::WillekeuirigState;
+
+const PANEL_WIDTH: f32 = 560.0;
+const PANEL_HEIGHT: f32 = 520.0;
+const ROW_HEIGHT: f32 = 40.0;
+const LIST_HEIGHT: f32 = 200.0;
+
+const ERROR_COLOR: [f32; 4] = [1.0, 0.4, 0.4, 1.0];
+
+/// Picks a single player world to play, or makes or deletes one
+pub struct WorldListState {
+    worlds: Vec<WorldInfo>,
+    selected: Option<usize>,
+    // delete has to be clicked twice
+    confirm_delete: bool,
+
+    new_name: String,
+    new_seed: String,
+
+    // why the last thing tried didn't work
+    error: Option<String>,
+    transition: Option<Transition>,
+}
+
+impl WorldListState {
+    fn refresh(&mut self) {
+        self.selected = None;
+        self.confirm_delete = false;
+
+        match saves::list_worlds(Path::new(saves::SAVES_DIRECTORY)) {
+            Ok(worlds) => self.worlds = worlds,
+            Err(err) => {
+                self.worlds = Vec::new();
+                self.error = Some(err.to_string());
+            }
+        }
+    }
+
+    fn play(&mut self, world: WorldInfo) {
+        self.transition = Some(Transition::Reset(Box::new(move |renderer, window| {
+            let state: Box<dyn RenderableState> = WillekeuirigState::load(renderer, window, world)?;
+            Ok(state)
+        })));
+    }
+
+    fn create_world(&mut self) {
+        let seed = saves::parse_seed(&self.new_seed);
+
+        match WorldInfo::create(Path::new(saves::SAVES_DIRECTORY), &self.new_name, seed) {
+            Ok(world) => {
+                eprintln!("[LOG] Created world '{}' with seed {}", world.get_name(), seed);
+                self.play(world);
+            },
+            Err(err) => self.error = Some(err.to_string())
+        }
+    }
+
+    fn delete_selected(&mut self) {
+        let world = match self.selected {
+            Some(index) => self.worlds[index].clone(),
+            None => return
+        };
+
+        if !self.confirm_delete {
+            self.confirm_delete = true;
+            return;
+        }
+
+        if let Err(err) = world.delete() {
+            self.error = Some(err.to_string());
+        }
+
+        self.refresh();
+    }
+}
+
+impl RenderableState for WorldListState {
+    fn get_state_id(&self) -> u32 { 3 }
+
+    fn is_cursor_visible(&self) -> bool { true }
+
+    fn take_transition(&mut self) -> Option<Transition> { self.transition.take() }
+
+    fn new(_renderer: &mut Renderer, _window: &Window) -> Result<Box<Self>, Error> {
+        let mut state = Self {
+            worlds: Vec::new(),
+            selected: None,
+            confirm_delete: false,
+
+            new_name: String::from("New World"),
+            new_seed: String::new(),
+
+            error: None,
+            transition: None,
+        };
+
+        state.refresh();
+
+        Ok(Box::new(state))
+    }
+
+    fn update(&mut self, _renderer: &Renderer, _delta_time: f32) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn on_resize(&mut self, _new_size: winit::dpi::PhysicalSize<u32>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn handle_keys(&mut self, input_manager: &InputManager) -> Result<bool, Error> {
+        if input_manager.key_just_pressed(VirtualKeyCode::Escape) {
+            self.transition = Some(Transition::Pop);
+        }
+
+        Ok(false)
+    }
+
+    fn handle_mouse(&mut self, _mouse_dx: f64, _mouse_dy: f64) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    fn prepare_frame(&mut self, renderer: &Renderer, _encoder: &mut wgpu::CommandEncoder) -> Result<(), Error> {
+        let mut ui = renderer.get_ui();
+        let panel = ui.get_screen_rect().anchored(Anchor::Center, PANEL_WIDTH, PANEL_HEIGHT);
+
+        ui.panel("world_list", panel);
+
+        let mut column = Layout::column(panel.shrink(20.0), 10.0);
+
+        ui.label(column.next(30.0), "Worlds", Anchor::Left);
+
+        // the list, each world a button that selects it
+        let worlds = &self.worlds;
+        let mut clicked = None;
+        let selected = self.selected;
+
+        ui.scroll_list("worlds", column.next(LIST_HEIGHT), ROW_HEIGHT, worlds.len(), |ui, index, rect| {
+            let world = &worlds[index];
+            let text = format!("{}  (seed {})", world.get_name(), world.get_seed());
+
+            if ui.button("world", rect.shrink(2.0), &text).clicked {
+                clicked = Some(index);
+            }
+
+            if selected == Some(index) {
+                let accent = ui.get_style().accent_color;
+                ui.rect(Rect::new(rect.x + 2.0, rect.y + 2.0, 4.0, rect.height - 4.0), accent);
+            }
+        });
+
+        if let Some(index) = clicked {
+            self.selected = Some(index);
+            self.confirm_delete = false;
+        }
+
+        let world_buttons = column.next(ROW_HEIGHT).columns(2, 10.0);
+
+        if ui.button("play", world_buttons[0], "Play").clicked {
+            if let Some(index) = self.selected {
+                self.play(self.worlds[index].clone());
+            }
+        }
+
+        let delete_text = if self.confirm_delete { "Really delete?" } else { "Delete" };
+
+        if ui.button("delete", world_buttons[1], delete_text).clicked {
+            self.delete_selected();
+        }
+
+        column.skip(10.0);
+        ui.label(column.next(30.0), "New world", Anchor::Left);
+
+        let fields = column.next(ROW_HEIGHT).columns(2, 10.0);
+        let name = ui.text_field("name", fields[0], &mut self.new_name);
+        let seed = ui.text_field("seed", fields[1], &mut self.new_seed);
+
+        if self.new_seed.is_empty() {
+            let hint = fields[1].shrink(ui.get_style().padding);
+            ui.label(hint, "Random seed", Anchor::Left);
+        }
+
+        let bottom_buttons = column.next(ROW_HEIGHT).columns(2, 10.0);
+        let create = ui.button("create", bottom_buttons[0], "Create and play").clicked;
+
+        if create || name.submitted || seed.submitted {
+            self.create_world();
+        }
+
+        if ui.button("back", bottom_buttons[1], "Back").clicked {
+            self.transition = Some(Transition::Pop);
+        }
+
+        if let Some(error) = &self.error {
+            let line = column.next(24.0);
+            let size = ui.get_style().text_size;
+
+            ui.text(line.x, line.y, error, size, ERROR_COLOR);
+        }
+
+        Ok(())
+    }
+
+    fn exit(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
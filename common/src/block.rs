@@ -1,56 +1,70 @@
-use renderer::vertex::Vertex;
-
-use super::identifier::Identifier;
-
-pub const VERTICES_FRONT: &[Vertex] = &[
-    Vertex { position: [-0.5, -0.5,  0.5], tex_coords: [0.0625, 0.0625], },
-    Vertex { position: [ 0.5, -0.5,  0.5], tex_coords: [0.125, 0.0625], },
-    Vertex { position: [ 0.5,  0.5,  0.5], tex_coords: [0.125, 0.0], },
-    Vertex { position: [-0.5,  0.5,  0.5], tex_coords: [0.0625, 0.0], },
-];
-
-pub const VERTICES_BACK: &[Vertex] = &[
-    Vertex { position: [-0.5,  0.5, -0.5], tex_coords: [0.0625, 0.0], },
-    Vertex { position: [ 0.5,  0.5, -0.5], tex_coords: [0.125, 0.0], },
-    Vertex { position: [ 0.5, -0.5, -0.5], tex_coords: [0.125, 0.0625], },
-    Vertex { position: [-0.5, -0.5, -0.5], tex_coords: [0.0625, 0.0625], },
-];
+use cgmath::Vector3;
+
+use super::{identifier::Identifier, tick::BlockTickHandler};
+
+/// Which way a block face points. The discriminant is the face index packed into chunk vertices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockFace {
+    Front = 0,
+    Back = 1,
+    Top = 2,
+    Bottom = 3,
+    Left = 4,
+    Right = 5,
+}
 
-pub const VERTICES_TOP: &[Vertex] = &[
-    Vertex { position: [-0.5,  0.5,  0.5], tex_coords: [0.0, 0.0], },
-    Vertex { position: [ 0.5,  0.5,  0.5], tex_coords: [0.0625, 0.0], },
-    Vertex { position: [ 0.5,  0.5, -0.5], tex_coords: [0.0625, 0.0625], },
-    Vertex { position: [-0.5,  0.5, -0.5], tex_coords: [0.0, 0.0625], },
-];
+/// Corners of a face as offsets from the block's minimum corner, with the texture corner for each.
+/// Wound counter-clockwise seen from outside, drawn as triangles 0 1 2 and 2 3 0.
+pub struct FaceQuad {
+    pub corners: [[u32; 3]; 4],
+    pub uvs: [[u32; 2]; 4],
+}
 
-pub const VERTICES_BOTTOM: &[Vertex] = &[
-    Vertex { position: [-0.5, -0.5, -0.5], tex_coords: [0.125, 0.0], },
-    Vertex { position: [ 0.5, -0.5, -0.5], tex_coords: [0.1875, 0.0], },
-    Vertex { position: [ 0.5, -0.5,  0.5], tex_coords: [0.1875, 0.0625], },
-    Vertex { position: [-0.5, -0.5,  0.5], tex_coords: [0.125, 0.0625], },
+const FACE_QUADS: [FaceQuad; 6] = [
+    // front
+    FaceQuad { corners: [[0, 0, 1], [1, 0, 1], [1, 1, 1], [0, 1, 1]], uvs: [[0, 1], [1, 1], [1, 0], [0, 0]] },
+    // back
+    FaceQuad { corners: [[0, 1, 0], [1, 1, 0], [1, 0, 0], [0, 0, 0]], uvs: [[1, 0], [0, 0], [0, 1], [1, 1]] },
+    // top
+    FaceQuad { corners: [[0, 1, 1], [1, 1, 1], [1, 1, 0], [0, 1, 0]], uvs: [[0, 0], [1, 0], [1, 1], [0, 1]] },
+    // bottom
+    FaceQuad { corners: [[0, 0, 0], [1, 0, 0], [1, 0, 1], [0, 0, 1]], uvs: [[1, 0], [0, 0], [0, 1], [1, 1]] },
+    // left
+    FaceQuad { corners: [[0, 0, 0], [0, 0, 1], [0, 1, 1], [0, 1, 0]], uvs: [[1, 1], [0, 1], [0, 0], [1, 0]] },
+    // right
+    FaceQuad { corners: [[1, 0, 1], [1, 0, 0], [1, 1, 0], [1, 1, 1]], uvs: [[1, 1], [0, 1], [0, 0], [1, 0]] },
 ];
 
-pub const VERTICES_LEFT: &[Vertex] = &[
-    Vertex { position: [-0.5, -0.5, -0.5], tex_coords: [0.0625, 0.0625], },
-    Vertex { position: [-0.5, -0.5,  0.5], tex_coords: [0.125, 0.0625], },
-    Vertex { position: [-0.5,  0.5,  0.5], tex_coords: [0.125, 0.0], },
-    Vertex { position: [-0.5,  0.5, -0.5], tex_coords: [0.0625, 0.0], },
-];
+impl BlockFace {
+    pub const ALL: [BlockFace; 6] = [
+        BlockFace::Front, BlockFace::Back, BlockFace::Top, BlockFace::Bottom, BlockFace::Left, BlockFace::Right
+    ];
+
+    pub fn get_index(&self) -> u32 { *self as u32 }
+
+    /// Points out of the block
+    pub fn get_normal(&self) -> Vector3<i32> {
+        match self {
+            BlockFace::Front => Vector3::new(0, 0, 1),
+            BlockFace::Back => Vector3::new(0, 0, -1),
+            BlockFace::Top => Vector3::new(0, 1, 0),
+            BlockFace::Bottom => Vector3::new(0, -1, 0),
+            BlockFace::Left => Vector3::new(-1, 0, 0),
+            BlockFace::Right => Vector3::new(1, 0, 0),
+        }
+    }
 
-pub const VERTICES_RIGHT: &[Vertex] = &[
-    Vertex { position: [ 0.5, -0.5,  0.5], tex_coords: [0.0625, 0.0625], },
-    Vertex { position: [ 0.5, -0.5, -0.5], tex_coords: [0.125, 0.0625], },
-    Vertex { position: [ 0.5,  0.5, -0.5], tex_coords: [0.125, 0.0], },
-    Vertex { position: [ 0.5,  0.5,  0.5], tex_coords: [0.0625, 0.0], },
-];
+    pub fn get_quad(&self) -> &'static FaceQuad { &FACE_QUADS[*self as usize] }
+}
 
+/// A tile of the block atlas, set from its pixel position. The renderer copies every tile the
+/// registry uses into its own texture layer, `layer` is filled in when the block is registered.
 #[derive(Debug, Clone)]
 pub struct TextureCoords {
-    bottom_left_x: f32,
-    bottom_left_y: f32,
+    tile_x: u32,
+    tile_y: u32,
 
-    top_right_x: f32,
-    top_right_y: f32
+    layer: u32,
 }
 
 impl TextureCoords {
@@ -59,12 +73,17 @@ impl TextureCoords {
 
     pub fn new(x: f32, y: f32) -> Self {
         Self {
-            bottom_left_x: x / Self::TEX_ATLAS_SIZE,
-            bottom_left_y: y / Self::TEX_ATLAS_SIZE,
-            top_right_x: (x + Self::TEX_WIDTH_HEIGHT) / Self::TEX_ATLAS_SIZE,
-            top_right_y: (y + Self::TEX_WIDTH_HEIGHT) / Self::TEX_ATLAS_SIZE,
+            tile_x: (x / Self::TEX_WIDTH_HEIGHT) as u32,
+            tile_y: (y / Self::TEX_WIDTH_HEIGHT) as u32,
+
+            layer: 0,
         }
     }
+
+    /// Column and row of the tile in the atlas
+    pub fn get_tile(&self) -> (u32, u32) { (self.tile_x, self.tile_y) }
+
+    pub fn get_layer(&self) -> u32 { self.layer }
 }
 
 #[derive(Debug, Clone)]
@@ -78,6 +97,12 @@ pub struct Block {
     texture_left: TextureCoords,
     texture_right: TextureCoords,
 
+    scheduled_tick_handler: Option<BlockTickHandler>,
+    random_tick_handler: Option<BlockTickHandler>,
+
+    // fluids don't stop movement, and mobs steer around them
+    is_fluid: bool,
+
     //pub position: cgmath::Vector3<f32>,
     //pub rotation: cgmath::Quaternion<f32>,
 }
@@ -93,78 +118,80 @@ impl Block {
             texture_btm: TextureCoords::new(tex_x, tex_y),
             texture_left: TextureCoords::new(tex_x, tex_y),
             texture_right: TextureCoords::new(tex_x, tex_y),
+
+            scheduled_tick_handler: None,
+            random_tick_handler: None,
+
+            is_fluid: false,
         }
     }
 
     pub fn get_identifier(&self) -> &Identifier { &self.identifier }
-}
 
-// texture getters
-impl Block {
-    pub fn get_vert_front(&self) -> Vec<Vertex> {
-        let mut verts: Vec<Vertex> = VERTICES_FRONT.into();
+    pub fn is_fluid(&self) -> bool { self.is_fluid }
 
-        verts[0].tex_coords = [ self.texture_back.bottom_left_x, self.texture_back.top_right_y ];
-        verts[1].tex_coords = [ self.texture_back.top_right_x, self.texture_back.top_right_y ];
-        verts[2].tex_coords = [ self.texture_back.top_right_x, self.texture_back.bottom_left_y ];
-        verts[3].tex_coords = [ self.texture_back.bottom_left_x, self.texture_back.bottom_left_y ];
+    /// Whether entities collide with this block
+    pub fn is_solid(&self) -> bool { !self.is_fluid }
 
-        verts
+    pub fn set_fluid(&mut self, is_fluid: bool) {
+        self.is_fluid = is_fluid;
     }
+}
 
-    pub fn get_vert_back(&self) -> Vec<Vertex> {
-        let mut verts: Vec<Vertex> = VERTICES_BACK.into();
-
-        verts[0].tex_coords = [ self.texture_back.top_right_x, self.texture_back.bottom_left_y ];
-        verts[1].tex_coords = [ self.texture_back.bottom_left_x, self.texture_back.bottom_left_y ];
-        verts[2].tex_coords = [ self.texture_back.bottom_left_x, self.texture_back.top_right_y ];
-        verts[3].tex_coords = [ self.texture_back.top_right_x, self.texture_back.top_right_y ];
-
-        verts
-    }
+// tick handlers
+impl Block {
+    pub fn get_scheduled_tick_handler(&self) -> Option<BlockTickHandler> { self.scheduled_tick_handler }
 
-    pub fn get_vert_top(&self) -> Vec<Vertex> {
-        let mut verts: Vec<Vertex> = VERTICES_TOP.into();
+    pub fn get_random_tick_handler(&self) -> Option<BlockTickHandler> { self.random_tick_handler }
 
-        verts[0].tex_coords = [ self.texture_top.bottom_left_x, self.texture_top.bottom_left_y ];
-        verts[1].tex_coords = [ self.texture_top.top_right_x, self.texture_top.bottom_left_y ];
-        verts[2].tex_coords = [ self.texture_top.top_right_x, self.texture_top.top_right_y ];
-        verts[3].tex_coords = [ self.texture_top.bottom_left_x, self.texture_top.top_right_y ];
+    pub fn ticks_randomly(&self) -> bool { self.random_tick_handler.is_some() }
 
-        verts
+    /// Runs when a tick scheduled for this block comes due
+    pub fn set_scheduled_tick_handler(&mut self, handler: BlockTickHandler) {
+        self.scheduled_tick_handler = Some(handler);
     }
 
-    pub fn get_vert_bottom(&self) -> Vec<Vertex> {
-        let mut verts: Vec<Vertex> = VERTICES_BOTTOM.into();
-
-        verts[0].tex_coords = [ self.texture_btm.top_right_x, self.texture_btm.bottom_left_y ];
-        verts[1].tex_coords = [ self.texture_btm.bottom_left_x, self.texture_btm.bottom_left_y ];
-        verts[2].tex_coords = [ self.texture_btm.bottom_left_x, self.texture_btm.top_right_y ];
-        verts[3].tex_coords = [ self.texture_btm.top_right_x, self.texture_btm.top_right_y ];
-
-        verts
+    /// Runs whenever this block is picked by the world's random ticks
+    pub fn set_random_tick_handler(&mut self, handler: BlockTickHandler) {
+        self.random_tick_handler = Some(handler);
     }
+}
 
-    pub fn get_vert_left(&self) -> Vec<Vertex> {
-        let mut verts: Vec<Vertex> = VERTICES_LEFT.into();
+// texture getters
+impl Block {
+    fn get_texture(&self, face: BlockFace) -> &TextureCoords {
+        match face {
+            BlockFace::Front => &self.texture_front,
+            BlockFace::Back => &self.texture_back,
+            BlockFace::Top => &self.texture_top,
+            BlockFace::Bottom => &self.texture_btm,
+            BlockFace::Left => &self.texture_left,
+            BlockFace::Right => &self.texture_right,
+        }
+    }
 
-        verts[0].tex_coords = [ self.texture_front.top_right_x, self.texture_front.top_right_y ];
-        verts[1].tex_coords = [ self.texture_front.bottom_left_x, self.texture_front.top_right_y ];
-        verts[2].tex_coords = [ self.texture_front.bottom_left_x, self.texture_front.bottom_left_y ];
-        verts[3].tex_coords = [ self.texture_front.top_right_x, self.texture_front.bottom_left_y ];
+    /// The texture layer drawn on this face, only meaningful once the block is registered
+    pub fn get_texture_index(&self, face: BlockFace) -> u32 { self.get_texture(face).get_layer() }
 
-        verts
-    }
+    /// The atlas tile of every face, in `BlockFace::ALL` order
+    pub fn get_texture_tiles(&self) -> [(u32, u32); 6] {
+        let mut tiles = [(0, 0); 6];
 
-    pub fn get_vert_right(&self) -> Vec<Vertex> {
-        let mut verts: Vec<Vertex> = VERTICES_RIGHT.into();
+        for (tile, face) in tiles.iter_mut().zip(BlockFace::ALL.iter()) {
+            *tile = self.get_texture(*face).get_tile();
+        }
 
-        verts[0].tex_coords = [ self.texture_front.top_right_x, self.texture_front.top_right_y ];
-        verts[1].tex_coords = [ self.texture_front.bottom_left_x, self.texture_front.top_right_y ];
-        verts[2].tex_coords = [ self.texture_front.bottom_left_x, self.texture_front.bottom_left_y ];
-        verts[3].tex_coords = [ self.texture_front.top_right_x, self.texture_front.bottom_left_y ];
+        tiles
+    }
 
-        verts
+    /// Set by the registry, in `BlockFace::ALL` order
+    pub(crate) fn set_texture_layers(&mut self, layers: [u32; 6]) {
+        self.texture_front.layer = layers[0];
+        self.texture_back.layer = layers[1];
+        self.texture_top.layer = layers[2];
+        self.texture_btm.layer = layers[3];
+        self.texture_left.layer = layers[4];
+        self.texture_right.layer = layers[5];
     }
 }
 
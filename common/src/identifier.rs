@@ -1,6 +1,6 @@
 use anyhow::{Result, Error, anyhow};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Identifier {
     namespace: String,
     name: String
@@ -23,6 +23,8 @@ impl Identifier {
 
         if splits.len() > 2 {
             Err(anyhow!(format!("IDs must contain only 1 colon (1 namespace, 1 identifying name), but '{}' has {}", id, splits.len())))
+        } else if splits.len() < 2 {
+            Err(anyhow!(format!("ID '{}' has no namespace, it should look like 'namespace:name'", id)))
         } else {
             let id = Self { namespace: String::from(splits[0]), name: String::from(splits[1]) };
 
@@ -2,6 +2,10 @@ pub mod block;
 pub mod identifier;
 pub mod registry;
 pub mod settings;
+pub mod serialization;
+pub mod tick;
+pub mod timestep;
+pub mod vertex;
 
 #[cfg(test)]
 mod tests {
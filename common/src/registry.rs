@@ -6,7 +6,9 @@ use crate::{block::Block, identifier::Identifier};
 
 #[derive(Default)]
 pub struct Registry {
-    blocks: HashMap<String, Vec<Block>>
+    blocks: HashMap<String, Vec<Block>>,
+    // every atlas tile a block uses, the index is the tile's texture layer
+    block_textures: Vec<(u32, u32)>,
 }
 
 impl Registry {
@@ -29,9 +31,17 @@ lazy_static! {
 
 // registers
 impl Registry {
-    pub fn register_block(&mut self, block: Block) -> Result<(), Error> {
+    pub fn register_block(&mut self, mut block: Block) -> Result<(), Error> {
         match block.get_identifier().validate() {
             Ok(()) => {
+                let mut layers = [0; 6];
+
+                for (layer, tile) in layers.iter_mut().zip(block.get_texture_tiles().iter()) {
+                    *layer = self.get_or_add_block_texture(*tile);
+                }
+
+                block.set_texture_layers(layers);
+
                 self.blocks.entry(block.get_identifier().get_namespace())
                     .or_insert(Vec::new())
                     .push(block);
@@ -41,6 +51,16 @@ impl Registry {
             Err(err) => Err(err)
         }
     }
+
+    fn get_or_add_block_texture(&mut self, tile: (u32, u32)) -> u32 {
+        match self.block_textures.iter().position(|texture| *texture == tile) {
+            Some(layer) => layer as u32,
+            None => {
+                self.block_textures.push(tile);
+                (self.block_textures.len() - 1) as u32
+            }
+        }
+    }
 }
 
 // getters
@@ -58,4 +78,42 @@ impl Registry {
             None => None
         }
     }
-}
\ No newline at end of file
+
+    /// The atlas tiles blocks use, one per texture layer
+    pub fn get_block_textures(&self) -> &[(u32, u32)] { &self.block_textures }
+
+    /// Every registered block, sorted so two registries with the same blocks list them in the same order
+    pub fn get_block_identifiers(&self) -> Vec<Identifier> {
+        let mut identifiers: Vec<Identifier> = self.blocks.values()
+            .flat_map(|blocks| blocks.iter().map(|block| block.get_identifier().clone()))
+            .collect();
+
+        identifiers.sort_by_key(|id| id.as_string());
+
+        identifiers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::BlockFace;
+
+    #[test]
+    fn blocks_share_texture_layers() {
+        let mut grass = Block::new(Identifier::new("test", "grass"), 0.0, 0.0);
+        grass.set_side_textures(32.0, 0.0);
+
+        let mut registry = Registry::new();
+        registry.register_block(grass).unwrap();
+        registry.register_block(Block::new(Identifier::new("test", "side"), 32.0, 0.0)).unwrap();
+
+        assert_eq!(registry.get_block_textures(), &[(1, 0), (0, 0)]);
+
+        let side = registry.get_block(&Identifier::new("test", "side")).unwrap();
+        let grass = registry.get_block(&Identifier::new("test", "grass")).unwrap();
+
+        assert_eq!(side.get_texture_index(BlockFace::Top), grass.get_texture_index(BlockFace::Left));
+        assert_eq!(grass.get_texture_index(BlockFace::Top), 1);
+    }
+}
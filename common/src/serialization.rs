@@ -0,0 +1,140 @@
+use anyhow::{Result, Error, anyhow};
+
+use crate::identifier::Identifier;
+
+// Little-endian byte buffer helpers shared by everything we write to disk
+
+#[derive(Debug, Default)]
+pub struct ByteWriter {
+    bytes: Vec<u8>,
+}
+
+impl ByteWriter {
+    pub fn new() -> Self {
+        Self { ..Default::default() }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> { self.bytes }
+
+    pub fn len(&self) -> usize { self.bytes.len() }
+
+    pub fn is_empty(&self) -> bool { self.bytes.is_empty() }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_i32(&mut self, value: i32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_f32(&mut self, value: f32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_u32(bytes.len() as u32);
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub fn write_string(&mut self, value: &str) {
+        self.write_bytes(value.as_bytes());
+    }
+
+    pub fn write_identifier(&mut self, id: &Identifier) {
+        self.write_string(&id.as_string());
+    }
+}
+
+pub struct ByteReader<'a> {
+    bytes: &'a [u8],
+    cursor: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, cursor: 0 }
+    }
+
+    pub fn remaining(&self) -> usize { self.bytes.len() - self.cursor }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], Error> {
+        if self.remaining() < count {
+            Err(anyhow!(format!("unexpected end of data: wanted {} bytes at offset {}, but only {} remain",
+                count, self.cursor, self.remaining())))
+        } else {
+            let slice = &self.bytes[self.cursor..self.cursor + count];
+            self.cursor += count;
+
+            Ok(slice)
+        }
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut array = [0u8; N];
+        array.copy_from_slice(self.take(N)?);
+
+        Ok(array)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, Error> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.take_array()?))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.take_array()?))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.take_array()?))
+    }
+
+    pub fn read_i32(&mut self) -> Result<i32, Error> {
+        Ok(i32::from_le_bytes(self.take_array()?))
+    }
+
+    pub fn read_f32(&mut self) -> Result<f32, Error> {
+        Ok(f32::from_le_bytes(self.take_array()?))
+    }
+
+    pub fn read_bytes(&mut self) -> Result<&'a [u8], Error> {
+        let len = self.read_u32()? as usize;
+
+        self.take(len)
+    }
+
+    pub fn read_string(&mut self) -> Result<String, Error> {
+        match std::str::from_utf8(self.read_bytes()?) {
+            Ok(string) => Ok(String::from(string)),
+            Err(err) => Err(anyhow!(format!("invalid utf-8 in string: {}", err)))
+        }
+    }
+
+    pub fn read_identifier(&mut self) -> Result<Identifier, Error> {
+        Identifier::from(self.read_string()?)
+    }
+}
@@ -1,15 +1,772 @@
+use std::{collections::BTreeMap, fs, io::ErrorKind, path::Path};
+
+use anyhow::{Result, Error, anyhow};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use toml::{Value, value::Table};
+
+/// The furthest the world can be loaded around the player, in chunks
+pub const MAX_DRAW_DISTANCE: usize = 32;
+
+// `get_name` and `from_name` for enums that are saved by name
+macro_rules! impl_names {
+    ($type:ident { $($variant:ident => $name:literal),* $(,)? }) => {
+        impl $type {
+            pub const NAMES: &'static [&'static str] = &[$($name),*];
+
+            pub fn get_name(&self) -> &'static str {
+                match self {
+                    $($type::$variant => $name),*
+                }
+            }
+
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $($name => Some($type::$variant),)*
+                    _ => None
+                }
+            }
+        }
+    };
+}
+
+/// How block textures are filtered. Mipmaps are only blended between with `Trilinear`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextureFiltering {
+    Nearest,
+    Bilinear,
+    Trilinear,
+}
+
+/// How detailed the sun's shadows are. `Off` skips drawing the shadow maps entirely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShadowQuality {
+    Off,
+    Low,
+    Medium,
+    High,
+}
+
+impl_names!(TextureFiltering {
+    Nearest => "nearest",
+    Bilinear => "bilinear",
+    Trilinear => "trilinear",
+});
+
+impl_names!(ShadowQuality {
+    Off => "off",
+    Low => "low",
+    Medium => "medium",
+    High => "high",
+});
+
+impl ShadowQuality {
+    /// Width and height of each cascade's shadow map
+    pub fn get_map_size(&self) -> u32 {
+        match self {
+            ShadowQuality::Off => 1,
+            ShadowQuality::Low => 1024,
+            ShadowQuality::Medium => 2048,
+            ShadowQuality::High => 2048,
+        }
+    }
+
+    /// Shadow map texels sampled in each direction when softening shadow edges, 0 for a single sample
+    pub fn get_filter_radius(&self) -> u32 {
+        match self {
+            ShadowQuality::Off | ShadowQuality::Low => 0,
+            ShadowQuality::Medium => 1,
+            ShadowQuality::High => 2,
+        }
+    }
+}
+
+/// How the HDR scene is squeezed into what the screen can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tonemapping {
+    /// Anything brighter than white is cut off
+    Clamp,
+    Reinhard,
+    Aces,
+}
+
+impl_names!(Tonemapping {
+    Clamp => "clamp",
+    Reinhard => "reinhard",
+    Aces => "aces",
+});
+
+/// How finished frames are shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PresentMode {
+    /// Waits for vertical sync, no tearing
+    Fifo,
+    /// No tearing, but doesn't wait for older frames to be shown either
+    Mailbox,
+    /// Shows frames as soon as they're done, which can tear
+    Immediate,
+}
+
+impl_names!(PresentMode {
+    Fifo => "fifo",
+    Mailbox => "mailbox",
+    Immediate => "immediate",
+});
+
+/// Presets for everything that trades looks for speed. `Custom` is anything in between.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphicsQuality {
+    Low,
+    Medium,
+    High,
+    Custom,
+}
+
+impl_names!(GraphicsQuality {
+    Low => "low",
+    Medium => "medium",
+    High => "high",
+    Custom => "custom",
+});
+
+/// Something the player can do with a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    /// Down while flying
+    Descend,
+    ToggleFlying,
+    Pause,
+    Console,
+    CycleTextureFiltering,
+    ToggleAnisotropy,
+    CycleShadows,
+    ToggleFxaa,
+}
+
+impl_names!(Action {
+    MoveForward => "move_forward",
+    MoveBack => "move_back",
+    MoveLeft => "move_left",
+    MoveRight => "move_right",
+    Jump => "jump",
+    Descend => "descend",
+    ToggleFlying => "toggle_flying",
+    Pause => "pause",
+    Console => "console",
+    CycleTextureFiltering => "cycle_texture_filtering",
+    ToggleAnisotropy => "toggle_anisotropy",
+    CycleShadows => "cycle_shadows",
+    ToggleFxaa => "toggle_fxaa",
+});
+
+impl Action {
+    pub const ALL: [Action; 13] = [
+        Action::MoveForward, Action::MoveBack, Action::MoveLeft, Action::MoveRight,
+        Action::Jump, Action::Descend, Action::ToggleFlying, Action::Pause, Action::Console,
+        Action::CycleTextureFiltering, Action::ToggleAnisotropy, Action::CycleShadows, Action::ToggleFxaa,
+    ];
+
+    /// Named like winit's `VirtualKeyCode`s
+    pub fn get_default_key(&self) -> &'static str {
+        match self {
+            Action::MoveForward => "W",
+            Action::MoveBack => "S",
+            Action::MoveLeft => "A",
+            Action::MoveRight => "D",
+            Action::Jump => "Space",
+            Action::Descend => "LShift",
+            Action::ToggleFlying => "P",
+            Action::Pause => "Escape",
+            Action::Console => "Grave",
+            Action::CycleTextureFiltering => "F6",
+            Action::ToggleAnisotropy => "F7",
+            Action::CycleShadows => "F8",
+            Action::ToggleFxaa => "F9",
+        }
+    }
+}
+
+/// Which key does what, by key name. Whether the names are real keys is up to whoever reads them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyBindings {
+    keys: BTreeMap<Action, String>,
+}
+
+impl KeyBindings {
+    pub fn get_key(&self, action: Action) -> &str {
+        self.keys.get(&action).map_or(action.get_default_key(), |key| key.as_str())
+    }
+
+    pub fn set_key(&mut self, action: Action, key: &str) {
+        self.keys.insert(action, key.to_string());
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            keys: Action::ALL.iter().map(|action| (*action, action.get_default_key().to_string())).collect(),
+        }
+    }
+}
+
+/// Fullscreen passes run between drawing the world and the text on top
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PostProcessing {
+    pub tonemapping: Tonemapping,
+    pub exposure: f32,
+    // 2.2 leaves the image as it is, higher brightens it
+    pub gamma: f32,
+    pub fxaa: bool,
+    // how much the screen's corners darken, 0 turns it off
+    pub vignette: f32,
+}
+
+impl Default for PostProcessing {
+    fn default() -> Self {
+        Self {
+            tonemapping: Tonemapping::Aces,
+            exposure: 1.0,
+            gamma: 2.2,
+            fxaa: true,
+            vignette: 0.3,
+        }
+    }
+}
+
+/// Saved as TOML, see `SettingsFile` for the layout
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(into = "SettingsFile")]
 pub struct Settings {
+    // in chunks
     draw_distance: usize,
+    // vertical, in degrees
+    fov: f32,
+    present_mode: PresentMode,
+
+    texture_filtering: TextureFiltering,
+    // 1 turns it off, otherwise 2, 4, 8 or 16
+    anisotropy: u8,
+
+    shadow_quality: ShadowQuality,
+
+    post_processing: PostProcessing,
+
+    mouse_sensitivity: f32,
+    // in blocks per second
+    movement_speed: f32,
+    key_bindings: KeyBindings,
 }
 
 impl Settings {
     pub fn new(draw_distance: usize) -> Self {
         Self {
-            draw_distance
+            draw_distance: draw_distance.clamp(1, MAX_DRAW_DISTANCE),
+            fov: 45.0,
+            present_mode: PresentMode::Fifo,
+
+            texture_filtering: TextureFiltering::Nearest,
+            anisotropy: 1,
+
+            shadow_quality: ShadowQuality::Medium,
+
+            post_processing: PostProcessing::default(),
+
+            mouse_sensitivity: 0.4,
+            movement_speed: 4.0,
+            key_bindings: KeyBindings::default(),
+        }
+    }
+
+    /// Reads a settings file written by `to_toml`. Whatever is missing keeps its default, and unknown
+    /// settings or values of the wrong kind are skipped with a warning. A file that isn't TOML at all gives the defaults.
+    pub fn from_toml(text: &str) -> (Self, Vec<String>) {
+        let mut warnings = Vec::new();
+
+        match toml::from_str::<Table>(text) {
+            Ok(table) => {
+                let settings = SettingsFile::read(table, &mut warnings).into_settings(&mut warnings);
+                (settings, warnings)
+            },
+            Err(err) => {
+                warnings.push(format!("{}, using the defaults", err));
+                (Self::default(), warnings)
+            }
         }
     }
 
+    pub fn to_toml(&self) -> Result<String, Error> {
+        let text = match toml::to_string(&SettingsFile::from(self.clone())) {
+            Ok(text) => text,
+            Err(err) => return Err(anyhow!(format!("couldn't write settings: {}", err)))
+        };
+
+        let header = format!(
+            "# Willekeurig settings, unknown ones are skipped with a warning\n\
+            # present_mode is one of {}, fifo waits for vsync\n\
+            # graphics quality is one of {}, the presets set everything down to fxaa\n\n",
+            PresentMode::NAMES.join(", "), GraphicsQuality::NAMES.join(", ")
+        );
+
+        Ok(header + &text)
+    }
+
+    /// Reads the settings at `path`, or the defaults if there's no file there yet
+    pub fn load(path: &Path) -> Result<(Self, Vec<String>), Error> {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(Self::from_toml(&text)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok((Self::default(), Vec::new())),
+            Err(err) => Err(anyhow!(format!("couldn't read settings file '{}': {}", path.display(), err)))
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        if let Some(directory) = path.parent() {
+            if let Err(err) = fs::create_dir_all(directory) {
+                return Err(anyhow!(format!("couldn't create settings folder '{}': {}", directory.display(), err)));
+            }
+        }
+
+        match fs::write(path, self.to_toml()?) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(anyhow!(format!("couldn't write settings file '{}': {}", path.display(), err)))
+        }
+    }
+}
+
+// getters
+impl Settings {
     pub fn get_draw_distance(&self) -> usize {
         self.draw_distance
     }
-}
\ No newline at end of file
+
+    pub fn get_fov(&self) -> f32 { self.fov }
+
+    pub fn get_present_mode(&self) -> PresentMode { self.present_mode }
+
+    pub fn get_texture_filtering(&self) -> TextureFiltering { self.texture_filtering }
+
+    pub fn get_anisotropy(&self) -> u8 { self.anisotropy }
+
+    pub fn get_shadow_quality(&self) -> ShadowQuality { self.shadow_quality }
+
+    pub fn get_post_processing(&self) -> &PostProcessing { &self.post_processing }
+
+    pub fn get_mouse_sensitivity(&self) -> f32 { self.mouse_sensitivity }
+
+    pub fn get_movement_speed(&self) -> f32 { self.movement_speed }
+
+    pub fn get_key_bindings(&self) -> &KeyBindings { &self.key_bindings }
+
+    pub fn get_key_bindings_mut(&mut self) -> &mut KeyBindings { &mut self.key_bindings }
+
+    /// The preset the graphics settings match, `Custom` if none
+    pub fn get_graphics_quality(&self) -> GraphicsQuality {
+        [GraphicsQuality::Low, GraphicsQuality::Medium, GraphicsQuality::High].iter()
+            .copied()
+            .find(|quality| {
+                let mut preset = self.clone();
+                preset.set_graphics_quality(*quality);
+
+                preset == *self
+            })
+            .unwrap_or(GraphicsQuality::Custom)
+    }
+}
+
+// setters
+impl Settings {
+    /// In chunks, between 1 and `MAX_DRAW_DISTANCE`
+    pub fn set_draw_distance(&mut self, draw_distance: usize) {
+        self.draw_distance = draw_distance.clamp(1, MAX_DRAW_DISTANCE);
+    }
+
+    /// Vertical, in degrees between 30 and 110
+    pub fn set_fov(&mut self, fov: f32) {
+        self.fov = fov.clamp(30.0, 110.0);
+    }
+
+    pub fn set_present_mode(&mut self, present_mode: PresentMode) {
+        self.present_mode = present_mode;
+    }
+
+    pub fn set_texture_filtering(&mut self, texture_filtering: TextureFiltering) {
+        self.texture_filtering = texture_filtering;
+    }
+
+    pub fn set_shadow_quality(&mut self, shadow_quality: ShadowQuality) {
+        self.shadow_quality = shadow_quality;
+    }
+
+    pub fn set_post_processing(&mut self, post_processing: PostProcessing) {
+        self.post_processing = post_processing;
+    }
+
+    /// Rounded down to a power of two between 1 and 16
+    pub fn set_anisotropy(&mut self, anisotropy: u8) {
+        let anisotropy = anisotropy.clamp(1, 16);
+
+        self.anisotropy = 1 << (7 - anisotropy.leading_zeros());
+    }
+
+    /// Sets shadows, texture filtering, anisotropy and FXAA to the preset's. `Custom` changes nothing.
+    pub fn set_graphics_quality(&mut self, quality: GraphicsQuality) {
+        let (shadow_quality, texture_filtering, anisotropy, fxaa) = match quality {
+            GraphicsQuality::Low => (ShadowQuality::Off, TextureFiltering::Nearest, 1, false),
+            GraphicsQuality::Medium => (ShadowQuality::Medium, TextureFiltering::Nearest, 1, true),
+            GraphicsQuality::High => (ShadowQuality::High, TextureFiltering::Trilinear, 16, true),
+            GraphicsQuality::Custom => return
+        };
+
+        self.shadow_quality = shadow_quality;
+        self.texture_filtering = texture_filtering;
+        self.anisotropy = anisotropy;
+        self.post_processing.fxaa = fxaa;
+    }
+
+    pub fn set_mouse_sensitivity(&mut self, mouse_sensitivity: f32) {
+        self.mouse_sensitivity = mouse_sensitivity.clamp(0.05, 5.0);
+    }
+
+    /// In blocks per second
+    pub fn set_movement_speed(&mut self, movement_speed: f32) {
+        self.movement_speed = movement_speed.clamp(0.5, 50.0);
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self { Self::new(5) }
+}
+
+// how settings are laid out in the file. Everything is optional, whatever's missing keeps its default.
+#[derive(Debug, Default, Serialize)]
+struct SettingsFile {
+    video: VideoSettings,
+    graphics: GraphicsSettings,
+    controls: ControlSettings,
+    // action names to key names
+    keys: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct VideoSettings {
+    render_distance: Option<usize>,
+    fov: Option<f32>,
+    present_mode: Option<PresentMode>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct GraphicsSettings {
+    quality: Option<GraphicsQuality>,
+    // presets leave these out, so changing the quality in the file is enough
+    #[serde(skip_serializing_if = "Option::is_none")]
+    texture_filtering: Option<TextureFiltering>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    anisotropy: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    shadows: Option<ShadowQuality>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fxaa: Option<bool>,
+    tonemapping: Option<Tonemapping>,
+    exposure: Option<f32>,
+    gamma: Option<f32>,
+    vignette: Option<f32>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct ControlSettings {
+    mouse_sensitivity: Option<f32>,
+    movement_speed: Option<f32>,
+}
+
+impl SettingsFile {
+    fn read(mut table: Table, warnings: &mut Vec<String>) -> Self {
+        let mut video = Section::take(&mut table, "video", warnings);
+        let video_settings = VideoSettings {
+            render_distance: video.read("render_distance", warnings),
+            fov: video.read("fov", warnings),
+            present_mode: video.read("present_mode", warnings),
+        };
+        video.finish(warnings);
+
+        let mut graphics = Section::take(&mut table, "graphics", warnings);
+        let graphics_settings = GraphicsSettings {
+            quality: graphics.read("quality", warnings),
+            texture_filtering: graphics.read("texture_filtering", warnings),
+            anisotropy: graphics.read("anisotropy", warnings),
+            shadows: graphics.read("shadows", warnings),
+            fxaa: graphics.read("fxaa", warnings),
+            tonemapping: graphics.read("tonemapping", warnings),
+            exposure: graphics.read("exposure", warnings),
+            gamma: graphics.read("gamma", warnings),
+            vignette: graphics.read("vignette", warnings),
+        };
+        graphics.finish(warnings);
+
+        let mut controls = Section::take(&mut table, "controls", warnings);
+        let control_settings = ControlSettings {
+            mouse_sensitivity: controls.read("mouse_sensitivity", warnings),
+            movement_speed: controls.read("movement_speed", warnings),
+        };
+        controls.finish(warnings);
+
+        // any action name goes here, `into_settings` warns about the unknown ones
+        let mut key_section = Section::take(&mut table, "keys", warnings);
+        let names: Vec<String> = key_section.table.keys().cloned().collect();
+        let keys = names.into_iter()
+            .filter_map(|name| key_section.read(&name, warnings).map(|key| (name, key)))
+            .collect();
+
+        for name in table.keys() {
+            warnings.push(format!("unknown setting '{}'", name));
+        }
+
+        Self {
+            video: video_settings,
+            graphics: graphics_settings,
+            controls: control_settings,
+            keys,
+        }
+    }
+
+    // everything it can use over the defaults, with a warning for what it can't
+    fn into_settings(self, warnings: &mut Vec<String>) -> Settings {
+        let mut settings = Settings::default();
+        let (video, graphics, controls) = (self.video, self.graphics, self.controls);
+
+        if let Some(render_distance) = video.render_distance {
+            settings.set_draw_distance(render_distance);
+        }
+
+        if let Some(fov) = get_finite(video.fov, "video.fov", warnings) {
+            settings.set_fov(fov);
+        }
+
+        if let Some(present_mode) = video.present_mode {
+            settings.set_present_mode(present_mode);
+        }
+
+        // the quality preset goes first so the values it sets can be overridden one by one
+        if let Some(quality) = graphics.quality {
+            settings.set_graphics_quality(quality);
+        }
+
+        if let Some(texture_filtering) = graphics.texture_filtering {
+            settings.set_texture_filtering(texture_filtering);
+        }
+
+        if let Some(anisotropy) = graphics.anisotropy {
+            settings.set_anisotropy(anisotropy.min(16) as u8);
+        }
+
+        if let Some(shadows) = graphics.shadows {
+            settings.set_shadow_quality(shadows);
+        }
+
+        let post_processing = &mut settings.post_processing;
+
+        if let Some(fxaa) = graphics.fxaa {
+            post_processing.fxaa = fxaa;
+        }
+
+        if let Some(tonemapping) = graphics.tonemapping {
+            post_processing.tonemapping = tonemapping;
+        }
+
+        if let Some(exposure) = get_finite(graphics.exposure, "graphics.exposure", warnings) {
+            post_processing.exposure = exposure.max(0.0);
+        }
+
+        if let Some(gamma) = get_finite(graphics.gamma, "graphics.gamma", warnings) {
+            post_processing.gamma = gamma.clamp(0.1, 10.0);
+        }
+
+        if let Some(vignette) = get_finite(graphics.vignette, "graphics.vignette", warnings) {
+            post_processing.vignette = vignette.clamp(0.0, 1.0);
+        }
+
+        if let Some(mouse_sensitivity) = get_finite(controls.mouse_sensitivity, "controls.mouse_sensitivity", warnings) {
+            settings.set_mouse_sensitivity(mouse_sensitivity);
+        }
+
+        if let Some(movement_speed) = get_finite(controls.movement_speed, "controls.movement_speed", warnings) {
+            settings.set_movement_speed(movement_speed);
+        }
+
+        for (name, key) in self.keys {
+            match Action::from_name(&name) {
+                Some(action) => settings.key_bindings.set_key(action, &key),
+                None => warnings.push(format!("unknown action 'keys.{}'", name))
+            }
+        }
+
+        settings
+    }
+}
+
+impl From<Settings> for SettingsFile {
+    fn from(settings: Settings) -> Self {
+        let quality = settings.get_graphics_quality();
+        let is_custom = quality == GraphicsQuality::Custom;
+        let post_processing = settings.post_processing;
+
+        Self {
+            video: VideoSettings {
+                render_distance: Some(settings.draw_distance),
+                fov: Some(settings.fov),
+                present_mode: Some(settings.present_mode),
+            },
+            graphics: GraphicsSettings {
+                quality: Some(quality),
+                texture_filtering: Some(settings.texture_filtering).filter(|_| is_custom),
+                anisotropy: Some(settings.anisotropy as u32).filter(|_| is_custom),
+                shadows: Some(settings.shadow_quality).filter(|_| is_custom),
+                fxaa: Some(post_processing.fxaa).filter(|_| is_custom),
+                tonemapping: Some(post_processing.tonemapping),
+                exposure: Some(post_processing.exposure),
+                gamma: Some(post_processing.gamma),
+                vignette: Some(post_processing.vignette),
+            },
+            controls: ControlSettings {
+                mouse_sensitivity: Some(settings.mouse_sensitivity),
+                movement_speed: Some(settings.movement_speed),
+            },
+            keys: Action::ALL.iter()
+                .map(|action| (action.get_name().to_string(), settings.key_bindings.get_key(*action).to_string()))
+                .collect(),
+        }
+    }
+}
+
+// a table of the settings file, taken apart one value at a time so a value of the wrong kind only loses itself
+struct Section {
+    name: &'static str,
+    table: Table,
+}
+
+impl Section {
+    fn take(file: &mut Table, name: &'static str, warnings: &mut Vec<String>) -> Self {
+        let table = match file.remove(name) {
+            Some(Value::Table(table)) => table,
+            Some(_) => {
+                warnings.push(format!("'{}' should be a table", name));
+                Table::new()
+            },
+            None => Table::new()
+        };
+
+        Self { name, table }
+    }
+
+    fn read<T: DeserializeOwned>(&mut self, key: &str, warnings: &mut Vec<String>) -> Option<T> {
+        match self.table.remove(key)?.try_into() {
+            Ok(value) => Some(value),
+            Err(err) => {
+                warnings.push(format!("couldn't read '{}.{}', {}", self.name, key, err));
+                None
+            }
+        }
+    }
+
+    // whatever wasn't read is unknown
+    fn finish(self, warnings: &mut Vec<String>) {
+        for key in self.table.keys() {
+            warnings.push(format!("unknown setting '{}.{}'", self.name, key));
+        }
+    }
+}
+
+// TOML has nan and inf, which no setting wants
+fn get_finite(value: Option<f32>, key: &str, warnings: &mut Vec<String>) -> Option<f32> {
+    match value {
+        Some(value) if !value.is_finite() => {
+            warnings.push(format!("'{}' should be a finite number", key));
+            None
+        },
+        value => value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, GraphicsQuality, PresentMode, Settings, ShadowQuality};
+
+    #[test]
+    fn round_trips_through_toml() {
+        let mut settings = Settings::new(12);
+        settings.set_fov(70.0);
+        settings.set_mouse_sensitivity(0.25);
+        settings.set_present_mode(PresentMode::Immediate);
+        settings.set_shadow_quality(ShadowQuality::Low);
+        settings.get_key_bindings_mut().set_key(Action::Jump, "J");
+
+        let (read, warnings) = Settings::from_toml(&settings.to_toml().unwrap());
+
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(read, settings);
+
+        // presets are saved by name only
+        settings.set_graphics_quality(GraphicsQuality::High);
+        let text = settings.to_toml().unwrap();
+
+        assert!(!text.lines().any(|line| line.starts_with("shadows")));
+        assert_eq!(Settings::from_toml(&text).0, settings);
+    }
+
+    #[test]
+    fn warns_about_what_it_cant_use() {
+        let text = "
+            [video]
+            fov = nan
+            render_distance = 500
+            colour = 3
+
+            [graphics]
+            quality = \"low\"
+            fxaa = true
+
+            [keys]
+            jump = \"J\"
+            dance = \"K\"
+
+            [sound]
+            volume = 1.0
+        ";
+
+        let (settings, warnings) = Settings::from_toml(text);
+
+        assert_eq!(warnings.len(), 4, "{:?}", warnings);
+        assert_eq!(settings.get_fov(), Settings::default().get_fov());
+        assert_eq!(settings.get_draw_distance(), super::MAX_DRAW_DISTANCE);
+        assert_eq!(settings.get_key_bindings().get_key(Action::Jump), "J");
+
+        // the preset, then fxaa on top of it
+        assert_eq!(settings.get_shadow_quality(), ShadowQuality::Off);
+        assert!(settings.get_post_processing().fxaa);
+        assert_eq!(settings.get_graphics_quality(), GraphicsQuality::Custom);
+
+        // a value of the wrong kind only loses that one setting
+        let (settings, warnings) = Settings::from_toml("[video]\nfov = \"wide\"\nrender_distance = 3\n\n[keys]\njump = \"J\"\npause = 1");
+
+        assert_eq!(warnings.len(), 2, "{:?}", warnings);
+        assert_eq!(settings.get_fov(), Settings::default().get_fov());
+        assert_eq!(settings.get_draw_distance(), 3);
+        assert_eq!(settings.get_key_bindings().get_key(Action::Jump), "J");
+        assert_eq!(settings.get_key_bindings().get_key(Action::Pause), "Escape");
+
+        // only a file that isn't TOML gives the defaults
+        let (settings, warnings) = Settings::from_toml("[video");
+
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert_eq!(settings, Settings::default());
+    }
+}
@@ -0,0 +1,24 @@
+use cgmath::Vector3;
+
+use crate::block::Block;
+
+/// Called with the world position of the block being ticked.
+pub type BlockTickHandler = fn(&mut dyn BlockTickContext, Vector3<i32>);
+
+/// The view of the world a block tick handler gets to work with.
+/// All positions are in world (block) coordinates.
+pub trait BlockTickContext {
+    fn current_tick(&self) -> u64;
+
+    fn get_block(&self, world_pos: Vector3<i32>) -> Option<Block>;
+
+    /// Returns false if the position isn't in a loaded chunk
+    fn set_block(&mut self, world_pos: Vector3<i32>, block: Option<Block>) -> bool;
+
+    /// Queues a scheduled tick for whatever block is at `world_pos`, `delay` ticks from now.
+    /// The tick only fires if the same block is still there when it comes due.
+    fn schedule_tick(&mut self, world_pos: Vector3<i32>, delay: u64);
+
+    /// Random value in 0..1, for things like crop growth chances
+    fn random(&mut self) -> f32;
+}
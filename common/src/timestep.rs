@@ -0,0 +1,80 @@
+/// Accumulates frame times and hands them out as fixed-size simulation steps,
+/// so movement, physics and world ticks behave the same at any frame rate.
+#[derive(Debug, Clone)]
+pub struct FixedTimestep {
+    step: f32,
+    accumulator: f32,
+    max_steps_per_frame: u32,
+    // how many were due but skipped in the last call to advance
+    dropped_steps: u32,
+}
+
+impl FixedTimestep {
+    pub const DEFAULT_STEP: f32 = 1.0 / 60.0;
+
+    pub fn new(step: f32, max_steps_per_frame: u32) -> Self {
+        Self {
+            step,
+            accumulator: 0.0,
+            max_steps_per_frame,
+            dropped_steps: 0,
+        }
+    }
+
+    pub fn get_step(&self) -> f32 { self.step }
+
+    /// Steps that were due in the last call to `advance` but got dropped
+    pub fn get_dropped_steps(&self) -> u32 { self.dropped_steps }
+
+    /// Adds a frame's delta time and returns how many simulation steps should run.
+    /// If a frame took so long that more than `max_steps_per_frame` are due, the
+    /// rest are dropped rather than making the next frame even slower.
+    pub fn advance(&mut self, delta_time: f32) -> u32 {
+        self.accumulator += delta_time;
+
+        let mut steps: u32 = 0;
+
+        while self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+
+        self.dropped_steps = steps.saturating_sub(self.max_steps_per_frame);
+
+        steps.min(self.max_steps_per_frame)
+    }
+
+    /// How far we are between the last simulation step and the next one (0..1),
+    /// used to interpolate what gets drawn between the two
+    pub fn get_alpha(&self) -> f32 {
+        (self.accumulator / self.step).clamp(0.0, 1.0)
+    }
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_STEP, 8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FixedTimestep;
+
+    #[test]
+    fn caps_steps_and_keeps_leftover_time() {
+        let mut timestep = FixedTimestep::new(0.25, 4);
+
+        assert_eq!(timestep.advance(0.125), 0);
+        assert_eq!(timestep.advance(0.25), 1);
+        assert_eq!(timestep.get_alpha(), 0.5);
+
+        // 10 steps are due, only 4 run and the leftover still carries over
+        assert_eq!(timestep.advance(2.5), 4);
+        assert_eq!(timestep.get_dropped_steps(), 6);
+        assert_eq!(timestep.get_alpha(), 0.5);
+
+        assert_eq!(timestep.advance(0.125), 1);
+        assert_eq!(timestep.get_dropped_steps(), 0);
+    }
+}
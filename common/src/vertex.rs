@@ -0,0 +1,109 @@
+/// Plain textured vertex. Lives here rather than in `renderer` so meshes can be
+/// built without a GPU, the renderer only describes its layout.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+}
+
+// first word
+const POSITION_BITS: u32 = 5;
+const POSITION_MASK: u32 = (1 << POSITION_BITS) - 1;
+const FACE_SHIFT: u32 = 15;
+const FACE_MASK: u32 = 0b111;
+const UV_SHIFT: u32 = 18;
+const AO_SHIFT: u32 = 20;
+const AO_MASK: u32 = 0b11;
+const LIGHT_SHIFT: u32 = 22;
+const LIGHT_MASK: u32 = 0b1111;
+
+// second word
+const TEXTURE_MASK: u32 = 0xffff;
+const CHUNK_SLOT_SHIFT: u32 = 16;
+const CHUNK_SLOT_MASK: u32 = 0xffff;
+
+pub const MAX_AO: u32 = AO_MASK;
+pub const MAX_LIGHT: u32 = LIGHT_MASK;
+
+/// Chunk mesh vertex, packed into 8 bytes:
+///
+/// | word | bits  | what |
+/// | ---- | ----- | ---- |
+/// | 0    | 0-14  | corner position in the chunk, 5 bits per axis (0..=CHUNK_SIZE) |
+/// | 0    | 15-17 | face index, see `BlockFace` |
+/// | 0    | 18-19 | texture corner, u then v |
+/// | 0    | 20-21 | ambient occlusion, 3 is unoccluded |
+/// | 0    | 22-25 | light level |
+/// | 1    | 0-15  | texture index |
+/// | 1    | 16-31 | chunk slot, filled in by the renderer |
+///
+/// The renderer looks the chunk's origin up by its slot, see `res/shaders/shader.wgsl` for the unpacking.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ChunkVertex {
+    data: [u32; 2],
+}
+
+impl ChunkVertex {
+    pub fn new(position: [u32; 3], face: u32, uv: [u32; 2], texture_index: u32, ao: u32, light: u32) -> Self {
+        let first =
+            (position[0] & POSITION_MASK) |
+            (position[1] & POSITION_MASK) << POSITION_BITS |
+            (position[2] & POSITION_MASK) << (POSITION_BITS * 2) |
+            (face & FACE_MASK) << FACE_SHIFT |
+            (uv[0] & 1) << UV_SHIFT |
+            (uv[1] & 1) << (UV_SHIFT + 1) |
+            (ao & AO_MASK) << AO_SHIFT |
+            (light & LIGHT_MASK) << LIGHT_SHIFT;
+
+        Self { data: [first, texture_index & TEXTURE_MASK] }
+    }
+
+    pub fn with_chunk_slot(mut self, slot: u32) -> Self {
+        self.data[1] = (self.data[1] & TEXTURE_MASK) | (slot & CHUNK_SLOT_MASK) << CHUNK_SLOT_SHIFT;
+        self
+    }
+}
+
+// getters
+impl ChunkVertex {
+    pub fn get_position(&self) -> [u32; 3] {
+        [
+            self.data[0] & POSITION_MASK,
+            (self.data[0] >> POSITION_BITS) & POSITION_MASK,
+            (self.data[0] >> (POSITION_BITS * 2)) & POSITION_MASK,
+        ]
+    }
+
+    pub fn get_face(&self) -> u32 { (self.data[0] >> FACE_SHIFT) & FACE_MASK }
+
+    pub fn get_uv(&self) -> [u32; 2] { [(self.data[0] >> UV_SHIFT) & 1, (self.data[0] >> (UV_SHIFT + 1)) & 1] }
+
+    pub fn get_ao(&self) -> u32 { (self.data[0] >> AO_SHIFT) & AO_MASK }
+
+    pub fn get_light(&self) -> u32 { (self.data[0] >> LIGHT_SHIFT) & LIGHT_MASK }
+
+    pub fn get_texture_index(&self) -> u32 { self.data[1] & TEXTURE_MASK }
+
+    pub fn get_chunk_slot(&self) -> u32 { (self.data[1] >> CHUNK_SLOT_SHIFT) & CHUNK_SLOT_MASK }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunk_vertex_round_trips() {
+        let vertex = ChunkVertex::new([16, 0, 9], 5, [1, 0], 300, 2, MAX_LIGHT).with_chunk_slot(4000);
+
+        assert_eq!(std::mem::size_of::<ChunkVertex>(), 8);
+        assert_eq!(vertex.get_position(), [16, 0, 9]);
+        assert_eq!(vertex.get_face(), 5);
+        assert_eq!(vertex.get_uv(), [1, 0]);
+        assert_eq!(vertex.get_texture_index(), 300);
+        assert_eq!(vertex.get_ao(), 2);
+        assert_eq!(vertex.get_light(), MAX_LIGHT);
+        assert_eq!(vertex.get_chunk_slot(), 4000);
+    }
+}
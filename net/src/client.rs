@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+
+use anyhow::{Result, Error, anyhow};
+use cgmath::{InnerSpace, Vector3};
+
+use common::{identifier::Identifier, registry::Registry};
+use world::{World, chunk::ChunkData, entity::{self, Entity, EntityBody, EntityId}};
+
+use crate::{protocol::{BlockPalette, Packet, PROTOCOL_VERSION}, transport::Transport};
+
+// don't bother the server about movements smaller than this
+const MIN_MOVE_DISTANCE: f32 = 0.001;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionState {
+    Connecting,
+    Playing,
+    Disconnected(String),
+}
+
+/// Someone else on the server, shown as an entity in our copy of the world
+pub struct RemotePlayer {
+    name: String,
+    entity_id: EntityId,
+    position: Vector3<f32>,
+}
+
+impl RemotePlayer {
+    pub fn get_name(&self) -> &str { &self.name }
+
+    pub fn get_entity_id(&self) -> EntityId { self.entity_id }
+
+    pub fn get_position(&self) -> Vector3<f32> { self.position }
+}
+
+/// The client's side of a connection. Keeps a copy of the server's world,
+/// filled in by the chunks and block changes the server sends.
+pub struct ClientSession {
+    transport: Box<dyn Transport>,
+    state: SessionState,
+
+    world: World,
+    palette: BlockPalette,
+
+    player_id: u64,
+    spawn_point: Vector3<f32>,
+    players: HashMap<u64, RemotePlayer>,
+
+    last_sent_position: Option<Vector3<f32>>,
+    // where the server last put our player, until the game moves it there
+    teleport: Option<Vector3<f32>>,
+}
+
+impl ClientSession {
+    /// Starts the handshake, the session is playing once the server accepts it
+    pub fn connect(mut transport: Box<dyn Transport>, name: &str, view_distance: usize) -> Result<Self, Error> {
+        transport.send(&Packet::Handshake {
+            protocol_version: PROTOCOL_VERSION,
+            name: name.to_string(),
+            view_distance: view_distance.min(u8::MAX as usize) as u8,
+        })?;
+
+        Ok(Self {
+            transport,
+            state: SessionState::Connecting,
+
+            world: World::remote(view_distance),
+            palette: BlockPalette::default(),
+
+            player_id: 0,
+            spawn_point: Vector3::new(0., 0., 0.),
+            players: HashMap::new(),
+
+            last_sent_position: None,
+            teleport: None,
+        })
+    }
+
+    /// Handles everything the server sent, then advances our copy of the world.
+    /// `player_pos` is where our player is, or anything near the action without one.
+    pub fn update(&mut self, player_pos: Vector3<f32>, delta_time: f32) -> Result<(), Error> {
+        if let SessionState::Disconnected(reason) = &self.state {
+            return Err(anyhow!(format!("disconnected: {}", reason)));
+        }
+
+        loop {
+            let packet = match self.transport.receive() {
+                Ok(Some(packet)) => packet,
+                Ok(None) => break,
+                Err(err) => return Err(self.close(err.to_string()))
+            };
+
+            if let Err(err) = self.handle_packet(packet) {
+                let _ = self.transport.send(&Packet::Disconnect { reason: err.to_string() });
+
+                return Err(self.close(err.to_string()));
+            }
+
+            if let SessionState::Disconnected(reason) = &self.state {
+                return Err(anyhow!(format!("disconnected: {}", reason)));
+            }
+        }
+
+        self.world.update(player_pos, delta_time);
+
+        Ok(())
+    }
+
+    /// Tells the server where our player is, if it moved since last time
+    pub fn send_position(&mut self, position: Vector3<f32>) -> Result<(), Error> {
+        if self.state != SessionState::Playing {
+            return Ok(());
+        }
+
+        if let Some(last_position) = self.last_sent_position {
+            if (position - last_position).magnitude2() < MIN_MOVE_DISTANCE * MIN_MOVE_DISTANCE {
+                return Ok(());
+            }
+        }
+
+        self.last_sent_position = Some(position);
+        self.send(&Packet::PlayerMove { position })
+    }
+
+    /// Asks the server to change a block. Our world only changes once the server says it did.
+    pub fn request_block_change(&mut self, world_pos: Vector3<i32>, block: Option<&Identifier>) -> Result<(), Error> {
+        let block = match self.palette.to_index(block) {
+            Some(block) => block,
+            None => return Err(anyhow!("the server doesn't know that block"))
+        };
+
+        self.send(&Packet::SetBlock { world_pos, block })
+    }
+
+    /// Asks the server for chunks this far around our player from now on
+    pub fn set_view_distance(&mut self, view_distance: usize) -> Result<(), Error> {
+        let view_distance = view_distance.clamp(1, u8::MAX as usize);
+        self.world.set_render_distance(view_distance);
+
+        if self.state != SessionState::Playing {
+            return Ok(());
+        }
+
+        self.send(&Packet::SetViewDistance { view_distance: view_distance as u8 })
+    }
+
+    /// Where the server wants our player moved to, if it sent one since the last call
+    pub fn take_teleport(&mut self) -> Option<Vector3<f32>> {
+        self.teleport.take()
+    }
+
+    pub fn disconnect(&mut self, reason: &str) {
+        let _ = self.transport.send(&Packet::Disconnect { reason: reason.to_string() });
+
+        self.state = SessionState::Disconnected(reason.to_string());
+    }
+
+    fn send(&mut self, packet: &Packet) -> Result<(), Error> {
+        match self.transport.send(packet) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(self.close(err.to_string()))
+        }
+    }
+
+    fn close(&mut self, reason: String) -> Error {
+        self.state = SessionState::Disconnected(reason.clone());
+
+        anyhow!(format!("disconnected: {}", reason))
+    }
+
+    fn handle_packet(&mut self, packet: Packet) -> Result<(), Error> {
+        match packet {
+            Packet::HandshakeAccepted { player_id, spawn_point, blocks, .. } => {
+                // chunks name their blocks, but block changes only send indices into this list
+                let registry = Registry::current();
+
+                if let Some(missing) = blocks.iter().find(|block| registry.get_block(block).is_none()) {
+                    return Err(anyhow!(format!("server has a block we don't know: '{}'", missing.as_string())));
+                }
+
+                self.palette = BlockPalette::new(blocks);
+                self.player_id = player_id;
+                self.spawn_point = spawn_point;
+                self.state = SessionState::Playing;
+            },
+            Packet::ChunkData { data } => {
+                let chunk_data = ChunkData::deserialize(&data, self.world.get_current_tick())?;
+
+                self.world.insert_chunk(chunk_data);
+            },
+            Packet::UnloadChunk { chunk_pos } => {
+                self.world.remove_chunk(chunk_pos);
+            },
+            Packet::BlockChange { world_pos, block } => {
+                let block = match block {
+                    0 => None,
+                    _ => match self.palette.to_identifier(block) {
+                        Some(id) => Registry::current().get_block(id),
+                        None => return Err(anyhow!(format!("server sent unknown block index {}", block)))
+                    }
+                };
+
+                self.world.set_block(world_pos, block);
+            },
+            Packet::PlayerJoined { player_id, name, position } => {
+                let mut body = EntityBody::new(position, entity::PLAYER_SIZE);
+                // their own client moves them, we just put them where the server says
+                body.has_gravity = false;
+
+                let entity_id = self.world.spawn_entity(Entity::new(entity::player_kind(), body).with_persistence(false));
+
+                self.players.insert(player_id, RemotePlayer { name, entity_id, position });
+            },
+            Packet::PlayerPosition { player_id, position } => {
+                if let Some(player) = self.players.get_mut(&player_id) {
+                    player.position = position;
+
+                    if let Some(entity) = self.world.get_entity_mut(player.entity_id) {
+                        entity.body.collider.position = position;
+                    }
+                }
+            },
+            Packet::PlayerLeft { player_id } => {
+                if let Some(player) = self.players.remove(&player_id) {
+                    self.world.despawn_entity(player.entity_id);
+                }
+            },
+            Packet::TimeUpdate { time, frozen } => {
+                self.world.set_time(time);
+                self.world.set_time_frozen(frozen);
+            },
+            Packet::PlayerTeleport { position } => {
+                // the server already knows we're there
+                self.last_sent_position = Some(position);
+                self.teleport = Some(position);
+            },
+            Packet::Disconnect { reason } => {
+                self.state = SessionState::Disconnected(reason);
+            },
+            other => return Err(anyhow!(format!("unexpected packet from server: {:?}", other)))
+        }
+
+        Ok(())
+    }
+}
+
+// getters
+impl ClientSession {
+    pub fn get_state(&self) -> &SessionState { &self.state }
+
+    pub fn is_playing(&self) -> bool { self.state == SessionState::Playing }
+
+    pub fn get_player_id(&self) -> u64 { self.player_id }
+
+    pub fn get_spawn_point(&self) -> Vector3<f32> { self.spawn_point }
+
+    pub fn get_players(&self) -> &HashMap<u64, RemotePlayer> { &self.players }
+
+    pub fn get_world(&self) -> &World { &self.world }
+
+    pub fn get_world_mut(&mut self) -> &mut World { &mut self.world }
+}
@@ -0,0 +1,5 @@
+pub mod protocol;
+pub mod transport;
+pub mod tcp;
+pub mod loopback;
+pub mod client;
@@ -0,0 +1,41 @@
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+
+use anyhow::{Result, Error, anyhow};
+
+use crate::{protocol::Packet, transport::Transport};
+
+/// One end of an in-process connection, for a single-player client talking to
+/// its own server. Packets are still encoded, so both sides see exactly what
+/// they would over the network.
+pub struct LoopbackTransport {
+    sender: Sender<Vec<u8>>,
+    receiver: Receiver<Vec<u8>>,
+}
+
+/// Both ends of a new connection
+pub fn pair() -> (LoopbackTransport, LoopbackTransport) {
+    let (client_sender, server_receiver) = mpsc::channel();
+    let (server_sender, client_receiver) = mpsc::channel();
+
+    (
+        LoopbackTransport { sender: client_sender, receiver: client_receiver },
+        LoopbackTransport { sender: server_sender, receiver: server_receiver },
+    )
+}
+
+impl Transport for LoopbackTransport {
+    fn send(&mut self, packet: &Packet) -> Result<(), Error> {
+        match self.sender.send(packet.encode()) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(anyhow!("connection closed"))
+        }
+    }
+
+    fn receive(&mut self) -> Result<Option<Packet>, Error> {
+        match self.receiver.try_recv() {
+            Ok(bytes) => Packet::decode(&bytes).map(Some),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(anyhow!("connection closed"))
+        }
+    }
+}
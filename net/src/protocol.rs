@@ -0,0 +1,263 @@
+use anyhow::{Result, Error, anyhow};
+use cgmath::Vector3;
+
+use common::{identifier::Identifier, serialization::{ByteReader, ByteWriter}};
+
+/// Bumped whenever a packet changes. Clients and servers only talk to the same version.
+pub const PROTOCOL_VERSION: u32 = 4;
+
+/// Frames bigger than this are treated as garbage rather than allocated
+pub const MAX_PACKET_SIZE: usize = 4 * 1024 * 1024;
+
+/// Blocks in packets are sent as indices into the list from `HandshakeAccepted`,
+/// with 0 for air
+pub type BlockIndex = u16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Packet {
+    /* -== CLIENT TO SERVER ==- */
+
+    /// First packet a client sends
+    Handshake { protocol_version: u32, name: String, view_distance: u8 },
+    /// Where the client's player is now
+    PlayerMove { position: Vector3<f32> },
+    /// Asks the server to change a block. The server answers with a `BlockChange` if it did.
+    SetBlock { world_pos: Vector3<i32>, block: BlockIndex },
+    /// How many chunks around the player the client wants now, capped by the server's render distance
+    SetViewDistance { view_distance: u8 },
+
+    /* -== SERVER TO CLIENT ==- */
+
+    /// The server's block registry, so both sides agree on what each `BlockIndex` means
+    HandshakeAccepted { player_id: u64, seed: u32, spawn_point: Vector3<f32>, current_tick: u64, blocks: Vec<Identifier> },
+    /// A chunk, written the same way chunks are saved
+    ChunkData { data: Vec<u8> },
+    UnloadChunk { chunk_pos: Vector3<i32> },
+    BlockChange { world_pos: Vector3<i32>, block: BlockIndex },
+    PlayerJoined { player_id: u64, name: String, position: Vector3<f32> },
+    PlayerPosition { player_id: u64, position: Vector3<f32> },
+    PlayerLeft { player_id: u64 },
+    /// The world's time, sent on joining, every so often, and whenever it's set or frozen
+    TimeUpdate { time: u64, frozen: bool },
+    /// Puts our own player somewhere else, after a move the server didn't accept or a teleport
+    PlayerTeleport { position: Vector3<f32> },
+
+    /* -== EITHER WAY ==- */
+
+    Disconnect { reason: String },
+}
+
+impl Packet {
+    fn get_id(&self) -> u8 {
+        match self {
+            Packet::Handshake { .. } => 0,
+            Packet::PlayerMove { .. } => 1,
+            Packet::SetBlock { .. } => 2,
+            Packet::HandshakeAccepted { .. } => 3,
+            Packet::ChunkData { .. } => 4,
+            Packet::UnloadChunk { .. } => 5,
+            Packet::BlockChange { .. } => 6,
+            Packet::PlayerJoined { .. } => 7,
+            Packet::PlayerPosition { .. } => 8,
+            Packet::PlayerLeft { .. } => 9,
+            Packet::Disconnect { .. } => 10,
+            Packet::TimeUpdate { .. } => 11,
+            Packet::SetViewDistance { .. } => 12,
+            Packet::PlayerTeleport { .. } => 13,
+        }
+    }
+
+    pub fn write_to(&self, writer: &mut ByteWriter) {
+        writer.write_u8(self.get_id());
+
+        match self {
+            Packet::Handshake { protocol_version, name, view_distance } => {
+                writer.write_u32(*protocol_version);
+                writer.write_string(name);
+                writer.write_u8(*view_distance);
+            },
+            Packet::PlayerMove { position } | Packet::PlayerTeleport { position } => write_vec3(writer, *position),
+            Packet::SetBlock { world_pos, block } | Packet::BlockChange { world_pos, block } => {
+                write_ivec3(writer, *world_pos);
+                writer.write_u16(*block);
+            },
+            Packet::HandshakeAccepted { player_id, seed, spawn_point, current_tick, blocks } => {
+                writer.write_u64(*player_id);
+                writer.write_u32(*seed);
+                write_vec3(writer, *spawn_point);
+                writer.write_u64(*current_tick);
+                writer.write_u32(blocks.len() as u32);
+
+                for block in blocks {
+                    writer.write_identifier(block);
+                }
+            },
+            Packet::ChunkData { data } => writer.write_bytes(data),
+            Packet::UnloadChunk { chunk_pos } => write_ivec3(writer, *chunk_pos),
+            Packet::PlayerJoined { player_id, name, position } => {
+                writer.write_u64(*player_id);
+                writer.write_string(name);
+                write_vec3(writer, *position);
+            },
+            Packet::PlayerPosition { player_id, position } => {
+                writer.write_u64(*player_id);
+                write_vec3(writer, *position);
+            },
+            Packet::PlayerLeft { player_id } => writer.write_u64(*player_id),
+            Packet::Disconnect { reason } => writer.write_string(reason),
+            Packet::TimeUpdate { time, frozen } => {
+                writer.write_u64(*time);
+                writer.write_bool(*frozen);
+            },
+            Packet::SetViewDistance { view_distance } => writer.write_u8(*view_distance),
+        }
+    }
+
+    pub fn read_from(reader: &mut ByteReader) -> Result<Self, Error> {
+        let id = reader.read_u8()?;
+
+        let packet = match id {
+            0 => Packet::Handshake {
+                protocol_version: reader.read_u32()?,
+                name: reader.read_string()?,
+                view_distance: reader.read_u8()?,
+            },
+            1 => Packet::PlayerMove { position: read_vec3(reader)? },
+            2 => Packet::SetBlock { world_pos: read_ivec3(reader)?, block: reader.read_u16()? },
+            3 => {
+                let player_id = reader.read_u64()?;
+                let seed = reader.read_u32()?;
+                let spawn_point = read_vec3(reader)?;
+                let current_tick = reader.read_u64()?;
+
+                let count = reader.read_u32()?;
+                let mut blocks = Vec::new();
+
+                for _ in 0..count {
+                    blocks.push(reader.read_identifier()?);
+                }
+
+                Packet::HandshakeAccepted { player_id, seed, spawn_point, current_tick, blocks }
+            },
+            4 => Packet::ChunkData { data: reader.read_bytes()?.to_vec() },
+            5 => Packet::UnloadChunk { chunk_pos: read_ivec3(reader)? },
+            6 => Packet::BlockChange { world_pos: read_ivec3(reader)?, block: reader.read_u16()? },
+            7 => Packet::PlayerJoined {
+                player_id: reader.read_u64()?,
+                name: reader.read_string()?,
+                position: read_vec3(reader)?,
+            },
+            8 => Packet::PlayerPosition { player_id: reader.read_u64()?, position: read_vec3(reader)? },
+            9 => Packet::PlayerLeft { player_id: reader.read_u64()? },
+            10 => Packet::Disconnect { reason: reader.read_string()? },
+            11 => Packet::TimeUpdate { time: reader.read_u64()?, frozen: reader.read_bool()? },
+            12 => Packet::SetViewDistance { view_distance: reader.read_u8()? },
+            13 => Packet::PlayerTeleport { position: read_vec3(reader)? },
+            _ => return Err(anyhow!(format!("unknown packet id {}", id)))
+        };
+
+        Ok(packet)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = ByteWriter::new();
+        self.write_to(&mut writer);
+
+        writer.into_bytes()
+    }
+
+    /// Reads a whole packet, complaining about leftover bytes since those mean both sides disagree on the format
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = ByteReader::new(bytes);
+        let packet = Self::read_from(&mut reader)?;
+
+        if reader.remaining() > 0 {
+            return Err(anyhow!(format!("{} unread bytes after packet {}", reader.remaining(), packet.get_id())));
+        }
+
+        Ok(packet)
+    }
+}
+
+fn write_vec3(writer: &mut ByteWriter, value: Vector3<f32>) {
+    writer.write_f32(value.x);
+    writer.write_f32(value.y);
+    writer.write_f32(value.z);
+}
+
+fn read_vec3(reader: &mut ByteReader) -> Result<Vector3<f32>, Error> {
+    Ok(Vector3::new(reader.read_f32()?, reader.read_f32()?, reader.read_f32()?))
+}
+
+fn write_ivec3(writer: &mut ByteWriter, value: Vector3<i32>) {
+    writer.write_i32(value.x);
+    writer.write_i32(value.y);
+    writer.write_i32(value.z);
+}
+
+fn read_ivec3(reader: &mut ByteReader) -> Result<Vector3<i32>, Error> {
+    Ok(Vector3::new(reader.read_i32()?, reader.read_i32()?, reader.read_i32()?))
+}
+
+/// Maps blocks to the indices used on the wire and back
+#[derive(Debug, Clone, Default)]
+pub struct BlockPalette {
+    blocks: Vec<Identifier>,
+}
+
+impl BlockPalette {
+    pub fn new(blocks: Vec<Identifier>) -> Self {
+        Self { blocks }
+    }
+
+    pub fn get_blocks(&self) -> &[Identifier] { &self.blocks }
+
+    pub fn to_index(&self, block: Option<&Identifier>) -> Option<BlockIndex> {
+        match block {
+            Some(block) => self.blocks.iter().position(|other| other == block).map(|index| index as BlockIndex + 1),
+            None => Some(0)
+        }
+    }
+
+    /// None for air, or an index the palette doesn't have
+    pub fn to_identifier(&self, index: BlockIndex) -> Option<&Identifier> {
+        match index {
+            0 => None,
+            _ => self.blocks.get(index as usize - 1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use common::identifier::Identifier;
+    use super::{Packet, PROTOCOL_VERSION};
+
+    #[test]
+    fn packets_round_trip() {
+        let packets = vec![
+            Packet::Handshake { protocol_version: PROTOCOL_VERSION, name: "crow".to_string(), view_distance: 4 },
+            Packet::HandshakeAccepted {
+                player_id: 7,
+                seed: 1234,
+                spawn_point: Vector3::new(0.5, 64., -3.),
+                current_tick: 99,
+                blocks: vec![Identifier::from_str("willekeurig:stone").unwrap()],
+            },
+            Packet::ChunkData { data: vec![1, 2, 3] },
+            Packet::BlockChange { world_pos: Vector3::new(-1, 2, -300), block: 3 },
+            Packet::TimeUpdate { time: 30000, frozen: true },
+            Packet::SetViewDistance { view_distance: 12 },
+            Packet::PlayerTeleport { position: Vector3::new(-8., 70.5, 1e6) },
+            Packet::Disconnect { reason: "bye".to_string() },
+        ];
+
+        for packet in packets {
+            assert_eq!(Packet::decode(&packet.encode()).unwrap(), packet);
+        }
+
+        assert!(Packet::decode(&[200]).is_err());
+    }
+}
@@ -0,0 +1,160 @@
+use std::{io::{ErrorKind, Read, Write}, net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs}};
+
+use anyhow::{Result, Error, anyhow};
+
+use crate::{protocol::{Packet, MAX_PACKET_SIZE}, transport::Transport};
+
+/// How much can wait to be sent before the other side counts as not keeping up
+pub const MAX_WRITE_BUFFER: usize = 4 * MAX_PACKET_SIZE;
+
+/// Packets over a non-blocking TCP stream, each prefixed with its length as a little-endian u32
+pub struct TcpTransport {
+    stream: TcpStream,
+
+    read_buffer: Vec<u8>,
+    // whatever the socket didn't take yet
+    write_buffer: Vec<u8>,
+    // the other side hung up, but packets sent before that may still be in the read buffer
+    is_closed: bool,
+}
+
+impl TcpTransport {
+    pub fn connect(address: impl ToSocketAddrs) -> Result<Self, Error> {
+        match TcpStream::connect(address) {
+            Ok(stream) => Self::from_stream(stream),
+            Err(err) => Err(anyhow!(format!("couldn't connect: {}", err)))
+        }
+    }
+
+    pub fn from_stream(stream: TcpStream) -> Result<Self, Error> {
+        stream.set_nonblocking(true)?;
+        // movement packets are tiny and shouldn't wait around to be batched
+        stream.set_nodelay(true)?;
+
+        Ok(Self {
+            stream,
+
+            read_buffer: Vec::new(),
+            write_buffer: Vec::new(),
+            is_closed: false,
+        })
+    }
+
+    pub fn get_peer_address(&self) -> Option<SocketAddr> { self.stream.peer_addr().ok() }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        while !self.write_buffer.is_empty() {
+            match self.stream.write(&self.write_buffer) {
+                Ok(0) => return Err(anyhow!("connection closed")),
+                Ok(written) => { self.write_buffer.drain(..written); },
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(anyhow!(format!("couldn't send: {}", err)))
+            }
+        }
+
+        Ok(())
+    }
+
+    fn fill_read_buffer(&mut self) -> Result<(), Error> {
+        let mut chunk = [0; 4096];
+
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => {
+                    self.is_closed = true;
+                    return Ok(());
+                },
+                Ok(read) => self.read_buffer.extend_from_slice(&chunk[..read]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(anyhow!(format!("couldn't receive: {}", err)))
+            }
+        }
+    }
+
+    // the next whole packet in the read buffer, if one has fully arrived
+    fn take_packet(&mut self) -> Result<Option<Packet>, Error> {
+        if self.read_buffer.len() < 4 {
+            return Ok(None);
+        }
+
+        let length = u32::from_le_bytes([self.read_buffer[0], self.read_buffer[1], self.read_buffer[2], self.read_buffer[3]]) as usize;
+
+        if length > MAX_PACKET_SIZE {
+            return Err(anyhow!(format!("packet of {} bytes is too big", length)));
+        }
+
+        if self.read_buffer.len() < 4 + length {
+            return Ok(None);
+        }
+
+        let packet = Packet::decode(&self.read_buffer[4..4 + length]);
+        self.read_buffer.drain(..4 + length);
+
+        packet.map(Some)
+    }
+}
+
+impl Transport for TcpTransport {
+    fn send(&mut self, packet: &Packet) -> Result<(), Error> {
+        let bytes = packet.encode();
+
+        // make as much room as the socket takes before deciding it's too much
+        self.flush()?;
+
+        if self.write_buffer.len() + 4 + bytes.len() > MAX_WRITE_BUFFER {
+            return Err(anyhow!(format!("other side isn't keeping up, {} bytes still waiting to be sent", self.write_buffer.len())));
+        }
+
+        self.write_buffer.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        self.write_buffer.extend_from_slice(&bytes);
+
+        self.flush()
+    }
+
+    fn receive(&mut self) -> Result<Option<Packet>, Error> {
+        if !self.is_closed {
+            self.flush()?;
+            self.fill_read_buffer()?;
+        }
+
+        match self.take_packet()? {
+            Some(packet) => Ok(Some(packet)),
+            // a closed connection still hands out what arrived before it closed, like the reason why
+            None if self.is_closed => Err(anyhow!("connection closed")),
+            None => Ok(None)
+        }
+    }
+}
+
+/// Accepts incoming connections without blocking
+pub struct TcpServer {
+    listener: TcpListener,
+}
+
+impl TcpServer {
+    pub fn bind(address: impl ToSocketAddrs) -> Result<Self, Error> {
+        let listener = match TcpListener::bind(address) {
+            Ok(listener) => listener,
+            Err(err) => return Err(anyhow!(format!("couldn't listen: {}", err)))
+        };
+
+        listener.set_nonblocking(true)?;
+
+        Ok(Self { listener })
+    }
+
+    pub fn get_local_address(&self) -> Result<SocketAddr, Error> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Ok(None) when nobody is waiting to connect
+    pub fn accept(&self) -> Result<Option<TcpTransport>, Error> {
+        match self.listener.accept() {
+            Ok((stream, _)) => TcpTransport::from_stream(stream).map(Some),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(None),
+            Err(err) => Err(anyhow!(format!("couldn't accept connection: {}", err)))
+        }
+    }
+}
@@ -0,0 +1,12 @@
+use anyhow::{Result, Error};
+
+use crate::protocol::Packet;
+
+/// A connection to the other side, whatever carries the bytes.
+/// Errors mean the connection is gone and should be dropped.
+pub trait Transport: Send {
+    fn send(&mut self, packet: &Packet) -> Result<(), Error>;
+
+    /// Never blocks. Ok(None) when nothing has arrived yet.
+    fn receive(&mut self) -> Result<Option<Packet>, Error>;
+}
@@ -0,0 +1,125 @@
+use cgmath::Vector3;
+
+use crate::box_collider::BoxCollider;
+
+/// Axis aligned box described by its min and max corners
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    pub fn from_collider(collider: &BoxCollider) -> Self {
+        Self::new(collider.position, collider.position + collider.size)
+    }
+
+    /// The 1x1x1 box of the voxel at the given block position
+    pub fn voxel(x: i32, y: i32, z: i32) -> Self {
+        let min = Vector3::new(x as f32, y as f32, z as f32);
+
+        Self::new(min, min + Vector3::new(1., 1., 1.))
+    }
+
+    pub fn size(&self) -> Vector3<f32> { self.max - self.min }
+
+    pub fn center(&self) -> Vector3<f32> { (self.min + self.max) * 0.5 }
+
+    pub fn translate(&self, offset: Vector3<f32>) -> Self {
+        Self::new(self.min + offset, self.max + offset)
+    }
+
+    /// Smallest box containing both this box and `other`
+    pub fn union(&self, other: &Self) -> Self {
+        Self::new(
+            Vector3::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y), self.min.z.min(other.min.z)),
+            Vector3::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y), self.max.z.max(other.max.z))
+        )
+    }
+
+    /// Box covering everything this box touches while moving by `motion`
+    pub fn swept(&self, motion: Vector3<f32>) -> Self {
+        self.union(&self.translate(motion))
+    }
+
+    /// Touching faces don't count as overlapping
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.min.x < other.max.x && self.max.x > other.min.x &&
+        self.min.y < other.max.y && self.max.y > other.min.y &&
+        self.min.z < other.max.z && self.max.z > other.min.z
+    }
+
+    pub fn contains_point(&self, point: Vector3<f32>) -> bool {
+        point.x >= self.min.x && point.x <= self.max.x &&
+        point.y >= self.min.y && point.y <= self.max.y &&
+        point.z >= self.min.z && point.z <= self.max.z
+    }
+
+    /// Casts a ray against this box. Returns the distance along `direction` (in multiples
+    /// of it) where the ray enters the box, and the normal of the face it hits.
+    /// Rays starting inside the box hit at distance 0 with a zero normal.
+    pub fn ray_intersection(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<(f32, Vector3<f32>)> {
+        let mut t_min = f32::NEG_INFINITY;
+        let mut t_max = f32::INFINITY;
+        let mut normal = Vector3::new(0., 0., 0.);
+
+        let axes = [
+            (origin.x, direction.x, self.min.x, self.max.x, Vector3::unit_x()),
+            (origin.y, direction.y, self.min.y, self.max.y, Vector3::unit_y()),
+            (origin.z, direction.z, self.min.z, self.max.z, Vector3::unit_z()),
+        ];
+
+        for (origin, direction, min, max, axis) in axes.iter().copied() {
+            if direction == 0. {
+                // parallel to this slab, so we have to already be inside it
+                if origin < min || origin > max {
+                    return None;
+                }
+            } else {
+                let near = (min - origin) / direction;
+                let far = (max - origin) / direction;
+
+                let (near, far, face) = if near <= far {
+                    (near, far, -axis)
+                } else {
+                    (far, near, axis)
+                };
+
+                if near > t_min {
+                    t_min = near;
+                    normal = face;
+                }
+
+                t_max = t_max.min(far);
+
+                if t_min > t_max {
+                    return None;
+                }
+            }
+        }
+
+        if t_max < 0. {
+            None
+        } else if t_min < 0. {
+            Some((0., Vector3::new(0., 0., 0.)))
+        } else {
+            Some((t_min, normal))
+        }
+    }
+
+    /// Sweeps this box along `motion` and returns when (0..1) it first touches `other`,
+    /// along with the normal of the face of `other` it runs into
+    pub fn sweep(&self, motion: Vector3<f32>, other: &Self) -> Option<(f32, Vector3<f32>)> {
+        // shrink this box to a point by growing the other one by our size
+        let expanded = Self::new(other.min - self.size(), other.max);
+
+        match expanded.ray_intersection(self.min, motion) {
+            Some((time, normal)) if time <= 1. => Some((time, normal)),
+            _ => None
+        }
+    }
+}
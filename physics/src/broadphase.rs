@@ -0,0 +1,356 @@
+use std::collections::{HashMap, HashSet};
+
+use cgmath::{InnerSpace, Vector3};
+
+use crate::aabb::Aabb;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ColliderHandle(u64);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub handle: ColliderHandle,
+    /// Distance along the (normalized) ray direction
+    pub distance: f32,
+    pub normal: Vector3<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SweptHit {
+    pub handle: ColliderHandle,
+    /// Fraction of the motion (0..1) done before touching the collider
+    pub time: f32,
+    pub normal: Vector3<f32>,
+}
+
+struct Entry<T> {
+    aabb: Aabb,
+    data: T,
+}
+
+type Cell = (i32, i32, i32);
+
+/// Uniform grid broadphase. Every collider is filed under each grid cell its box
+/// touches, so queries only have to look at colliders in the cells they cover
+/// instead of every collider in the world.
+pub struct SpatialHash<T> {
+    cell_size: f32,
+    next_handle: u64,
+
+    entries: HashMap<ColliderHandle, Entry<T>>,
+    cells: HashMap<Cell, Vec<ColliderHandle>>,
+}
+
+impl<T> SpatialHash<T> {
+    pub const DEFAULT_CELL_SIZE: f32 = 4.;
+
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            next_handle: 0,
+
+            entries: HashMap::new(),
+            cells: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize { self.entries.len() }
+
+    pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+    pub fn get(&self, handle: ColliderHandle) -> Option<(&Aabb, &T)> {
+        self.entries.get(&handle).map(|entry| (&entry.aabb, &entry.data))
+    }
+
+    pub fn get_mut(&mut self, handle: ColliderHandle) -> Option<&mut T> {
+        self.entries.get_mut(&handle).map(|entry| &mut entry.data)
+    }
+
+    pub fn insert(&mut self, aabb: Aabb, data: T) -> ColliderHandle {
+        let handle = ColliderHandle(self.next_handle);
+        self.next_handle += 1;
+
+        self.add_to_cells(handle, &aabb);
+        self.entries.insert(handle, Entry { aabb, data });
+
+        handle
+    }
+
+    pub fn remove(&mut self, handle: ColliderHandle) -> Option<T> {
+        let entry = self.entries.remove(&handle)?;
+        self.remove_from_cells(handle, &entry.aabb);
+
+        Some(entry.data)
+    }
+
+    /// Gives a collider a new box. Returns false if the handle isn't in the index.
+    pub fn move_collider(&mut self, handle: ColliderHandle, aabb: Aabb) -> bool {
+        let old_aabb = match self.entries.get(&handle) {
+            Some(entry) => entry.aabb,
+            None => return false
+        };
+
+        // most moves stay within the same cells, no need to touch the grid then
+        if self.cell_range(&old_aabb) != self.cell_range(&aabb) {
+            self.remove_from_cells(handle, &old_aabb);
+            self.add_to_cells(handle, &aabb);
+        }
+
+        if let Some(entry) = self.entries.get_mut(&handle) {
+            entry.aabb = aabb;
+        }
+
+        true
+    }
+
+    /// Every collider overlapping `aabb`
+    pub fn query_aabb(&self, aabb: &Aabb) -> Vec<ColliderHandle> {
+        self.candidates(aabb).into_iter()
+            .filter(|handle| self.entries[handle].aabb.overlaps(aabb))
+            .collect()
+    }
+
+    /// Every collider the ray passes through within `max_distance`, closest first.
+    /// Nothing is hit by a ray that doesn't end or doesn't start anywhere.
+    pub fn query_ray(&self, origin: Vector3<f32>, direction: Vector3<f32>, max_distance: f32) -> Vec<RayHit> {
+        if direction.magnitude2() == 0. || !is_finite(direction) || !is_finite(origin) || !max_distance.is_finite() {
+            return Vec::new();
+        }
+
+        let direction = direction.normalize();
+        let mut tested = HashSet::new();
+        let mut hits = Vec::new();
+
+        for cell in self.ray_cells(origin, direction, max_distance) {
+            if let Some(handles) = self.cells.get(&cell) {
+                for handle in handles {
+                    if !tested.insert(*handle) {
+                        continue;
+                    }
+
+                    if let Some((distance, normal)) = self.entries[handle].aabb.ray_intersection(origin, direction) {
+                        if distance <= max_distance {
+                            hits.push(RayHit { handle: *handle, distance, normal });
+                        }
+                    }
+                }
+            }
+        }
+
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+
+        hits
+    }
+
+    /// Every collider `aabb` runs into while moving by `motion`, earliest first.
+    /// Colliders it already overlaps are reported at time 0.
+    pub fn query_swept(&self, aabb: &Aabb, motion: Vector3<f32>) -> Vec<SweptHit> {
+        let mut hits: Vec<SweptHit> = self.candidates(&aabb.swept(motion)).into_iter()
+            .filter_map(|handle| {
+                aabb.sweep(motion, &self.entries[&handle].aabb)
+                    .map(|(time, normal)| SweptHit { handle, time, normal })
+            })
+            .collect();
+
+        hits.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        hits
+    }
+}
+
+impl<T> SpatialHash<T> {
+    fn cell_of(&self, point: Vector3<f32>) -> Cell {
+        (
+            (point.x / self.cell_size).floor() as i32,
+            (point.y / self.cell_size).floor() as i32,
+            (point.z / self.cell_size).floor() as i32,
+        )
+    }
+
+    fn cell_range(&self, aabb: &Aabb) -> (Cell, Cell) {
+        (self.cell_of(aabb.min), self.cell_of(aabb.max))
+    }
+
+    fn cells_in(&self, aabb: &Aabb) -> Vec<Cell> {
+        let (min, max) = self.cell_range(aabb);
+        let mut cells = Vec::new();
+
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                for z in min.2..=max.2 {
+                    cells.push((x, y, z));
+                }
+            }
+        }
+
+        cells
+    }
+
+    fn add_to_cells(&mut self, handle: ColliderHandle, aabb: &Aabb) {
+        for cell in self.cells_in(aabb) {
+            self.cells.entry(cell).or_default().push(handle);
+        }
+    }
+
+    fn remove_from_cells(&mut self, handle: ColliderHandle, aabb: &Aabb) {
+        for cell in self.cells_in(aabb) {
+            if let Some(handles) = self.cells.get_mut(&cell) {
+                handles.retain(|other| *other != handle);
+
+                if handles.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+    }
+
+    // everything filed in the cells `aabb` covers, without duplicates
+    fn candidates(&self, aabb: &Aabb) -> HashSet<ColliderHandle> {
+        let mut candidates = HashSet::new();
+
+        for cell in self.cells_in(aabb) {
+            if let Some(handles) = self.cells.get(&cell) {
+                candidates.extend(handles.iter().copied());
+            }
+        }
+
+        candidates
+    }
+
+    // grid cells a ray passes through in order (Amanatides & Woo)
+    fn ray_cells(&self, origin: Vector3<f32>, direction: Vector3<f32>, max_distance: f32) -> Vec<Cell> {
+        let mut cell = self.cell_of(origin);
+        let mut cells = vec![cell];
+
+        let step = |d: f32| if d > 0. { 1 } else if d < 0. { -1 } else { 0 };
+        let steps = (step(direction.x), step(direction.y), step(direction.z));
+
+        // distance along the ray to the first cell border on each axis, and between borders
+        let first_border = |origin: f32, cell: i32, dir: f32| {
+            if dir > 0. {
+                ((cell + 1) as f32 * self.cell_size - origin) / dir
+            } else if dir < 0. {
+                (cell as f32 * self.cell_size - origin) / dir
+            } else {
+                f32::INFINITY
+            }
+        };
+
+        let delta = |dir: f32| if dir != 0. { self.cell_size / dir.abs() } else { f32::INFINITY };
+
+        let mut t_max = Vector3::new(
+            first_border(origin.x, cell.0, direction.x),
+            first_border(origin.y, cell.1, direction.y),
+            first_border(origin.z, cell.2, direction.z),
+        );
+
+        let t_delta = Vector3::new(delta(direction.x), delta(direction.y), delta(direction.z));
+
+        loop {
+            if t_max.x < t_max.y && t_max.x < t_max.z {
+                if t_max.x > max_distance { break; }
+                cell.0 += steps.0;
+                t_max.x += t_delta.x;
+            } else if t_max.y < t_max.z {
+                if t_max.y > max_distance { break; }
+                cell.1 += steps.1;
+                t_max.y += t_delta.y;
+            } else {
+                if t_max.z > max_distance { break; }
+                cell.2 += steps.2;
+                t_max.z += t_delta.z;
+            }
+
+            cells.push(cell);
+        }
+
+        cells
+    }
+}
+
+fn is_finite(vector: Vector3<f32>) -> bool {
+    vector.x.is_finite() && vector.y.is_finite() && vector.z.is_finite()
+}
+
+impl<T> Default for SpatialHash<T> {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CELL_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use crate::aabb::Aabb;
+    use super::SpatialHash;
+
+    fn unit_box(x: f32, y: f32, z: f32) -> Aabb {
+        Aabb::new(Vector3::new(x, y, z), Vector3::new(x + 1., y + 1., z + 1.))
+    }
+
+    #[test]
+    fn insert_query_remove() {
+        let mut hash = SpatialHash::new(4.);
+
+        let near = hash.insert(unit_box(0., 0., 0.), "near");
+        let far = hash.insert(unit_box(20., 0., 0.), "far");
+
+        assert_eq!(hash.query_aabb(&unit_box(0.5, 0.5, 0.5)), vec![near]);
+        assert_eq!(hash.query_aabb(&unit_box(19.5, 0., 0.)), vec![far]);
+
+        assert_eq!(hash.remove(near), Some("near"));
+        assert!(hash.query_aabb(&unit_box(0.5, 0.5, 0.5)).is_empty());
+        assert_eq!(hash.len(), 1);
+    }
+
+    #[test]
+    fn move_collider_across_cells() {
+        let mut hash = SpatialHash::new(4.);
+        let handle = hash.insert(unit_box(0., 0., 0.), ());
+
+        assert!(hash.move_collider(handle, unit_box(-10., 5., 3.)));
+
+        assert!(hash.query_aabb(&unit_box(0., 0., 0.)).is_empty());
+        assert_eq!(hash.query_aabb(&unit_box(-10.5, 5., 3.)), vec![handle]);
+    }
+
+    #[test]
+    fn ray_hits_closest_first() {
+        let mut hash = SpatialHash::new(4.);
+
+        let second = hash.insert(unit_box(10., 0., 0.), ());
+        let first = hash.insert(unit_box(-5., 0., 0.), ());
+        hash.insert(unit_box(-5., 10., 0.), ()); // off the ray
+
+        let hits = hash.query_ray(Vector3::new(-20., 0.5, 0.5), Vector3::new(1., 0., 0.), 100.);
+
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].handle, first);
+        assert!((hits[0].distance - 15.).abs() < 1e-4);
+        assert_eq!(hits[0].normal, Vector3::new(-1., 0., 0.));
+        assert_eq!(hits[1].handle, second);
+
+        assert_eq!(hash.query_ray(Vector3::new(-20., 0.5, 0.5), Vector3::new(1., 0., 0.), 10.).len(), 0);
+
+        // these would walk the grid forever
+        assert!(hash.query_ray(Vector3::new(-20., 0.5, 0.5), Vector3::new(1., 0., 0.), f32::INFINITY).is_empty());
+        assert!(hash.query_ray(Vector3::new(-20., 0.5, 0.5), Vector3::new(1., 0., 0.), f32::NAN).is_empty());
+        assert!(hash.query_ray(Vector3::new(f32::NAN, 0.5, 0.5), Vector3::new(1., 0., 0.), 100.).is_empty());
+    }
+
+    #[test]
+    fn swept_query_finds_first_contact() {
+        let mut hash = SpatialHash::new(4.);
+        let wall = hash.insert(Aabb::new(Vector3::new(5., -10., -10.), Vector3::new(6., 10., 10.)), ());
+
+        let hits = hash.query_swept(&unit_box(0., 0., 0.), Vector3::new(8., 0., 0.));
+
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].handle, wall);
+        assert!((hits[0].time - 0.5).abs() < 1e-4);
+        assert_eq!(hits[0].normal, Vector3::new(-1., 0., 0.));
+
+        assert!(hash.query_swept(&unit_box(0., 0., 0.), Vector3::new(3., 0., 0.)).is_empty());
+    }
+}
@@ -1,4 +1,7 @@
 pub mod box_collider;
+pub mod aabb;
+pub mod voxel_collision;
+pub mod broadphase;
 
 /*#[cfg(test)]
 mod tests {
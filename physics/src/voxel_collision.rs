@@ -0,0 +1,337 @@
+use cgmath::{Vector3, Zero};
+
+use crate::{aabb::Aabb, box_collider::BoxCollider};
+
+// boxes closer than this are treated as touching, so rounding errors
+// after a clip don't leave us stuck inside the block we just hit
+const EPSILON: f32 = 1e-4;
+
+// how far below the collider we look when checking for ground
+const GROUND_PROBE: f32 = 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MoveResult {
+    /// New min corner of the collider
+    pub position: Vector3<f32>,
+    /// How far the collider actually moved
+    pub motion: Vector3<f32>,
+    /// Which axes were blocked (their motion got cut short)
+    pub collided: Vector3<bool>,
+    /// Standing on something after the move
+    pub grounded: bool,
+    /// The collider climbed a ledge to get here
+    pub stepped: bool,
+}
+
+impl MoveResult {
+    /// `velocity` with the blocked axes zeroed, so we don't keep pushing into walls
+    pub fn clip_velocity(&self, velocity: Vector3<f32>) -> Vector3<f32> {
+        Vector3::new(
+            if self.collided.x { 0. } else { velocity.x },
+            if self.collided.y { 0. } else { velocity.y },
+            if self.collided.z { 0. } else { velocity.z },
+        )
+    }
+}
+
+/// Moves `collider` by `motion` through a voxel grid, one axis at a time (Y, X, then Z),
+/// stopping at every solid voxel in the way and sliding along whatever it hits.
+///
+/// `is_solid` is asked about block positions and is free to look across chunk borders.
+/// If the collider starts out on the ground and gets blocked horizontally, it also tries
+/// to climb ledges up to `step_height` blocks high and keeps whichever move got further.
+pub fn move_and_slide<F>(collider: &BoxCollider, motion: Vector3<f32>, step_height: f32, is_solid: F) -> MoveResult
+    where F: Fn(i32, i32, i32) -> bool {
+    let start = Aabb::from_collider(collider);
+    let was_grounded = is_on_ground(&start, &is_solid);
+
+    let (moved, collided) = slide(&start, motion, &is_solid);
+
+    let mut result = MoveResult {
+        position: moved.min,
+        motion: moved.min - start.min,
+        collided,
+        grounded: (collided.y && motion.y < 0.) || is_on_ground(&moved, &is_solid),
+        stepped: false,
+    };
+
+    let blocked_horizontally = collided.x || collided.z;
+
+    if step_height > 0. && was_grounded && blocked_horizontally && motion.y <= 0. {
+        if let Some(stepped) = try_step(&start, motion, step_height, &is_solid) {
+            let horizontal = |m: Vector3<f32>| m.x * m.x + m.z * m.z;
+
+            if horizontal(stepped.motion) > horizontal(result.motion) + EPSILON {
+                result = stepped;
+            }
+        }
+    }
+
+    result
+}
+
+/// Whether there's a solid voxel right underneath the collider
+pub fn is_grounded<F>(collider: &BoxCollider, is_solid: F) -> bool
+    where F: Fn(i32, i32, i32) -> bool {
+    is_on_ground(&Aabb::from_collider(collider), &is_solid)
+}
+
+fn is_on_ground<F>(aabb: &Aabb, is_solid: &F) -> bool
+    where F: Fn(i32, i32, i32) -> bool {
+    let blockers = solid_voxels(&aabb.swept(Vector3::new(0., -GROUND_PROBE, 0.)), is_solid);
+
+    clip_axis(aabb, &blockers, Axis::Y, -GROUND_PROBE) > -GROUND_PROBE
+}
+
+fn slide<F>(start: &Aabb, motion: Vector3<f32>, is_solid: &F) -> (Aabb, Vector3<bool>)
+    where F: Fn(i32, i32, i32) -> bool {
+    // every voxel the move could possibly touch, so fast moves can't skip over anything
+    let blockers = solid_voxels(&start.swept(motion), is_solid);
+
+    let mut aabb = *start;
+    let mut collided = Vector3::new(false, false, false);
+
+    let dy = clip_axis(&aabb, &blockers, Axis::Y, motion.y);
+    aabb = aabb.translate(Vector3::new(0., dy, 0.));
+    collided.y = dy != motion.y;
+
+    let dx = clip_axis(&aabb, &blockers, Axis::X, motion.x);
+    aabb = aabb.translate(Vector3::new(dx, 0., 0.));
+    collided.x = dx != motion.x;
+
+    let dz = clip_axis(&aabb, &blockers, Axis::Z, motion.z);
+    aabb = aabb.translate(Vector3::new(0., 0., dz));
+    collided.z = dz != motion.z;
+
+    (aabb, collided)
+}
+
+// lift the collider, do the horizontal part of the move, then put it back down
+fn try_step<F>(start: &Aabb, motion: Vector3<f32>, step_height: f32, is_solid: &F) -> Option<MoveResult>
+    where F: Fn(i32, i32, i32) -> bool {
+    let (raised, _) = slide(start, Vector3::new(0., step_height, 0.), is_solid);
+    let lift = raised.min.y - start.min.y;
+
+    if lift <= EPSILON {
+        return None;
+    }
+
+    let (moved, collided) = slide(&raised, Vector3::new(motion.x, 0., motion.z), is_solid);
+    let (lowered, _) = slide(&moved, Vector3::new(0., -lift + motion.y.min(0.), 0.), is_solid);
+
+    // didn't land on anything, this wasn't a ledge
+    if !is_on_ground(&lowered, is_solid) {
+        return None;
+    }
+
+    Some(MoveResult {
+        position: lowered.min,
+        motion: lowered.min - start.min,
+        collided: Vector3::new(collided.x, false, collided.z),
+        grounded: true,
+        stepped: lowered.min.y > start.min.y + EPSILON,
+    })
+}
+
+fn solid_voxels<F>(area: &Aabb, is_solid: &F) -> Vec<Aabb>
+    where F: Fn(i32, i32, i32) -> bool {
+    let min = Vector3::new(
+        (area.min.x - EPSILON).floor() as i32,
+        (area.min.y - EPSILON).floor() as i32,
+        (area.min.z - EPSILON).floor() as i32,
+    );
+
+    let max = Vector3::new(
+        (area.max.x + EPSILON).ceil() as i32,
+        (area.max.y + EPSILON).ceil() as i32,
+        (area.max.z + EPSILON).ceil() as i32,
+    );
+
+    let mut voxels = Vec::new();
+
+    for x in min.x..max.x {
+        for y in min.y..max.y {
+            for z in min.z..max.z {
+                if is_solid(x, y, z) {
+                    voxels.push(Aabb::voxel(x, y, z));
+                }
+            }
+        }
+    }
+
+    voxels
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Axis { X, Y, Z }
+
+fn component(vec: Vector3<f32>, axis: Axis) -> f32 {
+    match axis {
+        Axis::X => vec.x,
+        Axis::Y => vec.y,
+        Axis::Z => vec.z,
+    }
+}
+
+// do the two boxes overlap on the axes other than `axis`?
+fn overlaps_across(a: &Aabb, b: &Aabb, axis: Axis) -> bool {
+    let overlap = |a_min: f32, a_max: f32, b_min: f32, b_max: f32| a_min < b_max - EPSILON && a_max > b_min + EPSILON;
+
+    let x = overlap(a.min.x, a.max.x, b.min.x, b.max.x);
+    let y = overlap(a.min.y, a.max.y, b.min.y, b.max.y);
+    let z = overlap(a.min.z, a.max.z, b.min.z, b.max.z);
+
+    match axis {
+        Axis::X => y && z,
+        Axis::Y => x && z,
+        Axis::Z => x && y,
+    }
+}
+
+// how far `aabb` can move along `axis` (up to `delta`) before it runs into one of `blockers`
+fn clip_axis(aabb: &Aabb, blockers: &[Aabb], axis: Axis, delta: f32) -> f32 {
+    if delta.is_zero() {
+        return delta;
+    }
+
+    let mut delta = delta;
+
+    for blocker in blockers {
+        if !overlaps_across(aabb, blocker, axis) {
+            continue;
+        }
+
+        if delta > 0. {
+            let gap = component(blocker.min, axis) - component(aabb.max, axis);
+
+            if gap >= -EPSILON && gap < delta {
+                delta = gap.max(0.);
+            }
+        } else {
+            let gap = component(blocker.max, axis) - component(aabb.min, axis);
+
+            if gap <= EPSILON && gap > delta {
+                delta = gap.min(0.);
+            }
+        }
+    }
+
+    delta
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use crate::box_collider::BoxCollider;
+    use super::move_and_slide;
+
+    const PLAYER_SIZE: Vector3<f32> = Vector3::new(0.6, 1.8, 0.6);
+
+    fn player_at(x: f32, y: f32, z: f32) -> BoxCollider {
+        BoxCollider::new(Vector3::new(x, y, z), PLAYER_SIZE, Vector3::new(0., 0., 0.))
+    }
+
+    fn approx(a: f32, b: f32) -> bool { (a - b).abs() < 1e-3 }
+
+    // flat floor filling y = 0
+    fn floor(_x: i32, y: i32, _z: i32) -> bool { y == 0 }
+
+    #[test]
+    fn lands_on_floor() {
+        let result = move_and_slide(&player_at(0.2, 1.5, 0.2), Vector3::new(0., -1., 0.), 0., floor);
+
+        assert!(approx(result.position.y, 1.));
+        assert!(result.collided.y);
+        assert!(result.grounded);
+    }
+
+    #[test]
+    fn fast_fall_does_not_tunnel() {
+        let result = move_and_slide(&player_at(0.2, 40., 0.2), Vector3::new(0., -100., 0.), 0., floor);
+
+        assert!(approx(result.position.y, 1.));
+        assert!(result.grounded);
+    }
+
+    #[test]
+    fn standing_on_floor_is_grounded_and_walks_freely() {
+        let result = move_and_slide(&player_at(0.2, 1., 0.2), Vector3::new(3., 0., 2.), 0., floor);
+
+        assert!(approx(result.position.x, 3.2));
+        assert!(approx(result.position.z, 2.2));
+        assert!(!result.collided.x && !result.collided.z);
+        assert!(result.grounded);
+    }
+
+    #[test]
+    fn slides_along_wall() {
+        // wall at x = 2
+        let wall = |x: i32, y: i32, _z: i32| y == 0 || x == 2;
+
+        let result = move_and_slide(&player_at(1.2, 1., 0.2), Vector3::new(0.5, 0., 0.5), 0., wall);
+
+        assert!(approx(result.position.x, 1.4));
+        assert!(approx(result.position.z, 0.7));
+        assert!(result.collided.x);
+        assert!(!result.collided.z);
+    }
+
+    #[test]
+    fn floor_across_chunk_seam() {
+        // solid only for negative x, the player straddles x = 0
+        let half_floor = |x: i32, y: i32, _z: i32| y == 0 && x < 0;
+
+        let result = move_and_slide(&player_at(-0.3, 1.2, 0.2), Vector3::new(0., -0.5, 0.), 0., half_floor);
+
+        assert!(approx(result.position.y, 1.));
+        assert!(result.grounded);
+    }
+
+    #[test]
+    fn steps_up_single_block_ledge() {
+        // one block high ledge starting at x = 2
+        let ledge = |x: i32, y: i32, _z: i32| y == 0 || (y == 1 && x >= 2);
+
+        let result = move_and_slide(&player_at(1.2, 1., 0.2), Vector3::new(0.5, 0., 0.), 1., ledge);
+
+        assert!(result.stepped);
+        assert!(approx(result.position.y, 2.));
+        assert!(approx(result.position.x, 1.7));
+        assert!(result.grounded);
+    }
+
+    #[test]
+    fn does_not_step_up_two_block_wall() {
+        let wall = |x: i32, y: i32, _z: i32| y == 0 || ((y == 1 || y == 2) && x >= 2);
+
+        let result = move_and_slide(&player_at(1.2, 1., 0.2), Vector3::new(0.5, 0., 0.), 1., wall);
+
+        assert!(!result.stepped);
+        assert!(approx(result.position.y, 1.));
+        assert!(approx(result.position.x, 1.4));
+        assert!(result.collided.x);
+    }
+
+    #[test]
+    fn does_not_step_up_while_airborne() {
+        let ledge = |x: i32, y: i32, _z: i32| y == 0 || (y == 1 && x >= 2);
+
+        let result = move_and_slide(&player_at(1.2, 1.5, 0.2), Vector3::new(0.5, 0., 0.), 1., ledge);
+
+        assert!(!result.stepped);
+        assert!(approx(result.position.x, 1.4));
+    }
+
+    #[test]
+    fn ceiling_stops_upward_motion() {
+        let ceiling = |_x: i32, y: i32, _z: i32| y == 0 || y == 4;
+
+        let result = move_and_slide(&player_at(0.2, 1., 0.2), Vector3::new(0., 2., 0.), 0., ceiling);
+
+        assert!(approx(result.position.y, 2.2));
+        assert!(result.collided.y);
+        assert!(!result.grounded);
+        assert_eq!(result.clip_velocity(Vector3::new(1., 5., 1.)), Vector3::new(1., 0., 1.));
+    }
+}
@@ -0,0 +1,125 @@
+use std::ops::Range;
+
+/// Hands out ranges of a big buffer, first fit, merging freed neighbours back together.
+/// Doesn't know about the GPU, sizes are in whatever unit the owner likes.
+pub struct ArenaAllocator {
+    capacity: u64,
+    // sorted by start, never touching each other
+    free_ranges: Vec<Range<u64>>,
+}
+
+impl ArenaAllocator {
+    pub fn new(capacity: u64) -> Self {
+        let mut arena = Self {
+            capacity: 0,
+            free_ranges: Vec::new(),
+        };
+
+        arena.grow(capacity);
+
+        arena
+    }
+
+    /// None if no free range is big enough, `grow` and try again
+    pub fn allocate(&mut self, size: u64) -> Option<Range<u64>> {
+        if size == 0 {
+            return None;
+        }
+
+        let index = self.free_ranges.iter().position(|range| range.end - range.start >= size)?;
+        let start = self.free_ranges[index].start;
+
+        if self.free_ranges[index].end - start == size {
+            self.free_ranges.remove(index);
+        } else {
+            self.free_ranges[index].start += size;
+        }
+
+        Some(start..start + size)
+    }
+
+    pub fn free(&mut self, range: Range<u64>) {
+        if range.start >= range.end {
+            return;
+        }
+
+        let index = self.free_ranges.iter()
+            .position(|free| free.start > range.start)
+            .unwrap_or(self.free_ranges.len());
+
+        self.free_ranges.insert(index, range);
+
+        // merge with the next one first, so `index` stays valid
+        if index + 1 < self.free_ranges.len() && self.free_ranges[index].end == self.free_ranges[index + 1].start {
+            let next = self.free_ranges.remove(index + 1);
+            self.free_ranges[index].end = next.end;
+        }
+
+        if index > 0 && self.free_ranges[index - 1].end == self.free_ranges[index].start {
+            let current = self.free_ranges.remove(index);
+            self.free_ranges[index - 1].end = current.end;
+        }
+    }
+
+    /// Adds the space between the old and new capacity to the free list
+    pub fn grow(&mut self, new_capacity: u64) {
+        if new_capacity <= self.capacity {
+            return;
+        }
+
+        let old_capacity = self.capacity;
+        self.capacity = new_capacity;
+
+        self.free(old_capacity..new_capacity);
+    }
+}
+
+// getters
+impl ArenaAllocator {
+    pub fn get_capacity(&self) -> u64 { self.capacity }
+
+    pub fn get_free_space(&self) -> u64 {
+        self.free_ranges.iter().map(|range| range.end - range.start).sum()
+    }
+
+    pub fn get_used_space(&self) -> u64 { self.capacity - self.get_free_space() }
+
+    pub fn get_free_range_count(&self) -> usize { self.free_ranges.len() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn freed_ranges_are_reused_and_merged() {
+        let mut arena = ArenaAllocator::new(100);
+
+        let a = arena.allocate(30).unwrap();
+        let b = arena.allocate(30).unwrap();
+        let c = arena.allocate(30).unwrap();
+
+        assert_eq!((a.clone(), b.clone(), c.clone()), (0..30, 30..60, 60..90));
+        assert_eq!(arena.allocate(20), None);
+
+        arena.free(a);
+        arena.free(c);
+        assert_eq!(arena.get_free_range_count(), 2);
+
+        // the middle one joins both neighbours and the tail back into one range
+        arena.free(b);
+        assert_eq!(arena.get_free_range_count(), 1);
+        assert_eq!(arena.allocate(100), Some(0..100));
+    }
+
+    #[test]
+    fn growing_extends_the_last_free_range() {
+        let mut arena = ArenaAllocator::new(10);
+
+        arena.allocate(4).unwrap();
+        arena.grow(20);
+
+        assert_eq!(arena.allocate(16), Some(4..20));
+        assert_eq!(arena.get_free_space(), 0);
+    }
+}
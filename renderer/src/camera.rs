@@ -8,6 +8,7 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     0.0, 0.0, 0.5, 1.0,
 );
 
+#[derive(Debug, Clone, Copy)]
 pub struct Projection {
     aspect: f32,
     fovy: Rad<f32>,
@@ -35,11 +36,26 @@ impl Projection {
         self.aspect = width as f32 / height as f32;
     }
 
+    pub fn set_fovy<F: Into<Rad<f32>>>(&mut self, fovy: F) {
+        self.fovy = fovy.into();
+    }
+
     pub fn calc_matrix(&self) -> Matrix4<f32> {
         OPENGL_TO_WGPU_MATRIX * perspective(self.fovy, self.aspect, self.znear, self.zfar)
     }
 }
 
+// getters
+impl Projection {
+    pub fn get_aspect(&self) -> f32 { self.aspect }
+
+    pub fn get_fovy(&self) -> Rad<f32> { self.fovy }
+
+    pub fn get_znear(&self) -> f32 { self.znear }
+
+    pub fn get_zfar(&self) -> f32 { self.zfar }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Camera {
     pub position: Point3<f32>,
@@ -61,18 +77,28 @@ impl Camera {
     }
 
     pub fn calc_matrix(&self) -> Matrix4<f32> {
+        self.look_from(self.position)
+    }
+
+    /// The view matrix as if the camera sat at the origin, for drawing things positioned relative to it
+    pub fn calc_relative_matrix(&self) -> Matrix4<f32> {
+        self.look_from(Point3::new(0.0, 0.0, 0.0))
+    }
+
+    fn look_from(&self, position: Point3<f32>) -> Matrix4<f32> {
+        Matrix4::look_at_dir(position, self.get_direction(), Vector3::unit_y())
+    }
+
+    /// Which way the camera is looking
+    pub fn get_direction(&self) -> Vector3<f32> {
         let (sin_pitch, cos_pitch) = self.pitch.0.sin_cos();
         let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
-        
-        Matrix4::look_at_dir(
-            self.position,
-            Vector3::new(
-                cos_pitch * cos_yaw,
-                sin_pitch,
-                cos_pitch * sin_yaw
-            ).normalize(),
-            Vector3::unit_y(),
-        )
+
+        Vector3::new(
+            cos_pitch * cos_yaw,
+            sin_pitch,
+            cos_pitch * sin_yaw
+        ).normalize()
     }
 
     pub fn pos_as_vec3(&self) -> Vector3<f32> {
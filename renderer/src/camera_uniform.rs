@@ -1,9 +1,16 @@
+use world::chunk::CHUNK_SIZE;
+
 use super::camera::{Camera, Projection};
 
 #[repr(C)]
 #[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct CameraUniform {
     pub view_proj: [[f32; 4]; 4],
+    // chunks are drawn relative to the camera so far away ones don't jitter:
+    // the chunk the camera is in, where in that chunk it is, and a view_proj from the origin
+    pub relative_view_proj: [[f32; 4]; 4],
+    pub camera_chunk: [i32; 4],
+    pub camera_offset: [f32; 4],
 }
 
 impl CameraUniform {
@@ -11,7 +18,10 @@ impl CameraUniform {
         use cgmath::SquareMatrix;
         
         Self {
-            view_proj: cgmath::Matrix4::identity().into()
+            view_proj: cgmath::Matrix4::identity().into(),
+            relative_view_proj: cgmath::Matrix4::identity().into(),
+            camera_chunk: [0; 4],
+            camera_offset: [0.0; 4],
         }
     }
 
@@ -19,5 +29,32 @@ impl CameraUniform {
         // update view_position later with the light stuff
         // self.view_position = camera.position.to_homogeneous;
         self.view_proj = (projection.calc_matrix() * camera.calc_matrix()).into();
+        self.relative_view_proj = (projection.calc_matrix() * camera.calc_relative_matrix()).into();
+
+        let chunk_size = CHUNK_SIZE as f32;
+        let position = [camera.position.x, camera.position.y, camera.position.z];
+
+        for (axis, position) in position.iter().enumerate() {
+            let chunk = (position / chunk_size).floor();
+
+            self.camera_chunk[axis] = chunk as i32;
+            self.camera_offset[axis] = position - chunk * chunk_size;
+        }
     }
-}
\ No newline at end of file
+}
+
+/// The camera uniform alone at binding 0, see `Renderer::get_camera_bind_group`
+pub fn bind_group_layout_entries() -> Vec<wgpu::BindGroupLayoutEntry> {
+    vec![
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }
+    ]
+}
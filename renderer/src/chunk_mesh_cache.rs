@@ -0,0 +1,361 @@
+use std::{collections::HashMap, ops::Range};
+
+use cgmath::Vector3;
+use wgpu::util::DeviceExt;
+
+use crate::{buffer_arena::ArenaAllocator, quad_indices::{self, INDICES_PER_QUAD}, vertex::ChunkVertex};
+
+// in vertices, the arena doubles whenever a mesh doesn't fit
+const INITIAL_VERTEX_CAPACITY: u64 = 1 << 18;
+// enough for most chunks, grown when a bigger one shows up
+const INITIAL_QUAD_CAPACITY: usize = 1 << 12;
+// chunks, each gets a slot holding its origin that its vertices point at
+const INITIAL_SLOT_CAPACITY: u64 = 1 << 10;
+// as many as the vertex has bits for
+const MAX_SLOTS: u64 = 1 << 16;
+
+const VERTEX_SIZE: u64 = std::mem::size_of::<ChunkVertex>() as u64;
+const ORIGIN_SIZE: u64 = std::mem::size_of::<[i32; 4]>() as u64;
+
+// what `multi_draw_indexed_indirect` reads for each draw
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct DrawIndexedIndirectArgs {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+/// Where one chunk's quads live in the shared vertex buffer, and which slot has its origin
+pub struct ChunkMesh {
+    vertices: Range<u64>,
+    slot: Range<u64>,
+    quad_count: u32,
+}
+
+impl ChunkMesh {
+    pub fn get_base_vertex(&self) -> i32 { self.vertices.start as i32 }
+
+    pub fn get_slot(&self) -> u32 { self.slot.start as u32 }
+
+    pub fn get_index_count(&self) -> u32 { self.quad_count * INDICES_PER_QUAD as u32 }
+}
+
+/// Owns the GPU copies of every chunk mesh, keyed by chunk position. All chunks share one
+/// vertex buffer, sub-allocated per chunk, and one quad index buffer, so drawing them
+/// binds buffers once. Chunk origins sit in a storage buffer, picked by the slot in each vertex,
+/// since indirect draws can't rely on instance offsets.
+/// Whoever owns the world tells it which chunks were remeshed or unloaded.
+pub struct ChunkMeshCache {
+    vertex_buffer: wgpu::Buffer,
+    vertex_arena: ArenaAllocator,
+
+    index_buffer: wgpu::Buffer,
+    quad_capacity: usize,
+
+    origin_buffer: wgpu::Buffer,
+    slot_arena: ArenaAllocator,
+    origin_bind_group_layout: wgpu::BindGroupLayout,
+    origin_bind_group: wgpu::BindGroup,
+
+    meshes: HashMap<Vector3<i32>, ChunkMesh>,
+
+    // None without MULTI_DRAW_INDIRECT, then every chunk gets its own draw_indexed
+    indirect_buffer: Option<wgpu::Buffer>,
+    indirect_capacity: usize,
+    indirect_count: u32,
+    draws_changed: bool,
+}
+
+impl ChunkMeshCache {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let supports_indirect = device.features().contains(wgpu::Features::MULTI_DRAW_INDIRECT);
+
+        let origin_buffer = create_origin_buffer(device, INITIAL_SLOT_CAPACITY);
+        let origin_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &origin_layout_entries(),
+            label: Some("chunk_origin_bind_group_layout"),
+        });
+        let origin_bind_group = create_origin_bind_group(device, &origin_bind_group_layout, &origin_buffer);
+
+        Self {
+            vertex_buffer: create_vertex_buffer(device, INITIAL_VERTEX_CAPACITY),
+            vertex_arena: ArenaAllocator::new(INITIAL_VERTEX_CAPACITY),
+
+            index_buffer: create_index_buffer(device, INITIAL_QUAD_CAPACITY),
+            quad_capacity: INITIAL_QUAD_CAPACITY,
+
+            origin_buffer,
+            slot_arena: ArenaAllocator::new(INITIAL_SLOT_CAPACITY),
+            origin_bind_group_layout,
+            origin_bind_group,
+
+            meshes: HashMap::new(),
+
+            indirect_buffer: if supports_indirect { Some(create_indirect_buffer(device, 0)) } else { None },
+            indirect_capacity: 0,
+            indirect_count: 0,
+            draws_changed: false,
+        }
+    }
+}
+
+impl ChunkMeshCache {
+    /// Uploads the chunk's quads into a free part of the shared vertex buffer, growing it if
+    /// nothing fits. The chunk's old range is freed first, so a remesh usually lands in place.
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, chunk_pos: Vector3<i32>, vertices: &[ChunkVertex]) {
+        self.free(chunk_pos);
+
+        let quad_count = quad_indices::quad_count(vertices.len());
+
+        if quad_count == 0 {
+            return;
+        }
+
+        let vertex_count = vertices.len() as u64;
+
+        let range = loop {
+            match self.vertex_arena.allocate(vertex_count) {
+                Some(range) => break range,
+                None => self.grow_vertex_buffer(device, queue, vertex_count)
+            }
+        };
+
+        let slot = loop {
+            match self.slot_arena.allocate(1) {
+                Some(slot) => break slot,
+                None if self.slot_arena.get_capacity() < MAX_SLOTS => self.grow_origin_buffer(device, queue),
+                None => {
+                    eprintln!("[LOG] Out of chunk slots, not drawing chunk {:?}", chunk_pos);
+
+                    self.vertex_arena.free(range);
+                    return;
+                }
+            }
+        };
+
+        if quad_count > self.quad_capacity {
+            self.quad_capacity = quad_count.next_power_of_two();
+            self.index_buffer = create_index_buffer(device, self.quad_capacity);
+        }
+
+        let vertices: Vec<ChunkVertex> = vertices.iter()
+            .map(|vertex| vertex.with_chunk_slot(slot.start as u32))
+            .collect();
+
+        queue.write_buffer(&self.vertex_buffer, range.start * VERTEX_SIZE, bytemuck::cast_slice(&vertices));
+        queue.write_buffer(&self.origin_buffer, slot.start * ORIGIN_SIZE, bytemuck::cast_slice(&[
+            [chunk_pos.x, chunk_pos.y, chunk_pos.z, 0]
+        ]));
+
+        self.meshes.insert(chunk_pos, ChunkMesh { vertices: range, slot, quad_count: quad_count as u32 });
+        self.draws_changed = true;
+    }
+
+    /// Stops drawing the chunk and gives its part of the vertex buffer back
+    pub fn free(&mut self, chunk_pos: Vector3<i32>) -> bool {
+        match self.meshes.remove(&chunk_pos) {
+            Some(mesh) => {
+                self.vertex_arena.free(mesh.vertices);
+                self.slot_arena.free(mesh.slot);
+                self.draws_changed = true;
+
+                true
+            },
+            None => false
+        }
+    }
+
+    pub fn clear(&mut self) {
+        let chunk_positions: Vec<Vector3<i32>> = self.meshes.keys().copied().collect();
+
+        for chunk_pos in chunk_positions {
+            self.free(chunk_pos);
+        }
+    }
+
+    /// Rewrites the indirect draw list if chunks changed. Call between uploading and drawing.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if !self.draws_changed {
+            return;
+        }
+
+        self.draws_changed = false;
+
+        if self.indirect_buffer.is_none() {
+            return;
+        }
+
+        let draws: Vec<DrawIndexedIndirectArgs> = self.meshes.values()
+            .map(|mesh| DrawIndexedIndirectArgs {
+                index_count: mesh.get_index_count(),
+                instance_count: 1,
+                first_index: 0,
+                base_vertex: mesh.get_base_vertex(),
+                first_instance: 0,
+            })
+            .collect();
+
+        if draws.len() > self.indirect_capacity {
+            self.indirect_capacity = draws.len().next_power_of_two();
+            self.indirect_buffer = Some(create_indirect_buffer(device, self.indirect_capacity));
+        }
+
+        if let Some(indirect_buffer) = &self.indirect_buffer {
+            queue.write_buffer(indirect_buffer, 0, bytemuck::cast_slice(&draws));
+        }
+
+        self.indirect_count = draws.len() as u32;
+    }
+
+    /// Draws every cached chunk. The pipeline and the other bind groups have to be set already,
+    /// the chunk origins are bound at `origin_group` (see `get_origin_bind_group_layout`).
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, origin_group: u32) {
+        if self.meshes.is_empty() {
+            return;
+        }
+
+        render_pass.set_bind_group(origin_group, &self.origin_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+
+        match &self.indirect_buffer {
+            Some(indirect_buffer) if !self.draws_changed => {
+                render_pass.multi_draw_indexed_indirect(indirect_buffer, 0, self.indirect_count);
+            },
+            _ => {
+                for mesh in self.meshes.values() {
+                    render_pass.draw_indexed(0..mesh.get_index_count(), mesh.get_base_vertex(), 0..1);
+                }
+            }
+        }
+    }
+
+    // doubles the vertex buffer until `needed` more vertices fit at the end, keeping what's in it
+    fn grow_vertex_buffer(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, needed: u64) {
+        let old_capacity = self.vertex_arena.get_capacity();
+        let mut new_capacity = old_capacity.max(1) * 2;
+
+        while new_capacity - old_capacity < needed {
+            new_capacity *= 2;
+        }
+
+        let new_buffer = create_vertex_buffer(device, new_capacity);
+        copy_buffer(device, queue, &self.vertex_buffer, &new_buffer, old_capacity * VERTEX_SIZE);
+
+        eprintln!("[LOG] Grew the chunk vertex buffer to {} vertices", new_capacity);
+
+        self.vertex_buffer = new_buffer;
+        self.vertex_arena.grow(new_capacity);
+    }
+
+    fn grow_origin_buffer(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let old_capacity = self.slot_arena.get_capacity();
+        let new_capacity = (old_capacity.max(1) * 2).min(MAX_SLOTS);
+
+        let new_buffer = create_origin_buffer(device, new_capacity);
+        copy_buffer(device, queue, &self.origin_buffer, &new_buffer, old_capacity * ORIGIN_SIZE);
+
+        self.origin_bind_group = create_origin_bind_group(device, &self.origin_bind_group_layout, &new_buffer);
+        self.origin_buffer = new_buffer;
+        self.slot_arena.grow(new_capacity);
+    }
+}
+
+// getters
+impl ChunkMeshCache {
+    pub fn get_mesh(&self, chunk_pos: Vector3<i32>) -> Option<&ChunkMesh> { self.meshes.get(&chunk_pos) }
+
+    pub fn contains(&self, chunk_pos: Vector3<i32>) -> bool { self.meshes.contains_key(&chunk_pos) }
+
+    pub fn len(&self) -> usize { self.meshes.len() }
+
+    pub fn is_empty(&self) -> bool { self.meshes.is_empty() }
+
+    pub fn uses_indirect_draws(&self) -> bool { self.indirect_buffer.is_some() }
+
+    pub fn get_origin_bind_group_layout(&self) -> &wgpu::BindGroupLayout { &self.origin_bind_group_layout }
+
+    /// Vertices in use and the vertex buffer's size
+    pub fn get_vertex_usage(&self) -> (u64, u64) {
+        (self.vertex_arena.get_used_space(), self.vertex_arena.get_capacity())
+    }
+}
+
+/// The chunk origins, a storage buffer indexed by the slot in each vertex
+pub fn origin_layout_entries() -> Vec<wgpu::BindGroupLayoutEntry> {
+    vec![
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }
+    ]
+}
+
+fn create_vertex_buffer(device: &wgpu::Device, capacity: u64) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Chunk Vertex Buffer"),
+        size: capacity * VERTEX_SIZE,
+        // COPY_SRC so it can be copied over when it grows
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false
+    })
+}
+
+fn create_origin_buffer(device: &wgpu::Device, capacity: u64) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Chunk Origin Buffer"),
+        size: capacity * ORIGIN_SIZE,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false
+    })
+}
+
+fn create_origin_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, buffer: &wgpu::Buffer) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }
+        ],
+        label: Some("chunk_origin_bind_group"),
+    })
+}
+
+// the copy is submitted right away, writes queued before it land first
+fn copy_buffer(device: &wgpu::Device, queue: &wgpu::Queue, from: &wgpu::Buffer, to: &wgpu::Buffer, size: u64) {
+    let mut encoder = device.create_command_encoder(
+        &wgpu::CommandEncoderDescriptor { label: Some("Chunk Buffer Grow Encoder") }
+    );
+
+    encoder.copy_buffer_to_buffer(from, 0, to, 0, size);
+    queue.submit(std::iter::once(encoder.finish()));
+}
+
+fn create_index_buffer(device: &wgpu::Device, quad_capacity: usize) -> wgpu::Buffer {
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Chunk Quad Index Buffer"),
+        contents: bytemuck::cast_slice(&quad_indices::build_quad_indices(quad_capacity)),
+        usage: wgpu::BufferUsages::INDEX
+    })
+}
+
+fn create_indirect_buffer(device: &wgpu::Device, draw_capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Chunk Indirect Buffer"),
+        // zero sized buffers aren't allowed
+        size: (draw_capacity.max(1) * std::mem::size_of::<DrawIndexedIndirectArgs>()) as u64,
+        usage: wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false
+    })
+}
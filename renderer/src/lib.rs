@@ -5,15 +5,30 @@ pub mod camera_uniform;
 pub mod camera;
 pub mod vertex;
 pub mod input_manager;
+pub mod buffer_arena;
+pub mod quad_indices;
+pub mod chunk_mesh_cache;
+pub mod sky;
+pub mod shadow;
+pub mod render_target;
+pub mod post_process;
+pub mod shader_library;
+pub mod pipeline;
+pub mod render_graph;
+pub mod ui;
+pub mod ui_renderer;
 
 // imports
-use std::{cell::RefCell, sync::{Arc, RwLock}};
+use std::{cell::{Cell, RefCell, RefMut}, collections::BTreeMap, rc::Rc};
 
 use anyhow::{Error, Result, anyhow};
+use common::settings::{PostProcessing, PresentMode};
 use input_manager::InputManager;
+use common::timestep::FixedTimestep;
 
+use wgpu::util::DeviceExt;
 use wgpu_glyph::{GlyphBrush, Section, Text, ab_glyph};
-use winit::{event::{DeviceEvent, KeyboardInput}, window::Window};
+use winit::{event::{DeviceEvent, ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent}, window::Window};
 
 //use texture;
 
@@ -22,20 +37,92 @@ pub enum RenderingError {
     GenericError(Error)
 }
 
+const SCREENSHOT_DIRECTORY: &str = "screenshots";
+const SCREENSHOT_KEY: VirtualKeyCode = VirtualKeyCode::F2;
+
+/// A camera to draw from instead of the state's own, see `Renderer::render_camera_to_image`
+#[derive(Debug, Clone, Copy)]
+pub struct ViewOverride {
+    pub camera: camera::Camera,
+    pub projection: camera::Projection,
+}
+
+/// Makes a state to go on the stack, see `Transition`
+pub type StateBuilder = Box<dyn FnOnce(&mut Renderer, &Window) -> Result<Box<dyn RenderableState>, Error>>;
+
+/// A change to the state stack a state asks for, see `RenderableState::take_transition`
+pub enum Transition {
+    Push(StateBuilder),
+    /// Removes the state asking
+    Pop,
+    /// Removes the state asking and puts another in its place
+    Replace(StateBuilder),
+    /// Removes every state, then pushes a new one
+    Reset(StateBuilder),
+    /// Removes every state and closes the game
+    Quit,
+}
+
 pub trait RenderableState {
     fn get_state_id(&self) -> u32;
 
     fn is_cursor_visible(&self) -> bool;
 
+    /// Whether the state covers the whole screen. States under a transparent one (like a pause menu) are drawn too.
+    fn is_opaque(&self) -> bool { true }
+
+    /// Whether keys and mouse movement stop here, or go on to the state underneath as well
+    fn blocks_input(&self) -> bool { true }
+
+    /// Whether the states underneath are paused while this one is on the stack
+    fn blocks_update(&self) -> bool { true }
+
+    /// Checked after every update, input and frame for a change to the state stack, only while the state is on top
+    fn take_transition(&mut self) -> Option<Transition> { None }
+
     fn new(renderer: &mut Renderer, window: &Window) -> Result<Box<Self>, Error> where Self: Sized;
 
+    /// Called at a fixed rate (see `Renderer::get_timestep`), possibly several times per frame
     fn update(&mut self, renderer: &Renderer, delta_time: f32) -> Result<(), Error>;
 
     fn on_resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) -> Result<(), Error>;
     fn handle_keys(&mut self, input_manager: &InputManager)  -> Result<bool, Error>;
     fn handle_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) -> Result<bool, Error>;
 
-    fn render<'a>(&'a mut self, renderer: &'a Renderer, render_pass: &mut wgpu::RenderPass<'a>, delta_time: f32) -> Result<(), Error>;
+    /// Called once per frame before `build_graph`, for updating what the frame's passes share
+    fn prepare_frame(&mut self, _renderer: &Renderer, _encoder: &mut wgpu::CommandEncoder) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called when shaders in `Renderer::get_shaders` were edited (only while hot reloading),
+    /// with their names. Failures to rebuild go to `Renderer::report_shader_reload`.
+    fn reload_shaders(&mut self, _renderer: &Renderer, _changed: &[String]) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called once per frame to add the state's passes, which run in whatever order their
+    /// resources need. Post processing and text are added after. By default it's just `RenderGraph::main_pass`,
+    /// which transparent states should leave out so they don't clear what's under them.
+    fn build_graph(&mut self, _renderer: &Renderer, graph: &mut render_graph::RenderGraph<'_>) -> Result<(), Error> {
+        graph.add_pass(graph.main_pass());
+        Ok(())
+    }
+
+    /// Called for each render pass the state added, with the pass's attachments already bound
+    fn render_pass<'a>(&'a mut self, renderer: &'a Renderer, pass: &render_graph::PassInfo<'a>, render_pass: &mut wgpu::RenderPass<'a>) -> Result<(), Error> {
+        self.render(renderer, render_pass, pass.get_delta_time())
+    }
+
+    /// Called for each encoder pass the state added
+    fn encode_pass(&mut self, _renderer: &Renderer, _pass: &render_graph::PassInfo<'_>, _encoder: &mut wgpu::CommandEncoder) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Draws the main pass when `render_pass` isn't overridden. `Renderer::get_interpolation_alpha`
+    /// says how far between the last two updates this frame is
+    fn render<'a>(&'a mut self, _renderer: &'a Renderer, _render_pass: &mut wgpu::RenderPass<'a>, _delta_time: f32) -> Result<(), Error> {
+        Ok(())
+    }
 
     fn exit(&mut self) -> Result<(), Error>;
 }
@@ -43,33 +130,89 @@ pub trait RenderableState {
 pub struct Renderer {
     adapter: wgpu::Adapter,
     surface: wgpu::Surface,
-    device: Arc<RwLock<wgpu::Device>>,
+    device: wgpu::Device,
     queue: wgpu::Queue,
     surface_config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
 
-    depth_texture: texture::Texture,
-
-    staging_belt: wgpu::util::StagingBelt,
+    shaders: shader_library::ShaderLibrary,
+    pipelines: RefCell<pipeline::PipelineCache>,
+    // why each shader that failed to reload did, shown until it's fixed
+    shader_errors: RefCell<BTreeMap<String, String>>,
+    shader_reload_timer: f32,
+
+    // one camera for every pass, see `update_camera`
+    camera_uniform: Cell<camera_uniform::CameraUniform>,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+
+    // the world is drawn here in HDR, then post processed onto the window
+    scene_target: render_target::RenderTarget,
+    post_processor: RefCell<post_process::PostProcessor>,
+    post_settings: Cell<PostProcessing>,
+    // applied to the surface at the start of the next frame
+    present_mode: Cell<PresentMode>,
+    screen_tint: Cell<[f32; 4]>,
+    // textures render graph passes make for themselves
+    transients: RefCell<render_graph::TransientPool>,
+
+    staging_belt: RefCell<wgpu::util::StagingBelt>,
     local_pool: futures::executor::LocalPool,
     local_spawner: futures::executor::LocalSpawner,
     glyph_brush: RefCell<GlyphBrush<()>>,
 
+    ui: RefCell<ui::Ui>,
+    ui_renderer: RefCell<ui_renderer::UiRenderer>,
+
     states: Vec<RefCell<Box<dyn RenderableState>>>,
     cursor_visible: bool,
 
     input_manager: InputManager,
+
+    timestep: FixedTimestep,
+
+    // set while drawing offscreen from a camera that isn't the state's
+    view_override: Option<ViewOverride>,
+    screenshot_requested: bool,
 } 
 
 // getters
 impl Renderer {
-    pub fn get_device(&self) -> Arc<RwLock<wgpu::Device>> { self.device.clone() }
+    pub fn get_device(&self) -> &wgpu::Device { &self.device }
 
     pub fn get_size(&self) -> winit::dpi::PhysicalSize<u32> { self.size }
 
     pub fn get_adapter(&self) -> &wgpu::Adapter { &self.adapter }
     pub fn get_queue(&self) -> &wgpu::Queue { &self.queue }
     pub fn get_surface_config(&self) -> &wgpu::SurfaceConfiguration { &self.surface_config }
+
+    pub fn supports_anisotropy(&self) -> bool {
+        self.adapter.get_downlevel_properties().flags.contains(wgpu::DownlevelFlags::ANISOTROPIC_FILTERING)
+    }
+
+    pub fn get_timestep(&self) -> &FixedTimestep { &self.timestep }
+    pub fn get_interpolation_alpha(&self) -> f32 { self.timestep.get_alpha() }
+
+    /// States should draw from this camera when there is one, instead of their own
+    pub fn get_view_override(&self) -> Option<&ViewOverride> { self.view_override.as_ref() }
+
+    /// Where pipelines should get their shader source, so they can be hot reloaded
+    pub fn get_shaders(&self) -> &shader_library::ShaderLibrary { &self.shaders }
+
+    /// Made from `camera_uniform::bind_group_layout_entries`
+    pub fn get_camera_bind_group(&self) -> &wgpu::BindGroup { &self.camera_bind_group }
+
+    pub fn get_camera_uniform(&self) -> camera_uniform::CameraUniform { self.camera_uniform.get() }
+
+    /// What pipelines drawing into the render graph's scene have to target
+    pub fn get_scene_format(&self) -> wgpu::TextureFormat { post_process::SCENE_FORMAT }
+
+    pub fn get_post_processing(&self) -> PostProcessing { self.post_settings.get() }
+
+    /// Widgets drawn through this show up over the frame being rendered
+    pub fn get_ui(&self) -> RefMut<'_, ui::Ui> { self.ui.borrow_mut() }
+
+    pub fn get_state_count(&self) -> usize { self.states.len() }
 }
 
 impl Renderer {
@@ -83,8 +226,10 @@ impl Renderer {
         }
     }
 
+    /// Puts `render_state` on top, unless the top state is already one of its kind
     pub fn push_state(&mut self, window: &Window, render_state: Box<dyn RenderableState>) -> Result<bool, Error> {
-        for state in &self.states {
+        // the same kind of state can be further down, like an error dialog over another one's game
+        if let Some(state) = self.peek() {
             if state.borrow().get_state_id() == render_state.get_state_id() {
                 return Ok(false);
             }
@@ -103,17 +248,97 @@ impl Renderer {
         }
     }
 
-    pub fn pop_state(&mut self) -> bool {
-        if let Some(_) = self.states.pop() {
-            true
-        } else {
-            false
+    /// Exits and removes the top state, letting the one under it set the cursor back up.
+    /// Returns false if there wasn't one.
+    pub fn pop_state(&mut self, window: &Window) -> Result<bool, Error> {
+        if self.states.is_empty() {
+            return Ok(false);
         }
+
+        self.exit_top_state()?;
+        self.update_cursor_visibility(window)?;
+
+        Ok(true)
     }
 
     pub fn clear_states(&mut self) {
         while let Some(_) = self.states.pop() { }
     }
+
+    /// Carries out the top state's `Transition`, if it asked for one. Returns false once the game should close.
+    pub fn process_transitions(&mut self, window: &Window) -> Result<bool, Error> {
+        let transition = match self.peek() {
+            Some(state) => state.borrow_mut().take_transition(),
+            None => return Ok(true)
+        };
+
+        let transition = match transition {
+            Some(transition) => transition,
+            None => return Ok(true)
+        };
+
+        match transition {
+            Transition::Push(build) => {
+                let state = build(self, window)?;
+                self.push_state(window, state)?;
+            },
+            Transition::Pop => {
+                self.pop_state(window)?;
+            },
+            Transition::Replace(build) => {
+                self.exit_top_state()?;
+
+                let state = build(self, window)?;
+                self.push_state(window, state)?;
+            },
+            Transition::Reset(build) => {
+                self.exit()?;
+
+                let state = build(self, window)?;
+                self.push_state(window, state)?;
+            },
+            Transition::Quit => {
+                self.exit()?;
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    // removes the top state, letting it clean up first
+    fn exit_top_state(&mut self) -> Result<(), Error> {
+        if let Some(state) = self.peek() {
+            state.borrow_mut().exit()?;
+        }
+
+        self.states.pop();
+        Ok(())
+    }
+
+    // the top state and every one under it down to the first opaque one, bottom first
+    fn get_visible_states(&self) -> &[RefCell<Box<dyn RenderableState>>] {
+        self.get_states_down_to(|state| state.is_opaque())
+    }
+
+    // the states that get to update, bottom first
+    fn get_updating_states(&self) -> &[RefCell<Box<dyn RenderableState>>] {
+        self.get_states_down_to(|state| state.blocks_update())
+    }
+
+    // the states that get keys and mouse movement, bottom first
+    fn get_input_states(&self) -> &[RefCell<Box<dyn RenderableState>>] {
+        self.get_states_down_to(|state| state.blocks_input())
+    }
+
+    // the states from the topmost one `stops` is true for up to the top, or all of them
+    fn get_states_down_to(&self, stops: impl Fn(&dyn RenderableState) -> bool) -> &[RefCell<Box<dyn RenderableState>>] {
+        let first = self.states.iter()
+            .rposition(|state| stops(&**state.borrow()))
+            .unwrap_or(0);
+
+        &self.states[first..]
+    }
 }
 
 impl Renderer {
@@ -133,7 +358,8 @@ impl Renderer {
 
         let (device, queue) = adapter.request_device(
             &wgpu::DeviceDescriptor {
-                features: wgpu::Features::empty(),
+                // chunks are drawn with one call where it's supported
+                features: adapter.features() & wgpu::Features::MULTI_DRAW_INDIRECT,
                 limits: wgpu::Limits::default(),
                 label: Some("Device Descriptor")
             },
@@ -152,7 +378,35 @@ impl Renderer {
 
         surface.configure(&device, &surface_config);
 
-        let depth_texture = texture::Texture::create_depth_texture(&device, &surface_config, "depth texture");
+        let scene_target = render_target::RenderTarget::new(&device, size.width, size.height, post_process::SCENE_FORMAT, "Scene Target");
+        let post_processor = post_process::PostProcessor::new(&device);
+
+        let shaders = shader_library::ShaderLibrary::from_build();
+        shaders.install_error_handler(&device);
+
+        let mut pipelines = pipeline::PipelineCache::new();
+        let ui_renderer = ui_renderer::UiRenderer::new(&device, &queue, &mut pipelines);
+
+        let camera_uniform = camera_uniform::CameraUniform::new();
+
+        let camera_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Camera Buffer"),
+                contents: bytemuck::cast_slice(&[camera_uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+            }
+        );
+
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &pipelines.get_bind_group_layout(&device, &camera_uniform::bind_group_layout_entries()),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                }
+            ],
+            label: Some("camera_bind_group"),
+        });
 
         // Font stuff, worry about making it work later
         let staging_belt = wgpu::util::StagingBelt::new(1024);
@@ -169,22 +423,44 @@ impl Renderer {
         Self {
             adapter,
             surface,
-            device: Arc::new(RwLock::new(device)),
+            device,
             queue,
             surface_config,
             size,
 
-            staging_belt,
+            staging_belt: RefCell::new(staging_belt),
             local_pool,
             local_spawner,
             glyph_brush: RefCell::new(glyph_brush),
 
-            depth_texture,
+            ui: RefCell::new(ui::Ui::new(window.scale_factor() as f32)),
+            ui_renderer: RefCell::new(ui_renderer),
+
+            shaders,
+            pipelines: RefCell::new(pipelines),
+            shader_errors: RefCell::new(BTreeMap::new()),
+            shader_reload_timer: 0.0,
+
+            camera_uniform: Cell::new(camera_uniform),
+            camera_buffer,
+            camera_bind_group,
+
+            scene_target,
+            post_processor: RefCell::new(post_processor),
+            post_settings: Cell::new(PostProcessing::default()),
+            present_mode: Cell::new(PresentMode::Fifo),
+            screen_tint: Cell::new([0.0; 4]),
+            transients: RefCell::new(render_graph::TransientPool::new()),
 
             states: Vec::new(),
             cursor_visible: true,
 
             input_manager: InputManager::new(),
+
+            timestep: FixedTimestep::default(),
+
+            view_override: None,
+            screenshot_requested: false,
         }
     }
 
@@ -194,6 +470,11 @@ impl Renderer {
 
             self.cursor_visible = visible;
 
+            // a grabbed cursor can't point at anything
+            if !visible {
+                self.ui.borrow_mut().mouse_moved(None);
+            }
+
             match window.set_cursor_grab(!self.cursor_visible) {
                 Ok(_) => {
                     window.set_cursor_visible(self.cursor_visible);
@@ -213,26 +494,20 @@ impl Renderer {
         self.surface_config.width = new_size.width;
         self.surface_config.height = new_size.height;
 
-        self.surface.configure(&self.device.clone().read().unwrap(), &self.surface_config);
+        self.surface.configure(&self.device, &self.surface_config);
         
-        self.depth_texture = texture::Texture::create_depth_texture(
-            &self.device.clone().read().unwrap(), &self.surface_config, "depth texture"
+        self.scene_target = render_target::RenderTarget::new(
+            &self.device, new_size.width.max(1), new_size.height.max(1), post_process::SCENE_FORMAT, "Scene Target"
         );
 
-        let states_len = self.states.len();
-
-        if states_len > 0 {
-            if let Some(state) = self.peek() {
-                match state.borrow_mut().on_resize(new_size) {
-                    Ok(_) => Ok(()),
-                    Err(err) => Err(anyhow!(err))
-                }
-            } else {
-                Ok(())
+        // states under a transparent one are still on screen
+        for state in &self.states {
+            if let Err(err) = state.borrow_mut().on_resize(new_size) {
+                return Err(anyhow!(err));
             }
-        } else {
-            Ok(())
         }
+
+        Ok(())
     }
 
     pub fn input(&mut self, window: &Window, event: &DeviceEvent, focused: bool) -> Result<bool, Error> {
@@ -249,24 +524,33 @@ impl Renderer {
                 ) => {
                     self.input_manager.process_keys(*key, *state);
 
-                    if let Some(state_) = self.peek() {
+                    if self.input_manager.key_just_pressed(SCREENSHOT_KEY) {
+                        self.screenshot_requested = true;
+                    }
+
+                    let mut handled = false;
+
+                    // top first, down to the first state that keeps input to itself
+                    for state_ in self.get_input_states().iter().rev() {
                         match state_.borrow_mut().handle_keys(&self.input_manager) {
-                            Ok(resp) => resp,
+                            Ok(resp) => handled |= resp,
                             Err(err) => return Err(anyhow!(err))
                         }
-                    } else {
-                        false
                     }
+
+                    handled
                 },
                 DeviceEvent::MouseMotion { delta: (delta_x, delta_y) } => {
-                    if let Some(state_) = self.peek() {
+                    let mut handled = self.states.is_empty();
+
+                    for state_ in self.get_input_states().iter().rev() {
                         match state_.borrow_mut().handle_mouse(*delta_x, *delta_y) {
-                            Ok(resp) => resp,
+                            Ok(resp) => handled |= resp,
                             Err(err) => return Err(anyhow!(err))
                         }
-                    } else {
-                        true
                     }
+
+                    handled
                 }
                 _ => false,
             };
@@ -290,103 +574,399 @@ impl Renderer {
         }
     }
 
+    /// Passes the cursor, clicks, scrolling, typing and scale factor changes on to the UI
+    pub fn window_input(&mut self, event: &WindowEvent) {
+        let mut ui = self.ui.borrow_mut();
+
+        match event {
+            WindowEvent::CursorMoved { position, .. } if self.cursor_visible => {
+                ui.mouse_moved(Some((position.x as f32, position.y as f32)));
+            },
+            WindowEvent::CursorLeft { .. } => ui.mouse_moved(None),
+            WindowEvent::MouseInput { state, button: MouseButton::Left, .. } => {
+                ui.mouse_button(*state == ElementState::Pressed && self.cursor_visible);
+            },
+            WindowEvent::MouseWheel { delta, .. } => {
+                ui.mouse_scrolled(match delta {
+                    MouseScrollDelta::LineDelta(_, lines) => *lines,
+                    // roughly a line's worth of pixels
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 20.0
+                });
+            },
+            WindowEvent::ReceivedCharacter(c) => ui.char_typed(*c),
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput { state: ElementState::Pressed, virtual_keycode: Some(key), .. },
+                ..
+            } => {
+                if let Some(key) = get_ui_key(*key) {
+                    ui.key_pressed(key);
+                }
+            },
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => ui.set_scale_factor(*scale_factor as f32),
+            _ => { }
+        }
+    }
+
+    /// Runs as many fixed simulation steps as the frame's `delta_time` adds up to
     pub fn update(&mut self, delta_time: f32) -> Result<(), Error> {
-        if let Some(state) = self.peek() {
-            state.borrow_mut().update(&self, delta_time)
-        } else {
-            Ok(())
+        let steps = self.timestep.advance(delta_time);
+        let step = self.timestep.get_step();
+
+        for _ in 0..steps {
+            // states under an overlay that doesn't pause them keep ticking
+            for state in self.get_updating_states() {
+                state.borrow_mut().update(self, step)?;
+            }
         }
+
+        Ok(())
     }
 
     pub fn render(&mut self, delta_time: f32) -> Result<(), RenderingError> {
+        if let Err(err) = self.reload_shaders(delta_time) {
+            return Err(RenderingError::GenericError(err));
+        }
+
+        if self.screenshot_requested {
+            self.screenshot_requested = false;
+            self.take_screenshot(delta_time);
+        }
+
+        let present_mode = get_present_mode(self.present_mode.get());
+
+        if present_mode != self.surface_config.present_mode {
+            self.surface_config.present_mode = present_mode;
+            self.surface.configure(&self.device, &self.surface_config);
+        }
+
         let frame = match self.surface.get_current_frame() {
             Ok(surface_frame) => surface_frame.output,
             Err(surface_err) => return Err(RenderingError::SurfaceError(surface_err))
         };
+
+        let frame_view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
                 
-        let mut encoder = self.device.clone()
-            .read().unwrap()
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Render Encoder") }
+        let mut encoder = self.device.create_command_encoder(
+            &wgpu::CommandEncoderDescriptor { label: Some("Render Encoder") }
         );
 
-        {
-            let depth_texture = &self.depth_texture.view;
-            
-            // render_pass needs some values stored in borrowed_state.
-            // at the end of the scope, values are dropped in the opposite
-            // order they're created in (last created is dropped first),
-            // so we create borrowed_state before render_pass so that
-            // the values on borrowed_state aren't destroyed while
-            // render_pass is referencing them :)
-            let mut borrowed_state;
-
-            let frame_view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
-
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[
-                    wgpu::RenderPassColorAttachment {
-                        view: &frame_view,
-                        resolve_target: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(wgpu::Color {
-                                r: 0.1,
-                                g: 0.2,
-                                b: 0.3,
-                                a: 1.0
-                            }),
-                            store: true
-                        }
+        for (i, (name, error)) in self.shader_errors.borrow().iter().enumerate() {
+            self.queue_string(
+                &format!("Shader error in {}: {}", name, error),
+                (5.0, self.size.height as f32 - 25.0 * (i + 1) as f32), [1.0, 0.3, 0.3, 1.0], 20.0
+            );
+        }
+
+        let encoded = self.encode_frame(&mut encoder, &self.scene_target, &frame_view,
+            self.surface_config.format, delta_time);
+
+        if let Err(err) = encoded {
+            return Err(RenderingError::GenericError(err));
+        }
+
+        self.submit(encoder);
+
+        Ok(())
+    }
+
+    /// Draws a frame of the current state into an offscreen image instead of the window.
+    /// `view` replaces the state's own camera, see `get_view_override`.
+    pub fn render_to_image(&mut self, view: Option<ViewOverride>, width: u32, height: u32, delta_time: f32) -> Result<image::RgbaImage, Error> {
+        let target = render_target::RenderTarget::new(&self.device, width, height, self.surface_config.format, "Offscreen Target");
+
+        // the window's scene target only fits images the size of the window
+        let scene = if (width, height) == (self.size.width, self.size.height) {
+            None
+        } else {
+            Some(render_target::RenderTarget::new(&self.device, width, height, post_process::SCENE_FORMAT, "Offscreen Scene Target"))
+        };
+
+        let mut encoder = self.device.create_command_encoder(
+            &wgpu::CommandEncoderDescriptor { label: Some("Offscreen Encoder") }
+        );
+
+        self.view_override = view;
+        let encoded = self.encode_frame(&mut encoder, scene.as_ref().unwrap_or(&self.scene_target),
+            target.get_color_view(), target.get_format(), delta_time);
+        self.view_override = None;
+
+        encoded?;
+        self.submit(encoder);
+
+        target.read_image(&self.device, &self.queue)
+    }
+
+    /// Draws the current state from `camera` into an image, text included
+    pub fn render_camera_to_image(&mut self, camera: camera::Camera, projection: camera::Projection,
+        width: u32, height: u32) -> Result<image::RgbaImage, Error> {
+        let step = self.timestep.get_step();
+
+        self.render_to_image(Some(ViewOverride { camera, projection }), width, height, step)
+    }
+
+    /// The cached pipeline for `descriptor`, made if it's new
+    pub fn get_pipeline(&self, descriptor: &pipeline::PipelineDescriptor) -> Result<Rc<wgpu::RenderPipeline>, Error> {
+        self.pipelines.borrow_mut().get_pipeline(&self.device, &self.shaders, descriptor)
+    }
+
+    /// The cached layout for `entries`, bind groups for pipelines from `get_pipeline` should use these
+    pub fn get_bind_group_layout(&self, entries: &[wgpu::BindGroupLayoutEntry]) -> Rc<wgpu::BindGroupLayout> {
+        self.pipelines.borrow_mut().get_bind_group_layout(&self.device, entries)
+    }
+
+    /// Points the shared camera uniform at `camera`, for everything drawn after
+    pub fn update_camera(&self, camera: &camera::Camera, projection: &camera::Projection) {
+        let mut uniform = self.camera_uniform.get();
+        uniform.update_view_proj(camera, projection);
+
+        self.camera_uniform.set(uniform);
+        self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    /// Shows why a shader failed to reload until it reloads fine again
+    pub fn report_shader_reload(&self, name: &str, result: Result<(), Error>) {
+        match result {
+            Ok(_) => {
+                if self.shader_errors.borrow_mut().remove(name).is_some() {
+                    eprintln!("[LOG] Shader '{}' compiles again", name);
+                }
+            },
+            Err(err) => {
+                eprintln!("[LOG] Couldn't reload shader '{}', keeping the old one: {}", name, err);
+                self.shader_errors.borrow_mut().insert(name.to_string(), err.to_string());
+            }
+        }
+    }
+
+    // every so often looks for edited shaders and has whatever uses them rebuild its pipelines
+    fn reload_shaders(&mut self, delta_time: f32) -> Result<(), Error> {
+        if !self.shaders.is_hot_reloading() {
+            return Ok(());
+        }
+
+        self.shader_reload_timer += delta_time;
+
+        if self.shader_reload_timer < shader_library::RELOAD_INTERVAL {
+            return Ok(());
+        }
+
+        self.shader_reload_timer = 0.0;
+
+        let changed = self.shaders.check_for_changes();
+
+        if changed.is_empty() {
+            return Ok(());
+        }
+
+        eprintln!("[LOG] Reloading shaders: {}", changed.join(", "));
+
+        let reloaded = self.pipelines.borrow_mut().reload(&self.device, &self.shaders, &changed);
+
+        for (name, result) in reloaded {
+            self.report_shader_reload(&name, result);
+        }
+
+        if changed.iter().any(|name| name.starts_with("post/")) {
+            let reloaded = self.post_processor.borrow_mut().reload(&self.device, &self.shaders);
+            self.report_shader_reload("post processing", reloaded);
+        }
+
+        for state in &self.states {
+            state.borrow_mut().reload_shaders(self, &changed)?;
+        }
+
+        Ok(())
+    }
+
+    /// Makes `view` drawable with `ui::Ui::image`, sampled linearly unless another `sampler` is given
+    pub fn add_ui_image(&self, view: &wgpu::TextureView, sampler: Option<&wgpu::Sampler>) -> ui::ImageId {
+        self.ui_renderer.borrow_mut().add_image(&self.device, view, sampler)
+    }
+
+    /// Changes how the scene is post processed from the next frame on
+    pub fn set_post_processing(&self, settings: PostProcessing) {
+        self.post_settings.set(settings);
+    }
+
+    /// Changes how frames are shown from the next one on, like turning vsync off
+    pub fn set_present_mode(&self, present_mode: PresentMode) {
+        self.present_mode.set(present_mode);
+    }
+
+    /// Tints the whole screen (e.g. while underwater), alpha is how strongly. `None` clears it.
+    pub fn set_screen_tint(&self, tint: Option<[f32; 4]>) {
+        self.screen_tint.set(tint.unwrap_or([0.0; 4]));
+    }
+
+    // builds the frame's render graph from the current state's passes plus
+    // post processing into `color_view`, text and the UI, then records it in order
+    fn encode_frame(&self, encoder: &mut wgpu::CommandEncoder, scene: &render_target::RenderTarget,
+        color_view: &wgpu::TextureView, color_format: wgpu::TextureFormat, delta_time: f32) -> Result<(), Error> {
+        let size = (scene.get_width(), scene.get_height());
+
+        self.ui.borrow_mut().begin_frame(size.0, size.1);
+
+        // borrowed for the whole frame, render passes keep referencing them until they end
+        let mut borrowed_states: Vec<_> = self.get_visible_states().iter()
+            .map(|state| state.borrow_mut())
+            .collect();
+
+        for state in borrowed_states.iter_mut() {
+            state.prepare_frame(self, encoder)?;
+        }
+
+        let mut graph = render_graph::RenderGraph::new(scene.get_color_view(), scene.get_depth_view(), color_view, size);
+
+        for (index, state) in borrowed_states.iter_mut().enumerate() {
+            graph.set_current_state(index);
+            state.build_graph(self, &mut graph)?;
+        }
+
+        // still clears the scene
+        if borrowed_states.is_empty() {
+            graph.add_pass(graph.main_pass());
+        }
+
+        let scene_color = graph.get_scene_color();
+        let output = graph.get_output();
+
+        graph.add_renderer_pass(render_graph::PassDesc::encoder(render_graph::POST_PROCESS_PASS)
+            .with_read(scene_color)
+            .with_write(output));
+        graph.add_renderer_pass(render_graph::PassDesc::encoder(render_graph::TEXT_PASS)
+            .with_write(output));
+        graph.add_renderer_pass(render_graph::PassDesc::encoder(render_graph::UI_PASS)
+            .with_write(output));
+
+        let compiled = graph.compile()?;
+        self.transients.borrow_mut().prepare(&self.device, compiled.get_slot_descs());
+
+        let transients = self.transients.borrow();
+        let views = graph.resolve_views(&compiled, &transients);
+
+        for index in compiled.get_order() {
+            let (owner, pass) = graph.get_pass(*index);
+            let info = render_graph::PassInfo::new(pass.get_name(), delta_time, size, &views);
+
+            match owner {
+                render_graph::PassOwner::Renderer => {
+                    self.run_renderer_pass(encoder, pass.get_name(), scene, color_view, color_format)?;
+                },
+                render_graph::PassOwner::State(state) if pass.is_render() => {
+                    let mut render_pass = render_graph::begin_render_pass(encoder, &graph, pass, &views)?;
+
+                    if let Some(state) = borrowed_states.get_mut(state) {
+                        state.render_pass(self, &info, &mut render_pass)?;
                     }
-                ],
-                depth_stencil_attachment: Some(
-                    wgpu::RenderPassDepthStencilAttachment {
-                        view: &depth_texture,
-                        depth_ops: Some(wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(1.0),
-                                store: true
-                        }),
-                        stencil_ops: None
+                },
+                render_graph::PassOwner::State(state) => {
+                    if let Some(state) = borrowed_states.get_mut(state) {
+                        state.encode_pass(self, &info, encoder)?;
                     }
-                )
-            });
+                }
+            }
+        }
 
-            let peek = self.peek();
-            if let Some(state) = peek {
-                borrowed_state = state.borrow_mut();
+        Ok(())
+    }
 
-                match borrowed_state.render(&self, &mut render_pass, delta_time) {
-                    Ok(_) => { },
-                    Err(err) => return Err(RenderingError::GenericError(err))
+    // the passes the renderer adds to every graph
+    fn run_renderer_pass(&self, encoder: &mut wgpu::CommandEncoder, name: &str, scene: &render_target::RenderTarget,
+        color_view: &wgpu::TextureView, color_format: wgpu::TextureFormat) -> Result<(), Error> {
+        let width = scene.get_width();
+        let height = scene.get_height();
+
+        match name {
+            render_graph::POST_PROCESS_PASS => {
+                self.post_processor.borrow_mut().run(&self.device, &self.queue, &self.shaders, encoder,
+                    scene.get_color_view(), color_view, color_format,
+                    (width, height), &self.post_settings.get(), self.screen_tint.get())
+            },
+            render_graph::TEXT_PASS => self.draw_queued_text(encoder, color_view, width, height),
+            render_graph::UI_PASS => {
+                let draw_list = self.ui.borrow_mut().finish_frame();
+                let pipeline = self.get_pipeline(&ui_renderer::descriptor(color_format))?;
+
+                self.ui_renderer.borrow_mut().draw(&self.device, &self.queue, &pipeline, encoder,
+                    color_view, color_format, (width, height), &draw_list)?;
+
+                // the UI's text goes over its own quads, and over the text queued before it
+                for text in &draw_list.texts {
+                    self.glyph_brush.borrow_mut().queue(Section {
+                        screen_position: text.position,
+                        bounds: text.bounds,
+                        text: vec![
+                            Text::new(&text.text)
+                                .with_color(text.color)
+                                .with_scale(text.size)
+                            ],
+                        ..Section::default()
+                    });
                 }
-            }
+
+                self.draw_queued_text(encoder, color_view, width, height)
+            },
+            _ => Err(anyhow!(format!("the renderer has no pass called '{}'", name)))
         }
+    }
 
-        self.glyph_brush.borrow_mut()
+    fn draw_queued_text(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView, width: u32, height: u32) -> Result<(), Error> {
+        let drawn = self.glyph_brush.borrow_mut()
             .draw_queued(
-                &self.device.clone().read().unwrap(),
-                &mut self.staging_belt,
-                &mut encoder,
-                &frame.texture.create_view(&wgpu::TextureViewDescriptor::default()),
-                self.size.width,
-                self.size.height,
-            )
-            .expect("Draw queued");
+                &self.device,
+                &mut self.staging_belt.borrow_mut(),
+                encoder,
+                view,
+                width,
+                height,
+            );
+
+        match drawn {
+            Ok(_) => Ok(()),
+            Err(err) => Err(anyhow!(format!("couldn't draw text: {}", err)))
+        }
+    }
 
+    fn submit(&mut self, encoder: wgpu::CommandEncoder) {
         // Submit the work!
-        self.staging_belt.finish();
+        self.staging_belt.borrow_mut().finish();
         self.queue.submit(std::iter::once(encoder.finish()));
 
         // Recall unused staging buffers
         use futures::task::SpawnExt;
 
         self.local_spawner
-            .spawn(self.staging_belt.recall())
+            .spawn(self.staging_belt.borrow_mut().recall())
             .expect("Recall staging belt");
 
         self.local_pool.run_until_stalled();
+    }
 
-        Ok(())
+    // saves what's on screen next to the executable's working directory, in screenshots/
+    fn take_screenshot(&mut self, delta_time: f32) {
+        let image = match self.render_to_image(None, self.size.width, self.size.height, delta_time) {
+            Ok(image) => image,
+            Err(err) => {
+                eprintln!("[LOG] Couldn't take screenshot: {}", err);
+                return;
+            }
+        };
+
+        let millis = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+            .map(|time| time.as_millis())
+            .unwrap_or(0);
+
+        let path = std::path::Path::new(SCREENSHOT_DIRECTORY).join(format!("screenshot_{}.png", millis));
+
+        if let Err(err) = std::fs::create_dir_all(SCREENSHOT_DIRECTORY) {
+            eprintln!("[LOG] Couldn't create '{}': {}", SCREENSHOT_DIRECTORY, err);
+            return;
+        }
+
+        match image.save(&path) {
+            Ok(_) => eprintln!("[LOG] Saved screenshot to '{}'", path.display()),
+            Err(err) => eprintln!("[LOG] Couldn't save screenshot to '{}': {}", path.display(), err)
+        }
     }
 
     pub fn queue_string(&self, text: &str, pos: (f32, f32), col: [f32; 4], scale: f32) {
@@ -402,11 +982,36 @@ impl Renderer {
         });
     }
 
+    /// Exits every state, top first
     pub fn exit(&mut self) -> Result<(), Error> {
-        if let Some(state) = self.peek() {
-            state.borrow_mut().exit()
-        } else {
-            Ok(())
+        while !self.states.is_empty() {
+            self.exit_top_state()?;
         }
+
+        Ok(())
     }
-}
\ No newline at end of file
+}
+
+// the keys the UI cares about
+fn get_ui_key(key: VirtualKeyCode) -> Option<ui::UiKey> {
+    match key {
+        VirtualKeyCode::Back => Some(ui::UiKey::Backspace),
+        VirtualKeyCode::Delete => Some(ui::UiKey::Delete),
+        VirtualKeyCode::Left => Some(ui::UiKey::Left),
+        VirtualKeyCode::Right => Some(ui::UiKey::Right),
+        VirtualKeyCode::Home => Some(ui::UiKey::Home),
+        VirtualKeyCode::End => Some(ui::UiKey::End),
+        VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => Some(ui::UiKey::Enter),
+        VirtualKeyCode::Tab => Some(ui::UiKey::Tab),
+        VirtualKeyCode::Escape => Some(ui::UiKey::Escape),
+        _ => None
+    }
+}
+
+fn get_present_mode(present_mode: PresentMode) -> wgpu::PresentMode {
+    match present_mode {
+        PresentMode::Fifo => wgpu::PresentMode::Fifo,
+        PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+        PresentMode::Immediate => wgpu::PresentMode::Immediate,
+    }
+}
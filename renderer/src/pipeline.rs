@@ -0,0 +1,381 @@
+use std::{collections::HashMap, rc::Rc};
+
+use anyhow::{Result, Error};
+
+use crate::{Renderer, shader_library::ShaderLibrary, texture};
+
+/// How what a pipeline draws mixes with what's already there
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    Opaque,
+    /// Mixed by the drawn colour's alpha
+    Alpha,
+    /// Added on top, for glows
+    Additive,
+}
+
+impl BlendMode {
+    pub fn get_state(&self) -> wgpu::BlendState {
+        match self {
+            BlendMode::Opaque => wgpu::BlendState::REPLACE,
+            BlendMode::Alpha => wgpu::BlendState::ALPHA_BLENDING,
+            BlendMode::Additive => wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            },
+        }
+    }
+}
+
+/// How a pipeline uses the depth buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DepthMode {
+    /// For passes without a depth buffer, like post processing and UI
+    None,
+    /// Drawn over everything without touching depth, like the sky
+    Ignore,
+    /// Hidden behind closer things without hiding anything itself, like transparent blocks
+    Test,
+    /// Hidden behind closer things and hiding further ones
+    Write,
+}
+
+/// Everything that makes a render pipeline, built up with the `with_*` functions.
+/// The renderer makes one pipeline per distinct descriptor and shares it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PipelineDescriptor {
+    label: &'static str,
+    // a name in the `ShaderLibrary`, with both stages' entry points called main
+    shader: &'static str,
+
+    vertex_layouts: Vec<wgpu::VertexBufferLayout<'static>>,
+    bind_groups: Vec<Vec<wgpu::BindGroupLayoutEntry>>,
+    // None for depth only pipelines
+    color_format: Option<wgpu::TextureFormat>,
+
+    blend: BlendMode,
+    depth: DepthMode,
+    depth_compare: wgpu::CompareFunction,
+    // constant and slope scale, the float stored as bits so descriptors can be hashed
+    depth_bias: (i32, u32),
+
+    topology: wgpu::PrimitiveTopology,
+    cull_mode: Option<wgpu::Face>,
+}
+
+impl PipelineDescriptor {
+    /// Opaque triangles, depth tested and written, back faces culled. No colour target until `with_color_format`.
+    pub fn new(label: &'static str, shader: &'static str) -> Self {
+        Self {
+            label,
+            shader,
+
+            vertex_layouts: Vec::new(),
+            bind_groups: Vec::new(),
+            color_format: None,
+
+            blend: BlendMode::Opaque,
+            depth: DepthMode::Write,
+            depth_compare: wgpu::CompareFunction::Less,
+            depth_bias: (0, 0),
+
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            cull_mode: Some(wgpu::Face::Back),
+        }
+    }
+
+    /// Adds a vertex buffer, in the order they're bound
+    pub fn with_vertex_layout(mut self, layout: wgpu::VertexBufferLayout<'static>) -> Self {
+        self.vertex_layouts.push(layout);
+        self
+    }
+
+    /// Adds the next bind group, the first one added is group 0
+    pub fn with_bind_group(mut self, entries: Vec<wgpu::BindGroupLayoutEntry>) -> Self {
+        self.bind_groups.push(entries);
+        self
+    }
+
+    pub fn with_color_format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.color_format = Some(format);
+        self
+    }
+
+    pub fn with_blend(mut self, blend: BlendMode) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    pub fn with_depth(mut self, depth: DepthMode) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    pub fn with_depth_compare(mut self, compare: wgpu::CompareFunction) -> Self {
+        self.depth_compare = compare;
+        self
+    }
+
+    pub fn with_depth_bias(mut self, constant: i32, slope_scale: f32) -> Self {
+        self.depth_bias = (constant, slope_scale.to_bits());
+        self
+    }
+
+    pub fn with_topology(mut self, topology: wgpu::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn with_cull_mode(mut self, cull_mode: Option<wgpu::Face>) -> Self {
+        self.cull_mode = cull_mode;
+        self
+    }
+}
+
+// getters
+impl PipelineDescriptor {
+    pub fn get_label(&self) -> &'static str { self.label }
+
+    pub fn get_shader(&self) -> &'static str { self.shader }
+
+    pub fn get_bind_groups(&self) -> &[Vec<wgpu::BindGroupLayoutEntry>] { &self.bind_groups }
+
+    pub fn get_depth_stencil(&self) -> Option<wgpu::DepthStencilState> {
+        let (depth_write_enabled, depth_compare) = match self.depth {
+            DepthMode::None => return None,
+            DepthMode::Ignore => (false, wgpu::CompareFunction::Always),
+            DepthMode::Test => (false, self.depth_compare),
+            DepthMode::Write => (true, self.depth_compare),
+        };
+
+        Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled,
+            depth_compare,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState {
+                constant: self.depth_bias.0,
+                slope_scale: f32::from_bits(self.depth_bias.1),
+                clamp: 0.0,
+            },
+        })
+    }
+}
+
+/// Render pipelines and bind group layouts, made once for each distinct description
+pub struct PipelineCache {
+    bind_group_layouts: HashMap<Vec<wgpu::BindGroupLayoutEntry>, Rc<wgpu::BindGroupLayout>>,
+    pipelines: HashMap<PipelineDescriptor, Rc<wgpu::RenderPipeline>>,
+}
+
+impl PipelineCache {
+    pub fn new() -> Self {
+        Self {
+            bind_group_layouts: HashMap::new(),
+            pipelines: HashMap::new(),
+        }
+    }
+
+    pub fn get_bind_group_layout(&mut self, device: &wgpu::Device, entries: &[wgpu::BindGroupLayoutEntry]) -> Rc<wgpu::BindGroupLayout> {
+        if let Some(layout) = self.bind_group_layouts.get(entries) {
+            return layout.clone();
+        }
+
+        let layout = Rc::new(device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries,
+            label: None,
+        }));
+
+        self.bind_group_layouts.insert(entries.to_vec(), layout.clone());
+        layout
+    }
+
+    /// Makes the pipeline the first time it's asked for, which fails if its shader doesn't compile
+    pub fn get_pipeline(&mut self, device: &wgpu::Device, shaders: &ShaderLibrary, descriptor: &PipelineDescriptor) -> Result<Rc<wgpu::RenderPipeline>, Error> {
+        if let Some(pipeline) = self.pipelines.get(descriptor) {
+            return Ok(pipeline.clone());
+        }
+
+        let pipeline = Rc::new(shaders.try_create(|| self.create_pipeline(device, shaders, descriptor))?);
+
+        self.pipelines.insert(descriptor.clone(), pipeline.clone());
+        Ok(pipeline)
+    }
+
+    /// Rebuilds the pipelines using `changed` shaders, giving whether each shader worked.
+    /// Pipelines whose shader fails keep the old version.
+    pub fn reload(&mut self, device: &wgpu::Device, shaders: &ShaderLibrary, changed: &[String]) -> Vec<(String, Result<(), Error>)> {
+        let mut results = Vec::new();
+
+        for name in changed {
+            let descriptors: Vec<PipelineDescriptor> = self.pipelines.keys()
+                .filter(|descriptor| descriptor.shader == name.as_str())
+                .cloned()
+                .collect();
+
+            if descriptors.is_empty() {
+                continue;
+            }
+
+            let mut rebuilt = Vec::new();
+            let mut result = Ok(());
+
+            for descriptor in descriptors {
+                match shaders.try_create(|| self.create_pipeline(device, shaders, &descriptor)) {
+                    Ok(pipeline) => rebuilt.push((descriptor, Rc::new(pipeline))),
+                    Err(err) => {
+                        result = Err(err);
+                        break;
+                    }
+                }
+            }
+
+            if result.is_ok() {
+                self.pipelines.extend(rebuilt);
+            }
+
+            results.push((name.clone(), result));
+        }
+
+        results
+    }
+
+    fn create_pipeline(&mut self, device: &wgpu::Device, shaders: &ShaderLibrary, descriptor: &PipelineDescriptor) -> wgpu::RenderPipeline {
+        let bind_group_layouts: Vec<Rc<wgpu::BindGroupLayout>> = descriptor.bind_groups.iter()
+            .map(|entries| self.get_bind_group_layout(device, entries))
+            .collect();
+        let bind_group_layouts: Vec<&wgpu::BindGroupLayout> = bind_group_layouts.iter().map(|layout| layout.as_ref()).collect();
+
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some(descriptor.label),
+            source: wgpu::ShaderSource::Wgsl(shaders.get_source(descriptor.shader).into())
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(descriptor.label),
+            bind_group_layouts: &bind_group_layouts,
+            push_constant_ranges: &[]
+        });
+
+        let targets = descriptor.color_format.map(|format| [wgpu::ColorTargetState {
+            format,
+            blend: Some(descriptor.blend.get_state()),
+            write_mask: wgpu::ColorWrites::ALL
+        }]);
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(descriptor.label),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "main",
+                buffers: &descriptor.vertex_layouts
+            },
+            fragment: targets.as_ref().map(|targets| wgpu::FragmentState {
+                module: &shader,
+                entry_point: "main",
+                targets
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: descriptor.topology,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: descriptor.cull_mode,
+                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+                polygon_mode: wgpu::PolygonMode::Fill,
+                clamp_depth: false,
+                conservative: false
+            },
+            depth_stencil: descriptor.get_depth_stencil(),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false
+            }
+        })
+    }
+}
+
+impl Default for PipelineCache {
+    fn default() -> Self { Self::new() }
+}
+
+/// A pipeline from the renderer's cache and what it was made from
+pub struct Material {
+    descriptor: PipelineDescriptor,
+    pipeline: Rc<wgpu::RenderPipeline>,
+}
+
+impl Material {
+    pub fn new(renderer: &Renderer, descriptor: PipelineDescriptor) -> Result<Self, Error> {
+        let pipeline = renderer.get_pipeline(&descriptor)?;
+
+        Ok(Self {
+            descriptor,
+            pipeline,
+        })
+    }
+
+    /// Picks up the pipeline again, after its shader was reloaded
+    pub fn refresh(&mut self, renderer: &Renderer) -> Result<(), Error> {
+        self.pipeline = renderer.get_pipeline(&self.descriptor)?;
+
+        Ok(())
+    }
+
+    /// Sets the pipeline along with `bind_groups`, as (group index, bind group)
+    pub fn bind<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, bind_groups: &[(u32, &'a wgpu::BindGroup)]) {
+        render_pass.set_pipeline(&self.pipeline);
+
+        for (index, bind_group) in bind_groups {
+            render_pass.set_bind_group(*index, bind_group, &[]);
+        }
+    }
+}
+
+// getters
+impl Material {
+    pub fn get_descriptor(&self) -> &PipelineDescriptor { &self.descriptor }
+
+    pub fn get_pipeline(&self) -> &wgpu::RenderPipeline { &self.pipeline }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{BlendMode, DepthMode, PipelineDescriptor};
+
+    #[test]
+    fn descriptors_differing_in_any_state_are_distinct() {
+        let opaque = PipelineDescriptor::new("Test", "shader.wgsl")
+            .with_color_format(wgpu::TextureFormat::Rgba16Float);
+
+        let same = PipelineDescriptor::new("Test", "shader.wgsl")
+            .with_color_format(wgpu::TextureFormat::Rgba16Float);
+
+        let mut descriptors = HashSet::new();
+        descriptors.insert(opaque.clone());
+        descriptors.insert(same);
+        descriptors.insert(opaque.clone().with_blend(BlendMode::Alpha));
+        descriptors.insert(opaque.clone().with_depth(DepthMode::Test));
+        descriptors.insert(opaque.clone().with_depth_bias(2, 2.0));
+
+        assert_eq!(descriptors.len(), 4);
+
+        let depth = opaque.with_depth(DepthMode::Test).get_depth_stencil().unwrap();
+        assert!(!depth.depth_write_enabled);
+        assert_eq!(depth.depth_compare, wgpu::CompareFunction::Less);
+
+        let shadow = PipelineDescriptor::new("Test", "shadow.wgsl").with_depth_bias(2, 2.0);
+        assert_eq!(shadow.get_depth_stencil().unwrap().bias.slope_scale, 2.0);
+    }
+}
@@ -0,0 +1,385 @@
+use std::collections::HashMap;
+
+use anyhow::{Result, Error, anyhow};
+use wgpu::util::DeviceExt;
+
+use common::settings::{PostProcessing, Tonemapping};
+
+use crate::shader_library::ShaderLibrary;
+
+/// What the scene is drawn into before post processing, bright enough to go past white
+pub const SCENE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+// between passes colours are stored as sRGB, so they get the precision where eyes notice it
+const INTERMEDIATE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// A fullscreen pass, run in the order they're listed here
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PostEffect {
+    /// HDR to displayable colour, exposure and gamma. Always runs first.
+    Tonemap,
+    Fxaa,
+    /// The screen tint (underwater) and darkened corners
+    Vignette,
+}
+
+impl PostEffect {
+    /// The passes `settings` asks for, in the order they run
+    pub fn chain(settings: &PostProcessing, tint: [f32; 4]) -> Vec<PostEffect> {
+        let mut effects = vec![PostEffect::Tonemap];
+
+        if settings.fxaa {
+            effects.push(PostEffect::Fxaa);
+        }
+
+        if settings.vignette > 0.0 || tint[3] > 0.0 {
+            effects.push(PostEffect::Vignette);
+        }
+
+        effects
+    }
+
+    /// The effect's shader in the `ShaderLibrary`, it's built on top of `post/fullscreen.wgsl`
+    pub fn get_shader_name(&self) -> &'static str {
+        match self {
+            PostEffect::Tonemap => "post/tonemap.wgsl",
+            PostEffect::Fxaa => "post/fxaa.wgsl",
+            PostEffect::Vignette => "post/vignette.wgsl",
+        }
+    }
+
+    fn get_source(&self, shaders: &ShaderLibrary) -> String {
+        format!("{}{}", shaders.get_source("post/fullscreen.wgsl"), shaders.get_source(self.get_shader_name()))
+    }
+}
+
+/// See `res/shaders/post/fullscreen.wgsl`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct PostUniform {
+    texel_size: [f32; 2],
+    exposure: f32,
+    gamma: f32,
+    vignette: f32,
+    // 1 when the target needs the shader to encode sRGB itself
+    encode_srgb: f32,
+    tonemapping: f32,
+    padding: f32,
+    tint: [f32; 4],
+}
+
+/// Runs the chain of post processing passes from the HDR scene to the screen
+pub struct PostProcessor {
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+
+    // pipelines are made the first time each effect draws into each format
+    pipelines: HashMap<(PostEffect, wgpu::TextureFormat), wgpu::RenderPipeline>,
+    uniform_buffers: HashMap<PostEffect, wgpu::Buffer>,
+
+    // passes ping-pong between these, kept until the size changes
+    intermediates: Option<(u32, u32, [wgpu::TextureView; 2])>,
+}
+
+impl PostProcessor {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(
+            &wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler {
+                            comparison: false,
+                            filtering: true,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("post_bind_group_layout"),
+            }
+        );
+
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                label: Some("Post Sampler"),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            }
+        );
+
+        Self {
+            bind_group_layout,
+            sampler,
+
+            pipelines: HashMap::new(),
+            uniform_buffers: HashMap::new(),
+
+            intermediates: None,
+        }
+    }
+
+    /// Records the passes taking `scene` (in `SCENE_FORMAT`) to `output`. `tint`'s alpha is how strong it is.
+    #[allow(clippy::too_many_arguments)]
+    pub fn run(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, shaders: &ShaderLibrary, encoder: &mut wgpu::CommandEncoder,
+        scene: &wgpu::TextureView, output: &wgpu::TextureView, output_format: wgpu::TextureFormat,
+        size: (u32, u32), settings: &PostProcessing, tint: [f32; 4]) -> Result<(), Error> {
+        let effects = PostEffect::chain(settings, tint);
+
+        if effects.len() > 1 {
+            self.ensure_intermediates(device, size);
+        }
+
+        for (i, effect) in effects.iter().enumerate() {
+            let is_last = i == effects.len() - 1;
+            let format = if is_last { output_format } else { INTERMEDIATE_FORMAT };
+
+            let uniform = PostUniform {
+                texel_size: [1.0 / size.0 as f32, 1.0 / size.1 as f32],
+                exposure: settings.exposure,
+                gamma: settings.gamma.max(0.1),
+                vignette: settings.vignette,
+                encode_srgb: if is_last && !format.describe().srgb { 1.0 } else { 0.0 },
+                tonemapping: match settings.tonemapping {
+                    Tonemapping::Clamp => 0.0,
+                    Tonemapping::Reinhard => 1.0,
+                    Tonemapping::Aces => 2.0,
+                },
+                padding: 0.0,
+                tint,
+            };
+
+            self.write_uniform(device, queue, *effect, uniform);
+            self.ensure_pipeline(device, shaders, *effect, format)?;
+
+            let intermediates = self.intermediates.as_ref().map(|(_, _, views)| views);
+
+            // pass i reads what pass i - 1 wrote, alternating between the two intermediates
+            let input = match (i, intermediates) {
+                (0, _) => scene,
+                (_, Some(views)) => &views[(i - 1) % 2],
+                (_, None) => return Err(anyhow!("post processing has no intermediate textures"))
+            };
+
+            let target = match (is_last, intermediates) {
+                (true, _) => output,
+                (false, Some(views)) => &views[i % 2],
+                (false, None) => return Err(anyhow!("post processing has no intermediate textures"))
+            };
+
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(input),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: self.uniform_buffers[effect].as_entire_binding(),
+                    },
+                ],
+                label: Some("post_bind_group"),
+            });
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Post Pass"),
+                color_attachments: &[
+                    wgpu::RenderPassColorAttachment {
+                        view: target,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            // every pixel gets drawn over
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: true
+                        }
+                    }
+                ],
+                depth_stencil_attachment: None
+            });
+
+            render_pass.set_pipeline(&self.pipelines[&(*effect, format)]);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        Ok(())
+    }
+
+    /// Rebuilds every pipeline made so far from the library's shaders.
+    /// If any of them fails to compile all the old ones are kept.
+    pub fn reload(&mut self, device: &wgpu::Device, shaders: &ShaderLibrary) -> Result<(), Error> {
+        let mut pipelines = Vec::new();
+
+        for (effect, format) in self.pipelines.keys() {
+            let pipeline = shaders.try_create(|| self.create_pipeline(device, &effect.get_source(shaders), *format));
+
+            match pipeline {
+                Ok(pipeline) => pipelines.push(((*effect, *format), pipeline)),
+                Err(err) => return Err(anyhow!(format!("{}: {}", effect.get_shader_name(), err)))
+            }
+        }
+
+        self.pipelines.extend(pipelines);
+
+        Ok(())
+    }
+
+    fn write_uniform(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, effect: PostEffect, uniform: PostUniform) {
+        match self.uniform_buffers.get(&effect) {
+            Some(buffer) => queue.write_buffer(buffer, 0, bytemuck::cast_slice(&[uniform])),
+            None => {
+                let buffer = device.create_buffer_init(
+                    &wgpu::util::BufferInitDescriptor {
+                        label: Some("Post Buffer"),
+                        contents: bytemuck::cast_slice(&[uniform]),
+                        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+                    }
+                );
+
+                self.uniform_buffers.insert(effect, buffer);
+            }
+        }
+    }
+
+    fn ensure_pipeline(&mut self, device: &wgpu::Device, shaders: &ShaderLibrary, effect: PostEffect,
+        format: wgpu::TextureFormat) -> Result<(), Error> {
+        if self.pipelines.contains_key(&(effect, format)) {
+            return Ok(());
+        }
+
+        let pipeline = shaders.try_create(|| self.create_pipeline(device, &effect.get_source(shaders), format));
+
+        match pipeline {
+            Ok(pipeline) => {
+                self.pipelines.insert((effect, format), pipeline);
+                Ok(())
+            },
+            Err(err) => Err(anyhow!(format!("{}: {}", effect.get_shader_name(), err)))
+        }
+    }
+
+    fn create_pipeline(&self, device: &wgpu::Device, source: &str, format: wgpu::TextureFormat) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("Post Shader"),
+            source: wgpu::ShaderSource::Wgsl(source.into())
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Post Pipeline Layout"),
+            bind_group_layouts: &[&self.bind_group_layout],
+            push_constant_ranges: &[]
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Post Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "main",
+                buffers: &[]
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "main",
+                targets: &[wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL
+                }]
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                clamp_depth: false,
+                conservative: false
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false
+            }
+        })
+    }
+
+    fn ensure_intermediates(&mut self, device: &wgpu::Device, size: (u32, u32)) {
+        if let Some((width, height, _)) = &self.intermediates {
+            if (*width, *height) == size {
+                return;
+            }
+        }
+
+        let create_view = || {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Post Intermediate"),
+                size: wgpu::Extent3d {
+                    width: size.0,
+                    height: size.1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: INTERMEDIATE_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+            });
+
+            texture.create_view(&wgpu::TextureViewDescriptor::default())
+        };
+
+        self.intermediates = Some((size.0, size.1, [create_view(), create_view()]));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::settings::PostProcessing;
+
+    use super::PostEffect;
+
+    #[test]
+    fn chain_runs_tonemapping_first() {
+        let mut settings = PostProcessing { fxaa: true, vignette: 0.0, ..PostProcessing::default() };
+
+        assert_eq!(PostEffect::chain(&settings, [0.0; 4]), vec![PostEffect::Tonemap, PostEffect::Fxaa]);
+
+        settings.fxaa = false;
+        assert_eq!(PostEffect::chain(&settings, [0.0; 4]), vec![PostEffect::Tonemap]);
+
+        // a tint needs the vignette pass even with the vignette itself off
+        assert_eq!(PostEffect::chain(&settings, [0.2, 0.4, 1.0, 0.5]), vec![PostEffect::Tonemap, PostEffect::Vignette]);
+    }
+}
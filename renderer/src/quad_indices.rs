@@ -0,0 +1,37 @@
+// every face of a chunk mesh is a quad with the same winding, so instead of each chunk
+// carrying its own indices, one index buffer covering the biggest chunk is shared by all of them
+
+pub const VERTICES_PER_QUAD: usize = 4;
+pub const INDICES_PER_QUAD: usize = 6;
+
+/// The two triangles of a quad, relative to its first vertex
+pub const QUAD_INDICES: [u32; INDICES_PER_QUAD] = [
+    0, 1, 2, // triangle 1
+    2, 3, 0, // triangle 2
+];
+
+/// Indices for `quad_count` quads laid out one after another
+pub fn build_quad_indices(quad_count: usize) -> Vec<u32> {
+    let mut indices = Vec::with_capacity(quad_count * INDICES_PER_QUAD);
+
+    for quad in 0..quad_count {
+        let first_vertex = (quad * VERTICES_PER_QUAD) as u32;
+
+        indices.extend(QUAD_INDICES.iter().map(|index| index + first_vertex));
+    }
+
+    indices
+}
+
+pub fn quad_count(vertex_count: usize) -> usize { vertex_count / VERTICES_PER_QUAD }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quads_follow_each_other() {
+        assert_eq!(build_quad_indices(2), vec![0, 1, 2, 2, 3, 0, 4, 5, 6, 6, 7, 4]);
+        assert_eq!(quad_count(8), 2);
+    }
+}
@@ -0,0 +1,528 @@
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::{Result, Error, anyhow};
+
+/// The pass `RenderableState::build_graph` adds unless it's overridden, see `RenderGraph::main_pass`
+pub const MAIN_PASS: &str = "main";
+/// Added by the renderer after the state's passes, turns the scene into the output
+pub const POST_PROCESS_PASS: &str = "post_process";
+/// Added by the renderer, draws queued text over the output
+pub const TEXT_PASS: &str = "text";
+/// Added by the renderer last, draws the frame's `Ui` over everything
+pub const UI_PASS: &str = "ui";
+
+/// What the scene is cleared to when nothing else is drawn
+pub const CLEAR_COLOR: wgpu::Color = wgpu::Color { r: 0.1, g: 0.2, b: 0.3, a: 1.0 };
+
+/// A texture in a `RenderGraph`, or anything else passes need ordering around
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ResourceId(usize);
+
+/// A transient texture. Ones with the same description that aren't needed at the same time share a texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureDesc {
+    pub width: u32,
+    pub height: u32,
+    pub format: wgpu::TextureFormat,
+}
+
+enum Resource<'a> {
+    // owned by the renderer, like the scene and the window
+    Imported(&'a wgpu::TextureView),
+    // made by the graph, only valid during the frame
+    Transient(TextureDesc),
+    // owned by whoever declared it (like shadow maps), only there to order passes
+    External,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PassOwner {
+    // the index of the state on the renderer's stack
+    State(usize),
+    Renderer,
+}
+
+/// A pass and the resources it touches, built up with the `with_*` functions
+#[derive(Debug, Clone)]
+pub struct PassDesc {
+    name: &'static str,
+    // render passes get a `wgpu::RenderPass` with their attachments, the others the command encoder
+    is_render: bool,
+
+    reads: Vec<ResourceId>,
+    writes: Vec<ResourceId>,
+    color_attachments: Vec<(ResourceId, Option<wgpu::Color>)>,
+    depth_attachment: Option<(ResourceId, Option<f32>)>,
+}
+
+impl PassDesc {
+    /// A pass drawing into its attachments, see `RenderableState::render_pass`
+    pub fn render(name: &'static str) -> Self {
+        Self {
+            name,
+            is_render: true,
+
+            reads: Vec::new(),
+            writes: Vec::new(),
+            color_attachments: Vec::new(),
+            depth_attachment: None,
+        }
+    }
+
+    /// A pass recording its own commands, see `RenderableState::encode_pass`
+    pub fn encoder(name: &'static str) -> Self {
+        Self {
+            is_render: false,
+            ..Self::render(name)
+        }
+    }
+
+    /// Runs after every pass writing `resource`
+    pub fn with_read(mut self, resource: ResourceId) -> Self {
+        self.reads.push(resource);
+        self
+    }
+
+    /// Runs before every pass reading `resource`, and in the order they were added with other passes writing it
+    pub fn with_write(mut self, resource: ResourceId) -> Self {
+        self.writes.push(resource);
+        self
+    }
+
+    /// Draws into `resource`, cleared to `clear` first if given. Counts as writing it.
+    pub fn with_color_attachment(mut self, resource: ResourceId, clear: Option<wgpu::Color>) -> Self {
+        self.color_attachments.push((resource, clear));
+        self.with_write(resource)
+    }
+
+    /// Depth tests against `resource`, cleared to `clear` first if given. Counts as writing it.
+    pub fn with_depth_attachment(mut self, resource: ResourceId, clear: Option<f32>) -> Self {
+        self.depth_attachment = Some((resource, clear));
+        self.with_write(resource)
+    }
+
+    fn touches(&self, resource: ResourceId) -> bool {
+        self.reads.contains(&resource) || self.writes.contains(&resource)
+    }
+}
+
+// getters
+impl PassDesc {
+    pub fn get_name(&self) -> &'static str { self.name }
+
+    pub fn is_render(&self) -> bool { self.is_render }
+}
+
+/// The passes making up a frame and the textures they pass between each other.
+/// Passes can be added in any order, they run in the order their reads and writes need.
+pub struct RenderGraph<'a> {
+    resources: Vec<(&'static str, Resource<'a>)>,
+    passes: Vec<PassDesc>,
+    owners: Vec<PassOwner>,
+    // whose `build_graph` is adding passes
+    current_state: usize,
+    size: (u32, u32),
+
+    scene_color: ResourceId,
+    scene_depth: ResourceId,
+    output: ResourceId,
+}
+
+impl<'a> RenderGraph<'a> {
+    pub(crate) fn new(scene_color: &'a wgpu::TextureView, scene_depth: &'a wgpu::TextureView,
+        output: &'a wgpu::TextureView, size: (u32, u32)) -> Self {
+        Self {
+            resources: vec![
+                ("scene_color", Resource::Imported(scene_color)),
+                ("scene_depth", Resource::Imported(scene_depth)),
+                ("output", Resource::Imported(output)),
+            ],
+            passes: Vec::new(),
+            owners: Vec::new(),
+            current_state: 0,
+            size,
+
+            scene_color: ResourceId(0),
+            scene_depth: ResourceId(1),
+            output: ResourceId(2),
+        }
+    }
+
+    /// A texture that only lives for this frame, its contents start out undefined
+    pub fn create_texture(&mut self, name: &'static str, desc: TextureDesc) -> ResourceId {
+        self.resources.push((name, Resource::Transient(desc)));
+        ResourceId(self.resources.len() - 1)
+    }
+
+    /// Something the graph doesn't manage, for ordering passes around it
+    pub fn declare(&mut self, name: &'static str) -> ResourceId {
+        self.resources.push((name, Resource::External));
+        ResourceId(self.resources.len() - 1)
+    }
+
+    /// Draws the whole scene in one go, clearing it first. What states without passes of their own get.
+    pub fn main_pass(&self) -> PassDesc {
+        PassDesc::render(MAIN_PASS)
+            .with_color_attachment(self.scene_color, Some(CLEAR_COLOR))
+            .with_depth_attachment(self.scene_depth, Some(1.0))
+    }
+
+    pub fn add_pass(&mut self, pass: PassDesc) {
+        self.passes.push(pass);
+        self.owners.push(PassOwner::State(self.current_state));
+    }
+
+    pub(crate) fn set_current_state(&mut self, index: usize) {
+        self.current_state = index;
+    }
+
+    pub(crate) fn add_renderer_pass(&mut self, pass: PassDesc) {
+        self.passes.push(pass);
+        self.owners.push(PassOwner::Renderer);
+    }
+
+    /// Works out the order passes run in and which transient textures share memory
+    pub(crate) fn compile(&self) -> Result<CompiledGraph, Error> {
+        let passes = &self.passes;
+
+        let order = sort_passes(passes)?;
+
+        let mut transients = Vec::new();
+        let mut transient_resources = Vec::new();
+
+        for (index, (_, resource)) in self.resources.iter().enumerate() {
+            if let Resource::Transient(desc) = resource {
+                let uses: Vec<usize> = order.iter().enumerate()
+                    .filter(|(_, pass)| passes[**pass].touches(ResourceId(index)))
+                    .map(|(step, _)| step)
+                    .collect();
+
+                // textures no pass uses don't get made at all
+                if let (Some(first), Some(last)) = (uses.first(), uses.last()) {
+                    transients.push((*desc, *first, *last));
+                    transient_resources.push(index);
+                }
+            }
+        }
+
+        let (slots, slot_descs) = assign_slots(&transients);
+
+        Ok(CompiledGraph {
+            order,
+            slots: transient_resources.into_iter().zip(slots).collect(),
+            slot_descs,
+        })
+    }
+
+    /// The view behind every resource, `None` for external ones
+    pub(crate) fn resolve_views<'b>(&'b self, compiled: &CompiledGraph, transients: &'b TransientPool) -> Vec<Option<&'b wgpu::TextureView>> {
+        self.resources.iter().enumerate()
+            .map(|(index, (_, resource))| match resource {
+                Resource::Imported(view) => Some(*view),
+                Resource::Transient(_) => compiled.slots.get(&index).map(|slot| transients.get_view(*slot)),
+                Resource::External => None
+            })
+            .collect()
+    }
+
+    pub(crate) fn get_pass(&self, index: usize) -> (PassOwner, &PassDesc) { (self.owners[index], &self.passes[index]) }
+}
+
+// getters
+impl<'a> RenderGraph<'a> {
+    /// The HDR texture the world is drawn into, post processed into `get_output` at the end of the frame
+    pub fn get_scene_color(&self) -> ResourceId { self.scene_color }
+
+    pub fn get_scene_depth(&self) -> ResourceId { self.scene_depth }
+
+    /// The window (or image) the frame ends up in
+    pub fn get_output(&self) -> ResourceId { self.output }
+
+    /// How big the scene and output are, in pixels
+    pub fn get_size(&self) -> (u32, u32) { self.size }
+}
+
+pub(crate) struct CompiledGraph {
+    order: Vec<usize>,
+    // transient resource index to texture slot in the `TransientPool`
+    slots: HashMap<usize, usize>,
+    slot_descs: Vec<TextureDesc>,
+}
+
+impl CompiledGraph {
+    pub fn get_order(&self) -> &[usize] { &self.order }
+
+    pub fn get_slot_descs(&self) -> &[TextureDesc] { &self.slot_descs }
+}
+
+/// What a pass gets to know about the frame while it runs
+pub struct PassInfo<'a> {
+    name: &'static str,
+    delta_time: f32,
+    size: (u32, u32),
+    views: &'a [Option<&'a wgpu::TextureView>],
+}
+
+impl<'a> PassInfo<'a> {
+    pub(crate) fn new(name: &'static str, delta_time: f32, size: (u32, u32), views: &'a [Option<&'a wgpu::TextureView>]) -> Self {
+        Self {
+            name,
+            delta_time,
+            size,
+            views,
+        }
+    }
+}
+
+// getters
+impl<'a> PassInfo<'a> {
+    pub fn get_name(&self) -> &'static str { self.name }
+
+    pub fn get_delta_time(&self) -> f32 { self.delta_time }
+
+    pub fn get_size(&self) -> (u32, u32) { self.size }
+
+    /// The texture behind `resource` this frame, `None` for ones made with `RenderGraph::declare`
+    pub fn get_view(&self, resource: ResourceId) -> Option<&'a wgpu::TextureView> {
+        self.views.get(resource.0).copied().flatten()
+    }
+}
+
+/// Starts a wgpu render pass on `pass`'s attachments, `views` coming from `RenderGraph::resolve_views`
+pub(crate) fn begin_render_pass<'a>(encoder: &'a mut wgpu::CommandEncoder, graph: &RenderGraph<'_>, pass: &PassDesc,
+    views: &[Option<&'a wgpu::TextureView>]) -> Result<wgpu::RenderPass<'a>, Error> {
+    let get_view = |resource: ResourceId| match views[resource.0] {
+        Some(view) => Ok(view),
+        None => Err(anyhow!(format!("render pass '{}' draws into '{}', which isn't a texture",
+            pass.name, graph.resources[resource.0].0)))
+    };
+
+    let mut color_attachments = Vec::new();
+
+    for (resource, clear) in &pass.color_attachments {
+        color_attachments.push(wgpu::RenderPassColorAttachment {
+            view: get_view(*resource)?,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: match clear {
+                    Some(color) => wgpu::LoadOp::Clear(*color),
+                    None => wgpu::LoadOp::Load
+                },
+                store: true
+            }
+        });
+    }
+
+    let depth_stencil_attachment = match pass.depth_attachment {
+        Some((resource, clear)) => Some(wgpu::RenderPassDepthStencilAttachment {
+            view: get_view(resource)?,
+            depth_ops: Some(wgpu::Operations {
+                load: match clear {
+                    Some(depth) => wgpu::LoadOp::Clear(depth),
+                    None => wgpu::LoadOp::Load
+                },
+                store: true
+            }),
+            stencil_ops: None
+        }),
+        None => None
+    };
+
+    Ok(encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(pass.name),
+        color_attachments: &color_attachments,
+        depth_stencil_attachment,
+    }))
+}
+
+/// The textures behind transient resources, kept from frame to frame
+pub struct TransientPool {
+    textures: Vec<(TextureDesc, wgpu::Texture, wgpu::TextureView)>,
+}
+
+impl TransientPool {
+    pub fn new() -> Self {
+        Self {
+            textures: Vec::new(),
+        }
+    }
+
+    /// Makes sure there's a texture for every slot, reusing the last frame's where they match
+    pub fn prepare(&mut self, device: &wgpu::Device, slot_descs: &[TextureDesc]) {
+        let mut previous = std::mem::take(&mut self.textures);
+
+        for desc in slot_descs {
+            match previous.iter().position(|(previous_desc, _, _)| previous_desc == desc) {
+                Some(index) => self.textures.push(previous.swap_remove(index)),
+                None => {
+                    let texture = device.create_texture(&wgpu::TextureDescriptor {
+                        label: Some("Transient Texture"),
+                        size: wgpu::Extent3d {
+                            width: desc.width,
+                            height: desc.height,
+                            depth_or_array_layers: 1,
+                        },
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: wgpu::TextureDimension::D2,
+                        format: desc.format,
+                        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+                    });
+
+                    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                    self.textures.push((*desc, texture, view));
+                }
+            }
+        }
+    }
+
+    pub fn get_view(&self, slot: usize) -> &wgpu::TextureView { &self.textures[slot].2 }
+}
+
+impl Default for TransientPool {
+    fn default() -> Self { Self::new() }
+}
+
+/// The order to run `passes` in: after the passes writing what they read, writers of the same
+/// resource in the order they were added, otherwise as added
+pub fn sort_passes(passes: &[PassDesc]) -> Result<Vec<usize>, Error> {
+    let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); passes.len()];
+    let mut dependencies = vec![0; passes.len()];
+
+    let mut add_dependency = |before: usize, after: usize| {
+        if before != after && !dependents[before].contains(&after) {
+            dependents[before].push(after);
+            dependencies[after] += 1;
+        }
+    };
+
+    let mut writers: HashMap<ResourceId, Vec<usize>> = HashMap::new();
+
+    for (index, pass) in passes.iter().enumerate() {
+        for resource in &pass.writes {
+            let resource_writers = writers.entry(*resource).or_default();
+
+            if let Some(previous) = resource_writers.last() {
+                add_dependency(*previous, index);
+            }
+
+            if !resource_writers.contains(&index) {
+                resource_writers.push(index);
+            }
+        }
+    }
+
+    for (index, pass) in passes.iter().enumerate() {
+        for resource in &pass.reads {
+            // a pass that writes what it reads is already ordered among the writers
+            if pass.writes.contains(resource) {
+                continue;
+            }
+
+            for writer in writers.get(resource).into_iter().flatten() {
+                add_dependency(*writer, index);
+            }
+        }
+    }
+
+    let mut ready: BTreeSet<usize> = (0..passes.len()).filter(|index| dependencies[*index] == 0).collect();
+    let mut order = Vec::with_capacity(passes.len());
+
+    while let Some(index) = ready.iter().next().copied() {
+        ready.remove(&index);
+        order.push(index);
+
+        for dependent in &dependents[index] {
+            dependencies[*dependent] -= 1;
+
+            if dependencies[*dependent] == 0 {
+                ready.insert(*dependent);
+            }
+        }
+    }
+
+    if order.len() < passes.len() {
+        let stuck: Vec<&str> = (0..passes.len())
+            .filter(|index| !order.contains(index))
+            .map(|index| passes[index].name)
+            .collect();
+
+        return Err(anyhow!(format!("render passes {} depend on each other in a loop", stuck.join(", "))));
+    }
+
+    Ok(order)
+}
+
+/// Gives each transient (description, first step, last step) a texture slot. Transients with the
+/// same description share a slot when one's last use comes before the other's first.
+pub fn assign_slots(transients: &[(TextureDesc, usize, usize)]) -> (Vec<usize>, Vec<TextureDesc>) {
+    let mut by_first_use: Vec<usize> = (0..transients.len()).collect();
+    by_first_use.sort_by_key(|index| transients[*index].1);
+
+    // each slot's description and the last step it's busy until
+    let mut slots: Vec<(TextureDesc, usize)> = Vec::new();
+    let mut assigned = vec![0; transients.len()];
+
+    for index in by_first_use {
+        let (desc, first, last) = transients[index];
+
+        match slots.iter().position(|(slot_desc, busy_until)| *slot_desc == desc && *busy_until < first) {
+            Some(slot) => {
+                slots[slot].1 = last;
+                assigned[index] = slot;
+            },
+            None => {
+                slots.push((desc, last));
+                assigned[index] = slots.len() - 1;
+            }
+        }
+    }
+
+    (assigned, slots.into_iter().map(|(desc, _)| desc).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PassDesc, ResourceId, TextureDesc, assign_slots, sort_passes};
+
+    #[test]
+    fn passes_run_after_what_they_read() {
+        let scene = ResourceId(0);
+        let shadows = ResourceId(1);
+        let output = ResourceId(2);
+
+        let passes = vec![
+            PassDesc::render("post").with_read(scene).with_write(output),
+            PassDesc::render("terrain").with_read(shadows).with_color_attachment(scene, None),
+            PassDesc::render("sky").with_color_attachment(scene, None),
+            PassDesc::encoder("shadows").with_write(shadows),
+        ];
+
+        // terrain and sky both write the scene, in the order they were added
+        assert_eq!(sort_passes(&passes).unwrap(), vec![3, 1, 2, 0]);
+
+        let looping = vec![
+            PassDesc::render("a").with_read(scene).with_write(shadows),
+            PassDesc::render("b").with_read(shadows).with_write(scene),
+        ];
+
+        assert!(sort_passes(&looping).is_err());
+    }
+
+    #[test]
+    fn transients_share_textures_when_they_can() {
+        let half = TextureDesc { width: 640, height: 360, format: wgpu::TextureFormat::Rgba16Float };
+        let full = TextureDesc { width: 1280, height: 720, format: wgpu::TextureFormat::Rgba16Float };
+
+        let (slots, descs) = assign_slots(&[
+            (half, 0, 1),
+            (half, 1, 2),
+            // free again after step 1
+            (half, 2, 3),
+            (full, 3, 4),
+        ]);
+
+        assert_eq!(slots, vec![0, 1, 0, 2]);
+        assert_eq!(descs, vec![half, half, full]);
+    }
+}
@@ -0,0 +1,174 @@
+use std::num::NonZeroU32;
+
+use anyhow::{Result, Error, anyhow};
+
+use crate::texture;
+
+/// Colour and depth textures to draw into instead of the window, and read back afterwards
+pub struct RenderTarget {
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+
+    color: wgpu::Texture,
+    color_view: wgpu::TextureView,
+    depth: texture::Texture,
+}
+
+impl RenderTarget {
+    pub fn new(device: &wgpu::Device, width: u32, height: u32, format: wgpu::TextureFormat, label: &str) -> Self {
+        let color = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC
+        });
+
+        let color_view = color.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth = texture::Texture::create_depth_texture_with_size(device, width, height, label);
+
+        Self {
+            width,
+            height,
+            format,
+
+            color,
+            color_view,
+            depth,
+        }
+    }
+
+    /// Copies the colour texture back from the GPU. Waits for everything submitted so far to finish.
+    pub fn read_image(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<image::RgbaImage, Error> {
+        let bytes_per_pixel = match self.format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb |
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => 4,
+            other => return Err(anyhow!(format!("can't read back render targets in {:?}", other)))
+        };
+
+        let padded_bytes_per_row = padded_bytes_per_row(self.width * bytes_per_pixel);
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Render Target Readback"),
+            size: (padded_bytes_per_row * self.height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(
+            &wgpu::CommandEncoderDescriptor { label: Some("Readback Encoder") }
+        );
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &self.color,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_bytes_per_row),
+                    rows_per_image: NonZeroU32::new(self.height),
+                },
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            }
+        );
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+
+        device.poll(wgpu::Maintain::Wait);
+
+        if let Err(err) = futures::executor::block_on(mapping) {
+            return Err(anyhow!(format!("couldn't read back render target: {}", err)));
+        }
+
+        let is_bgra = matches!(self.format, wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb);
+        let pixels = unpad_rows(&slice.get_mapped_range(), self.width, self.height, padded_bytes_per_row, is_bgra);
+
+        buffer.unmap();
+
+        match image::RgbaImage::from_raw(self.width, self.height, pixels) {
+            Some(image) => Ok(image),
+            None => Err(anyhow!("render target readback has the wrong size"))
+        }
+    }
+}
+
+// getters
+impl RenderTarget {
+    pub fn get_width(&self) -> u32 { self.width }
+
+    pub fn get_height(&self) -> u32 { self.height }
+
+    pub fn get_format(&self) -> wgpu::TextureFormat { self.format }
+
+    pub fn get_color_texture(&self) -> &wgpu::Texture { &self.color }
+
+    pub fn get_color_view(&self) -> &wgpu::TextureView { &self.color_view }
+
+    pub fn get_depth_view(&self) -> &wgpu::TextureView { &self.depth.view }
+}
+
+/// Rows copied out of textures have to start on `COPY_BYTES_PER_ROW_ALIGNMENT`
+pub fn padded_bytes_per_row(bytes_per_row: u32) -> u32 {
+    let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+    bytes_per_row.div_ceil(alignment) * alignment
+}
+
+/// Drops the padding after each row of 4 byte pixels, swapping blue and red for BGRA data
+pub fn unpad_rows(data: &[u8], width: u32, height: u32, padded_bytes_per_row: u32, is_bgra: bool) -> Vec<u8> {
+    let bytes_per_row = (width * 4) as usize;
+    let mut pixels = Vec::with_capacity(bytes_per_row * height as usize);
+
+    for row in data.chunks(padded_bytes_per_row as usize).take(height as usize) {
+        pixels.extend_from_slice(&row[..bytes_per_row]);
+    }
+
+    if is_bgra {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{padded_bytes_per_row, unpad_rows};
+
+    #[test]
+    fn readback_rows_lose_their_padding() {
+        assert_eq!(padded_bytes_per_row(4), 256);
+        assert_eq!(padded_bytes_per_row(256), 256);
+        assert_eq!(padded_bytes_per_row(260), 512);
+
+        // 2x2 BGRA pixels, each row padded to 256 bytes
+        let mut data = vec![0u8; 512];
+        data[..8].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        data[256..264].copy_from_slice(&[9, 10, 11, 12, 13, 14, 15, 16]);
+
+        assert_eq!(
+            unpad_rows(&data, 2, 2, 256, true),
+            vec![3, 2, 1, 4, 7, 6, 5, 8, 11, 10, 9, 12, 15, 14, 13, 16]
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, sync::{Arc, Mutex}};
+
+use anyhow::{Result, Error, anyhow};
+
+/// Every shader in `res/shaders`, compiled in so builds run without the files
+const EMBEDDED_SHADERS: &[(&str, &str)] = &[
+    ("shader.wgsl", include_str!("../../res/shaders/shader.wgsl")),
+    ("sky.wgsl", include_str!("../../res/shaders/sky.wgsl")),
+    ("shadow.wgsl", include_str!("../../res/shaders/shadow.wgsl")),
+    ("ui.wgsl", include_str!("../../res/shaders/ui.wgsl")),
+    ("post/fullscreen.wgsl", include_str!("../../res/shaders/post/fullscreen.wgsl")),
+    ("post/tonemap.wgsl", include_str!("../../res/shaders/post/tonemap.wgsl")),
+    ("post/fxaa.wgsl", include_str!("../../res/shaders/post/fxaa.wgsl")),
+    ("post/vignette.wgsl", include_str!("../../res/shaders/post/vignette.wgsl")),
+];
+
+/// Seconds between checks of the shader directory for edits
+pub const RELOAD_INTERVAL: f32 = 0.5;
+
+/// Where pipelines get their shader source from.
+/// In debug builds that's `res/shaders` on disk, which is watched so edits show up without a restart.
+pub struct ShaderLibrary {
+    // None when shaders only come from the build
+    directory: Option<PathBuf>,
+    sources: HashMap<&'static str, String>,
+
+    // Some while `try_create` is collecting errors instead of panicking on them
+    captured_errors: Arc<Mutex<Option<Vec<String>>>>,
+}
+
+impl ShaderLibrary {
+    /// Reads shaders from `directory` when given, falling back to the built in copy of any it can't read
+    pub fn new(directory: Option<PathBuf>) -> Self {
+        let mut library = Self {
+            directory,
+            sources: EMBEDDED_SHADERS.iter().map(|(name, source)| (*name, source.to_string())).collect(),
+
+            captured_errors: Arc::new(Mutex::new(None)),
+        };
+
+        library.check_for_changes();
+        library
+    }
+
+    /// Watches the repository's `res/shaders` in debug builds, release builds only use the built in shaders
+    pub fn from_build() -> Self {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("..").join("res").join("shaders");
+
+        if cfg!(debug_assertions) && directory.is_dir() {
+            eprintln!("[LOG] Hot reloading shaders from '{}'", directory.display());
+            Self::new(Some(directory))
+        } else {
+            Self::new(None)
+        }
+    }
+
+    /// Rereads the shader directory, returning the names of shaders that changed since the last check
+    pub fn check_for_changes(&mut self) -> Vec<String> {
+        let directory = match &self.directory {
+            Some(directory) => directory,
+            None => return Vec::new()
+        };
+
+        let mut changed = Vec::new();
+
+        for (name, _) in EMBEDDED_SHADERS {
+            // files that are missing or half written keep their last source
+            let source = match std::fs::read_to_string(directory.join(name)) {
+                Ok(source) => source,
+                Err(_) => continue
+            };
+
+            if self.sources.get(name) != Some(&source) {
+                self.sources.insert(name, source);
+                changed.push(name.to_string());
+            }
+        }
+
+        changed
+    }
+
+    /// Makes wgpu errors go to `try_create` while it runs. Any other error still panics like wgpu's own handler.
+    pub fn install_error_handler(&self, device: &wgpu::Device) {
+        let captured_errors = self.captured_errors.clone();
+
+        device.on_uncaptured_error(move |err| {
+            let mut captured = match captured_errors.lock() {
+                Ok(captured) => captured,
+                Err(_) => panic!("wgpu error: {}", err)
+            };
+
+            match captured.as_mut() {
+                Some(errors) => errors.push(err.to_string()),
+                None => panic!("wgpu error: {}", err)
+            }
+        });
+    }
+
+    /// Runs `create`, failing if wgpu reports errors meanwhile (like a shader that doesn't compile).
+    /// Needs `install_error_handler`, otherwise those errors panic.
+    pub fn try_create<T>(&self, create: impl FnOnce() -> T) -> Result<T, Error> {
+        self.set_capturing(Some(Vec::new()));
+        let created = create();
+
+        match self.set_capturing(None) {
+            Some(errors) if !errors.is_empty() => Err(anyhow!(errors.join("\n"))),
+            _ => Ok(created)
+        }
+    }
+
+    fn set_capturing(&self, errors: Option<Vec<String>>) -> Option<Vec<String>> {
+        match self.captured_errors.lock() {
+            Ok(mut captured) => std::mem::replace(&mut *captured, errors),
+            Err(_) => None
+        }
+    }
+}
+
+// getters
+impl ShaderLibrary {
+    /// Whether shaders are being read from disk and can change
+    pub fn is_hot_reloading(&self) -> bool { self.directory.is_some() }
+
+    /// The source of a shader in `res/shaders`, e.g. `"post/fxaa.wgsl"`
+    pub fn get_source(&self, name: &str) -> &str {
+        match self.sources.get(name) {
+            Some(source) => source,
+            None => panic!("there's no shader called '{}' in res/shaders", name)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShaderLibrary;
+
+    #[test]
+    fn edited_shaders_are_reported_once() {
+        let directory = std::env::temp_dir().join(format!("shader_library_test_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("sky.wgsl"), "// first").unwrap();
+
+        let mut library = ShaderLibrary::new(Some(directory.clone()));
+
+        assert_eq!(library.get_source("sky.wgsl"), "// first");
+        assert!(library.check_for_changes().is_empty());
+
+        std::fs::write(directory.join("sky.wgsl"), "// second").unwrap();
+
+        assert_eq!(library.check_for_changes(), vec!["sky.wgsl".to_string()]);
+        assert!(library.check_for_changes().is_empty());
+        assert_eq!(library.get_source("sky.wgsl"), "// second");
+
+        // shaders missing from the directory come from the build
+        assert!(library.get_source("shadow.wgsl").contains("fn main"));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,497 @@
+use std::{num::NonZeroU32, rc::Rc};
+
+use cgmath::{InnerSpace, Matrix4, Point3, Vector3, Vector4};
+use wgpu::util::DeviceExt;
+
+use anyhow::{Result, Error};
+
+use common::settings::ShadowQuality;
+
+use crate::{Renderer, camera::{self, Camera, Projection}, camera_uniform, chunk_mesh_cache::{self, ChunkMeshCache}, pipeline::{Material, PipelineDescriptor}, texture, vertex::{ChunkVertex, VertexLayout}};
+
+/// How many shadow maps the view is split into, each covering a longer stretch of it
+pub const CASCADE_COUNT: usize = 3;
+
+// close cascades get more detail the closer this is to 1, 0 splits the distance evenly
+const SPLIT_LAMBDA: f32 = 0.8;
+// blocks this far outside a cascade (towards the sun) still cast shadows into it
+const CASTER_DISTANCE: f32 = 64.0;
+// surfaces are pushed this far along their normal (in shadow map texels) before sampling, against shadow acne
+const NORMAL_OFFSET: f32 = 1.5;
+
+/// Where the view's cascades end, as distances along the view direction
+pub fn calc_cascade_splits(near: f32, far: f32) -> [f32; CASCADE_COUNT] {
+    let mut splits = [far; CASCADE_COUNT];
+
+    // a blend between logarithmic (detail up close) and even splits
+    for (i, split) in splits.iter_mut().enumerate().take(CASCADE_COUNT - 1) {
+        let t = (i + 1) as f32 / CASCADE_COUNT as f32;
+
+        let logarithmic = near * (far / near).powf(t);
+        let even = near + (far - near) * t;
+
+        *split = SPLIT_LAMBDA * logarithmic + (1.0 - SPLIT_LAMBDA) * even;
+    }
+
+    splits
+}
+
+/// The light's view-projection for the part of the view between `near` and `far`, for positions
+/// relative to the camera (like the block shader's), and how big one of its texels is in blocks.
+///
+/// The cascade is a sphere around that part of the view, so it doesn't change size as the camera
+/// turns, and it's moved in whole shadow map texels so shadow edges don't shimmer as the camera moves.
+pub fn calc_cascade_matrix(camera: &Camera, projection: &Projection, near: f32, far: f32,
+    light_direction: Vector3<f32>, map_size: u32) -> (Matrix4<f32>, f32) {
+    let forward = camera.get_direction();
+    let right = forward.cross(Vector3::unit_y()).normalize();
+    let up = right.cross(forward);
+
+    let tan_half_fovy = (projection.get_fovy().0 * 0.5).tan();
+
+    let corners: Vec<Vector3<f32>> = [near, far].iter()
+        .flat_map(|distance| {
+            let half_height = distance * tan_half_fovy;
+            let half_width = half_height * projection.get_aspect();
+
+            [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].iter()
+                .map(|(x, y)| forward * *distance + right * (half_width * x) + up * (half_height * y))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    let center = corners.iter().fold(Vector3::new(0.0, 0.0, 0.0), |sum, corner| sum + corner) / corners.len() as f32;
+    let radius = corners.iter().map(|corner| (corner - center).magnitude()).fold(0.0f32, f32::max);
+    // rounded up so the sphere doesn't change size from floating point noise
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let light_up = if light_direction.y.abs() > 0.99 { Vector3::unit_z() } else { Vector3::unit_y() };
+
+    // worked out in f64, the camera can be far enough from the origin for f32 to lose whole texels
+    let to_f64 = |v: Vector3<f32>| Vector3::new(v.x as f64, v.y as f64, v.z as f64);
+
+    let light_view = Matrix4::look_at_dir(Point3::new(0.0, 0.0, 0.0), to_f64(-light_direction), to_f64(light_up));
+
+    let camera_pos = to_f64(camera.pos_as_vec3());
+    let light_camera = (light_view * camera_pos.extend(0.0)).truncate();
+    let light_center = light_camera + (light_view * to_f64(center).extend(0.0)).truncate();
+
+    let texel_size = radius as f64 * 2.0 / map_size as f64;
+    let snapped_center = Vector3::new(
+        (light_center.x / texel_size).floor() * texel_size,
+        (light_center.y / texel_size).floor() * texel_size,
+        light_center.z,
+    );
+
+    // from camera relative positions into the cascade's light space, centered on the cascade
+    let offset = light_camera - snapped_center;
+    let offset = Vector3::new(offset.x as f32, offset.y as f32, offset.z as f32);
+
+    let light_rotation = Matrix4::look_at_dir(Point3::new(0.0, 0.0, 0.0), -light_direction, light_up);
+    let projection = cgmath::ortho(-radius, radius, -radius, radius, -(radius + CASTER_DISTANCE), radius);
+
+    (camera::OPENGL_TO_WGPU_MATRIX * projection * Matrix4::from_translation(offset) * light_rotation, texel_size as f32)
+}
+
+/// Everything the block shader needs to look up shadows
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShadowUniform {
+    pub cascades: [[[f32; 4]; 4]; CASCADE_COUNT],
+    // view distance each cascade ends at, the last element is unused
+    pub splits: [f32; 4],
+    // size of a shadow map texel in blocks, per cascade
+    pub texel_sizes: [f32; 4],
+    // towards the light, w is how strong shadows are (0 turns them off)
+    pub light_direction: [f32; 4],
+    // texel size, filter radius, normal offset, unused
+    pub params: [f32; 4],
+}
+
+impl ShadowUniform {
+    pub fn new() -> Self {
+        Self {
+            cascades: [[[0.0; 4]; 4]; CASCADE_COUNT],
+            splits: [0.0; 4],
+            texel_sizes: [0.0; 4],
+            light_direction: [0.0, 1.0, 0.0, 0.0],
+            params: [0.0; 4],
+        }
+    }
+}
+
+impl Default for ShadowUniform {
+    fn default() -> Self { Self::new() }
+}
+
+/// Cascaded shadow maps from the sun (or the moon at night), following the camera.
+///
+/// Also owns the lighting bind group lit pipelines use: the sky's environment uniform,
+/// the shadow uniform, the shadow maps and their comparison sampler.
+pub struct ShadowMaps {
+    quality: ShadowQuality,
+
+    cascade_views: Vec<wgpu::TextureView>,
+    map_view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+
+    uniform: ShadowUniform,
+    uniform_buffer: wgpu::Buffer,
+
+    // one light matrix per cascade for drawing into its map
+    cascade_buffers: Vec<wgpu::Buffer>,
+    cascade_bind_groups: Vec<wgpu::BindGroup>,
+    material: Material,
+
+    lighting_layout: Rc<wgpu::BindGroupLayout>,
+    lighting_bind_group: wgpu::BindGroup,
+}
+
+impl ShadowMaps {
+    /// The shadow pipeline draws the same chunk meshes as the block pipeline,
+    /// with the renderer's camera and the chunk origins
+    pub fn new(renderer: &Renderer, quality: ShadowQuality, environment_buffer: &wgpu::Buffer) -> Result<Self, Error> {
+        let device = renderer.get_device();
+        let uniform = ShadowUniform::new();
+
+        let uniform_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Shadow Buffer"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+            }
+        );
+
+        let cascade_layout = renderer.get_bind_group_layout(&cascade_layout_entries());
+
+        let cascade_buffers: Vec<wgpu::Buffer> = (0..CASCADE_COUNT)
+            .map(|_| device.create_buffer_init(
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some("Shadow Cascade Buffer"),
+                    contents: bytemuck::cast_slice(&uniform.cascades[0]),
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+                }
+            ))
+            .collect();
+
+        let cascade_bind_groups = cascade_buffers.iter()
+            .map(|buffer| device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &cascade_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }
+                ],
+                label: Some("shadow_cascade_bind_group"),
+            }))
+            .collect();
+
+        let lighting_layout = renderer.get_bind_group_layout(&lighting_layout_entries());
+
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                label: Some("Shadow Sampler"),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                // linear comparisons blend the 4 closest texels, a bit of filtering for free
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                compare: Some(wgpu::CompareFunction::LessEqual),
+                ..Default::default()
+            }
+        );
+
+        let (cascade_views, map_view) = create_maps(device, quality);
+        let lighting_bind_group = create_lighting_bind_group(device, &lighting_layout, environment_buffer,
+            &uniform_buffer, &map_view, &sampler);
+
+        let material = Material::new(renderer, PipelineDescriptor::new("Shadow Pipeline", "shadow.wgsl")
+            .with_vertex_layout(ChunkVertex::desc())
+            .with_bind_group(cascade_layout_entries())
+            .with_bind_group(camera_uniform::bind_group_layout_entries())
+            .with_bind_group(chunk_mesh_cache::origin_layout_entries())
+            // depth only, both sides so blocks lit from behind (the sun's side of a wall) still cast shadows
+            .with_cull_mode(None)
+            .with_depth_compare(wgpu::CompareFunction::LessEqual)
+            .with_depth_bias(2, 2.0))?;
+
+        Ok(Self {
+            quality,
+
+            cascade_views,
+            map_view,
+            sampler,
+
+            uniform,
+            uniform_buffer,
+
+            cascade_buffers,
+            cascade_bind_groups,
+            material,
+
+            lighting_layout,
+            lighting_bind_group,
+        })
+    }
+
+    /// Recreates the maps at the new quality's size
+    pub fn set_quality(&mut self, device: &wgpu::Device, quality: ShadowQuality, environment_buffer: &wgpu::Buffer) {
+        if quality == self.quality {
+            return;
+        }
+
+        let (cascade_views, map_view) = create_maps(device, quality);
+
+        self.quality = quality;
+        self.cascade_views = cascade_views;
+        self.map_view = map_view;
+        self.lighting_bind_group = create_lighting_bind_group(device, &self.lighting_layout, environment_buffer,
+            &self.uniform_buffer, &self.map_view, &self.sampler);
+    }
+
+    /// Moves the cascades along with the camera. `light_direction` points at the sun,
+    /// `shadow_distance` is how far from the camera shadows reach.
+    pub fn update(&mut self, queue: &wgpu::Queue, camera: &Camera, projection: &Projection,
+        light_direction: Vector3<f32>, shadow_distance: f32) {
+        // once the sun is down, the moon casts (fainter) shadows
+        let (light_direction, brightness) = if light_direction.y >= 0.0 {
+            (light_direction, 1.0)
+        } else {
+            (-light_direction, 0.5)
+        };
+
+        // shadows fade out as the light gets close to the horizon, where they'd get endlessly long
+        let strength = match self.quality {
+            ShadowQuality::Off => 0.0,
+            _ => brightness * (light_direction.y / 0.15).clamp(0.0, 1.0),
+        };
+
+        let map_size = self.quality.get_map_size();
+        let near = projection.get_znear();
+        let far = shadow_distance.min(projection.get_zfar()).max(near * 2.0);
+
+        let splits = calc_cascade_splits(near, far);
+        let mut cascade_near = near;
+
+        for (cascade, split) in splits.iter().enumerate() {
+            let (matrix, texel_size) = calc_cascade_matrix(camera, projection, cascade_near, *split, light_direction, map_size);
+
+            self.uniform.cascades[cascade] = matrix.into();
+            self.uniform.splits[cascade] = *split;
+            self.uniform.texel_sizes[cascade] = texel_size;
+
+            queue.write_buffer(&self.cascade_buffers[cascade], 0, bytemuck::cast_slice(&self.uniform.cascades[cascade]));
+
+            cascade_near = *split;
+        }
+
+        self.uniform.light_direction = Vector4::new(light_direction.x, light_direction.y, light_direction.z, strength).into();
+        self.uniform.params = [
+            1.0 / map_size as f32,
+            self.quality.get_filter_radius() as f32,
+            NORMAL_OFFSET,
+            0.0,
+        ];
+
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+    }
+
+    /// Picks up the pipeline again after `shadow.wgsl` was reloaded
+    pub fn refresh(&mut self, renderer: &Renderer) -> Result<(), Error> {
+        self.material.refresh(renderer)
+    }
+
+    /// Draws the chunks into every cascade's map, in the render graph's pass writing them
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, camera_bind_group: &wgpu::BindGroup, chunk_meshes: &ChunkMeshCache) {
+        if self.quality == ShadowQuality::Off {
+            return;
+        }
+
+        for (view, bind_group) in self.cascade_views.iter().zip(self.cascade_bind_groups.iter()) {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shadow Pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(
+                    wgpu::RenderPassDepthStencilAttachment {
+                        view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: true
+                        }),
+                        stencil_ops: None
+                    }
+                )
+            });
+
+            self.material.bind(&mut render_pass, &[(0, bind_group), (1, camera_bind_group)]);
+
+            chunk_meshes.draw(&mut render_pass, 2);
+        }
+    }
+}
+
+// getters
+impl ShadowMaps {
+    pub fn get_quality(&self) -> ShadowQuality { self.quality }
+
+    pub fn get_uniform(&self) -> &ShadowUniform { &self.uniform }
+
+    /// Environment and shadows, for pipelines that light what they draw
+    pub fn get_lighting_bind_group_layout(&self) -> &wgpu::BindGroupLayout { &self.lighting_layout }
+
+    pub fn get_lighting_bind_group(&self) -> &wgpu::BindGroup { &self.lighting_bind_group }
+}
+
+fn uniform_layout_entry(binding: u32, visibility: wgpu::ShaderStages) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+/// A cascade's light matrix, for drawing into its shadow map
+pub fn cascade_layout_entries() -> Vec<wgpu::BindGroupLayoutEntry> {
+    vec![uniform_layout_entry(0, wgpu::ShaderStages::VERTEX)]
+}
+
+/// Environment and shadows, see `ShadowMaps::get_lighting_bind_group`
+pub fn lighting_layout_entries() -> Vec<wgpu::BindGroupLayoutEntry> {
+    vec![
+        uniform_layout_entry(0, wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT),
+        uniform_layout_entry(1, wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT),
+        wgpu::BindGroupLayoutEntry {
+            binding: 2,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2Array,
+                sample_type: wgpu::TextureSampleType::Depth,
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 3,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler {
+                comparison: true,
+                filtering: true,
+            },
+            count: None,
+        },
+    ]
+}
+
+// a depth texture with a layer per cascade, a view to draw into each layer and one to sample them all
+fn create_maps(device: &wgpu::Device, quality: ShadowQuality) -> (Vec<wgpu::TextureView>, wgpu::TextureView) {
+    let map_size = quality.get_map_size();
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Shadow Maps"),
+        size: wgpu::Extent3d {
+            width: map_size,
+            height: map_size,
+            depth_or_array_layers: CASCADE_COUNT as u32,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: texture::Texture::DEPTH_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+    });
+
+    let cascade_views = (0..CASCADE_COUNT as u32)
+        .map(|layer| texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Shadow Cascade View"),
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            base_array_layer: layer,
+            array_layer_count: NonZeroU32::new(1),
+            ..Default::default()
+        }))
+        .collect();
+
+    let map_view = texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some("Shadow Map View"),
+        dimension: Some(wgpu::TextureViewDimension::D2Array),
+        ..Default::default()
+    });
+
+    (cascade_views, map_view)
+}
+
+fn create_lighting_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, environment_buffer: &wgpu::Buffer,
+    shadow_buffer: &wgpu::Buffer, map_view: &wgpu::TextureView, sampler: &wgpu::Sampler) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: environment_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: shadow_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(map_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+        label: Some("lighting_bind_group"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, Vector3, Vector4};
+
+    use crate::camera::{Camera, Projection};
+    use super::{calc_cascade_matrix, calc_cascade_splits};
+
+    #[test]
+    fn cascades_cover_their_slice_and_snap_to_texels() {
+        let splits = calc_cascade_splits(0.1, 96.0);
+
+        assert!(splits[0] < splits[1] && splits[1] < splits[2]);
+        assert_eq!(splits[2], 96.0);
+
+        let projection = Projection::new(1280, 720, Deg(45.0), 0.1, 1000.0);
+        let light = Vector3::new(0.3, 0.9, 0.2);
+        let camera = Camera::new((1000.25, 70.0, -5000.5), Deg(30.0), Deg(-10.0));
+
+        let (matrix, texel_size) = calc_cascade_matrix(&camera, &projection, 0.1, splits[0], light, 1024);
+
+        assert!(texel_size > 0.0 && texel_size < 0.1);
+
+        // a point in the middle of the first slice lands inside the map
+        let inside = camera.get_direction() * (splits[0] * 0.5);
+        let clip = matrix * Vector4::new(inside.x, inside.y, inside.z, 1.0);
+
+        assert!(clip.x.abs() <= 1.0 && clip.y.abs() <= 1.0);
+        assert!(clip.z >= 0.0 && clip.z <= 1.0);
+
+        // moving the camera less than a texel sideways keeps world positions on the same texels
+        let moved = Camera::new((1000.25 + 0.001, 70.0, -5000.5), Deg(30.0), Deg(-10.0));
+        let (moved_matrix, _) = calc_cascade_matrix(&moved, &projection, 0.1, splits[0], light, 1024);
+
+        let origin = Vector4::new(0.0, 0.0, 0.0, 1.0);
+        let shifted = Vector4::new(-0.001, 0.0, 0.0, 1.0);
+
+        let before = matrix * origin;
+        let after = moved_matrix * shifted;
+
+        assert!((before.x - after.x).abs() < 1e-3 && (before.y - after.y).abs() < 1e-3);
+    }
+}
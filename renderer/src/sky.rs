@@ -0,0 +1,233 @@
+use cgmath::{InnerSpace, Matrix4, SquareMatrix, Vector3};
+use wgpu::util::DeviceExt;
+
+use std::rc::Rc;
+
+use anyhow::{Result, Error};
+
+use crate::{Renderer, camera::{Camera, Projection}, pipeline::{DepthMode, Material, PipelineDescriptor}};
+
+const DAY_ZENITH: [f32; 3] = [0.25, 0.5, 0.95];
+const DAY_HORIZON: [f32; 3] = [0.7, 0.85, 1.0];
+const NIGHT_ZENITH: [f32; 3] = [0.01, 0.01, 0.04];
+const NIGHT_HORIZON: [f32; 3] = [0.05, 0.06, 0.12];
+const SUNSET_HORIZON: [f32; 3] = [1.0, 0.5, 0.2];
+
+// even at midnight blocks aren't pitch black
+const MIN_SKY_LIGHT: f32 = 0.15;
+// fog starts this far into the render distance
+const FOG_START: f32 = 0.6;
+
+/// What the sky looks like at a time of day, 0 and 1 being midnight and 0.5 noon
+#[derive(Debug, Clone, Copy)]
+pub struct SkyColors {
+    pub zenith: [f32; 3],
+    pub horizon: [f32; 3],
+    /// Points at the sun, the moon is straight opposite
+    pub sun_direction: Vector3<f32>,
+    /// How much daylight reaches the blocks, 0 to 1
+    pub sky_light: f32,
+}
+
+impl SkyColors {
+    pub fn at(time_of_day: f32) -> Self {
+        // rises at 0.25, sets at 0.75, slightly tilted so it doesn't pass straight overhead
+        let angle = (time_of_day - 0.25) * std::f32::consts::PI * 2.0;
+        let sun_direction = Vector3::new(angle.cos(), angle.sin(), 0.25).normalize();
+
+        let elevation = sun_direction.y;
+        let daylight = smoothstep(-0.1, 0.25, elevation);
+        let sunset = (1.0 - elevation.abs() / 0.3).max(0.0) * 0.6;
+
+        let horizon = mix(mix(NIGHT_HORIZON, DAY_HORIZON, daylight), SUNSET_HORIZON, sunset);
+
+        Self {
+            zenith: mix(NIGHT_ZENITH, DAY_ZENITH, daylight),
+            horizon,
+            sun_direction,
+            sky_light: MIN_SKY_LIGHT + (1.0 - MIN_SKY_LIGHT) * daylight,
+        }
+    }
+
+    /// Fog fades into the horizon so far away blocks melt into the sky
+    pub fn get_fog_color(&self) -> [f32; 3] { self.horizon }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn mix(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t, a[2] + (b[2] - a[2]) * t]
+}
+
+/// Shared by the sky and anything drawn in it (the block shader's fog and sky light)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct EnvironmentUniform {
+    // turns screen positions back into view directions, camera at the origin
+    pub inv_relative_view_proj: [[f32; 4]; 4],
+    pub zenith_color: [f32; 4],
+    // also the fog colour
+    pub horizon_color: [f32; 4],
+    // w is the sky light
+    pub sun_direction: [f32; 4],
+    // start and end distance in blocks
+    pub fog: [f32; 4],
+}
+
+impl EnvironmentUniform {
+    pub fn new() -> Self {
+        Self {
+            inv_relative_view_proj: Matrix4::identity().into(),
+            zenith_color: [0.0; 4],
+            horizon_color: [0.0; 4],
+            sun_direction: [0.0, 1.0, 0.0, 1.0],
+            fog: [0.0; 4],
+        }
+    }
+}
+
+impl Default for EnvironmentUniform {
+    fn default() -> Self { Self::new() }
+}
+
+/// Draws the sky behind everything else and hands the time of day's colours to other shaders
+pub struct Sky {
+    material: Material,
+
+    uniform: EnvironmentUniform,
+    buffer: wgpu::Buffer,
+    bind_group_layout: Rc<wgpu::BindGroupLayout>,
+    bind_group: wgpu::BindGroup,
+
+    colors: SkyColors,
+}
+
+impl Sky {
+    /// Draws into the renderer's scene
+    pub fn new(renderer: &Renderer) -> Result<Self, Error> {
+        let device = renderer.get_device();
+        let uniform = EnvironmentUniform::new();
+
+        let buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor {
+                label: Some("Environment Buffer"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+            }
+        );
+
+        let bind_group_layout = renderer.get_bind_group_layout(&environment_layout_entries());
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }
+            ],
+            label: Some("environment_bind_group"),
+        });
+
+        let material = Material::new(renderer, PipelineDescriptor::new("Sky Pipeline", "sky.wgsl")
+            .with_bind_group(environment_layout_entries())
+            .with_color_format(renderer.get_scene_format())
+            // drawn first and behind everything, it never touches the depth buffer
+            .with_depth(DepthMode::Ignore)
+            .with_cull_mode(None))?;
+
+        Ok(Self {
+            material,
+
+            uniform,
+            buffer,
+            bind_group_layout,
+            bind_group,
+
+            colors: SkyColors::at(0.5),
+        })
+    }
+
+    /// `fog_distance` is where blocks vanish completely, usually the render distance in blocks
+    pub fn update(&mut self, queue: &wgpu::Queue, camera: &Camera, projection: &Projection, time_of_day: f32, fog_distance: f32) {
+        self.colors = SkyColors::at(time_of_day);
+
+        let relative_view_proj = projection.calc_matrix() * camera.calc_relative_matrix();
+
+        if let Some(inverse) = relative_view_proj.invert() {
+            self.uniform.inv_relative_view_proj = inverse.into();
+        }
+
+        let [zenith_r, zenith_g, zenith_b] = self.colors.zenith;
+        let [horizon_r, horizon_g, horizon_b] = self.colors.horizon;
+        let sun = self.colors.sun_direction;
+
+        self.uniform.zenith_color = [zenith_r, zenith_g, zenith_b, 1.0];
+        self.uniform.horizon_color = [horizon_r, horizon_g, horizon_b, 1.0];
+        self.uniform.sun_direction = [sun.x, sun.y, sun.z, self.colors.sky_light];
+        self.uniform.fog = [fog_distance * FOG_START, fog_distance, 0.0, 0.0];
+
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+    }
+
+    /// Picks up the pipeline again after `sky.wgsl` was reloaded
+    pub fn refresh(&mut self, renderer: &Renderer) -> Result<(), Error> {
+        self.material.refresh(renderer)
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        // one triangle covering the screen, made up in the shader
+        self.material.bind(render_pass, &[(0, &self.bind_group)]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+// getters
+impl Sky {
+    pub fn get_colors(&self) -> &SkyColors { &self.colors }
+
+    /// For pipelines that want the fog and sky light, see `EnvironmentUniform`
+    pub fn get_bind_group_layout(&self) -> &wgpu::BindGroupLayout { &self.bind_group_layout }
+
+    pub fn get_bind_group(&self) -> &wgpu::BindGroup { &self.bind_group }
+
+    /// The `EnvironmentUniform`, for other bind groups to include
+    pub fn get_buffer(&self) -> &wgpu::Buffer { &self.buffer }
+}
+
+/// The `EnvironmentUniform` alone at binding 0
+pub fn environment_layout_entries() -> Vec<wgpu::BindGroupLayoutEntry> {
+    vec![
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sun_is_up_during_the_day() {
+        let noon = SkyColors::at(0.5);
+        let midnight = SkyColors::at(0.0);
+
+        assert!(noon.sun_direction.y > 0.9);
+        assert!(midnight.sun_direction.y < -0.9);
+        assert!(noon.sky_light > 0.99);
+        assert!((midnight.sky_light - MIN_SKY_LIGHT).abs() < 0.001);
+        assert!(noon.zenith[2] > midnight.zenith[2]);
+    }
+}
@@ -1,8 +1,24 @@
-use std::num::NonZeroU32;
+use std::num::{NonZeroU32, NonZeroU8};
 
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use image::GenericImageView;
 
+use common::settings::TextureFiltering;
+
+/// How a texture is sampled, see `Texture::create_sampler`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SamplerOptions {
+    pub filtering: TextureFiltering,
+    // 1 is off. Only used with trilinear filtering, and only where the adapter supports it
+    pub anisotropy: u8,
+}
+
+impl Default for SamplerOptions {
+    fn default() -> Self {
+        Self { filtering: TextureFiltering::Nearest, anisotropy: 1 }
+    }
+}
+
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
@@ -69,19 +85,148 @@ impl Texture {
             }
         );
 
-        let (bind_group, bind_group_layout) = create_bind_group(device, &view, &sampler, false);
+        let (bind_group, bind_group_layout) = create_bind_group(device, &view, &sampler, wgpu::TextureViewDimension::D2, false);
         
         Ok(Self { texture, view, sampler, bind_group, bind_group_layout })
     }
+
+    /// Cuts `tiles` (column, row) out of an atlas with `tile_size` pixel tiles into the layers of
+    /// a texture array, each with its own mip chain so far away blocks don't shimmer or bleed into each other
+    pub fn array_from_atlas_bytes(device: &wgpu::Device, queue: &wgpu::Queue, bytes: &[u8],
+      tiles: &[(u32, u32)], tile_size: u32, options: SamplerOptions, label: &str) -> Result<Self> {
+        let atlas = image::load_from_memory_with_format(bytes, image::ImageFormat::Png)?.to_rgba8();
+
+        if tiles.is_empty() {
+            return Err(anyhow!(format!("texture array '{}' needs at least one layer", label)));
+        }
+
+        // down to 1x1
+        let mip_level_count = 32 - tile_size.leading_zeros();
+
+        let texture = device.create_texture(
+            &wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: tile_size,
+                    height: tile_size,
+                    depth_or_array_layers: tiles.len() as u32,
+                },
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            }
+        );
+
+        for (layer, (tile_x, tile_y)) in tiles.iter().enumerate() {
+            let (x, y) = (tile_x * tile_size, tile_y * tile_size);
+
+            if x + tile_size > atlas.width() || y + tile_size > atlas.height() {
+                return Err(anyhow!(format!("tile ({}, {}) is outside of the atlas", tile_x, tile_y)));
+            }
+
+            let tile = image::imageops::crop_imm(&atlas, x, y, tile_size, tile_size).to_image();
+
+            for mip_level in 0..mip_level_count {
+                let mip_size = (tile_size >> mip_level).max(1);
+
+                let mip = if mip_level == 0 {
+                    tile.clone()
+                } else {
+                    image::imageops::resize(&tile, mip_size, mip_size, image::imageops::FilterType::Triangle)
+                };
+
+                queue.write_texture(
+                    wgpu::ImageCopyTexture {
+                        texture: &texture,
+                        mip_level,
+                        origin: wgpu::Origin3d { x: 0, y: 0, z: layer as u32 },
+                        aspect: wgpu::TextureAspect::All
+                    },
+                    &mip,
+                    wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: NonZeroU32::new(4 * mip_size),
+                        rows_per_image: NonZeroU32::new(mip_size),
+                    },
+                    wgpu::Extent3d { width: mip_size, height: mip_size, depth_or_array_layers: 1 },
+                );
+            }
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let sampler = Self::create_sampler(device, options);
+
+        let (bind_group, bind_group_layout) = create_bind_group(device, &view, &sampler, wgpu::TextureViewDimension::D2Array, false);
+
+        Ok(Self { texture, view, sampler, bind_group, bind_group_layout })
+    }
+
+    pub fn create_sampler(device: &wgpu::Device, options: SamplerOptions) -> wgpu::Sampler {
+        let (filter, mipmap_filter) = match options.filtering {
+            TextureFiltering::Nearest => (wgpu::FilterMode::Nearest, wgpu::FilterMode::Nearest),
+            TextureFiltering::Bilinear => (wgpu::FilterMode::Linear, wgpu::FilterMode::Nearest),
+            TextureFiltering::Trilinear => (wgpu::FilterMode::Linear, wgpu::FilterMode::Linear),
+        };
+
+        // anisotropic sampling needs every filter to be linear
+        let anisotropy_clamp = match options.filtering {
+            TextureFiltering::Trilinear if options.anisotropy > 1 => NonZeroU8::new(options.anisotropy.min(16)),
+            _ => None
+        };
+
+        device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: filter,
+                min_filter: filter,
+                mipmap_filter,
+                anisotropy_clamp,
+                ..Default::default()
+            }
+        )
+    }
+
+    /// Swaps the sampler, and with it the bind group. The layout stays the same, so pipelines built with it still work.
+    pub fn set_sampler_options(&mut self, device: &wgpu::Device, options: SamplerOptions) {
+        self.sampler = Self::create_sampler(device, options);
+
+        self.bind_group = device.create_bind_group(
+            &wgpu::BindGroupDescriptor {
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&self.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    }
+                ],
+                label: Some("diffuse_bind_group"),
+            }
+        );
+    }
 }
 
 impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
     pub fn create_depth_texture(device: &wgpu::Device, surface_config: &wgpu::SurfaceConfiguration, label: &str) -> Self {
+        Self::create_depth_texture_with_size(device, surface_config.width, surface_config.height, label)
+    }
+
+    pub fn create_depth_texture_with_size(device: &wgpu::Device, width: u32, height: u32, label: &str) -> Self {
         let size = wgpu::Extent3d {
-            width: surface_config.width,
-            height: surface_config.height,
+            width,
+            height,
             depth_or_array_layers: 1
         };
 
@@ -113,37 +258,42 @@ impl Texture {
             }
         );
 
-        let (bind_group, bind_group_layout) = create_bind_group(&device, &view, &sampler, true);
+        let (bind_group, bind_group_layout) = create_bind_group(device, &view, &sampler, wgpu::TextureViewDimension::D2, true);
 
         Self { texture, view, sampler, bind_group, bind_group_layout }
     }
 }
 
-pub fn create_bind_group(device: &wgpu::Device, view: &wgpu::TextureView,
-    sampler: &wgpu::Sampler, comparison: bool) -> (wgpu::BindGroup, wgpu::BindGroupLayout) {
+/// A texture at binding 0 and its sampler at 1, as `create_bind_group` lays them out
+pub fn bind_group_layout_entries(view_dimension: wgpu::TextureViewDimension, comparison: bool) -> Vec<wgpu::BindGroupLayoutEntry> {
+    vec![
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        },
+        wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler {
+                comparison,
+                filtering: true,
+            },
+            count: None,
+        },
+    ]
+}
+
+pub fn create_bind_group(device: &wgpu::Device, view: &wgpu::TextureView, sampler: &wgpu::Sampler,
+    view_dimension: wgpu::TextureViewDimension, comparison: bool) -> (wgpu::BindGroup, wgpu::BindGroupLayout) {
     let texture_bind_group_layout = device.create_bind_group_layout(
         &wgpu::BindGroupLayoutDescriptor {
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        multisampled: false,
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Sampler {
-                        comparison,
-                        filtering: true,
-                    },
-                    count: None,
-                },
-            ],
+            entries: &bind_group_layout_entries(view_dimension, comparison),
             label: Some("texture_bind_group_layout"),
         }
     );
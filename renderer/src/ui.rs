@@ -0,0 +1,970 @@
+use std::{collections::{HashMap, hash_map::DefaultHasher}, hash::{Hash, Hasher}, ops::RangeInclusive};
+
+// the UI font is monospaced, every character is this many times the text size wide
+const CHAR_ADVANCE: f32 = 0.48;
+// lines scrolled per notch of the mouse wheel
+const SCROLL_LINES: f32 = 3.0;
+
+/// A rectangle in logical pixels (physical pixels divided by the window's scale factor), from the top left
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    pub fn get_right(&self) -> f32 { self.x + self.width }
+
+    pub fn get_bottom(&self) -> f32 { self.y + self.height }
+
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && y >= self.y && x < self.get_right() && y < self.get_bottom()
+    }
+
+    /// The part covered by both, `None` when they don't overlap
+    pub fn intersect(&self, other: &Rect) -> Option<Rect> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = self.get_right().min(other.get_right());
+        let bottom = self.get_bottom().min(other.get_bottom());
+
+        if right > x && bottom > y {
+            Some(Rect::new(x, y, right - x, bottom - y))
+        } else {
+            None
+        }
+    }
+
+    /// Moved in by `amount` on every side
+    pub fn shrink(&self, amount: f32) -> Rect {
+        Rect::new(self.x + amount, self.y + amount, (self.width - amount * 2.0).max(0.0), (self.height - amount * 2.0).max(0.0))
+    }
+
+    pub fn offset(&self, x: f32, y: f32) -> Rect {
+        Rect::new(self.x + x, self.y + y, self.width, self.height)
+    }
+
+    /// A `width` by `height` rect placed inside this one at `anchor`
+    pub fn anchored(&self, anchor: Anchor, width: f32, height: f32) -> Rect {
+        let (factor_x, factor_y) = anchor.get_factors();
+
+        Rect::new(
+            self.x + (self.width - width) * factor_x,
+            self.y + (self.height - height) * factor_y,
+            width,
+            height
+        )
+    }
+
+    /// `count` rows of the same height with `spacing` between them
+    pub fn rows(&self, count: usize, spacing: f32) -> Vec<Rect> {
+        let height = (self.height - spacing * count.saturating_sub(1) as f32) / count.max(1) as f32;
+
+        (0..count)
+            .map(|index| Rect::new(self.x, self.y + (height + spacing) * index as f32, self.width, height))
+            .collect()
+    }
+
+    /// `count` columns of the same width with `spacing` between them
+    pub fn columns(&self, count: usize, spacing: f32) -> Vec<Rect> {
+        let width = (self.width - spacing * count.saturating_sub(1) as f32) / count.max(1) as f32;
+
+        (0..count)
+            .map(|index| Rect::new(self.x + (width + spacing) * index as f32, self.y, width, self.height))
+            .collect()
+    }
+}
+
+/// Where something sits inside whatever it's placed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// How far along each axis the anchor is, 0 being the left or top
+    pub fn get_factors(&self) -> (f32, f32) {
+        match self {
+            Anchor::TopLeft => (0.0, 0.0),
+            Anchor::Top => (0.5, 0.0),
+            Anchor::TopRight => (1.0, 0.0),
+            Anchor::Left => (0.0, 0.5),
+            Anchor::Center => (0.5, 0.5),
+            Anchor::Right => (1.0, 0.5),
+            Anchor::BottomLeft => (0.0, 1.0),
+            Anchor::Bottom => (0.5, 1.0),
+            Anchor::BottomRight => (1.0, 1.0),
+        }
+    }
+}
+
+/// Hands out rects one after another down a column or along a row
+#[derive(Debug, Clone, Copy)]
+pub struct Layout {
+    area: Rect,
+    spacing: f32,
+    horizontal: bool,
+    // how far along the area the next rect starts
+    used: f32,
+}
+
+impl Layout {
+    /// Top to bottom, every rect as wide as `area`
+    pub fn column(area: Rect, spacing: f32) -> Self {
+        Self { area, spacing, horizontal: false, used: 0.0 }
+    }
+
+    /// Left to right, every rect as tall as `area`
+    pub fn row(area: Rect, spacing: f32) -> Self {
+        Self { area, spacing, horizontal: true, used: 0.0 }
+    }
+
+    /// The next rect, `size` being its height in a column and its width in a row
+    pub fn next(&mut self, size: f32) -> Rect {
+        let rect = if self.horizontal {
+            Rect::new(self.area.x + self.used, self.area.y, size, self.area.height)
+        } else {
+            Rect::new(self.area.x, self.area.y + self.used, self.area.width, size)
+        };
+
+        self.used += size + self.spacing;
+        rect
+    }
+
+    /// Leaves a gap of `size` before the next rect
+    pub fn skip(&mut self, size: f32) {
+        self.used += size;
+    }
+
+    /// Whatever's left of the area
+    pub fn remaining(&self) -> Rect {
+        if self.horizontal {
+            Rect::new(self.area.x + self.used, self.area.y, (self.area.width - self.used).max(0.0), self.area.height)
+        } else {
+            Rect::new(self.area.x, self.area.y + self.used, self.area.width, (self.area.height - self.used).max(0.0))
+        }
+    }
+}
+
+/// Keys widgets react to, see `Ui::key_pressed`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UiKey {
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Home,
+    End,
+    Enter,
+    Tab,
+    Escape,
+}
+
+/// What widgets look like
+#[derive(Debug, Clone, Copy)]
+pub struct Style {
+    pub text_size: f32,
+    pub padding: f32,
+
+    pub text_color: [f32; 4],
+    pub panel_color: [f32; 4],
+    pub widget_color: [f32; 4],
+    pub hovered_color: [f32; 4],
+    pub active_color: [f32; 4],
+    // checkbox ticks, slider fills, carets and the focus outline
+    pub accent_color: [f32; 4],
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            text_size: 20.0,
+            padding: 6.0,
+
+            text_color: [1.0, 1.0, 1.0, 1.0],
+            panel_color: [0.05, 0.05, 0.08, 0.8],
+            widget_color: [0.2, 0.22, 0.28, 0.9],
+            hovered_color: [0.3, 0.33, 0.42, 0.9],
+            active_color: [0.15, 0.16, 0.2, 0.9],
+            accent_color: [0.45, 0.7, 1.0, 1.0],
+        }
+    }
+}
+
+/// A texture the UI can draw, see `Renderer::add_ui_image`. The default is plain white.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ImageId(pub usize);
+
+/// Identifies a widget from one frame to the next, made from its name and the ids pushed around it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UiId(u64);
+
+/// What happened to a widget this frame
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Response {
+    pub hovered: bool,
+    /// Clicked, or Enter was pressed while it had focus
+    pub clicked: bool,
+    /// Its value was changed
+    pub changed: bool,
+    /// Enter was pressed in a text field
+    pub submitted: bool,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct UiVertex {
+    // physical pixels from the top left
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    pub color: [f32; 4],
+}
+
+/// Triangles drawn with one image, only inside `clip` (physical x, y, width and height) when there is one
+#[derive(Debug, Clone, PartialEq)]
+pub struct UiBatch {
+    pub image: ImageId,
+    pub clip: Option<[u32; 4]>,
+    pub indices: std::ops::Range<u32>,
+}
+
+/// Text in physical pixels, drawn over every quad
+#[derive(Debug, Clone, PartialEq)]
+pub struct UiText {
+    pub text: String,
+    pub position: (f32, f32),
+    pub bounds: (f32, f32),
+    pub size: f32,
+    pub color: [f32; 4],
+}
+
+/// Everything a frame of UI draws, see `Ui::finish_frame`
+#[derive(Debug, Clone, Default)]
+pub struct UiDrawList {
+    pub vertices: Vec<UiVertex>,
+    pub indices: Vec<u32>,
+    pub batches: Vec<UiBatch>,
+    pub texts: Vec<UiText>,
+}
+
+/// Immediate mode UI: widgets are drawn and checked for input by calling their function
+/// every frame they're shown. Positions and sizes are logical pixels, see `Rect`.
+pub struct Ui {
+    style: Style,
+    scale_factor: f32,
+    // physical
+    screen_size: (u32, u32),
+
+    // physical, None while the cursor is grabbed or outside the window
+    cursor: Option<(f32, f32)>,
+    mouse_down: bool,
+    mouse_pressed: bool,
+    mouse_released: bool,
+    scroll: f32,
+    typed: String,
+    keys: Vec<UiKey>,
+
+    // the topmost widget under the cursor last frame, the only one that counts as hovered
+    hovered: Option<UiId>,
+    next_hovered: Option<UiId>,
+    // the widget being clicked or dragged
+    active: Option<UiId>,
+    focused: Option<UiId>,
+    // char index of the caret in the focused text field
+    caret: usize,
+    // widgets that can take focus this frame, in order, for tabbing through them
+    focusable: Vec<UiId>,
+    scroll_offsets: HashMap<UiId, f32>,
+
+    id_stack: Vec<u64>,
+    clip_stack: Vec<Rect>,
+    draw_list: UiDrawList,
+}
+
+impl Ui {
+    pub fn new(scale_factor: f32) -> Self {
+        Self {
+            style: Style::default(),
+            scale_factor,
+            screen_size: (1, 1),
+
+            cursor: None,
+            mouse_down: false,
+            mouse_pressed: false,
+            mouse_released: false,
+            scroll: 0.0,
+            typed: String::new(),
+            keys: Vec::new(),
+
+            hovered: None,
+            next_hovered: None,
+            active: None,
+            focused: None,
+            caret: 0,
+            focusable: Vec::new(),
+            scroll_offsets: HashMap::new(),
+
+            id_stack: Vec::new(),
+            clip_stack: Vec::new(),
+            draw_list: UiDrawList::default(),
+        }
+    }
+
+    // input, in between frames
+
+    /// Where the cursor is in physical pixels, `None` once it's left the window or been grabbed
+    pub fn mouse_moved(&mut self, position: Option<(f32, f32)>) {
+        self.cursor = position;
+    }
+
+    /// The left mouse button
+    pub fn mouse_button(&mut self, pressed: bool) {
+        if pressed {
+            self.mouse_pressed = true;
+        } else if self.mouse_down {
+            self.mouse_released = true;
+        }
+
+        self.mouse_down = pressed;
+    }
+
+    /// Lines scrolled, positive being up
+    pub fn mouse_scrolled(&mut self, lines: f32) {
+        self.scroll += lines;
+    }
+
+    pub fn char_typed(&mut self, c: char) {
+        if !c.is_control() {
+            self.typed.push(c);
+        }
+    }
+
+    pub fn key_pressed(&mut self, key: UiKey) {
+        self.keys.push(key);
+    }
+
+    /// Widgets are laid out in logical pixels, this many physical ones each
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        self.scale_factor = scale_factor;
+    }
+
+    // frames
+
+    /// Starts a frame drawn on a screen `width` by `height` physical pixels
+    pub fn begin_frame(&mut self, width: u32, height: u32) {
+        self.screen_size = (width, height);
+
+        // before the widgets of last frame are forgotten
+        if self.key_just_pressed(UiKey::Tab) {
+            self.focus_next();
+        }
+
+        self.hovered = self.next_hovered.take();
+        self.focusable.clear();
+        self.id_stack.clear();
+        self.clip_stack.clear();
+        self.draw_list = UiDrawList::default();
+    }
+
+    /// Ends the frame, handing over what it drew. Input that came in since the last one is used up.
+    pub fn finish_frame(&mut self) -> UiDrawList {
+        // clicking on nothing takes focus away
+        if self.mouse_pressed && self.hovered.is_none() {
+            self.focused = None;
+        }
+
+        if !self.mouse_down {
+            self.active = None;
+        }
+
+        self.mouse_pressed = false;
+        self.mouse_released = false;
+        self.scroll = 0.0;
+        self.typed.clear();
+        self.keys.clear();
+
+        std::mem::take(&mut self.draw_list)
+    }
+
+    // ids
+
+    /// Makes ids of widgets made before `pop_id` unique to `name`, for lists of the same widgets
+    pub fn push_id(&mut self, name: impl Hash) {
+        let id = self.make_id(name);
+        self.id_stack.push(id.0);
+    }
+
+    pub fn pop_id(&mut self) {
+        self.id_stack.pop();
+    }
+
+    pub fn make_id(&self, name: impl Hash) -> UiId {
+        let mut hasher = DefaultHasher::new();
+        self.id_stack.hash(&mut hasher);
+        name.hash(&mut hasher);
+
+        UiId(hasher.finish())
+    }
+
+    pub fn focus(&mut self, id: UiId) {
+        if self.focused != Some(id) {
+            self.focused = Some(id);
+            self.caret = usize::MAX;
+        }
+    }
+
+    /// For after the focused text field's text was replaced from outside, like by history or completion
+    pub fn move_caret_to_end(&mut self) {
+        self.caret = usize::MAX;
+    }
+
+    // drawing
+
+    pub fn rect(&mut self, rect: Rect, color: [f32; 4]) {
+        self.quad(rect, ImageId::default(), [0.0, 0.0, 1.0, 1.0], color);
+    }
+
+    /// Draws `image` tinted by `color`, `uv` being the left, top, right and bottom of the part to show
+    pub fn image(&mut self, rect: Rect, image: ImageId, uv: [f32; 4], color: [f32; 4]) {
+        self.quad(rect, image, uv, color);
+    }
+
+    /// A rect that also stops the mouse reaching whatever's under it
+    pub fn panel(&mut self, name: impl Hash, rect: Rect) {
+        let id = self.make_id(name);
+        self.hit_test(id, rect);
+        self.rect(rect, self.style.panel_color);
+    }
+
+    /// One line of text with its top left at `x`, `y`
+    pub fn text(&mut self, x: f32, y: f32, text: &str, size: f32, color: [f32; 4]) {
+        let scale = self.scale_factor;
+        let mut bounds = Rect::new(x, y, self.get_screen_size().0 - x, size * 1.25);
+
+        // text is only drawn if it fits its clip vertically, glyph rendering can't cut it in half
+        if let Some(clip) = self.clip_stack.last() {
+            if y < clip.y || y + size > clip.get_bottom() + 0.5 {
+                return;
+            }
+
+            bounds.width = (clip.get_right() - x).max(0.0);
+        }
+
+        self.draw_list.texts.push(UiText {
+            text: text.to_string(),
+            position: (x * scale, y * scale),
+            bounds: (bounds.width * scale, bounds.height * scale),
+            size: size * scale,
+            color,
+        });
+    }
+
+    /// Text in the style's size and colour placed inside `rect` at `anchor`
+    pub fn label(&mut self, rect: Rect, text: &str, anchor: Anchor) {
+        let size = self.style.text_size;
+        let placed = rect.anchored(anchor, text_width(text, size), size);
+
+        self.text(placed.x, placed.y, text, size, self.style.text_color);
+    }
+
+    // widgets
+
+    pub fn button(&mut self, name: impl Hash, rect: Rect, text: &str) -> Response {
+        let id = self.make_id(name);
+        let mut response = self.interact(id, rect, true);
+
+        response.clicked |= self.is_focused(id) && self.key_just_pressed(UiKey::Enter);
+
+        let color = self.get_widget_color(id, response.hovered);
+        self.rect(rect, color);
+        self.draw_focus(id, rect);
+        self.label(rect, text, Anchor::Center);
+
+        response
+    }
+
+    /// A box ticked when `value` is, with `text` next to it. Clicking anywhere on it flips `value`.
+    pub fn checkbox(&mut self, name: impl Hash, rect: Rect, text: &str, value: &mut bool) -> Response {
+        let id = self.make_id(name);
+        let mut response = self.interact(id, rect, true);
+
+        if response.clicked || (self.is_focused(id) && self.key_just_pressed(UiKey::Enter)) {
+            *value = !*value;
+            response.changed = true;
+        }
+
+        let color = self.get_widget_color(id, response.hovered);
+        let check = Rect::new(rect.x, rect.y, rect.height, rect.height);
+
+        self.rect(check, color);
+        self.draw_focus(id, check);
+
+        if *value {
+            let tick = check.shrink(rect.height * 0.25);
+            self.rect(tick, self.style.accent_color);
+        }
+
+        let text_area = Rect::new(rect.x + rect.height + self.style.padding, rect.y, rect.width - rect.height - self.style.padding, rect.height);
+        self.label(text_area, text, Anchor::Left);
+
+        response
+    }
+
+    /// Drags `value` within `range`, with `text` written over it. Left and right nudge it while focused.
+    pub fn slider(&mut self, name: impl Hash, rect: Rect, text: &str, value: &mut f32, range: RangeInclusive<f32>) -> Response {
+        let id = self.make_id(name);
+        let mut response = self.interact(id, rect, true);
+
+        let (min, max) = (*range.start(), *range.end());
+        let old_value = *value;
+
+        if self.active == Some(id) {
+            if let Some((x, _)) = self.get_cursor() {
+                let fraction = ((x - rect.x) / rect.width.max(1.0)).clamp(0.0, 1.0);
+                *value = min + (max - min) * fraction;
+            }
+        }
+
+        if self.is_focused(id) {
+            let step = (max - min) * 0.05;
+
+            if self.key_just_pressed(UiKey::Left) {
+                *value = (*value - step).max(min);
+            }
+
+            if self.key_just_pressed(UiKey::Right) {
+                *value = (*value + step).min(max);
+            }
+        }
+
+        response.changed = (*value - old_value).abs() > f32::EPSILON;
+
+        let color = self.get_widget_color(id, response.hovered);
+        let fraction = if max > min { ((*value - min) / (max - min)).clamp(0.0, 1.0) } else { 0.0 };
+
+        self.rect(rect, color);
+        self.rect(Rect::new(rect.x, rect.y, rect.width * fraction, rect.height), self.style.accent_color);
+        self.draw_focus(id, rect);
+        self.label(rect, text, Anchor::Center);
+
+        response
+    }
+
+    /// One line of editable text. Clicking focuses it, then typing edits `text` at the caret.
+    pub fn text_field(&mut self, name: impl Hash, rect: Rect, text: &mut String) -> Response {
+        let id = self.make_id(name);
+        let mut response = self.interact(id, rect, true);
+
+        if self.is_focused(id) {
+            let mut chars: Vec<char> = text.chars().collect();
+            self.caret = self.caret.min(chars.len());
+
+            for c in self.typed.chars() {
+                chars.insert(self.caret, c);
+                self.caret += 1;
+            }
+
+            for key in self.keys.clone() {
+                match key {
+                    UiKey::Backspace if self.caret > 0 => {
+                        self.caret -= 1;
+                        chars.remove(self.caret);
+                    },
+                    UiKey::Delete if self.caret < chars.len() => { chars.remove(self.caret); },
+                    UiKey::Left => self.caret = self.caret.saturating_sub(1),
+                    UiKey::Right => self.caret = (self.caret + 1).min(chars.len()),
+                    UiKey::Home => self.caret = 0,
+                    UiKey::End => self.caret = chars.len(),
+                    UiKey::Enter => response.submitted = true,
+                    UiKey::Escape => self.focused = None,
+                    _ => { }
+                }
+            }
+
+            let edited: String = chars.into_iter().collect();
+
+            if edited != *text {
+                *text = edited;
+                response.changed = true;
+            }
+        }
+
+        let color = if self.is_focused(id) { self.style.active_color } else { self.get_widget_color(id, response.hovered) };
+        self.rect(rect, color);
+        self.draw_focus(id, rect);
+
+        // long text scrolls so the caret stays visible
+        let size = self.style.text_size;
+        let inner = rect.shrink(self.style.padding);
+        let fits = (inner.width / (size * CHAR_ADVANCE)).floor().max(1.0) as usize;
+        let caret = if self.is_focused(id) { self.caret.min(text.chars().count()) } else { 0 };
+        let start = (caret + 1).saturating_sub(fits);
+
+        let shown: String = text.chars().skip(start).take(fits).collect();
+        let line = inner.anchored(Anchor::Left, inner.width, size);
+
+        self.text(line.x, line.y, &shown, size, self.style.text_color);
+
+        if self.is_focused(id) {
+            let caret_x = line.x + text_width_chars(caret - start, size);
+            self.rect(Rect::new(caret_x, line.y, 2.0, size), self.style.accent_color);
+        }
+
+        response
+    }
+
+    /// A list of `count` items `item_height` tall, scrolled with the mouse wheel.
+    /// `item` draws the ones that are visible, each inside its own id and clipped to the list.
+    pub fn scroll_list(&mut self, name: impl Hash, rect: Rect, item_height: f32, count: usize, mut item: impl FnMut(&mut Ui, usize, Rect)) {
+        let id = self.make_id(name);
+
+        // the list itself is under its items
+        self.hit_test(id, rect);
+        self.rect(rect, self.style.panel_color);
+
+        let content_height = item_height * count as f32;
+        let max_offset = (content_height - rect.height).max(0.0);
+        let mut offset = self.scroll_offsets.get(&id).copied().unwrap_or(0.0);
+
+        let under_cursor = self.get_cursor().is_some_and(|(x, y)| rect.contains(x, y));
+
+        if under_cursor {
+            offset -= self.scroll * item_height * SCROLL_LINES;
+        }
+
+        offset = offset.max(0.0).min(max_offset);
+        self.scroll_offsets.insert(id, offset);
+
+        let first = (offset / item_height.max(1.0)).floor() as usize;
+        let last = (((offset + rect.height) / item_height.max(1.0)).ceil() as usize).min(count);
+
+        self.push_clip(rect);
+        self.id_stack.push(id.0);
+
+        for index in first..last {
+            let item_rect = Rect::new(rect.x, rect.y + index as f32 * item_height - offset, rect.width, item_height);
+
+            self.push_id(index);
+            item(self, index, item_rect);
+            self.pop_id();
+        }
+
+        self.id_stack.pop();
+        self.pop_clip();
+
+        // a scroll bar when not everything fits
+        if max_offset > 0.0 {
+            let bar_height = (rect.height * rect.height / content_height).max(12.0);
+            let bar_y = rect.y + (rect.height - bar_height) * offset / max_offset;
+
+            self.rect(Rect::new(rect.get_right() - 4.0, bar_y, 4.0, bar_height), self.style.accent_color);
+        }
+    }
+
+    /// Only draws and hit tests inside `rect` until `pop_clip`
+    pub fn push_clip(&mut self, rect: Rect) {
+        let clip = match self.clip_stack.last() {
+            Some(outer) => outer.intersect(&rect).unwrap_or(Rect::new(rect.x, rect.y, 0.0, 0.0)),
+            None => rect
+        };
+
+        self.clip_stack.push(clip);
+    }
+
+    pub fn pop_clip(&mut self) {
+        self.clip_stack.pop();
+    }
+
+    // hovering, clicking and focus for a widget in `rect`
+    fn interact(&mut self, id: UiId, rect: Rect, focusable: bool) -> Response {
+        let hovered = self.hit_test(id, rect);
+
+        if focusable {
+            self.focusable.push(id);
+        }
+
+        if hovered && self.mouse_pressed {
+            self.active = Some(id);
+
+            if focusable {
+                self.focus(id);
+            }
+        }
+
+        Response {
+            hovered,
+            clicked: hovered && self.mouse_released && self.active == Some(id),
+            ..Response::default()
+        }
+    }
+
+    // whether `id` is the topmost widget under the cursor, as of last frame
+    fn hit_test(&mut self, id: UiId, rect: Rect) -> bool {
+        let visible = match self.clip_stack.last() {
+            Some(clip) => clip.intersect(&rect),
+            None => Some(rect)
+        };
+
+        let under_cursor = match (self.get_cursor(), visible) {
+            (Some((x, y)), Some(visible)) => visible.contains(x, y),
+            _ => false
+        };
+
+        if under_cursor {
+            self.next_hovered = Some(id);
+        }
+
+        under_cursor && self.hovered == Some(id)
+    }
+
+    fn focus_next(&mut self) {
+        // focusable is still last frame's
+        if self.focusable.is_empty() {
+            return;
+        }
+
+        let next = match self.focused.and_then(|id| self.focusable.iter().position(|other| *other == id)) {
+            Some(index) => (index + 1) % self.focusable.len(),
+            None => 0
+        };
+
+        let id = self.focusable[next];
+        self.focus(id);
+    }
+
+    fn get_widget_color(&self, id: UiId, hovered: bool) -> [f32; 4] {
+        if self.active == Some(id) {
+            self.style.active_color
+        } else if hovered {
+            self.style.hovered_color
+        } else {
+            self.style.widget_color
+        }
+    }
+
+    fn draw_focus(&mut self, id: UiId, rect: Rect) {
+        if self.is_focused(id) {
+            self.rect(Rect::new(rect.x, rect.get_bottom() - 2.0, rect.width, 2.0), self.style.accent_color);
+        }
+    }
+
+    fn quad(&mut self, rect: Rect, image: ImageId, uv: [f32; 4], color: [f32; 4]) {
+        let scale = self.scale_factor;
+        let clip = self.clip_stack.last().map(|clip| {
+            let x = (clip.x * scale).max(0.0);
+            let y = (clip.y * scale).max(0.0);
+
+            [
+                x.min(self.screen_size.0 as f32) as u32,
+                y.min(self.screen_size.1 as f32) as u32,
+                ((clip.get_right() * scale).min(self.screen_size.0 as f32) - x).max(0.0) as u32,
+                ((clip.get_bottom() * scale).min(self.screen_size.1 as f32) - y).max(0.0) as u32,
+            ]
+        });
+
+        let list = &mut self.draw_list;
+        let first = list.vertices.len() as u32;
+
+        let (left, top) = (rect.x * scale, rect.y * scale);
+        let (right, bottom) = (rect.get_right() * scale, rect.get_bottom() * scale);
+
+        list.vertices.extend_from_slice(&[
+            UiVertex { position: [left, top], uv: [uv[0], uv[1]], color },
+            UiVertex { position: [left, bottom], uv: [uv[0], uv[3]], color },
+            UiVertex { position: [right, bottom], uv: [uv[2], uv[3]], color },
+            UiVertex { position: [right, top], uv: [uv[2], uv[1]], color },
+        ]);
+
+        let start = list.indices.len() as u32;
+        list.indices.extend_from_slice(&[first, first + 1, first + 2, first, first + 2, first + 3]);
+        let end = list.indices.len() as u32;
+
+        match list.batches.last_mut() {
+            Some(batch) if batch.image == image && batch.clip == clip => batch.indices.end = end,
+            _ => list.batches.push(UiBatch { image, clip, indices: start..end })
+        }
+    }
+}
+
+// getters
+impl Ui {
+    pub fn get_style(&self) -> &Style { &self.style }
+
+    pub fn get_style_mut(&mut self) -> &mut Style { &mut self.style }
+
+    pub fn get_scale_factor(&self) -> f32 { self.scale_factor }
+
+    /// In logical pixels
+    pub fn get_screen_size(&self) -> (f32, f32) {
+        (self.screen_size.0 as f32 / self.scale_factor, self.screen_size.1 as f32 / self.scale_factor)
+    }
+
+    /// The whole screen, for anchoring things to
+    pub fn get_screen_rect(&self) -> Rect {
+        let (width, height) = self.get_screen_size();
+        Rect::new(0.0, 0.0, width, height)
+    }
+
+    /// In logical pixels
+    pub fn get_cursor(&self) -> Option<(f32, f32)> {
+        self.cursor.map(|(x, y)| (x / self.scale_factor, y / self.scale_factor))
+    }
+
+    /// Whether the cursor was over any widget or panel last frame, so clicks shouldn't go to the game
+    pub fn is_hovered(&self) -> bool { self.hovered.is_some() }
+
+    pub fn is_focused(&self, id: UiId) -> bool { self.focused == Some(id) }
+
+    /// Whether a widget like a text field has the keyboard
+    pub fn has_focus(&self) -> bool { self.focused.is_some() }
+
+    pub fn key_just_pressed(&self, key: UiKey) -> bool { self.keys.contains(&key) }
+}
+
+/// How wide `text` is at `size`, in the same pixels as `size`
+pub fn text_width(text: &str, size: f32) -> f32 {
+    text_width_chars(text.chars().count(), size)
+}
+
+fn text_width_chars(chars: usize, size: f32) -> f32 {
+    chars as f32 * size * CHAR_ADVANCE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Anchor, Layout, Rect, Response, Ui, UiKey};
+
+    // a frame with a button in the middle of an 800 by 600 screen
+    fn button_frame(ui: &mut Ui) -> bool {
+        ui.begin_frame(800, 600);
+        let clicked = ui.button("play", Rect::new(300.0, 250.0, 200.0, 50.0), "Play").clicked;
+        ui.finish_frame();
+
+        clicked
+    }
+
+    #[test]
+    fn buttons_click_on_release_over_them() {
+        // everything is twice as big in physical pixels
+        let mut ui = Ui::new(2.0);
+        ui.mouse_moved(Some((800.0, 550.0)));
+
+        // hovering takes a frame to register
+        assert!(!button_frame(&mut ui));
+
+        ui.mouse_button(true);
+        assert!(!button_frame(&mut ui));
+
+        ui.mouse_button(false);
+        assert!(button_frame(&mut ui));
+        assert!(!button_frame(&mut ui));
+
+        // pressing outside and releasing inside doesn't count
+        ui.mouse_moved(Some((10.0, 10.0)));
+        ui.mouse_button(true);
+        button_frame(&mut ui);
+        ui.mouse_moved(Some((800.0, 550.0)));
+        ui.mouse_button(false);
+        assert!(!button_frame(&mut ui));
+    }
+
+    fn field_frame(ui: &mut Ui, text: &mut String) -> Response {
+        ui.begin_frame(800, 600);
+        let response = ui.text_field("seed", Rect::new(0.0, 0.0, 200.0, 30.0), text);
+        ui.finish_frame();
+
+        response
+    }
+
+    #[test]
+    fn text_fields_edit_at_the_caret() {
+        let mut ui = Ui::new(1.0);
+        let mut text = String::from("sed");
+
+        // not focused yet
+        ui.char_typed('x');
+        assert!(!field_frame(&mut ui, &mut text).changed);
+
+        let id = ui.make_id("seed");
+        ui.focus(id);
+
+        ui.char_typed('d');
+        assert!(field_frame(&mut ui, &mut text).changed);
+        assert_eq!(text, "sedd");
+
+        ui.key_pressed(UiKey::Home);
+        ui.key_pressed(UiKey::Delete);
+        field_frame(&mut ui, &mut text);
+
+        ui.char_typed('S');
+        field_frame(&mut ui, &mut text);
+        assert_eq!(text, "Sedd");
+
+        ui.key_pressed(UiKey::Enter);
+        assert!(field_frame(&mut ui, &mut text).submitted);
+    }
+
+    fn two_fields_frame(ui: &mut Ui, name: &mut String, seed: &mut String) {
+        ui.begin_frame(800, 600);
+        ui.text_field("name", Rect::new(0.0, 0.0, 200.0, 30.0), name);
+        ui.text_field("seed", Rect::new(0.0, 40.0, 200.0, 30.0), seed);
+        ui.finish_frame();
+    }
+
+    #[test]
+    fn tab_moves_focus_between_fields() {
+        let mut ui = Ui::new(1.0);
+        let (mut name, mut seed) = (String::new(), String::new());
+        let (name_id, seed_id) = (ui.make_id("name"), ui.make_id("seed"));
+
+        two_fields_frame(&mut ui, &mut name, &mut seed);
+
+        ui.key_pressed(UiKey::Tab);
+        ui.char_typed('a');
+        two_fields_frame(&mut ui, &mut name, &mut seed);
+        assert!(ui.is_focused(name_id));
+
+        ui.key_pressed(UiKey::Tab);
+        ui.char_typed('b');
+        two_fields_frame(&mut ui, &mut name, &mut seed);
+        assert!(ui.is_focused(seed_id));
+
+        // and around to the first again
+        ui.key_pressed(UiKey::Tab);
+        two_fields_frame(&mut ui, &mut name, &mut seed);
+        assert!(ui.is_focused(name_id));
+
+        assert_eq!(name, "a");
+        assert_eq!(seed, "b");
+    }
+
+    #[test]
+    fn layouts_hand_out_rects_in_order() {
+        let screen = Rect::new(0.0, 0.0, 800.0, 600.0);
+        let menu = screen.anchored(Anchor::Center, 200.0, 100.0);
+
+        assert_eq!(menu, Rect::new(300.0, 250.0, 200.0, 100.0));
+
+        let mut column = Layout::column(menu, 10.0);
+        assert_eq!(column.next(40.0), Rect::new(300.0, 250.0, 200.0, 40.0));
+        assert_eq!(column.next(40.0), Rect::new(300.0, 300.0, 200.0, 40.0));
+        assert_eq!(column.remaining().height, 0.0);
+
+        let halves = menu.columns(2, 10.0);
+        assert_eq!(halves[1], Rect::new(405.0, 250.0, 95.0, 100.0));
+    }
+}
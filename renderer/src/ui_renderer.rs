@@ -0,0 +1,244 @@
+use std::{num::NonZeroU32, rc::Rc};
+
+use anyhow::{Result, Error, anyhow};
+
+use crate::{pipeline::{BlendMode, DepthMode, PipelineCache, PipelineDescriptor}, texture, ui::{ImageId, UiDrawList, UiVertex}, vertex::VertexLayout};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct UiUniform {
+    screen_size: [f32; 2],
+    // 1 when the target is sRGB, so the shader has to turn the UI's colours linear
+    linearize: f32,
+    padding: f32,
+}
+
+/// Draws what a frame of `Ui` put together
+pub struct UiRenderer {
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+
+    image_layout: Rc<wgpu::BindGroupLayout>,
+    sampler: wgpu::Sampler,
+    // bind groups for every `ImageId`, the first one being plain white
+    images: Vec<wgpu::BindGroup>,
+    white: wgpu::Texture,
+
+    // grown as needed, never shrunk
+    vertex_buffer: Option<(wgpu::Buffer, usize)>,
+    index_buffer: Option<(wgpu::Buffer, usize)>,
+}
+
+impl UiRenderer {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, pipelines: &mut PipelineCache) -> Self {
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("UI Uniform Buffer"),
+            size: std::mem::size_of::<UiUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &pipelines.get_bind_group_layout(device, &uniform_layout_entries()),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                }
+            ],
+            label: Some("ui_uniform_bind_group"),
+        });
+
+        let image_layout = pipelines.get_bind_group_layout(device, &texture::bind_group_layout_entries(wgpu::TextureViewDimension::D2, false));
+
+        let sampler = device.create_sampler(
+            &wgpu::SamplerDescriptor {
+                label: Some("UI Sampler"),
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            }
+        );
+
+        // rects are drawn as a white image tinted their colour
+        let white = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("UI White Texture"),
+            size: wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        });
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &white,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All
+            },
+            &[255; 4],
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(4),
+                rows_per_image: NonZeroU32::new(1),
+            },
+            wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+        );
+
+        let mut renderer = Self {
+            uniform_buffer,
+            uniform_bind_group,
+
+            image_layout,
+            sampler,
+            images: Vec::new(),
+            white,
+
+            vertex_buffer: None,
+            index_buffer: None,
+        };
+
+        let white_view = renderer.white.create_view(&wgpu::TextureViewDescriptor::default());
+        renderer.add_image(device, &white_view, None);
+
+        renderer
+    }
+
+    /// Makes `view` drawable with `Ui::image`, sampled linearly unless another `sampler` is given
+    pub fn add_image(&mut self, device: &wgpu::Device, view: &wgpu::TextureView, sampler: Option<&wgpu::Sampler>) -> ImageId {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.image_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler.unwrap_or(&self.sampler)),
+                }
+            ],
+            label: Some("ui_image_bind_group"),
+        });
+
+        self.images.push(bind_group);
+        ImageId(self.images.len() - 1)
+    }
+
+    /// Records a pass drawing `draw_list`'s quads over `target`, which is `size` big. Text is up to the glyph brush.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, pipeline: &wgpu::RenderPipeline, encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView, format: wgpu::TextureFormat, size: (u32, u32), draw_list: &UiDrawList) -> Result<(), Error> {
+        if draw_list.indices.is_empty() {
+            return Ok(());
+        }
+
+        let uniform = UiUniform {
+            screen_size: [size.0 as f32, size.1 as f32],
+            linearize: if format.describe().srgb { 1.0 } else { 0.0 },
+            padding: 0.0,
+        };
+
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+
+        let vertex_buffer = upload(device, queue, &mut self.vertex_buffer, bytemuck::cast_slice(&draw_list.vertices),
+            wgpu::BufferUsages::VERTEX, "UI Vertex Buffer");
+        let index_buffer = upload(device, queue, &mut self.index_buffer, bytemuck::cast_slice(&draw_list.indices),
+            wgpu::BufferUsages::INDEX, "UI Index Buffer");
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("UI Pass"),
+            color_attachments: &[
+                wgpu::RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true
+                    }
+                }
+            ],
+            depth_stencil_attachment: None
+        });
+
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+
+        for batch in &draw_list.batches {
+            let [x, y, width, height] = batch.clip.unwrap_or([0, 0, size.0, size.1]);
+
+            // clipped away completely
+            if width == 0 || height == 0 {
+                continue;
+            }
+
+            let image = match self.images.get(batch.image.0) {
+                Some(image) => image,
+                None => return Err(anyhow!(format!("the UI has no image {}", batch.image.0)))
+            };
+
+            render_pass.set_scissor_rect(x, y, width, height);
+            render_pass.set_bind_group(1, image, &[]);
+            render_pass.draw_indexed(batch.indices.clone(), 0, 0..1);
+        }
+
+        Ok(())
+    }
+}
+
+/// Alpha blended over whatever's there, drawing into `format`
+pub fn descriptor(format: wgpu::TextureFormat) -> PipelineDescriptor {
+    PipelineDescriptor::new("UI Pipeline", "ui.wgsl")
+        .with_vertex_layout(UiVertex::desc())
+        .with_bind_group(uniform_layout_entries())
+        .with_bind_group(texture::bind_group_layout_entries(wgpu::TextureViewDimension::D2, false))
+        .with_color_format(format)
+        .with_blend(BlendMode::Alpha)
+        .with_depth(DepthMode::None)
+        .with_cull_mode(None)
+}
+
+fn uniform_layout_entries() -> Vec<wgpu::BindGroupLayoutEntry> {
+    vec![
+        wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }
+    ]
+}
+
+// writes `contents` into `buffer`, replacing it with a bigger one first if it doesn't fit
+fn upload<'a>(device: &wgpu::Device, queue: &wgpu::Queue, buffer: &'a mut Option<(wgpu::Buffer, usize)>,
+    contents: &[u8], usage: wgpu::BufferUsages, label: &str) -> &'a wgpu::Buffer {
+    let fits = matches!(buffer, Some((_, capacity)) if *capacity >= contents.len());
+
+    if !fits {
+        let capacity = contents.len().next_power_of_two().max(1024);
+
+        *buffer = Some((device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: capacity as wgpu::BufferAddress,
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }), capacity));
+    }
+
+    let (buffer, _) = buffer.as_ref().expect("the buffer was just made");
+    queue.write_buffer(buffer, 0, contents);
+
+    buffer
+}
@@ -0,0 +1,50 @@
+// Shared by every post processing pass, the pass's own fragment shader is appended to this
+
+[[block]]
+struct PostParams {
+    texel_size: vec2<f32>;
+    exposure: f32;
+    gamma: f32;
+    vignette: f32;
+    encode_srgb: f32;
+    tonemapping: f32;
+    padding: f32;
+    tint: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var t_input: texture_2d<f32>;
+[[group(0), binding(1)]]
+var s_input: sampler;
+[[group(0), binding(2)]]
+var<uniform> params: PostParams;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+};
+
+// one triangle big enough to cover the whole screen
+[[stage(vertex)]]
+fn main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let x = f32(i32(vertex_index & 1u) * 4 - 1);
+    let y = f32(i32(vertex_index >> 1u) * 4 - 1);
+
+    var out: VertexOutput;
+    out.uv = vec2<f32>(x * 0.5 + 0.5, 0.5 - y * 0.5);
+    out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    return out;
+}
+
+fn sample_input(uv: vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(t_input, s_input, uv, 0.0).rgb;
+}
+
+// passes work in linear colour, the last one encodes it when the target doesn't do that itself
+fn finish(color: vec3<f32>) -> vec4<f32> {
+    if (params.encode_srgb > 0.5) {
+        return vec4<f32>(pow(clamp(color, vec3<f32>(0.0, 0.0, 0.0), vec3<f32>(1.0, 1.0, 1.0)), vec3<f32>(1.0 / 2.2, 1.0 / 2.2, 1.0 / 2.2)), 1.0);
+    }
+
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,52 @@
+
+// Fast approximate anti-aliasing, blurs along the edges it finds
+
+let FXAA_REDUCE_MIN: f32 = 0.0078125;
+let FXAA_REDUCE_MUL: f32 = 0.125;
+let FXAA_SPAN_MAX: f32 = 8.0;
+
+// edges are found in perceived brightness, not linear light
+fn luma(color: vec3<f32>) -> f32 {
+    return sqrt(dot(color, vec3<f32>(0.299, 0.587, 0.114)));
+}
+
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let texel = params.texel_size;
+
+    let color_middle = sample_input(in.uv);
+
+    let luma_nw = luma(sample_input(in.uv + vec2<f32>(-1.0, -1.0) * texel));
+    let luma_ne = luma(sample_input(in.uv + vec2<f32>(1.0, -1.0) * texel));
+    let luma_sw = luma(sample_input(in.uv + vec2<f32>(-1.0, 1.0) * texel));
+    let luma_se = luma(sample_input(in.uv + vec2<f32>(1.0, 1.0) * texel));
+    let luma_middle = luma(color_middle);
+
+    let luma_min = min(luma_middle, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_middle, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    var direction: vec2<f32> = vec2<f32>(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+
+    let direction_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * FXAA_REDUCE_MUL, FXAA_REDUCE_MIN);
+    let direction_scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + direction_reduce);
+
+    direction = clamp(direction * direction_scale, vec2<f32>(-FXAA_SPAN_MAX, -FXAA_SPAN_MAX), vec2<f32>(FXAA_SPAN_MAX, FXAA_SPAN_MAX)) * texel;
+
+    let color_a = 0.5 * (
+        sample_input(in.uv + direction * (1.0 / 3.0 - 0.5)) +
+        sample_input(in.uv + direction * (2.0 / 3.0 - 0.5)));
+    let color_b = color_a * 0.5 + 0.25 * (
+        sample_input(in.uv + direction * -0.5) +
+        sample_input(in.uv + direction * 0.5));
+
+    let luma_b = luma(color_b);
+
+    if (luma_b < luma_min || luma_b > luma_max) {
+        return finish(color_a);
+    }
+
+    return finish(color_b);
+}
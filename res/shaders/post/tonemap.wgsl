@@ -0,0 +1,21 @@
+
+// HDR to displayable colour, with exposure and gamma
+
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    var color: vec3<f32> = sample_input(in.uv) * params.exposure;
+
+    if (params.tonemapping > 1.5) {
+        // fitted ACES curve (Krzysztof Narkowicz)
+        color = clamp((color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14), vec3<f32>(0.0, 0.0, 0.0), vec3<f32>(1.0, 1.0, 1.0));
+    } elseif (params.tonemapping > 0.5) {
+        color = color / (color + vec3<f32>(1.0, 1.0, 1.0));
+    } else {
+        color = clamp(color, vec3<f32>(0.0, 0.0, 0.0), vec3<f32>(1.0, 1.0, 1.0));
+    }
+
+    // 2.2 is the screen's own gamma, anything else brightens or darkens the image
+    color = pow(color, vec3<f32>(2.2 / params.gamma, 2.2 / params.gamma, 2.2 / params.gamma));
+
+    return finish(color);
+}
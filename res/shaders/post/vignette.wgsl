@@ -0,0 +1,16 @@
+
+// Screen tint (like being underwater) and darkened corners
+
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    var color: vec3<f32> = sample_input(in.uv);
+
+    color = mix(color, color * params.tint.rgb, params.tint.a);
+
+    let distance_from_center = distance(in.uv, vec2<f32>(0.5, 0.5));
+    let darkening = clamp((distance_from_center - 0.3) / 0.5, 0.0, 1.0);
+
+    color = color * (1.0 - params.vignette * darkening * darkening);
+
+    return finish(color);
+}
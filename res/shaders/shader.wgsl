@@ -3,38 +3,202 @@
 [[block]]
 struct Camera {
     view_proj: mat4x4<f32>;
+    relative_view_proj: mat4x4<f32>;
+    chunk: vec4<i32>;
+    offset: vec4<f32>;
 };
 [[group(1), binding(0)]]
 var<uniform> camera: Camera;
 
+// indexed by the chunk slot in each vertex
+[[block]]
+struct ChunkOrigins {
+    origins: array<vec4<i32>>;
+};
+[[group(2), binding(0)]]
+var<storage, read> chunk_origins: ChunkOrigins;
+
+// see renderer::sky::EnvironmentUniform
+[[block]]
+struct Environment {
+    inv_relative_view_proj: mat4x4<f32>;
+    zenith_color: vec4<f32>;
+    horizon_color: vec4<f32>;
+    sun_direction: vec4<f32>;
+    fog: vec4<f32>;
+};
+[[group(3), binding(0)]]
+var<uniform> environment: Environment;
+
+// see renderer::shadow::ShadowUniform
+[[block]]
+struct Shadows {
+    cascades: array<mat4x4<f32>, 3>;
+    splits: vec4<f32>;
+    texel_sizes: vec4<f32>;
+    light_direction: vec4<f32>;
+    params: vec4<f32>;
+};
+[[group(3), binding(1)]]
+var<uniform> shadows: Shadows;
+
+// see common::vertex::ChunkVertex for the layout
 struct VertexInput {
-    [[location(0)]] position: vec3<f32>;
-    [[location(1)]] tex_coords: vec2<f32>;
+    [[location(0)]] data: vec2<u32>;
 };
 
 struct VertexOutput {
     [[builtin(position)]] clip_position: vec4<f32>;
     [[location(0)]] tex_coords: vec2<f32>;
+    [[location(1)]] shade: f32;
+    [[location(2), interpolate(flat)]] layer: i32;
+    [[location(3)]] fog: f32;
+    [[location(4)]] position: vec3<f32>;
+    [[location(5)]] normal: vec3<f32>;
+    [[location(6)]] view_depth: f32;
 };
 
+let CHUNK_SIZE: i32 = 16;
+
 [[stage(vertex)]]
 fn main(
     model: VertexInput,
 ) -> VertexOutput {
+    let packed = model.data.x;
+
+    let local = vec3<f32>(
+        f32(packed & 31u),
+        f32((packed >> 5u) & 31u),
+        f32((packed >> 10u) & 31u),
+    );
+    let face = (packed >> 15u) & 7u;
+    let uv = vec2<f32>(f32((packed >> 18u) & 1u), f32((packed >> 19u) & 1u));
+    let ao = f32((packed >> 20u) & 3u);
+    let light = f32((packed >> 22u) & 15u);
+    let texture_index = model.data.y & 65535u;
+    let chunk_pos = chunk_origins.origins[model.data.y >> 16u].xyz;
+
+    // blocks are centered on their position, the packed corners start at the block's minimum
+    let chunk_offset = (chunk_pos - camera.chunk.xyz) * vec3<i32>(CHUNK_SIZE, CHUNK_SIZE, CHUNK_SIZE);
+    let position = vec3<f32>(chunk_offset) + local - vec3<f32>(0.5, 0.5, 0.5) - camera.offset.xyz;
+
+    // top faces brightest, bottom darkest, sides in between
+    var face_shade: f32 = 0.8;
+    if (face == 2u) {
+        face_shade = 1.0;
+    } elseif (face == 3u) {
+        face_shade = 0.5;
+    } elseif (face == 4u || face == 5u) {
+        face_shade = 0.7;
+    }
+
+    // see common::block::BlockFace::get_normal
+    var normal: vec3<f32> = vec3<f32>(0.0, 0.0, 1.0);
+    if (face == 1u) {
+        normal = vec3<f32>(0.0, 0.0, -1.0);
+    } elseif (face == 2u) {
+        normal = vec3<f32>(0.0, 1.0, 0.0);
+    } elseif (face == 3u) {
+        normal = vec3<f32>(0.0, -1.0, 0.0);
+    } elseif (face == 4u) {
+        normal = vec3<f32>(-1.0, 0.0, 0.0);
+    } elseif (face == 5u) {
+        normal = vec3<f32>(1.0, 0.0, 0.0);
+    }
+
     var out: VertexOutput;
-    out.tex_coords = model.tex_coords;
-    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    out.tex_coords = uv;
+    out.layer = i32(texture_index);
+    out.shade = face_shade * (0.4 + 0.6 * ao / 3.0) * (light / 15.0) * environment.sun_direction.w;
+    // fades in linearly between the fog's start and end distance
+    out.fog = clamp((length(position) - environment.fog.x) / (environment.fog.y - environment.fog.x), 0.0, 1.0);
+    out.clip_position = camera.relative_view_proj * vec4<f32>(position, 1.0);
+    out.position = position;
+    out.normal = normal;
+    // w is the distance along the view direction with a perspective projection
+    out.view_depth = out.clip_position.w;
     return out;
 }
 
 // Fragment shader
 
 [[group(0), binding(0)]]
-var t_diffuse: texture_2d<f32>;
+var t_diffuse: texture_2d_array<f32>;
 [[group(0), binding(1)]]
 var s_diffuse: sampler;
 
+[[group(3), binding(2)]]
+var t_shadow: texture_depth_2d_array;
+[[group(3), binding(3)]]
+var s_shadow: sampler_comparison;
+
+// how much light is left in full shadow
+let SHADOW_DARKNESS: f32 = 0.6;
+
+// 1 where the light reaches, 0 in full shadow
+fn calc_shadow(position: vec3<f32>, normal: vec3<f32>, view_depth: f32) -> f32 {
+    let light = shadows.light_direction;
+
+    if (light.w <= 0.0 || view_depth > shadows.splits.z) {
+        return 1.0;
+    }
+
+    // faces turned away from the light shade themselves
+    if (dot(normal, light.xyz) <= 0.0) {
+        return 0.0;
+    }
+
+    var cascade: i32 = 0;
+    if (view_depth > shadows.splits.y) {
+        cascade = 2;
+    } elseif (view_depth > shadows.splits.x) {
+        cascade = 1;
+    }
+
+    // pushed off the surface a little so it doesn't shadow itself
+    let offset = normal * shadows.texel_sizes[cascade] * shadows.params.z;
+    let shadow_position = shadows.cascades[cascade] * vec4<f32>(position + offset, 1.0);
+    let uv = shadow_position.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5, 0.5);
+
+    let texel = shadows.params.x;
+    let radius = i32(shadows.params.y);
+
+    // percentage closer filtering, averages the comparisons around the position
+    var lit: f32 = 0.0;
+    var samples: f32 = 0.0;
+    var x: i32 = -radius;
+
+    loop {
+        if (x > radius) {
+            break;
+        }
+
+        var y: i32 = -radius;
+
+        loop {
+            if (y > radius) {
+                break;
+            }
+
+            let sample_uv = uv + vec2<f32>(f32(x), f32(y)) * texel;
+            lit = lit + textureSampleCompareLevel(t_shadow, s_shadow, sample_uv, cascade, shadow_position.z);
+            samples = samples + 1.0;
+
+            y = y + 1;
+        }
+
+        x = x + 1;
+    }
+
+    return lit / samples;
+}
+
 [[stage(fragment)]]
 fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
-}
\ No newline at end of file
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.layer);
+    let shadow = mix(SHADOW_DARKNESS, 1.0, calc_shadow(in.position, normalize(in.normal), in.view_depth));
+    let light = mix(1.0, shadow, shadows.light_direction.w);
+
+    let lit = mix(color.rgb * in.shade * light, environment.horizon_color.rgb, in.fog);
+    return vec4<f32>(lit, color.a);
+}
@@ -0,0 +1,50 @@
+// Draws chunk meshes into a shadow map, positioned the same way as shader.wgsl does
+
+[[block]]
+struct Cascade {
+    light_view_proj: mat4x4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> cascade: Cascade;
+
+[[block]]
+struct Camera {
+    view_proj: mat4x4<f32>;
+    relative_view_proj: mat4x4<f32>;
+    chunk: vec4<i32>;
+    offset: vec4<f32>;
+};
+[[group(1), binding(0)]]
+var<uniform> camera: Camera;
+
+[[block]]
+struct ChunkOrigins {
+    origins: array<vec4<i32>>;
+};
+[[group(2), binding(0)]]
+var<storage, read> chunk_origins: ChunkOrigins;
+
+struct VertexInput {
+    [[location(0)]] data: vec2<u32>;
+};
+
+let CHUNK_SIZE: i32 = 16;
+
+[[stage(vertex)]]
+fn main(
+    model: VertexInput,
+) -> [[builtin(position)]] vec4<f32> {
+    let packed = model.data.x;
+
+    let local = vec3<f32>(
+        f32(packed & 31u),
+        f32((packed >> 5u) & 31u),
+        f32((packed >> 10u) & 31u),
+    );
+    let chunk_pos = chunk_origins.origins[model.data.y >> 16u].xyz;
+
+    let chunk_offset = (chunk_pos - camera.chunk.xyz) * vec3<i32>(CHUNK_SIZE, CHUNK_SIZE, CHUNK_SIZE);
+    let position = vec3<f32>(chunk_offset) + local - vec3<f32>(0.5, 0.5, 0.5) - camera.offset.xyz;
+
+    return cascade.light_view_proj * vec4<f32>(position, 1.0);
+}
@@ -0,0 +1,62 @@
+// Vertex shader
+
+[[block]]
+struct Environment {
+    inv_relative_view_proj: mat4x4<f32>;
+    zenith_color: vec4<f32>;
+    horizon_color: vec4<f32>;
+    sun_direction: vec4<f32>;
+    fog: vec4<f32>;
+};
+[[group(0), binding(0)]]
+var<uniform> environment: Environment;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] screen_position: vec2<f32>;
+};
+
+// one triangle big enough to cover the whole screen
+[[stage(vertex)]]
+fn main([[builtin(vertex_index)]] vertex_index: u32) -> VertexOutput {
+    let x = f32(i32(vertex_index & 1u) * 4 - 1);
+    let y = f32(i32(vertex_index >> 1u) * 4 - 1);
+
+    var out: VertexOutput;
+    out.screen_position = vec2<f32>(x, y);
+    out.clip_position = vec4<f32>(x, y, 1.0, 1.0);
+    return out;
+}
+
+// Fragment shader
+
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let far = environment.inv_relative_view_proj * vec4<f32>(in.screen_position, 1.0, 1.0);
+    let direction = normalize(far.xyz / far.w);
+
+    let height = clamp(direction.y, 0.0, 1.0);
+    var color = mix(environment.horizon_color.rgb, environment.zenith_color.rgb, pow(height, 0.5));
+
+    // below the horizon fades to a darker horizon colour
+    if (direction.y < 0.0) {
+        color = environment.horizon_color.rgb * (1.0 + direction.y * 0.5);
+    }
+
+    let sun = environment.sun_direction.xyz;
+    let sun_amount = dot(direction, sun);
+    let moon_amount = dot(direction, -sun);
+
+    if (sun_amount > 0.9995) {
+        color = vec3<f32>(1.0, 0.95, 0.8);
+    } else {
+        // glow around the sun
+        color = color + vec3<f32>(1.0, 0.8, 0.5) * pow(max(sun_amount, 0.0), 64.0) * 0.4;
+    }
+
+    if (moon_amount > 0.9997) {
+        color = vec3<f32>(0.85, 0.85, 0.9);
+    }
+
+    return vec4<f32>(color, 1.0);
+}
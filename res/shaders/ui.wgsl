@@ -0,0 +1,55 @@
+// Vertex shader
+
+[[block]]
+struct UiParams {
+    screen_size: vec2<f32>;
+    // 1 when the target stores linear colour, UI colours are given in sRGB
+    linearize: f32;
+    padding: f32;
+};
+[[group(0), binding(0)]]
+var<uniform> params: UiParams;
+
+struct VertexInput {
+    [[location(0)]] position: vec2<f32>;
+    [[location(1)]] uv: vec2<f32>;
+    [[location(2)]] color: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] uv: vec2<f32>;
+    [[location(1)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn main(model: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    // positions are in pixels from the top left
+    out.clip_position = vec4<f32>(
+        model.position.x / params.screen_size.x * 2.0 - 1.0,
+        1.0 - model.position.y / params.screen_size.y * 2.0,
+        0.0,
+        1.0
+    );
+    out.uv = model.uv;
+    out.color = model.color;
+
+    if (params.linearize > 0.5) {
+        out.color = vec4<f32>(pow(model.color.rgb, vec3<f32>(2.2, 2.2, 2.2)), model.color.a);
+    }
+
+    return out;
+}
+
+// Fragment shader
+
+[[group(1), binding(0)]]
+var t_image: texture_2d<f32>;
+[[group(1), binding(1)]]
+var s_image: sampler;
+
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return textureSample(t_image, s_image, in.uv) * in.color;
+}
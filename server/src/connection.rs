@@ -0,0 +1,109 @@
+use std::collections::HashSet;
+
+use cgmath::Vector3;
+
+use net::{protocol::Packet, transport::Transport};
+use world::entity::EntityId;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionState {
+    Handshaking,
+    Playing,
+    /// Dropped at the end of the step, with why
+    Closed(String),
+}
+
+/// A connected client, as the server sees it
+pub struct Connection {
+    id: u64,
+    transport: Box<dyn Transport>,
+    state: ConnectionState,
+
+    name: String,
+    view_distance: usize,
+
+    pub(crate) entity_id: Option<EntityId>,
+    pub(crate) position: Vector3<f32>,
+    // moved since everyone else was last told
+    pub(crate) has_moved: bool,
+
+    pub(crate) sent_chunks: HashSet<Vector3<i32>>,
+}
+
+impl Connection {
+    pub fn new(id: u64, transport: Box<dyn Transport>) -> Self {
+        Self {
+            id,
+            transport,
+            state: ConnectionState::Handshaking,
+
+            name: String::new(),
+            view_distance: 0,
+
+            entity_id: None,
+            position: Vector3::new(0., 0., 0.),
+            has_moved: false,
+
+            sent_chunks: HashSet::new(),
+        }
+    }
+
+    pub fn get_id(&self) -> u64 { self.id }
+
+    pub fn get_state(&self) -> &ConnectionState { &self.state }
+
+    pub fn is_playing(&self) -> bool { self.state == ConnectionState::Playing }
+
+    pub fn is_closed(&self) -> bool { matches!(self.state, ConnectionState::Closed(_)) }
+
+    pub fn get_name(&self) -> &str { &self.name }
+
+    pub fn get_view_distance(&self) -> usize { self.view_distance }
+
+    pub fn get_position(&self) -> Vector3<f32> { self.position }
+
+    pub(crate) fn set_view_distance(&mut self, view_distance: usize) {
+        self.view_distance = view_distance;
+    }
+
+    pub(crate) fn start_playing(&mut self, name: String, view_distance: usize, entity_id: EntityId, position: Vector3<f32>) {
+        self.state = ConnectionState::Playing;
+
+        self.name = name;
+        self.view_distance = view_distance;
+        self.entity_id = Some(entity_id);
+        self.position = position;
+    }
+
+    /// Sending to a closed connection does nothing, a failed send closes it
+    pub fn send(&mut self, packet: &Packet) {
+        if self.is_closed() {
+            return;
+        }
+
+        if let Err(err) = self.transport.send(packet) {
+            self.state = ConnectionState::Closed(err.to_string());
+        }
+    }
+
+    pub(crate) fn receive(&mut self) -> Option<Packet> {
+        if self.is_closed() {
+            return None;
+        }
+
+        match self.transport.receive() {
+            Ok(packet) => packet,
+            Err(err) => {
+                self.state = ConnectionState::Closed(err.to_string());
+                None
+            }
+        }
+    }
+
+    /// Tells the client why before closing
+    pub fn kick(&mut self, reason: &str) {
+        self.send(&Packet::Disconnect { reason: reason.to_string() });
+
+        self.state = ConnectionState::Closed(reason.to_string());
+    }
+}
@@ -0,0 +1,610 @@
+use std::{collections::BTreeMap, net::{SocketAddr, ToSocketAddrs}, path::PathBuf, sync::{Arc, atomic::{AtomicBool, Ordering}}, thread, time::{Duration, Instant}};
+
+use anyhow::{Result, Error};
+use cgmath::{InnerSpace, Vector3};
+
+use common::registry::Registry;
+use net::{loopback::{self, LoopbackTransport}, protocol::{BlockPalette, Packet, PROTOCOL_VERSION}, tcp::TcpServer, transport::Transport};
+use world::{World, blocks, chunk, entity::{self, Entity, EntityBody}, storage::WorldStorage, tick};
+
+use self::connection::Connection;
+
+pub mod connection;
+
+pub struct ServerConfig {
+    pub seed: u32,
+    pub render_distance: usize,
+    /// Where chunks are saved. Without one, the world is gone when the server stops.
+    pub world_directory: Option<PathBuf>,
+    /// Ticks between saving every loaded chunk, 0 to only save when stopping
+    pub autosave_interval: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            seed: rand::random(),
+            render_distance: 5,
+            world_directory: None,
+            autosave_interval: tick::TICKS_PER_SECOND as u64 * 60 * 5,
+        }
+    }
+}
+
+/// Hosts a world without a window or GPU
+pub struct Server {
+    config: ServerConfig,
+    world: World,
+    palette: BlockPalette,
+
+    listener: Option<TcpServer>,
+    // ordered so everyone hears about players in the order they joined
+    connections: BTreeMap<u64, Connection>,
+    next_connection_id: u64,
+
+    spawn_point: Vector3<f32>,
+    last_autosave: u64,
+    last_time_sync: u64,
+}
+
+impl Server {
+    /// Same rate the client simulates at, so entities move the same on both
+    pub const STEP: f32 = 1.0 / 60.0;
+
+    /// How many chunks each client gets sent per step, so joining doesn't stall everyone else
+    pub const CHUNKS_PER_STEP: usize = 8;
+
+    /// Ticks between reminding everyone what time it is. Clients keep their own time
+    /// going in between, this just stops them drifting.
+    pub const TIME_SYNC_INTERVAL: u64 = tick::TICKS_PER_SECOND as u64 * 10;
+
+    /// Furthest a player can move between two moves it sends, one step apart. Enough for the
+    /// fastest movement speed setting while falling at terminal velocity, with some to spare.
+    pub const MAX_MOVE_DISTANCE: f32 = 2.0;
+
+    /// How far from a player the blocks it changes can be
+    pub const REACH_DISTANCE: f32 = 8.0;
+
+    pub fn new(config: ServerConfig) -> Result<Self, Error> {
+        blocks::register_blocks()?;
+
+        let storage = match &config.world_directory {
+            Some(directory) => WorldStorage::open(directory)?,
+            None => WorldStorage::in_memory()
+        };
+
+        let mut world = World::with_storage(config.seed, config.render_distance, storage);
+        world.set_build_meshes(false);
+        world.set_track_block_changes(true);
+
+        Ok(Self {
+            config,
+            world,
+            palette: BlockPalette::new(Registry::current().get_block_identifiers()),
+
+            listener: None,
+            connections: BTreeMap::new(),
+            next_connection_id: 1,
+
+            spawn_point: Vector3::unit_y() * 64.0,
+            last_autosave: 0,
+            last_time_sync: 0,
+        })
+    }
+
+    /// Starts accepting TCP connections. Returns the address actually bound, for when port 0 was asked for.
+    pub fn listen(&mut self, address: impl ToSocketAddrs) -> Result<SocketAddr, Error> {
+        let listener = TcpServer::bind(address)?;
+        let local_address = listener.get_local_address()?;
+
+        self.listener = Some(listener);
+
+        Ok(local_address)
+    }
+
+    /// An in-process connection, for a client running in the same process (single player)
+    pub fn connect_loopback(&mut self) -> LoopbackTransport {
+        let (client_end, server_end) = loopback::pair();
+
+        self.add_connection(Box::new(server_end));
+
+        client_end
+    }
+
+    pub fn add_connection(&mut self, transport: Box<dyn Transport>) -> u64 {
+        let id = self.next_connection_id;
+        self.next_connection_id += 1;
+
+        self.connections.insert(id, Connection::new(id, transport));
+
+        id
+    }
+
+    /// Advances the world by one fixed step
+    pub fn step(&mut self) -> Result<(), Error> {
+        self.accept_connections();
+        self.receive_packets();
+
+        // keep the area around spawn loaded while nobody is around
+        let centers: Vec<Vector3<f32>> = self.connections.values()
+            .filter(|connection| connection.is_playing())
+            .map(|connection| connection.get_position())
+            .collect();
+
+        let centers = if centers.is_empty() { vec![self.spawn_point] } else { centers };
+
+        self.world.load_chunks_around(&centers);
+        self.world.update(centers[0], Self::STEP);
+
+        self.send_replaced_chunks();
+        self.send_block_changes();
+        self.send_time();
+        self.send_player_positions();
+        self.stream_chunks();
+        self.drop_closed_connections();
+
+        let current_tick = self.world.get_current_tick();
+
+        if self.config.autosave_interval > 0 && current_tick - self.last_autosave >= self.config.autosave_interval {
+            self.last_autosave = current_tick;
+            self.world.save_all()?;
+        }
+
+        Ok(())
+    }
+
+    /// Steps the world in real time until `running` is cleared, then saves it
+    pub fn run(&mut self, running: Arc<AtomicBool>) -> Result<(), Error> {
+        let step = Duration::from_secs_f32(Self::STEP);
+        let mut next_step = Instant::now();
+
+        while running.load(Ordering::Relaxed) {
+            self.step()?;
+
+            next_step += step;
+
+            let now = Instant::now();
+
+            if next_step > now {
+                thread::sleep(next_step - now);
+            } else {
+                // too far behind to catch up, don't try to run all the missed steps at once
+                next_step = now;
+            }
+        }
+
+        self.shutdown()
+    }
+
+    /// How far around players chunks are loaded, and the most any of them can ask for.
+    /// Players further out than that are brought back in.
+    pub fn set_render_distance(&mut self, render_distance: usize) {
+        let render_distance = render_distance.max(1);
+
+        self.config.render_distance = render_distance;
+        self.world.set_render_distance(render_distance);
+
+        for connection in self.connections.values_mut() {
+            if connection.get_view_distance() > render_distance {
+                connection.set_view_distance(render_distance);
+            }
+        }
+    }
+
+    /// Moves a player somewhere it couldn't have walked to, telling its client
+    pub fn teleport_player(&mut self, id: u64, position: Vector3<f32>) {
+        self.move_player(id, position);
+
+        if let Some(connection) = self.connections.get_mut(&id) {
+            connection.send(&Packet::PlayerTeleport { position });
+        }
+    }
+
+    /// Kicks everyone and saves the world
+    pub fn shutdown(&mut self) -> Result<(), Error> {
+        for connection in self.connections.values_mut() {
+            connection.kick("Server closed");
+        }
+
+        self.drop_closed_connections();
+
+        self.world.save_all()
+    }
+}
+
+// networking
+impl Server {
+    fn accept_connections(&mut self) {
+        loop {
+            let accepted = match &self.listener {
+                Some(listener) => listener.accept(),
+                None => return
+            };
+
+            match accepted {
+                Ok(Some(transport)) => { self.add_connection(Box::new(transport)); },
+                Ok(None) => return,
+                Err(err) => {
+                    eprintln!("[LOG] {}", err);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn receive_packets(&mut self) {
+        let ids: Vec<u64> = self.connections.keys().copied().collect();
+
+        for id in ids {
+            while let Some(packet) = self.connections.get_mut(&id).and_then(|connection| connection.receive()) {
+                self.handle_packet(id, packet);
+            }
+        }
+    }
+
+    fn handle_packet(&mut self, id: u64, packet: Packet) {
+        let is_playing = match self.connections.get(&id) {
+            Some(connection) => connection.is_playing(),
+            None => return
+        };
+
+        match packet {
+            Packet::Handshake { protocol_version, name, view_distance } if !is_playing => {
+                self.accept_player(id, protocol_version, name, view_distance as usize);
+            },
+            Packet::PlayerMove { position } if is_playing => {
+                let accepted = match self.connections.get(&id) {
+                    Some(connection) => connection.get_position(),
+                    None => return
+                };
+
+                let is_finite = position.x.is_finite() && position.y.is_finite() && position.z.is_finite();
+
+                // too far in one step, put the player back where it last was
+                if !is_finite || (position - accepted).magnitude() > Self::MAX_MOVE_DISTANCE {
+                    if let Some(connection) = self.connections.get_mut(&id) {
+                        connection.send(&Packet::PlayerTeleport { position: accepted });
+                    }
+
+                    return;
+                }
+
+                self.move_player(id, position);
+            },
+            Packet::SetBlock { world_pos, block } if is_playing => {
+                let block = match block {
+                    0 => None,
+                    _ => match self.palette.to_identifier(block) {
+                        Some(id) => Registry::current().get_block(id),
+                        None => {
+                            self.kick(id, &format!("unknown block index {}", block));
+                            return;
+                        }
+                    }
+                };
+
+                let can_reach = match self.connections.get(&id) {
+                    Some(connection) => {
+                        let center = Vector3::new(world_pos.x as f32 + 0.5, world_pos.y as f32 + 0.5, world_pos.z as f32 + 0.5);
+
+                        (center - connection.get_position()).magnitude() <= Self::REACH_DISTANCE &&
+                            connection.sent_chunks.contains(&chunk::world_to_chunk_pos(world_pos))
+                    },
+                    None => return
+                };
+
+                // out of reach, or somewhere the player can't even see: quietly ignored
+                if !can_reach {
+                    return;
+                }
+
+                // everyone, the sender included, hears about it with the rest of this step's block changes
+                self.world.set_block(world_pos, block);
+            },
+            Packet::SetViewDistance { view_distance } if is_playing => {
+                let view_distance = (view_distance as usize).clamp(1, self.config.render_distance);
+
+                // chunks out of the new range are taken back on the next stream
+                if let Some(connection) = self.connections.get_mut(&id) {
+                    connection.set_view_distance(view_distance);
+                }
+            },
+            Packet::Disconnect { reason } => {
+                if let Some(connection) = self.connections.get_mut(&id) {
+                    println!("[LOG] {} left ({})", connection.get_name(), reason);
+                    connection.kick(&reason);
+                }
+            },
+            other => self.kick(id, &format!("unexpected packet {:?}", other))
+        }
+    }
+
+    fn move_player(&mut self, id: u64, position: Vector3<f32>) {
+        if let Some(connection) = self.connections.get_mut(&id) {
+            connection.position = position;
+            connection.has_moved = true;
+
+            // the client simulates its own player, we only keep its body where it says
+            if let Some(entity) = connection.entity_id.and_then(|entity_id| self.world.get_entity_mut(entity_id)) {
+                entity.body.collider.position = position;
+            }
+        }
+    }
+
+    fn accept_player(&mut self, id: u64, protocol_version: u32, name: String, view_distance: usize) {
+        if protocol_version != PROTOCOL_VERSION {
+            self.kick(id, &format!("Server runs protocol version {}, but the client uses {}", PROTOCOL_VERSION, protocol_version));
+            return;
+        }
+
+        let mut body = EntityBody::new(self.spawn_point, entity::PLAYER_SIZE);
+        body.has_gravity = false;
+
+        let entity_id = self.world.spawn_entity(Entity::new(entity::player_kind(), body).with_persistence(false));
+        let view_distance = view_distance.clamp(1, self.config.render_distance);
+
+        let accepted = Packet::HandshakeAccepted {
+            player_id: id,
+            seed: self.world.get_seed(),
+            spawn_point: self.spawn_point,
+            current_tick: self.world.get_current_tick(),
+            blocks: self.palette.get_blocks().to_vec(),
+        };
+
+        let joined = Packet::PlayerJoined { player_id: id, name: name.clone(), position: self.spawn_point };
+
+        // tell the new player about everyone already here, and everyone about the new player
+        let others: Vec<Packet> = self.connections.values()
+            .filter(|other| other.is_playing())
+            .map(|other| Packet::PlayerJoined { player_id: other.get_id(), name: other.get_name().to_string(), position: other.get_position() })
+            .collect();
+
+        self.broadcast(&joined, Some(id));
+
+        if let Some(connection) = self.connections.get_mut(&id) {
+            println!("[LOG] {} joined", name);
+
+            connection.start_playing(name, view_distance, entity_id, self.spawn_point);
+            connection.send(&accepted);
+
+            connection.send(&Packet::TimeUpdate { time: self.world.get_time().get_ticks(), frozen: self.world.get_time().is_frozen() });
+
+            for packet in &others {
+                connection.send(packet);
+            }
+        }
+    }
+
+    fn kick(&mut self, id: u64, reason: &str) {
+        if let Some(connection) = self.connections.get_mut(&id) {
+            connection.kick(reason);
+        }
+    }
+
+    fn broadcast(&mut self, packet: &Packet, except: Option<u64>) {
+        for connection in self.connections.values_mut() {
+            if connection.is_playing() && Some(connection.get_id()) != except {
+                connection.send(packet);
+            }
+        }
+    }
+
+    // whole chunks that changed go out again to whoever has them, instead of block by block
+    fn send_replaced_chunks(&mut self) {
+        let current_tick = self.world.get_current_tick();
+
+        for chunk_pos in self.world.take_replaced_chunks() {
+            let packet = match self.world.get_chunk(chunk_pos) {
+                Some(chunk) => Packet::ChunkData { data: chunk.get_chunk_data().serialize(current_tick) },
+                None => continue
+            };
+
+            for connection in self.connections.values_mut() {
+                if connection.is_playing() && connection.sent_chunks.contains(&chunk_pos) {
+                    connection.send(&packet);
+                }
+            }
+        }
+    }
+
+    fn send_block_changes(&mut self) {
+        for world_pos in self.world.take_block_changes() {
+            let block = self.world.get_block(world_pos);
+            let index = match self.palette.to_index(block.as_ref().map(|block| block.get_identifier())) {
+                Some(index) => index,
+                None => continue
+            };
+
+            let packet = Packet::BlockChange { world_pos, block: index };
+            let chunk_pos = chunk::world_to_chunk_pos(world_pos);
+
+            // players who haven't got the chunk yet will see the change when they do
+            for connection in self.connections.values_mut() {
+                if connection.is_playing() && connection.sent_chunks.contains(&chunk_pos) {
+                    connection.send(&packet);
+                }
+            }
+        }
+    }
+
+    fn send_time(&mut self) {
+        let current_tick = self.world.get_current_tick();
+        let is_due = current_tick - self.last_time_sync >= Self::TIME_SYNC_INTERVAL;
+
+        if !self.world.take_time_changed() && !is_due {
+            return;
+        }
+
+        self.last_time_sync = current_tick;
+
+        let time = self.world.get_time();
+        let packet = Packet::TimeUpdate { time: time.get_ticks(), frozen: time.is_frozen() };
+
+        self.broadcast(&packet, None);
+    }
+
+    fn send_player_positions(&mut self) {
+        let moved: Vec<(u64, Vector3<f32>)> = self.connections.values_mut()
+            .filter(|connection| connection.is_playing() && connection.has_moved)
+            .map(|connection| {
+                connection.has_moved = false;
+                (connection.get_id(), connection.get_position())
+            })
+            .collect();
+
+        for (player_id, position) in moved {
+            self.broadcast(&Packet::PlayerPosition { player_id, position }, Some(player_id));
+        }
+    }
+
+    // sends each player the closest loaded chunks they don't have yet, and takes back the ones out of their range
+    fn stream_chunks(&mut self) {
+        let loaded: Vec<Vector3<i32>> = self.world.get_renderable_chunks().keys().copied().collect();
+        let current_tick = self.world.get_current_tick();
+
+        for connection in self.connections.values_mut() {
+            if !connection.is_playing() {
+                continue;
+            }
+
+            let position = connection.get_position();
+            let center = chunk::world_to_chunk_pos(Vector3::new(position.x.floor() as i32, position.y.floor() as i32, position.z.floor() as i32));
+            let view_distance = connection.get_view_distance() as i32;
+
+            let in_range = |chunk_pos: &Vector3<i32>| {
+                let offset = chunk_pos - center;
+
+                [offset.x, offset.y, offset.z].iter().all(|axis| *axis >= -view_distance && *axis < view_distance)
+            };
+
+            let out_of_range: Vec<Vector3<i32>> = connection.sent_chunks.iter()
+                .filter(|chunk_pos| !in_range(chunk_pos) || !loaded.contains(chunk_pos))
+                .copied()
+                .collect();
+
+            for chunk_pos in out_of_range {
+                connection.sent_chunks.remove(&chunk_pos);
+                connection.send(&Packet::UnloadChunk { chunk_pos });
+            }
+
+            let mut unsent: Vec<Vector3<i32>> = loaded.iter()
+                .filter(|chunk_pos| in_range(chunk_pos) && !connection.sent_chunks.contains(chunk_pos))
+                .copied()
+                .collect();
+
+            unsent.sort_by_key(|chunk_pos| {
+                let offset = chunk_pos - center;
+                offset.x * offset.x + offset.y * offset.y + offset.z * offset.z
+            });
+
+            for chunk_pos in unsent.into_iter().take(Self::CHUNKS_PER_STEP) {
+                if let Some(chunk) = self.world.get_chunk(chunk_pos) {
+                    connection.send(&Packet::ChunkData { data: chunk.get_chunk_data().serialize(current_tick) });
+                    connection.sent_chunks.insert(chunk_pos);
+                }
+            }
+        }
+    }
+
+    fn drop_closed_connections(&mut self) {
+        let closed: Vec<u64> = self.connections.values()
+            .filter(|connection| connection.is_closed())
+            .map(|connection| connection.get_id())
+            .collect();
+
+        for id in closed {
+            if let Some(connection) = self.connections.remove(&id) {
+                if let Some(entity_id) = connection.entity_id {
+                    self.world.despawn_entity(entity_id);
+                    self.broadcast(&Packet::PlayerLeft { player_id: id }, None);
+                }
+            }
+        }
+    }
+}
+
+// getters
+impl Server {
+    pub fn get_config(&self) -> &ServerConfig { &self.config }
+
+    pub fn get_world(&self) -> &World { &self.world }
+
+    pub fn get_world_mut(&mut self) -> &mut World { &mut self.world }
+
+    pub fn get_spawn_point(&self) -> Vector3<f32> { self.spawn_point }
+
+    pub fn get_connections(&self) -> impl Iterator<Item = &Connection> { self.connections.values() }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use net::{protocol::{Packet, PROTOCOL_VERSION}, transport::Transport};
+    use super::{Server, ServerConfig};
+
+    #[test]
+    fn world_runs_without_gpu() {
+        let mut server = Server::new(ServerConfig {
+            seed: 1234,
+            render_distance: 1,
+            ..Default::default()
+        }).unwrap();
+
+        for _ in 0..120 {
+            server.step().unwrap();
+        }
+
+        let spawn = server.get_spawn_point();
+
+        assert!(server.get_world().get_chunk_from_world(&spawn).is_some(), "spawn chunk never loaded");
+        assert!(server.get_world().get_current_tick() > 0);
+        assert!(!server.get_world().builds_meshes());
+    }
+
+    #[test]
+    fn players_cant_move_or_build_too_far() {
+        let mut server = Server::new(ServerConfig {
+            seed: 1234,
+            render_distance: 1,
+            ..Default::default()
+        }).unwrap();
+
+        let mut client = server.connect_loopback();
+        client.send(&Packet::Handshake { protocol_version: PROTOCOL_VERSION, name: "crow".to_string(), view_distance: 1 }).unwrap();
+
+        // long enough to build and send the chunks around spawn
+        for _ in 0..20 {
+            server.step().unwrap();
+        }
+
+        while client.receive().unwrap().is_some() { }
+
+        let spawn = server.get_spawn_point();
+        let step = spawn + Vector3::new(0.5, 0., 0.);
+
+        client.send(&Packet::PlayerMove { position: step }).unwrap();
+        client.send(&Packet::PlayerMove { position: spawn + Vector3::new(100., 0., 0.) }).unwrap();
+        server.step().unwrap();
+
+        let mut packets = Vec::new();
+
+        while let Some(packet) = client.receive().unwrap() {
+            packets.push(packet);
+        }
+
+        assert!(packets.contains(&Packet::PlayerTeleport { position: step }));
+        assert_eq!(server.get_connections().next().unwrap().get_position(), step);
+
+        // 0 is air in the palette, then grass, dirt and stone
+        let near = Vector3::new(1, 62, 1);
+        let far = Vector3::new(-15, 50, -15);
+
+        client.send(&Packet::SetBlock { world_pos: near, block: 3 }).unwrap();
+        client.send(&Packet::SetBlock { world_pos: far, block: 3 }).unwrap();
+        server.step().unwrap();
+
+        assert!(server.get_world().get_block(near).is_some());
+        assert!(server.get_world().get_block(far).is_none());
+    }
+}
@@ -0,0 +1,77 @@
+use std::{io::BufRead, path::PathBuf, sync::{Arc, atomic::{AtomicBool, Ordering}}, thread};
+
+use anyhow::{Result, Error, anyhow};
+
+use server::{Server, ServerConfig};
+
+fn main() {
+    let (config, port) = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("usage: server [--seed <seed>] [--world <directory>] [--render-distance <chunks>] [--port <port>]");
+            std::process::exit(1);
+        }
+    };
+
+    println!("[LOG] Starting server with seed {}", config.seed);
+
+    let mut server = match Server::new(config) {
+        Ok(server) => server,
+        Err(err) => {
+            eprintln!("An error occurred while starting the server: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    match server.listen(("0.0.0.0", port)) {
+        Ok(address) => println!("[LOG] Listening on {}", address),
+        Err(err) => {
+            eprintln!("An error occurred while starting the server: {}", err);
+            std::process::exit(1);
+        }
+    }
+
+    let running = Arc::new(AtomicBool::new(true));
+
+    // typing "stop" shuts the server down cleanly, saving the world on the way out
+    let stop_flag = running.clone();
+
+    thread::spawn(move || {
+        for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+            if line.trim() == "stop" {
+                stop_flag.store(false, Ordering::Relaxed);
+                break;
+            }
+        }
+    });
+
+    match server.run(running) {
+        Ok(_) => println!("[LOG] Server stopped"),
+        Err(err) => {
+            eprintln!("[FATAL ERROR] {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+const DEFAULT_PORT: u16 = 24242;
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<(ServerConfig, u16), Error> {
+    let mut config = ServerConfig::default();
+    let mut port = DEFAULT_PORT;
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| anyhow!(format!("missing value for '{}'", arg)));
+
+        match arg.as_str() {
+            "--seed" => config.seed = value()?.parse()?,
+            "--world" => config.world_directory = Some(PathBuf::from(value()?)),
+            "--render-distance" => config.render_distance = value()?.parse()?,
+            "--port" => port = value()?.parse()?,
+            _ => return Err(anyhow!(format!("unknown argument '{}'", arg)))
+        }
+    }
+
+    Ok((config, port))
+}
@@ -0,0 +1,170 @@
+use std::{thread, time::Duration};
+
+use cgmath::Vector3;
+
+use common::identifier::Identifier;
+use net::{client::{ClientSession, SessionState}, protocol::Packet, tcp::TcpTransport, transport::Transport};
+use server::{Server, ServerConfig};
+
+const VIEW_DISTANCE: usize = 2;
+
+fn start_server() -> (Server, std::net::SocketAddr) {
+    let mut server = Server::new(ServerConfig {
+        seed: 4321,
+        render_distance: VIEW_DISTANCE,
+        ..Default::default()
+    }).unwrap();
+
+    let address = server.listen("127.0.0.1:0").unwrap();
+
+    (server, address)
+}
+
+// steps the server and every client until `done` says so, failing after a while
+fn run_until(server: &mut Server, clients: &mut [&mut ClientSession], what: &str, done: impl Fn(&Server, &[&mut ClientSession]) -> bool) {
+    for _ in 0..2000 {
+        server.step().unwrap();
+
+        for client in clients.iter_mut() {
+            let spawn = client.get_spawn_point();
+            client.update(spawn, Server::STEP).unwrap();
+        }
+
+        if done(server, clients) {
+            return;
+        }
+
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    panic!("timed out waiting for {}", what);
+}
+
+fn connect(address: std::net::SocketAddr, name: &str) -> ClientSession {
+    ClientSession::connect(Box::new(TcpTransport::connect(address).unwrap()), name, VIEW_DISTANCE).unwrap()
+}
+
+#[test]
+fn two_clients_share_one_world() {
+    let (mut server, address) = start_server();
+
+    let mut alice = connect(address, "alice");
+    let mut bob = connect(address, "bob");
+
+    run_until(&mut server, &mut [&mut alice, &mut bob], "both players to join", |_, clients| {
+        clients.iter().all(|client| client.is_playing() && client.get_players().len() == 1)
+    });
+
+    assert_ne!(alice.get_player_id(), bob.get_player_id());
+    assert_eq!(alice.get_players()[&bob.get_player_id()].get_name(), "bob");
+    assert_eq!(bob.get_players()[&alice.get_player_id()].get_name(), "alice");
+
+    // chunks stream in until both have the one they spawned in, with the server's blocks
+    let spawn = server.get_spawn_point();
+
+    run_until(&mut server, &mut [&mut alice, &mut bob], "the spawn chunk", |_, clients| {
+        clients.iter().all(|client| client.get_world().get_chunk_from_world(&spawn).is_some())
+    });
+
+    let spawn_chunk = server.get_world().get_chunk_from_world(&spawn).unwrap().get_pos();
+
+    assert_eq!(
+        alice.get_world().get_chunk(spawn_chunk).unwrap().get_chunk_data().get_blocks(),
+        server.get_world().get_chunk(spawn_chunk).unwrap().get_chunk_data().get_blocks()
+    );
+
+    // movement goes through the server to the other player, as long as it's no further than a step could take them
+    let moved_to = spawn + Vector3::new(1.5, 0., -1.);
+    alice.send_position(moved_to).unwrap();
+
+    let alice_id = alice.get_player_id();
+
+    run_until(&mut server, &mut [&mut alice, &mut bob], "bob to see alice move", |_, clients| {
+        clients[1].get_players()[&alice_id].get_position() == moved_to
+    });
+
+    // so do block changes, back to the player who asked for them too
+    let block_pos = Vector3::new(spawn.x as i32, spawn.y as i32, spawn.z as i32);
+    let stone = Identifier::from_str("willekeurig:stone").unwrap();
+
+    bob.request_block_change(block_pos, Some(&stone)).unwrap();
+
+    run_until(&mut server, &mut [&mut alice, &mut bob], "the block change", |_, clients| {
+        clients.iter().all(|client| client.get_world().get_block(block_pos).map(|block| block.get_identifier().clone()) == Some(stone.clone()))
+    });
+
+    assert!(server.get_world().get_block(block_pos).is_some());
+
+    // leaving despawns the player for everyone else
+    bob.disconnect("done");
+    drop(bob);
+
+    run_until(&mut server, &mut [&mut alice], "bob to leave", |_, clients| clients[0].get_players().is_empty());
+}
+
+#[test]
+fn mismatched_protocol_version_is_refused() {
+    let (mut server, address) = start_server();
+
+    let mut transport = TcpTransport::connect(address).unwrap();
+    transport.send(&Packet::Handshake { protocol_version: 9999, name: "time traveller".to_string(), view_distance: 2 }).unwrap();
+
+    for _ in 0..2000 {
+        server.step().unwrap();
+
+        match transport.receive() {
+            Ok(Some(Packet::Disconnect { reason })) => {
+                assert!(reason.contains("protocol version"), "unexpected reason: {}", reason);
+                return;
+            },
+            Ok(Some(other)) => panic!("expected to be refused, got {:?}", other),
+            Ok(None) => thread::sleep(Duration::from_millis(1)),
+            Err(err) => panic!("connection dropped without a reason: {}", err)
+        }
+    }
+
+    panic!("never got refused");
+}
+
+#[test]
+fn loopback_client_plays_single_player() {
+    let mut server = Server::new(ServerConfig {
+        seed: 99,
+        render_distance: VIEW_DISTANCE,
+        ..Default::default()
+    }).unwrap();
+
+    let transport = server.connect_loopback();
+    let mut client = ClientSession::connect(Box::new(transport), "solo", VIEW_DISTANCE).unwrap();
+
+    run_until(&mut server, &mut [&mut client], "the loopback client to get chunks", |_, clients| {
+        clients[0].is_playing() && !clients[0].get_world().get_renderable_chunks().is_empty()
+    });
+
+    assert_eq!(client.get_state(), &SessionState::Playing);
+}
+
+#[test]
+fn world_time_reaches_clients() {
+    let mut server = Server::new(ServerConfig {
+        seed: 99,
+        render_distance: 1,
+        ..Default::default()
+    }).unwrap();
+
+    let transport = server.connect_loopback();
+    let mut client = ClientSession::connect(Box::new(transport), "solo", 1).unwrap();
+
+    run_until(&mut server, &mut [&mut client], "the client to join", |_, clients| clients[0].is_playing());
+
+    // setting and freezing the time is sent right away, not at the next sync
+    server.get_world_mut().set_time(12345);
+    server.get_world_mut().set_time_frozen(true);
+
+    run_until(&mut server, &mut [&mut client], "the time update", |server, clients| {
+        clients[0].get_world().get_time() == server.get_world().get_time()
+    });
+
+    assert_eq!(client.get_world().get_time().get_ticks(), 12345);
+    assert!(client.get_world().get_time().is_frozen());
+}
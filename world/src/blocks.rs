@@ -0,0 +1,26 @@
+use anyhow::{Result, Error};
+
+use common::{block::Block, identifier::Identifier, registry::Registry};
+
+/// Registers the built-in blocks and makes them the current registry.
+/// Client and server both call this, so their registries line up.
+pub fn register_blocks() -> Result<(), Error> {
+    let mut grass_block = Block::new(Identifier::from_str("willekeurig:grass_block")?,0.0, 0.0);
+    grass_block.set_texture_bottom(64.0, 0.0); 
+    grass_block.set_side_textures(32.0, 0.0);
+
+    let dirt = Block::new(Identifier::from_str("willekeurig:dirt")?, 64.0, 0.0);
+    let stone = Block::new(Identifier::from_str("willekeurig:stone")?, 0.0, 32.0);
+
+    let mut registry = Registry::new();
+
+    registry.register_block(grass_block)?;
+
+    registry.register_block(dirt)?;
+
+    registry.register_block(stone)?;
+
+    registry.make_current();
+
+    Ok(())
+}
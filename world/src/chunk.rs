@@ -1,17 +1,26 @@
 use std::collections::HashMap;
 
+use anyhow::{Result, Error, anyhow};
 use cgmath::{Vector3, Zero};
 use physics::box_collider::BoxCollider;
-use wgpu::util::DeviceExt;
 
-use renderer::vertex::Vertex;
-use common::{block::Block, identifier::Identifier, registry::Registry};
+use common::{block::{Block, BlockFace}, identifier::Identifier, registry::Registry, serialization::{ByteReader, ByteWriter}, vertex::{self, ChunkVertex}};
 
-use crate::{World, block_culling::{cull_neighbors, CullCode}};
+use crate::{World, block_culling::{cull_neighbors, CullCode}, tick::ScheduledTick};
 
 pub const CHUNK_SIZE: usize = 16;
 pub const CHUNK_BIT_SIZE: usize = 4;
 
+// which bit of `cull_neighbors`' result says each face is visible
+const FACE_CULL_CODES: [(BlockFace, u8); 6] = [
+    (BlockFace::Front, CullCode::F as u8),
+    (BlockFace::Back, CullCode::B as u8),
+    (BlockFace::Top, CullCode::U as u8),
+    (BlockFace::Bottom, CullCode::D as u8),
+    (BlockFace::Left, CullCode::L as u8),
+    (BlockFace::Right, CullCode::R as u8),
+];
+
 pub const BLOCK_Y_SHIFT: usize = 4;
 pub const BLOCK_Z_SHIFT: usize = 8;
 
@@ -22,26 +31,16 @@ pub struct Chunk {
 
     chunk_data: ChunkData,
 
-    //chunk_aabb_tree: Vec<Option<Aabb3<f32>>>,
-
     chunk_neighbors: Vec<Vector3<i32>>,
-    //chunk_colliders: Vec<Aabb3<f32>>,
     chunk_colliders: Vec<BoxCollider>,
 
-    chunk_verticies: Vec<Vertex>,
-    chunk_indicies: Vec<u32>,
-    
-    vertex_buffer: Option<wgpu::Buffer>,
-    index_buffer: Option<wgpu::Buffer>,
+    // uploading these is up to whoever draws the chunk. Faces are quads,
+    // drawn with the indices from the renderer's `quad_indices`
+    chunk_verticies: Vec<ChunkVertex>,
 } 
 
 impl Chunk {
     pub fn new(pos: Vector3<i32>) -> Self {
-        
-        //let a = dbvt::DynamicBoundingVolumeTree::<Aabb3<f32>>::new();
-
-        //let b = Aabb3::new(Point3 { x: 0f32, y: 0f32, z: 0f32 }, Point3 { x: 1f32, y: 1f32, z: 1f32 });
-        //a.insert(b);
 
         Self {
             is_first_build: true,
@@ -49,7 +48,6 @@ impl Chunk {
             is_surrounded: false,
             chunk_data: ChunkData::new(pos),
 
-            //chunk_aabb_tree: vec![None; CHUNK_SIZE.pow(3)],
             chunk_colliders: Vec::new(),
 
             chunk_neighbors: vec![
@@ -62,13 +60,19 @@ impl Chunk {
             ],
 
             chunk_verticies: Vec::new(),
-            chunk_indicies: Vec::new(),
-            
-            vertex_buffer: None,
-            index_buffer: None,
         }
     }
 
+    /// Wraps previously saved chunk data, skipping terrain generation
+    pub fn from_chunk_data(chunk_data: ChunkData) -> Self {
+        let mut chunk = Self::new(chunk_data.pos);
+
+        chunk.chunk_data = chunk_data;
+        chunk.is_first_build = false;
+
+        chunk
+    }
+
     // TODO: take into account player position
     pub fn is_visible(&self, world: &World) -> bool {
         let mut count = 0;
@@ -88,118 +92,42 @@ impl Chunk {
 
     pub fn is_empty(&self) -> bool { self.chunk_data.is_empty }
 
-    pub fn build_mesh(&mut self, device: &wgpu::Device) {
-        if !self.chunk_data.is_empty {
-            let (verts, indies) = self.chunk_data.build_mesh();
-
-            self.chunk_verticies = verts;
-            self.chunk_indicies = indies;
+    /// Rebuilds the colliders, and the mesh too unless `with_mesh` is false (nothing to draw it on a server)
+    pub fn build_mesh(&mut self, with_mesh: bool) {
+        self.chunk_verticies.clear();
 
-            self.vertex_buffer = Some(
-                device.create_buffer_init(
-                    &wgpu::util::BufferInitDescriptor {
-                        label: Some("Vertex Buffer"),
-                        contents: bytemuck::cast_slice(self.chunk_verticies.as_slice()),
-                        usage: wgpu::BufferUsages::VERTEX
-                    }
-                )
-            );
-        
-            self.index_buffer = Some(
-                device.create_buffer_init(
-                    &wgpu::util::BufferInitDescriptor {
-                        label: Some("Index Buffer"),
-                        contents: bytemuck::cast_slice(self.chunk_indicies.as_slice()),
-                        usage: wgpu::BufferUsages::INDEX
-                    }
-                )
-            );
+        if !self.chunk_data.is_empty {
+            if with_mesh {
+                self.chunk_verticies = self.chunk_data.build_mesh();
+            }
 
             let colliders = self.chunk_data.gen_collision_mesh();
 
             self.set_collision_mesh(colliders);
+        } else {
+            // the last block was removed, don't keep colliding with the old blocks
+            self.chunk_colliders.clear();
         }
 
+        self.is_first_build = false;
         self.is_dirty = false;
     }
 
     fn set_collision_mesh(&mut self, box_data: HashMap<Vector3<usize>, Vector3<usize>>) {
-        
-
-        let mut collider_index = 0;
-        let existing_collider_count = self.chunk_colliders.len();
+        // rebuilt from scratch every time, the old boxes may not line up with the new blocks
+        self.chunk_colliders.clear();
 
         for (collider_pos, collider_size) in box_data {
             let collider_pos = Vector3::new(collider_pos.x as f32, collider_pos.y as f32, collider_pos.z as f32);
             let collider_size = Vector3::new(collider_size.x as f32, collider_size.y as f32, collider_size.z as f32);
             let position = self.chunk_data.to_world_pos_f32(collider_pos);
 
-            if collider_index < existing_collider_count {
-                self.chunk_colliders[collider_index].position = collider_pos;
-                self.chunk_colliders[collider_index].size = collider_size;
-            } else {
-                self.chunk_colliders.push(BoxCollider::new(position, collider_size, Vector3::zero()));
-            }
-            
-            collider_index += 1;
-        }
-
-        /*for (collider_start, collider_size) in box_data {
-            let collider_end = collider_start + collider_size;
-
-            let min = Point3 {
-                x: collider_start.x as f32,
-                y: collider_start.y as f32,
-                z: collider_start.z as f32,
-            };
-
-            let max = Point3 {
-                x: collider_end.x as f32,
-                y: collider_end.y as f32,
-                z: collider_end.z as f32,
-            };
-
-            if collider_index < existing_collider_count {
-                self.chunk_colliders[collider_index] = Aabb3::new(min, max);
-            } else {
-                self.chunk_colliders.push(Aabb3::new(min, max));
-            }
-            
-            collider_index += 1;
-        }*/
-
-        // delete all unused boxes if this mesh generation had less boxes than the previous one.
-        if collider_index < existing_collider_count {
-            for index in collider_index..existing_collider_count - 1 {
-                self.chunk_colliders.remove(index);
-            }
+            self.chunk_colliders.push(BoxCollider::new(position, collider_size, Vector3::zero()));
         }
     }
 
-    pub fn has_collision(&self, aabb: &BoxCollider) -> Option<&BoxCollider> {
-        for chunk_collider in &self.chunk_colliders {
-            if aabb.collision_with(chunk_collider) {
-                return Some(chunk_collider);
-            }
-
-            // TODO: return the block!
-            /*if aabb.contains(chunk_aabb) {
-                return true;
-            }
-
-            if let Some(swept_aabb) = aabb.swept_collision_with(block_aabb) {
-                if let Some(block) = self.get_block(aabb.position.x as usize, aabb.position.y as usize, aabb.position.z as usize) {
-                    return Some((
-                        swept_aabb,
-                        block
-                    ));
-                }
-            }*/
-        //}
-        }
-
-        None
-    }
+    /// Greedy-meshed collision boxes for the solid blocks, in world space
+    pub fn get_colliders(&self) -> &[BoxCollider] { &self.chunk_colliders }
 
     pub fn is_dirty(&self) -> bool { self.is_dirty }
 
@@ -235,14 +163,23 @@ impl Chunk {
         self.chunk_data.get_block(x, y, z)
     }
 
-    pub fn get_buffers(&self) -> Option<(&wgpu::Buffer, &wgpu::Buffer, u32)> {
-        if let Some(vertex_buffer) = &self.vertex_buffer {
-            if let Some(index_buffer) = &self.index_buffer {
-                return Some((vertex_buffer, index_buffer, self.chunk_indicies.len() as u32))
-            }
-        }
+    pub fn get_chunk_data(&self) -> &ChunkData { &self.chunk_data }
 
-        None
+    pub fn schedule_tick(&mut self, x: usize, y: usize, z: usize, due_tick: u64) -> bool {
+        self.chunk_data.schedule_tick(x, y, z, due_tick)
+    }
+
+    pub fn take_due_ticks(&mut self, current_tick: u64) -> Vec<ScheduledTick> {
+        self.chunk_data.take_due_ticks(current_tick)
+    }
+
+    /// The quads from the last build, four vertices each. None if there's nothing to draw
+    pub fn get_mesh(&self) -> Option<&[ChunkVertex]> {
+        if self.chunk_verticies.is_empty() {
+            None
+        } else {
+            Some(&self.chunk_verticies)
+        }
     }
 
     pub fn local_to_world_pos(&self, x: usize, y: usize, z: usize) -> Vector3<f32> {
@@ -250,8 +187,7 @@ impl Chunk {
     }
 
     pub fn dispose(&mut self) {
-        let _ = self.index_buffer.take();
-        let _ = self.vertex_buffer.take();
+        self.chunk_verticies.clear();
         self.chunk_data.blocks.clear();
     }
 }
@@ -261,7 +197,9 @@ pub struct ChunkData {
     is_empty: bool,
     pos: Vector3<i32>,
     blocks: Vec<Option<Identifier>>,
-    air_blocks: usize,
+    solid_blocks: usize,
+
+    scheduled_ticks: Vec<ScheduledTick>,
 }
 
 impl ChunkData {
@@ -270,43 +208,43 @@ impl ChunkData {
             is_empty: true,
             pos,
             blocks: vec![None; CHUNK_SIZE.pow(3)],
-            air_blocks: 0,
+            solid_blocks: 0,
+
+            scheduled_ticks: Vec::new(),
         }
     }
 
+    pub fn get_pos(&self) -> Vector3<i32> { self.pos }
+
     pub fn get_blocks(&self) -> Vec<Option<Identifier>> {
         self.blocks.clone()
     }
 
     pub fn add_block(&mut self, x: usize, y: usize, z: usize, block: Option<Block>) -> bool {
-        let index = pos_as_index(x, y, z);
+        if x >= CHUNK_SIZE || y >= CHUNK_SIZE || z >= CHUNK_SIZE {
+            return false;
+        }
 
-        if index < self.blocks.len() && index > 0 {
-            if let Some(_) = &block {
-                self.is_empty = false;
+        let index = pos_as_index(x, y, z);
+        let was_solid = self.blocks[index].is_some();
 
-                if self.air_blocks > 0 {
-                    self.air_blocks -= 1;
-                }
-            } else {
-                // only increment if we're replacing a block
-                if let Some(_) = self.get_block(x, y, z) {
-                    self.air_blocks += 1;
-                }
+        if let Some(data) = block {
+            if !was_solid {
+                self.solid_blocks += 1;
             }
 
-            self.is_empty = self.air_blocks > 0;
-
-            if let Some(data) = block {
-                self.blocks[index] = Some(data.get_identifier().clone());
-            } else {
-                self.blocks[index] = None;
+            self.blocks[index] = Some(data.get_identifier().clone());
+        } else {
+            if was_solid {
+                self.solid_blocks -= 1;
             }
 
-            true
-        } else {
-            false
+            self.blocks[index] = None;
         }
+
+        self.is_empty = self.solid_blocks == 0;
+
+        true
     }
 
     pub fn remove_block(&mut self, x: usize, y: usize, z: usize) -> bool {
@@ -316,7 +254,7 @@ impl ChunkData {
     pub fn get_block(&self, x: usize, y: usize, z: usize) -> Option<Block> {
         let index = pos_as_index(x, y, z);
 
-        if index > 0 && index < self.blocks.len() {
+        if x < CHUNK_SIZE && y < CHUNK_SIZE && z < CHUNK_SIZE {
             let block_id = &self.blocks[index];
 
             if let Some(block_id) = block_id {
@@ -333,6 +271,15 @@ impl ChunkData {
         self.get_block(local.x, local.y, local.z)
     }
 
+    /// Like `get_block`, without looking the block up in the registry
+    pub fn get_block_id(&self, x: usize, y: usize, z: usize) -> Option<&Identifier> {
+        if x < CHUNK_SIZE && y < CHUNK_SIZE && z < CHUNK_SIZE {
+            self.blocks[pos_as_index(x, y, z)].as_ref()
+        } else {
+            None
+        }
+    }
+
     pub fn local_to_world_pos(&self, x: usize, y: usize, z: usize) -> Vector3<f32> {
         Vector3::new(
             self.pos.x as f32 * CHUNK_SIZE as f32 + x as f32,
@@ -357,87 +304,32 @@ impl ChunkData {
         )
     }
 
-    pub fn build_mesh(&mut self) -> (Vec<Vertex>, Vec<u32>) {
+    /// Four vertices per visible face, see the renderer's `quad_indices` for how they're indexed.
+    /// Positions are relative to the chunk, whoever draws it adds the chunk's origin.
+    pub fn build_mesh(&mut self) -> Vec<ChunkVertex> {
         if self.is_empty {
-            return (Vec::new(), Vec::new())
+            return Vec::new()
         }
 
-        let mut vertices: Vec<Vertex> = Vec::new();
-        let mut indicies: Vec<u32> = Vec::new();
+        let mut vertices: Vec<ChunkVertex> = Vec::new();
 
         for z in 0..CHUNK_SIZE {
             for y in 0..CHUNK_SIZE {
                 for x in 0..CHUNK_SIZE {
                     if let Some(block) = self.get_block(x, y, z) {
-                        let block_pos = self.local_to_world_pos(x, y, z);
-
                         let cull_code = cull_neighbors(&self, x, y, z);
-    
-                        if (cull_code & (CullCode::F as u8)) == CullCode::F as u8 {
-                            let (verts, indies) = self.build_face(
-                                vertices, indicies, block.get_vert_front(),
-                                &block_pos
-                            );
-    
-                            vertices = verts;
-                            indicies = indies;
-                        }
-    
-                        if (cull_code & (CullCode::B as u8)) == CullCode::B as u8 {
-                            let (verts, indies) = self.build_face(
-                                vertices, indicies, block.get_vert_back(),
-                                &block_pos
-                            );
-    
-                            vertices = verts;
-                            indicies = indies;
-                        }
-    
-                        if (cull_code & (CullCode::U as u8)) == CullCode::U as u8 {
-                            let (verts, indies) = self.build_face(
-                                vertices, indicies, block.get_vert_top(),
-                                &block_pos
-                            );
-    
-                            vertices = verts;
-                            indicies = indies;
-                        }
-    
-                        if (cull_code & (CullCode::D as u8)) == CullCode::D as u8 {
-                            let (verts, indies) = self.build_face(
-                                vertices, indicies, block.get_vert_bottom(),
-                                &block_pos
-                            );
-    
-                            vertices = verts;
-                            indicies = indies;
-                        }
-    
-                        if (cull_code & (CullCode::L as u8)) == CullCode::L as u8 {
-                            let (verts, indies) = self.build_face(
-                                vertices, indicies, block.get_vert_left(),
-                                &block_pos
-                            );
-    
-                            vertices = verts;
-                            indicies = indies;
-                        }
-    
-                        if (cull_code & (CullCode::R as u8)) == CullCode::R as u8 {
-                            let (verts, indies) = self.build_face(
-                                vertices, indicies, block.get_vert_right(),
-                                &block_pos
-                            );
-    
-                            vertices = verts;
-                            indicies = indies;
+
+                        for (face, cull) in FACE_CULL_CODES.iter() {
+                            if (cull_code & cull) == *cull {
+                                self.build_face(&mut vertices, &block, *face, Vector3::new(x, y, z));
+                            }
                         }
                     }
                 }
             }
         }
         
-        (vertices, indicies)
+        vertices
     }
 
     pub fn gen_collision_mesh(&self) -> HashMap<Vector3<usize>, Vector3<usize>> {
@@ -617,30 +509,245 @@ impl ChunkData {
         can_spread_z
     }
 
-    fn build_face(&self, mut vertices: Vec<Vertex>, mut indicies: Vec<u32>, block_verts: Vec<Vertex>,
-        block_position: &Vector3<f32>) -> (Vec<Vertex>, Vec<u32>) {
-        let block_indicies: Vec<u32> = vec![
-            0, 1, 2, // triangle 1
-            2, 3, 0, // triangle 2
-        ];
+    fn build_face(&self, vertices: &mut Vec<ChunkVertex>, block: &Block, face: BlockFace, local_pos: Vector3<usize>) {
+        let quad = face.get_quad();
+        let texture_index = block.get_texture_index(face);
 
-        let index = vertices.len() as u32;
-        
-        for vert in block_verts {
-            let vert_pos = [
-                block_position.x + vert.position[0],
-                block_position.y + vert.position[1],
-                block_position.z + vert.position[2]
+        for (corner, uv) in quad.corners.iter().zip(quad.uvs.iter()) {
+            let position = [
+                local_pos.x as u32 + corner[0],
+                local_pos.y as u32 + corner[1],
+                local_pos.z as u32 + corner[2],
             ];
-            
-            vertices.push(Vertex { position: vert_pos, tex_coords: vert.tex_coords });
+
+            let ao = self.corner_ao(face, local_pos, corner);
+
+            vertices.push(ChunkVertex::new(position, face.get_index(), *uv, texture_index, ao, vertex::MAX_LIGHT));
+        }
+    }
+
+    // 0 (darkest) to 3 from the blocks touching this corner in front of the face.
+    // Blocks in other chunks don't count, they aren't known while meshing
+    fn corner_ao(&self, face: BlockFace, local_pos: Vector3<usize>, corner: &[u32; 3]) -> u32 {
+        let normal = face.get_normal();
+        let layer = Vector3::new(local_pos.x as i32, local_pos.y as i32, local_pos.z as i32) + normal;
+
+        // step towards the corner along the two axes the face lies in
+        let mut sides = Vec::with_capacity(2);
+
+        for axis in 0..3 {
+            if normal[axis] == 0 {
+                let mut side = Vector3::new(0, 0, 0);
+                side[axis] = if corner[axis] == 1 { 1 } else { -1 };
+
+                sides.push(side);
+            }
+        }
+
+        let side_a = self.is_occluder(layer + sides[0]);
+        let side_b = self.is_occluder(layer + sides[1]);
+        let diagonal = self.is_occluder(layer + sides[0] + sides[1]);
+
+        if side_a && side_b {
+            0
+        } else {
+            vertex::MAX_AO - (side_a as u32 + side_b as u32 + diagonal as u32)
+        }
+    }
+
+    fn is_occluder(&self, local_pos: Vector3<i32>) -> bool {
+        let size = CHUNK_SIZE as i32;
+
+        if local_pos.x < 0 || local_pos.y < 0 || local_pos.z < 0 || local_pos.x >= size || local_pos.y >= size || local_pos.z >= size {
+            return false;
+        }
+
+        self.get_block(local_pos.x as usize, local_pos.y as usize, local_pos.z as usize).is_some()
+    }
+}
+
+// scheduled ticks
+impl ChunkData {
+    /// Schedules a tick for the block currently at the given local position.
+    /// Returns false if there's no block there, or the same tick is already queued.
+    pub fn schedule_tick(&mut self, x: usize, y: usize, z: usize, due_tick: u64) -> bool {
+        if x >= CHUNK_SIZE || y >= CHUNK_SIZE || z >= CHUNK_SIZE {
+            return false;
+        }
+
+        let local_pos = Vector3::new(x, y, z);
+
+        match &self.blocks[pos_as_index(x, y, z)] {
+            Some(block_id) => {
+                let already_scheduled = self.scheduled_ticks.iter()
+                    .any(|tick| tick.local_pos == local_pos && tick.due_tick == due_tick);
+
+                if !already_scheduled {
+                    self.scheduled_ticks.push(ScheduledTick { local_pos, block: block_id.clone(), due_tick });
+                }
+
+                !already_scheduled
+            },
+            None => false
         }
+    }
+
+    /// Removes and returns every scheduled tick that is due, oldest first
+    pub fn take_due_ticks(&mut self, current_tick: u64) -> Vec<ScheduledTick> {
+        let mut due = Vec::new();
+        let mut index = 0;
 
-        for f_index in &block_indicies {
-            indicies.push(f_index.clone() + index);
+        while index < self.scheduled_ticks.len() {
+            if self.scheduled_ticks[index].due_tick <= current_tick {
+                due.push(self.scheduled_ticks.swap_remove(index));
+            } else {
+                index += 1;
+            }
         }
 
-        (vertices, indicies)
+        due.sort_by_key(|tick| tick.due_tick);
+
+        due
+    }
+
+    pub fn get_scheduled_ticks(&self) -> &[ScheduledTick] { &self.scheduled_ticks }
+}
+
+// persistence
+impl ChunkData {
+    pub const FORMAT_VERSION: u16 = 1;
+
+    /// Scheduled ticks are stored relative to `current_tick`, so they keep
+    /// their remaining delay no matter when the chunk gets loaded again.
+    pub fn serialize(&self, current_tick: u64) -> Vec<u8> {
+        let mut writer = ByteWriter::new();
+        self.write_to(&mut writer, current_tick);
+
+        writer.into_bytes()
+    }
+
+    pub fn deserialize(bytes: &[u8], current_tick: u64) -> Result<Self, Error> {
+        Self::read_from(&mut ByteReader::new(bytes), current_tick)
+    }
+
+    pub fn write_to(&self, writer: &mut ByteWriter, current_tick: u64) {
+        writer.write_u16(Self::FORMAT_VERSION);
+
+        writer.write_i32(self.pos.x);
+        writer.write_i32(self.pos.y);
+        writer.write_i32(self.pos.z);
+
+        // palette index 0 is air, everything else is palette[index - 1]
+        let mut palette: Vec<&Identifier> = Vec::new();
+        let mut indices: Vec<u16> = Vec::with_capacity(self.blocks.len());
+
+        for block in &self.blocks {
+            let palette_index = match block {
+                Some(block_id) => match palette.iter().position(|id| *id == block_id) {
+                    Some(index) => index + 1,
+                    None => {
+                        palette.push(block_id);
+                        palette.len()
+                    }
+                },
+                None => 0
+            };
+
+            indices.push(palette_index as u16);
+        }
+
+        writer.write_u16(palette.len() as u16);
+
+        for block_id in palette {
+            writer.write_identifier(block_id);
+        }
+
+        // run-length encode the block indices, most chunks are long runs of air/stone
+        let mut runs: Vec<(u16, u16)> = Vec::new();
+
+        for index in indices {
+            match runs.last_mut() {
+                Some((length, last)) if *last == index && *length < u16::MAX => *length += 1,
+                _ => runs.push((1, index))
+            }
+        }
+
+        writer.write_u32(runs.len() as u32);
+
+        for (length, index) in runs {
+            writer.write_u16(length);
+            writer.write_u16(index);
+        }
+
+        writer.write_u32(self.scheduled_ticks.len() as u32);
+
+        for tick in &self.scheduled_ticks {
+            writer.write_u16(pos_as_index(tick.local_pos.x, tick.local_pos.y, tick.local_pos.z) as u16);
+            writer.write_identifier(&tick.block);
+            writer.write_u64(tick.due_tick.saturating_sub(current_tick));
+        }
+    }
+
+    pub fn read_from(reader: &mut ByteReader, current_tick: u64) -> Result<Self, Error> {
+        let version = reader.read_u16()?;
+
+        if version != Self::FORMAT_VERSION {
+            return Err(anyhow!(format!("unsupported chunk format version {} (expected {})", version, Self::FORMAT_VERSION)));
+        }
+
+        let pos = Vector3::new(reader.read_i32()?, reader.read_i32()?, reader.read_i32()?);
+        let mut chunk_data = Self::new(pos);
+
+        let palette_len = reader.read_u16()?;
+        let mut palette = Vec::with_capacity(palette_len as usize);
+
+        for _ in 0..palette_len {
+            palette.push(reader.read_identifier()?);
+        }
+
+        let run_count = reader.read_u32()?;
+        let mut index = 0;
+
+        for _ in 0..run_count {
+            let length = reader.read_u16()? as usize;
+            let palette_index = reader.read_u16()? as usize;
+
+            if index + length > chunk_data.blocks.len() {
+                return Err(anyhow!(format!("chunk [{},{},{}] has more than {} blocks", pos.x, pos.y, pos.z, chunk_data.blocks.len())));
+            }
+
+            let block_id = match palette_index {
+                0 => None,
+                _ => match palette.get(palette_index - 1) {
+                    Some(block_id) => Some(block_id.clone()),
+                    None => return Err(anyhow!(format!("palette index {} is out of range", palette_index)))
+                }
+            };
+
+            if block_id.is_some() {
+                chunk_data.solid_blocks += length;
+            }
+
+            for block in &mut chunk_data.blocks[index..index + length] {
+                *block = block_id.clone();
+            }
+
+            index += length;
+        }
+
+        chunk_data.is_empty = chunk_data.solid_blocks == 0;
+
+        let tick_count = reader.read_u32()?;
+
+        for _ in 0..tick_count {
+            let local_pos = index_as_pos(reader.read_u16()? as usize);
+            let block = reader.read_identifier()?;
+            let due_tick = current_tick + reader.read_u64()?;
+
+            chunk_data.scheduled_ticks.push(ScheduledTick { local_pos, block, due_tick });
+        }
+
+        Ok(chunk_data)
     }
 }
 
@@ -655,4 +762,69 @@ pub fn index_as_pos(index: usize) -> Vector3<usize> {
     let block_z = (index >> BLOCK_Z_SHIFT) & 0xF;
 
     Vector3::new(block_x, block_y, block_z)
-}
\ No newline at end of file
+}
+
+/// Chunk position containing the given block position
+pub fn world_to_chunk_pos(world_pos: Vector3<i32>) -> Vector3<i32> {
+    Vector3::new(
+        world_pos.x.div_euclid(CHUNK_SIZE as i32),
+        world_pos.y.div_euclid(CHUNK_SIZE as i32),
+        world_pos.z.div_euclid(CHUNK_SIZE as i32)
+    )
+}
+
+/// Position of a block within the chunk it's in
+pub fn world_to_local_pos(world_pos: Vector3<i32>) -> Vector3<usize> {
+    Vector3::new(
+        world_pos.x.rem_euclid(CHUNK_SIZE as i32) as usize,
+        world_pos.y.rem_euclid(CHUNK_SIZE as i32) as usize,
+        world_pos.z.rem_euclid(CHUNK_SIZE as i32) as usize
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // chunks only keep identifiers, so the blocks have to be in the current registry
+    fn stone() -> Block {
+        crate::blocks::register_blocks().unwrap();
+
+        Registry::current().get_block(&Identifier::from_str("willekeurig:stone").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn mesh_is_chunk_local_and_occluded_corners_darken() {
+        let mut chunk_data = ChunkData::new(Vector3::new(100, 0, -100));
+        chunk_data.add_block(1, 0, 1, Some(stone()));
+
+        let vertices = chunk_data.build_mesh();
+        let stone_layer = stone().get_texture_index(BlockFace::Top);
+
+        // a lone block shows all six faces, lit evenly, wherever the chunk is
+        assert_eq!(vertices.len(), 6 * 4);
+        assert!(vertices.iter().all(|vertex| vertex.get_ao() == vertex::MAX_AO));
+        for vertex in &vertices {
+            let [x, y, z] = vertex.get_position();
+
+            assert!((1..=2).contains(&x) && y <= 1 && (1..=2).contains(&z));
+            assert_eq!(vertex.get_texture_index(), stone_layer);
+        }
+
+        // a block above and in front shades the front edge of the top face
+        chunk_data.add_block(1, 1, 2, Some(stone()));
+
+        let vertices = chunk_data.build_mesh();
+        let top_corners: Vec<&ChunkVertex> = vertices.iter()
+            .filter(|vertex| vertex.get_face() == BlockFace::Top.get_index() && vertex.get_position()[1] == 1)
+            .collect();
+
+        assert_eq!(top_corners.len(), 4);
+
+        for vertex in top_corners {
+            let expected = if vertex.get_position()[2] == 2 { vertex::MAX_AO - 1 } else { vertex::MAX_AO };
+
+            assert_eq!(vertex.get_ao(), expected);
+        }
+    }
+}
@@ -1,8 +1,10 @@
-use std::{collections::HashMap, sync::{Arc, RwLock}};
+use std::collections::HashMap;
 
 use cgmath::{Vector3, Zero};
 
-use crate::{chunk::{self, Chunk}, generator};
+use common::block::Block;
+
+use crate::{chunk::{self, Chunk}, generator, tick::ScheduledTick};
 
 pub struct ChunkManager {
     /* -== CHUNK LISTS ==- */
@@ -15,6 +17,13 @@ pub struct ChunkManager {
 
     cam_pos: Vector3<f32>,
     force_visibility_update: bool,
+
+    // servers only need the colliders
+    build_meshes: bool,
+    // None unless someone wants to know what changed
+    block_changes: Option<Vec<Vector3<i32>>>,
+    // chunks swapped out as a whole, tracked along with the block changes
+    replaced_chunks: Option<Vec<Vector3<i32>>>,
 }
 
 const ASYNC_NUM_CHUNKS_PER_FRAME: usize = 2;
@@ -31,6 +40,10 @@ impl ChunkManager {
 
             cam_pos: Vector3::zero(),
             force_visibility_update: false,
+
+            build_meshes: true,
+            block_changes: None,
+            replaced_chunks: None,
         }
     }
 }
@@ -38,6 +51,31 @@ impl ChunkManager {
 impl ChunkManager {
     pub fn get_renderable_chunks(&self) -> &HashMap<Vector3<i32>, Chunk> { &self.chunk_render_list }
 
+    pub fn builds_meshes(&self) -> bool { self.build_meshes }
+
+    pub fn set_track_block_changes(&mut self, track: bool) {
+        self.block_changes = if track { Some(Vec::new()) } else { None };
+        self.replaced_chunks = if track { Some(Vec::new()) } else { None };
+    }
+
+    pub fn take_block_changes(&mut self) -> Vec<Vector3<i32>> {
+        match &mut self.block_changes {
+            Some(changes) => std::mem::take(changes),
+            None => Vec::new()
+        }
+    }
+
+    pub fn take_replaced_chunks(&mut self) -> Vec<Vector3<i32>> {
+        match &mut self.replaced_chunks {
+            Some(chunks) => std::mem::take(chunks),
+            None => Vec::new()
+        }
+    }
+
+    pub fn set_build_meshes(&mut self, build_meshes: bool) {
+        self.build_meshes = build_meshes;
+    }
+
     pub fn world_to_chunk_coords(&self, world_pos: &Vector3<f32>) -> Vector3<i32> {
         Vector3::new(
             abs_ceil(world_pos.x / chunk::CHUNK_SIZE as f32) as i32,
@@ -47,16 +85,8 @@ impl ChunkManager {
     }
 
     pub fn add_chunk(&mut self, new_chunk: Chunk) -> bool {
-        for (_, chunk) in &self.chunk_render_list {
-            if chunk.get_pos() == new_chunk.get_pos() {
-                return false;
-            }
-        }
-
-        for (_, chunk) in &self.chunks_to_load {
-            if chunk.get_pos() == new_chunk.get_pos() {
-                return false;
-            }
+        if self.contains_chunk(new_chunk.get_pos()) {
+            return false;
         }
 
         self.chunks_to_load.entry(new_chunk.get_pos()).or_insert(new_chunk);
@@ -64,6 +94,36 @@ impl ChunkManager {
         true
     }
 
+    /// Whether the chunk is loaded or anywhere in the queue to be
+    pub fn contains_chunk(&self, chunk_pos: Vector3<i32>) -> bool {
+        self.chunk_render_list.contains_key(&chunk_pos) ||
+        self.chunks_to_load.contains_key(&chunk_pos) ||
+        self.chunks_to_build.contains_key(&chunk_pos)
+    }
+
+    /// Positions of the loaded chunks and the ones still queued
+    pub fn get_all_chunk_positions(&self) -> Vec<Vector3<i32>> {
+        self.chunk_render_list.keys()
+            .chain(self.chunks_to_load.keys())
+            .chain(self.chunks_to_build.keys())
+            .copied()
+            .collect()
+    }
+
+    /// Like `get_chunk`, but also finds chunks that are still queued
+    pub fn get_any_chunk(&self, chunk_pos: Vector3<i32>) -> Option<&Chunk> {
+        self.chunk_render_list.get(&chunk_pos)
+            .or_else(|| self.chunks_to_build.get(&chunk_pos))
+            .or_else(|| self.chunks_to_load.get(&chunk_pos))
+    }
+
+    /// Takes a chunk out of the manager, whether it's loaded or still queued
+    pub fn remove_chunk(&mut self, chunk_pos: Vector3<i32>) -> Option<Chunk> {
+        self.chunk_render_list.remove(&chunk_pos)
+            .or_else(|| self.chunks_to_build.remove(&chunk_pos))
+            .or_else(|| self.chunks_to_load.remove(&chunk_pos))
+    }
+
     pub fn get_chunk_from_world(&self, world_pos: &Vector3<f32>) -> Option<&Chunk> {
         let pos = self.world_to_chunk_coords(world_pos);
 
@@ -81,13 +141,97 @@ impl ChunkManager {
     pub fn get_chunk_mut(&mut self, chunk_pos: Vector3<i32>) -> Option<&mut Chunk> {
         self.chunk_render_list.get_mut(&chunk_pos)
     }
+
+    pub fn get_loaded_chunk_positions(&self) -> Vec<Vector3<i32>> {
+        self.chunk_render_list.keys().copied().collect()
+    }
+
+    pub fn get_block(&self, world_pos: Vector3<i32>) -> Option<Block> {
+        let local = chunk::world_to_local_pos(world_pos);
+
+        self.get_chunk(chunk::world_to_chunk_pos(world_pos))
+            .and_then(|chunk| chunk.get_block(local.x, local.y, local.z))
+    }
+
+    /// Whether the block at `world_pos` blocks movement. Unloaded chunks count as empty.
+    pub fn is_solid(&self, world_pos: Vector3<i32>) -> bool {
+        self.get_block(world_pos).is_some_and(|block| block.is_solid())
+    }
+
+    /// Returns false if the chunk at `world_pos` isn't loaded
+    pub fn set_block(&mut self, world_pos: Vector3<i32>, block: Option<Block>) -> bool {
+        let local = chunk::world_to_local_pos(world_pos);
+
+        let changed = match self.get_chunk_mut(chunk::world_to_chunk_pos(world_pos)) {
+            Some(chunk) => chunk.add_block(local.x, local.y, local.z, block),
+            None => false
+        };
+
+        if changed {
+            if let Some(changes) = &mut self.block_changes {
+                changes.push(world_pos);
+            }
+        }
+
+        changed
+    }
+
+    /// Swaps a loaded chunk for `chunk`, which gets built on the next update.
+    /// Returns false if there's no loaded chunk at its position.
+    pub fn replace_chunk(&mut self, chunk: Chunk) -> bool {
+        let chunk_pos = chunk.get_pos();
+
+        match self.chunk_render_list.get_mut(&chunk_pos) {
+            Some(loaded) => *loaded = chunk,
+            None => return false
+        }
+
+        if let Some(chunks) = &mut self.replaced_chunks {
+            chunks.push(chunk_pos);
+        }
+
+        true
+    }
+
+    pub fn schedule_tick(&mut self, world_pos: Vector3<i32>, due_tick: u64) -> bool {
+        let local = chunk::world_to_local_pos(world_pos);
+
+        match self.get_chunk_mut(chunk::world_to_chunk_pos(world_pos)) {
+            Some(chunk) => chunk.schedule_tick(local.x, local.y, local.z, due_tick),
+            None => false
+        }
+    }
+
+    /// Pulls every due scheduled tick out of the loaded chunks, paired with its world position
+    pub fn take_due_ticks(&mut self, current_tick: u64) -> Vec<(Vector3<i32>, ScheduledTick)> {
+        let mut due = Vec::new();
+
+        for (chunk_pos, chunk) in self.chunk_render_list.iter_mut() {
+            for tick in chunk.take_due_ticks(current_tick) {
+                let world_pos = chunk_pos * chunk::CHUNK_SIZE as i32 + Vector3::new(
+                    tick.local_pos.x as i32, tick.local_pos.y as i32, tick.local_pos.z as i32
+                );
+
+                due.push((world_pos, tick));
+            }
+        }
+
+        due.sort_by_key(|(_, tick)| tick.due_tick);
+
+        due
+    }
 }
 
 impl ChunkManager {
-    pub fn update(&mut self, device: Arc<RwLock<wgpu::Device>>, player_pos: Vector3<f32>, seed: u32) {
+    /// Returns the positions of every chunk whose mesh and colliders were (re)built
+    pub fn update(&mut self, _player_pos: Vector3<f32>, seed: u32) -> Vec<Vector3<i32>> {
         self.load_chunks();
-        self.build_chunks(device, seed);
+
+        let mut built = self.build_chunks(seed);
+        built.extend(self.rebuild_dirty_chunks());
         // TODO: logic for detecting what chunks are visible
+
+        built
     }
 
     fn load_chunks(&mut self) {
@@ -95,11 +239,7 @@ impl ChunkManager {
             return;
         }
 
-        let mut chunks_to_load = if self.chunks_to_load.len() < ASYNC_NUM_CHUNKS_PER_FRAME {
-            self.chunks_to_load.len() - 1
-        } else {
-            ASYNC_NUM_CHUNKS_PER_FRAME
-        };
+        let mut chunks_to_load = self.chunks_to_load.len().min(ASYNC_NUM_CHUNKS_PER_FRAME);
 
         while chunks_to_load > 0 {
             let key = match self.chunks_to_load.keys().next() {
@@ -117,16 +257,13 @@ impl ChunkManager {
         }
     }
 
-    fn build_chunks(&mut self, device: Arc<RwLock<wgpu::Device>>, seed: u32) {
+    fn build_chunks(&mut self, seed: u32) -> Vec<Vector3<i32>> {
         if self.chunks_to_build.len() < 1 {
-            return;
+            return Vec::new();
         }
         
-        let length = if self.chunks_to_build.len() < ASYNC_NUM_CHUNKS_PER_FRAME {
-            self.chunks_to_build.len() - 1
-        } else {
-            ASYNC_NUM_CHUNKS_PER_FRAME
-        };
+        let length = self.chunks_to_build.len().min(ASYNC_NUM_CHUNKS_PER_FRAME);
+        let build_meshes = self.build_meshes;
 
         let mut nonempty_chunks = 0;
         let mut keys = Vec::new();
@@ -142,7 +279,7 @@ impl ChunkManager {
                 generator::gen_smooth_terrain(chunk, &height_map);
             }
             
-            chunk.build_mesh(&device.clone().read().unwrap());
+            chunk.build_mesh(build_meshes);
 
             if !chunk.is_empty() {
                 nonempty_chunks += 1;
@@ -153,16 +290,34 @@ impl ChunkManager {
 
         keys.reverse();
 
-        for key in keys {
-            let chunk = self.chunks_to_build.remove(&key);
+        for key in &keys {
+            let chunk = self.chunks_to_build.remove(key);
 
             if let Some(chunk) = chunk {
-                self.chunk_render_list.entry(key).or_insert(chunk);
+                self.chunk_render_list.entry(*key).or_insert(chunk);
             }
         }
+
+        keys
     } 
 }
 
+impl ChunkManager {
+    // remesh loaded chunks whose blocks changed since their last build (ticks, block edits, ...)
+    fn rebuild_dirty_chunks(&mut self) -> Vec<Vector3<i32>> {
+        let mut rebuilt = Vec::new();
+
+        for (chunk_pos, chunk) in self.chunk_render_list.iter_mut() {
+            if chunk.is_dirty() {
+                chunk.build_mesh(self.build_meshes);
+                rebuilt.push(*chunk_pos);
+            }
+        }
+
+        rebuilt
+    }
+}
+
 fn abs_ceil(f: f32) -> f32 {
     if f >= 0.0 {
         f
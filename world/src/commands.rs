@@ -0,0 +1,881 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Result, Error, anyhow};
+use cgmath::Vector3;
+
+use common::{block::Block, identifier::Identifier, registry::Registry, settings::MAX_DRAW_DISTANCE};
+
+use crate::{World, chunk, time::TICKS_PER_DAY};
+
+/// Blocks typed without a namespace are looked for in this one
+pub const DEFAULT_NAMESPACE: &str = "willekeurig";
+
+// the most blocks a single /fill can change
+const MAX_FILL_VOLUME: i64 = 32 * 32 * 32;
+// lines kept by `CommandHistory`
+const MAX_HISTORY: usize = 100;
+
+/// What an argument has to look like
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArgKind {
+    Integer,
+    Number,
+    /// A number, or `~` and an optional offset from where the command was run
+    Coordinate,
+    /// A block's identifier, the namespace being optional for built-in blocks, or `air`
+    Block,
+    /// Any single word, the suggestions are only for tab completion
+    Word(&'static [&'static str]),
+    /// One of these words exactly
+    Choice(&'static [&'static str]),
+}
+
+#[derive(Debug, Clone)]
+pub struct ArgSpec {
+    name: &'static str,
+    kind: ArgKind,
+    optional: bool,
+}
+
+impl ArgSpec {
+    fn parse(&self, text: &str) -> Result<ArgValue, Error> {
+        let invalid = |what: &str| anyhow!(format!("<{}> should be {}, not '{}'", self.name, what, text));
+
+        match self.kind {
+            ArgKind::Integer => text.parse().map(ArgValue::Integer).map_err(|_| invalid("a whole number")),
+            ArgKind::Number => match text.parse::<f32>() {
+                Ok(number) if number.is_finite() => Ok(ArgValue::Number(number)),
+                _ => Err(invalid("a number"))
+            },
+            ArgKind::Coordinate => {
+                let (relative, number) = match text.strip_prefix('~') {
+                    Some(offset) => (true, offset),
+                    None => (false, text)
+                };
+
+                let value = match number {
+                    "" if relative => 0.0,
+                    _ => number.parse().ok()
+                        .filter(|value: &f32| value.is_finite())
+                        .ok_or_else(|| invalid("a number or ~"))?
+                };
+
+                // anything further out doesn't fit in a block position
+                if !(i32::MIN as f32..i32::MAX as f32).contains(&value) {
+                    return Err(invalid(&format!("between {} and {}", i32::MIN, i32::MAX)));
+                }
+
+                Ok(ArgValue::Coordinate { value, relative })
+            },
+            ArgKind::Block => {
+                if text == "air" {
+                    return Ok(ArgValue::Block(None));
+                }
+
+                let id = parse_identifier(text)?;
+
+                match Registry::current().get_block(&id) {
+                    Some(_) => Ok(ArgValue::Block(Some(id))),
+                    None => Err(anyhow!(format!("there's no block called '{}'", id.as_string())))
+                }
+            },
+            ArgKind::Word(_) => Ok(ArgValue::Word(text.to_string())),
+            ArgKind::Choice(choices) => {
+                if choices.contains(&text) {
+                    Ok(ArgValue::Word(text.to_string()))
+                } else {
+                    Err(invalid(&format!("one of {}", choices.join(", "))))
+                }
+            }
+        }
+    }
+
+    // what tab completion offers for this argument
+    fn get_suggestions(&self) -> Vec<String> {
+        match self.kind {
+            ArgKind::Coordinate => vec![String::from("~")],
+            ArgKind::Block => {
+                let mut suggestions = vec![String::from("air")];
+
+                for id in Registry::current().get_block_identifiers() {
+                    if id.get_namespace() == DEFAULT_NAMESPACE {
+                        suggestions.push(id.get_name());
+                    }
+
+                    suggestions.push(id.as_string());
+                }
+
+                suggestions
+            },
+            ArgKind::Word(words) | ArgKind::Choice(words) => words.iter().map(|word| word.to_string()).collect(),
+            ArgKind::Integer | ArgKind::Number => Vec::new()
+        }
+    }
+
+    fn get_usage(&self) -> String {
+        if self.optional {
+            format!("[{}]", self.name)
+        } else {
+            format!("<{}>", self.name)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgValue {
+    Integer(i64),
+    Number(f32),
+    Coordinate { value: f32, relative: bool },
+    /// `None` for air
+    Block(Option<Identifier>),
+    Word(String),
+}
+
+/// A command's parsed arguments, in the order it declared them. Optional ones left out are `None`.
+#[derive(Debug, Clone)]
+pub struct Args {
+    values: Vec<Option<ArgValue>>,
+}
+
+impl Args {
+    pub fn is_present(&self, index: usize) -> bool {
+        matches!(self.values.get(index), Some(Some(_)))
+    }
+
+    pub fn get(&self, index: usize) -> Option<&ArgValue> {
+        self.values.get(index).and_then(|value| value.as_ref())
+    }
+
+    pub fn get_integer(&self, index: usize) -> Option<i64> {
+        match self.get(index) {
+            Some(ArgValue::Integer(value)) => Some(*value),
+            _ => None
+        }
+    }
+
+    /// Integers count as numbers too
+    pub fn get_number(&self, index: usize) -> Option<f32> {
+        match self.get(index) {
+            Some(ArgValue::Number(value)) => Some(*value),
+            Some(ArgValue::Integer(value)) => Some(*value as f32),
+            _ => None
+        }
+    }
+
+    pub fn get_word(&self, index: usize) -> Option<&str> {
+        match self.get(index) {
+            Some(ArgValue::Word(word)) => Some(word),
+            _ => None
+        }
+    }
+
+    /// The block from the registry, `None` for air or if the argument isn't a block
+    pub fn get_block(&self, index: usize) -> Option<Block> {
+        match self.get(index) {
+            Some(ArgValue::Block(Some(id))) => Registry::current().get_block(id),
+            _ => None
+        }
+    }
+
+    /// Three coordinates starting at `index`, relative ones measured from `origin`
+    pub fn get_position(&self, index: usize, origin: Vector3<f32>) -> Option<Vector3<f32>> {
+        let mut position = [0.0; 3];
+
+        for (axis, (value, origin)) in position.iter_mut().zip([origin.x, origin.y, origin.z]).enumerate() {
+            *value = match self.get(index + axis) {
+                Some(ArgValue::Coordinate { value, relative: true }) => origin + value,
+                Some(ArgValue::Coordinate { value, relative: false }) => *value,
+                _ => return None
+            };
+        }
+
+        Some(Vector3::new(position[0], position[1], position[2]))
+    }
+
+    /// Like `get_position`, rounded down to the block the position is in
+    pub fn get_block_pos(&self, index: usize, origin: Vector3<f32>) -> Option<Vector3<i32>> {
+        self.get_position(index, origin)
+            .map(|position| Vector3::new(position.x.floor() as i32, position.y.floor() as i32, position.z.floor() as i32))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameMode {
+    /// Walking only
+    Survival,
+    /// Flying can be toggled
+    Creative,
+}
+
+impl GameMode {
+    pub const NAMES: &'static [&'static str] = &["survival", "creative"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "survival" => Some(GameMode::Survival),
+            "creative" => Some(GameMode::Creative),
+            _ => None
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            GameMode::Survival => "survival",
+            GameMode::Creative => "creative",
+        }
+    }
+}
+
+/// What a command wants done outside the world, by whoever ran it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommandEffect {
+    /// Moves whoever ran the command
+    Teleport(Vector3<f32>),
+    SetGameMode(GameMode),
+    /// In chunks, the world's own was already changed
+    SetRenderDistance(usize),
+}
+
+/// What a command runs against. Output and effects pile up until they're taken.
+pub struct CommandContext<'a> {
+    world: &'a mut World,
+    // where relative coordinates are measured from, usually whoever ran the command
+    origin: Vector3<f32>,
+
+    output: Vec<String>,
+    effects: Vec<CommandEffect>,
+}
+
+impl<'a> CommandContext<'a> {
+    pub fn new(world: &'a mut World, origin: Vector3<f32>) -> Self {
+        Self {
+            world,
+            origin,
+
+            output: Vec::new(),
+            effects: Vec::new(),
+        }
+    }
+
+    pub fn print(&mut self, line: impl Into<String>) {
+        self.output.push(line.into());
+    }
+
+    pub fn request(&mut self, effect: CommandEffect) {
+        self.effects.push(effect);
+    }
+
+    pub fn take_output(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.output)
+    }
+
+    pub fn take_effects(&mut self) -> Vec<CommandEffect> {
+        std::mem::take(&mut self.effects)
+    }
+}
+
+// getters
+impl<'a> CommandContext<'a> {
+    pub fn get_world(&self) -> &World { self.world }
+
+    pub fn get_world_mut(&mut self) -> &mut World { self.world }
+
+    pub fn get_origin(&self) -> Vector3<f32> { self.origin }
+}
+
+pub type CommandHandler = fn(&mut CommandContext, &Args) -> Result<(), Error>;
+
+pub struct Command {
+    name: String,
+    description: String,
+    args: Vec<ArgSpec>,
+    handler: CommandHandler,
+}
+
+impl Command {
+    pub fn new(name: &str, description: &str, handler: CommandHandler) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            args: Vec::new(),
+            handler,
+        }
+    }
+
+    pub fn with_arg(mut self, name: &'static str, kind: ArgKind) -> Self {
+        self.args.push(ArgSpec { name, kind, optional: false });
+        self
+    }
+
+    /// Optional arguments have to come after all the others
+    pub fn with_optional_arg(mut self, name: &'static str, kind: ArgKind) -> Self {
+        self.args.push(ArgSpec { name, kind, optional: true });
+        self
+    }
+
+    fn parse_args(&self, words: &[&str]) -> Result<Args, Error> {
+        let required = self.args.iter().filter(|arg| !arg.optional).count();
+
+        if words.len() < required || words.len() > self.args.len() {
+            return Err(anyhow!(format!("usage: {}", self.get_usage())));
+        }
+
+        let mut values = Vec::with_capacity(self.args.len());
+
+        for (index, arg) in self.args.iter().enumerate() {
+            values.push(match words.get(index) {
+                Some(word) => Some(arg.parse(word)?),
+                None => None
+            });
+        }
+
+        Ok(Args { values })
+    }
+}
+
+// getters
+impl Command {
+    pub fn get_name(&self) -> &str { &self.name }
+
+    pub fn get_description(&self) -> &str { &self.description }
+
+    /// Like `/setblock <x> <y> <z> <block>`
+    pub fn get_usage(&self) -> String {
+        let mut usage = format!("/{}", self.name);
+
+        for arg in &self.args {
+            usage.push(' ');
+            usage.push_str(&arg.get_usage());
+        }
+
+        usage
+    }
+}
+
+/// Every command that can be run, by name
+#[derive(Default)]
+pub struct CommandRegistry {
+    commands: BTreeMap<String, Command>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self { ..Default::default() }
+    }
+
+    pub fn register(&mut self, command: Command) -> Result<(), Error> {
+        if self.commands.contains_key(command.get_name()) {
+            return Err(anyhow!(format!("there's already a command called '/{}'", command.get_name())));
+        }
+
+        self.commands.insert(command.get_name().to_string(), command);
+        Ok(())
+    }
+
+    /// `/help`, `/tp`, `/seed`, `/setblock`, `/fill`, `/time`, `/gamemode`, `/renderdistance` and `/regen-chunk`
+    pub fn register_builtins(&mut self) -> Result<(), Error> {
+        // run by the registry itself, since it needs to see the other commands
+        self.register(Command::new("help", "Lists the commands, or how to use one", |_, _| Ok(()))
+            .with_optional_arg("command", ArgKind::Word(&[])))?;
+
+        self.register(Command::new("tp", "Teleports you", teleport)
+            .with_arg("x", ArgKind::Coordinate)
+            .with_arg("y", ArgKind::Coordinate)
+            .with_arg("z", ArgKind::Coordinate))?;
+
+        self.register(Command::new("seed", "Shows the world's seed", seed))?;
+
+        self.register(Command::new("setblock", "Changes a block", set_block)
+            .with_arg("x", ArgKind::Coordinate)
+            .with_arg("y", ArgKind::Coordinate)
+            .with_arg("z", ArgKind::Coordinate)
+            .with_arg("block", ArgKind::Block))?;
+
+        self.register(Command::new("fill", "Changes every block in a box", fill)
+            .with_arg("x1", ArgKind::Coordinate)
+            .with_arg("y1", ArgKind::Coordinate)
+            .with_arg("z1", ArgKind::Coordinate)
+            .with_arg("x2", ArgKind::Coordinate)
+            .with_arg("y2", ArgKind::Coordinate)
+            .with_arg("z2", ArgKind::Coordinate)
+            .with_arg("block", ArgKind::Block))?;
+
+        self.register(Command::new("time", "Shows, sets, moves on or freezes the time of day", time)
+            .with_arg("action", ArgKind::Choice(&["query", "set", "add", "freeze", "unfreeze"]))
+            .with_optional_arg("value", ArgKind::Word(&["day", "noon", "night", "midnight"])))?;
+
+        self.register(Command::new("gamemode", "Switches between walking only and being able to fly", game_mode)
+            .with_arg("mode", ArgKind::Choice(GameMode::NAMES)))?;
+
+        self.register(Command::new("renderdistance", "Sets how many chunks around you are loaded", render_distance)
+            .with_arg("chunks", ArgKind::Integer))?;
+
+        self.register(Command::new("regen-chunk", "Generates a chunk again, undoing every change to it", regen_chunk)
+            .with_optional_arg("x", ArgKind::Coordinate)
+            .with_optional_arg("y", ArgKind::Coordinate)
+            .with_optional_arg("z", ArgKind::Coordinate))?;
+
+        Ok(())
+    }
+
+    /// Runs a line like `/setblock ~ ~-1 ~ stone`, the slash being optional
+    pub fn execute(&self, line: &str, context: &mut CommandContext) -> Result<(), Error> {
+        let words: Vec<&str> = line.trim().trim_start_matches('/').split_whitespace().collect();
+
+        let (name, words) = match words.split_first() {
+            Some(split) => split,
+            None => return Ok(())
+        };
+
+        let command = match self.commands.get(*name) {
+            Some(command) => command,
+            None => return Err(anyhow!(format!("unknown command '/{}', try /help", name)))
+        };
+
+        let args = command.parse_args(words)?;
+
+        if command.get_name() == "help" {
+            return self.help(context, &args);
+        }
+
+        (command.handler)(context, &args)
+    }
+
+    fn help(&self, context: &mut CommandContext, args: &Args) -> Result<(), Error> {
+        match args.get_word(0).map(|name| name.trim_start_matches('/')) {
+            Some(name) => match self.commands.get(name) {
+                Some(command) => {
+                    context.print(command.get_usage());
+                    context.print(command.get_description());
+                },
+                None => return Err(anyhow!(format!("unknown command '/{}'", name)))
+            },
+            None => {
+                for command in self.commands.values() {
+                    context.print(format!("{} - {}", command.get_usage(), command.get_description()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Every way the last word of `line` could be finished, as whole lines, in order
+    pub fn complete(&self, line: &str) -> Vec<String> {
+        let words: Vec<&str> = line.trim_start().trim_start_matches('/').split(' ').collect();
+        let (last, before) = match words.split_last() {
+            Some(split) => split,
+            None => return Vec::new()
+        };
+
+        let candidates: Vec<String> = match before.split_first() {
+            // still typing the command's name
+            None => self.commands.keys().cloned().collect(),
+            Some((name, args)) => match self.commands.get(*name).and_then(|command| command.args.get(args.len())) {
+                Some(arg) => arg.get_suggestions(),
+                None => Vec::new()
+            }
+        };
+
+        let prefix = &line[..line.len() - last.len()];
+
+        let mut completions: Vec<String> = candidates.into_iter()
+            .filter(|candidate| candidate.starts_with(last))
+            .map(|candidate| format!("{}{}", prefix, candidate))
+            .collect();
+
+        completions.sort();
+        completions.dedup();
+
+        completions
+    }
+}
+
+// getters
+impl CommandRegistry {
+    pub fn get(&self, name: &str) -> Option<&Command> { self.commands.get(name) }
+
+    /// Sorted by name
+    pub fn get_commands(&self) -> impl Iterator<Item = &Command> { self.commands.values() }
+}
+
+/// Lines entered before, newest last, to go back through with the arrow keys
+#[derive(Debug, Default)]
+pub struct CommandHistory {
+    lines: Vec<String>,
+    // the line being looked at while going back through them
+    cursor: Option<usize>,
+}
+
+impl CommandHistory {
+    pub fn new() -> Self {
+        Self { ..Default::default() }
+    }
+
+    /// Remembers `line` unless it's empty or the same as the last one, and stops browsing
+    pub fn push(&mut self, line: &str) {
+        self.cursor = None;
+
+        let line = line.trim();
+
+        if line.is_empty() || self.lines.last().is_some_and(|last| last == line) {
+            return;
+        }
+
+        self.lines.push(line.to_string());
+
+        if self.lines.len() > MAX_HISTORY {
+            self.lines.remove(0);
+        }
+    }
+
+    /// One line further back, staying on the oldest. `None` without any history.
+    pub fn back(&mut self) -> Option<&str> {
+        if self.lines.is_empty() {
+            return None;
+        }
+
+        let cursor = match self.cursor {
+            Some(cursor) => cursor.saturating_sub(1),
+            None => self.lines.len() - 1
+        };
+
+        self.cursor = Some(cursor);
+        Some(&self.lines[cursor])
+    }
+
+    /// One line forward again. Going past the newest gives an empty line, and `None` if we weren't browsing.
+    pub fn forward(&mut self) -> Option<&str> {
+        let cursor = self.cursor?;
+
+        if cursor + 1 < self.lines.len() {
+            self.cursor = Some(cursor + 1);
+            Some(&self.lines[cursor + 1])
+        } else {
+            self.cursor = None;
+            Some("")
+        }
+    }
+
+    pub fn get_lines(&self) -> &[String] { &self.lines }
+}
+
+/// `stone` is short for `willekeurig:stone`
+pub fn parse_identifier(text: &str) -> Result<Identifier, Error> {
+    if text.contains(':') {
+        Identifier::from_str(text)
+    } else {
+        let id = Identifier::new(DEFAULT_NAMESPACE, text);
+        id.validate()?;
+
+        Ok(id)
+    }
+}
+
+// built-in commands
+
+fn teleport(context: &mut CommandContext, args: &Args) -> Result<(), Error> {
+    let position = get_position(context, args, 0)?;
+
+    context.request(CommandEffect::Teleport(position));
+    context.print(format!("Teleported to {:.1} {:.1} {:.1}", position.x, position.y, position.z));
+
+    Ok(())
+}
+
+fn seed(context: &mut CommandContext, _args: &Args) -> Result<(), Error> {
+    let seed = context.get_world().get_seed();
+    context.print(format!("Seed: {}", seed));
+
+    Ok(())
+}
+
+fn set_block(context: &mut CommandContext, args: &Args) -> Result<(), Error> {
+    let world_pos = get_block_pos(context, args, 0)?;
+    let block = args.get_block(3);
+
+    if !context.get_world_mut().set_block(world_pos, block) {
+        return Err(anyhow!(format!("the block at {} {} {} isn't loaded", world_pos.x, world_pos.y, world_pos.z)));
+    }
+
+    context.print(format!("Changed the block at {} {} {}", world_pos.x, world_pos.y, world_pos.z));
+    Ok(())
+}
+
+fn fill(context: &mut CommandContext, args: &Args) -> Result<(), Error> {
+    let from = get_block_pos(context, args, 0)?;
+    let to = get_block_pos(context, args, 3)?;
+    let block = args.get_block(6);
+
+    let min = Vector3::new(from.x.min(to.x), from.y.min(to.y), from.z.min(to.z));
+    let max = Vector3::new(from.x.max(to.x), from.y.max(to.y), from.z.max(to.z));
+
+    let volume = (max.x as i64 - min.x as i64 + 1) * (max.y as i64 - min.y as i64 + 1) * (max.z as i64 - min.z as i64 + 1);
+
+    if volume > MAX_FILL_VOLUME {
+        return Err(anyhow!(format!("that's {} blocks, at most {} can be filled at once", volume, MAX_FILL_VOLUME)));
+    }
+
+    let world = context.get_world_mut();
+    let mut changed = 0;
+
+    for x in min.x..=max.x {
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                // unloaded blocks are skipped rather than failing the whole fill
+                if world.set_block(Vector3::new(x, y, z), block.clone()) {
+                    changed += 1;
+                }
+            }
+        }
+    }
+
+    context.print(format!("Filled {} blocks", changed));
+    Ok(())
+}
+
+fn time(context: &mut CommandContext, args: &Args) -> Result<(), Error> {
+    let action = args.get_word(0).unwrap_or("query");
+    let value = args.get_word(1);
+
+    let world = context.get_world_mut();
+
+    match (action, value) {
+        ("query", _) => { },
+        ("set", Some(value)) => {
+            match value {
+                "day" => world.set_time_of_day(0.3),
+                "noon" => world.set_time_of_day(0.5),
+                "night" => world.set_time_of_day(0.8),
+                "midnight" => world.set_time_of_day(0.0),
+                ticks => match ticks.parse::<u64>() {
+                    Ok(ticks) => world.set_time(ticks),
+                    Err(_) => return Err(anyhow!(format!("'{}' isn't a time, try day, noon, night, midnight or a number of ticks", ticks)))
+                }
+            }
+        },
+        ("add", Some(value)) => {
+            let ticks = match value.parse::<u64>() {
+                Ok(ticks) => ticks,
+                Err(_) => return Err(anyhow!(format!("'{}' isn't a number of ticks", value)))
+            };
+
+            let current = world.get_time().get_ticks();
+
+            match current.checked_add(ticks) {
+                Some(time) => world.set_time(time),
+                None => return Err(anyhow!(format!("adding {} ticks would run past the end of time", ticks)))
+            }
+        },
+        ("freeze", _) => world.set_time_frozen(true),
+        ("unfreeze", _) => world.set_time_frozen(false),
+        (action, None) => return Err(anyhow!(format!("usage: /time {} <value>", action))),
+        (action, _) => return Err(anyhow!(format!("unknown action '{}'", action)))
+    }
+
+    let time = *world.get_time();
+    let ticks_into_day = time.get_ticks() % TICKS_PER_DAY;
+
+    context.print(format!("Day {}, tick {} of {}{}", time.get_day(), ticks_into_day, TICKS_PER_DAY,
+        if time.is_frozen() { " (frozen)" } else { "" }));
+
+    Ok(())
+}
+
+fn game_mode(context: &mut CommandContext, args: &Args) -> Result<(), Error> {
+    let mode = match args.get_word(0).and_then(GameMode::from_name) {
+        Some(mode) => mode,
+        None => return Err(anyhow!("unknown game mode"))
+    };
+
+    context.request(CommandEffect::SetGameMode(mode));
+    context.print(format!("Switched to {} mode", mode.get_name()));
+
+    Ok(())
+}
+
+fn render_distance(context: &mut CommandContext, args: &Args) -> Result<(), Error> {
+    let chunks = args.get_integer(0).unwrap_or(0);
+
+    if !(1..=MAX_DRAW_DISTANCE as i64).contains(&chunks) {
+        return Err(anyhow!(format!("the render distance has to be between 1 and {} chunks", MAX_DRAW_DISTANCE)));
+    }
+
+    context.get_world_mut().set_render_distance(chunks as usize);
+    context.request(CommandEffect::SetRenderDistance(chunks as usize));
+    context.print(format!("Render distance set to {} chunks", chunks));
+
+    Ok(())
+}
+
+fn regen_chunk(context: &mut CommandContext, args: &Args) -> Result<(), Error> {
+    // either all three coordinates or none
+    let world_pos = if args.is_present(0) {
+        get_block_pos(context, args, 0)?
+    } else {
+        let origin = context.get_origin();
+        Vector3::new(origin.x.floor() as i32, origin.y.floor() as i32, origin.z.floor() as i32)
+    };
+
+    let chunk_pos = chunk::world_to_chunk_pos(world_pos);
+
+    if !context.get_world_mut().regenerate_chunk(chunk_pos) {
+        return Err(anyhow!(format!("chunk [{},{},{}] isn't loaded", chunk_pos.x, chunk_pos.y, chunk_pos.z)));
+    }
+
+    context.print(format!("Regenerated chunk [{},{},{}]", chunk_pos.x, chunk_pos.y, chunk_pos.z));
+    Ok(())
+}
+
+fn get_position(context: &CommandContext, args: &Args, index: usize) -> Result<Vector3<f32>, Error> {
+    match args.get_position(index, context.get_origin()) {
+        Some(position) => Ok(position),
+        None => Err(anyhow!("expected an x, y and z coordinate"))
+    }
+}
+
+fn get_block_pos(context: &CommandContext, args: &Args, index: usize) -> Result<Vector3<i32>, Error> {
+    match args.get_block_pos(index, context.get_origin()) {
+        Some(world_pos) => Ok(world_pos),
+        None => Err(anyhow!("expected an x, y and z coordinate"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use common::identifier::Identifier;
+
+    use crate::{World, blocks, time::TICKS_PER_DAY};
+    use super::{CommandContext, CommandEffect, CommandHistory, CommandRegistry, GameMode};
+
+    // a headless world with the chunks around the origin generated
+    fn world() -> World {
+        blocks::register_blocks().unwrap();
+
+        let mut world = World::new(1234, 1);
+        world.set_build_meshes(false);
+
+        let origin = Vector3::new(0.5, 8.0, 0.5);
+        world.load_chunks_around(&[origin]);
+
+        for _ in 0..16 {
+            world.update(origin, 0.0);
+        }
+
+        world
+    }
+
+    fn registry() -> CommandRegistry {
+        let mut registry = CommandRegistry::new();
+        registry.register_builtins().unwrap();
+
+        registry
+    }
+
+    fn block_at(world: &World, x: i32, y: i32, z: i32) -> Option<Identifier> {
+        world.get_block(Vector3::new(x, y, z)).map(|block| block.get_identifier().clone())
+    }
+
+    #[test]
+    fn commands_change_the_world() {
+        let registry = registry();
+        let mut world = world();
+        let stone = Some(Identifier::from_str("willekeurig:stone").unwrap());
+        let generated = block_at(&world, 2, 3, 2);
+
+        let mut context = CommandContext::new(&mut world, Vector3::new(1.5, 2.5, 1.5));
+
+        registry.execute("/setblock ~1 ~1 ~1 stone", &mut context).unwrap();
+        registry.execute("/fill 4 0 4 5 1 5 willekeurig:dirt", &mut context).unwrap();
+        registry.execute("/time set noon", &mut context).unwrap();
+        registry.execute("time freeze", &mut context).unwrap();
+
+        assert!(registry.execute("/setblock 0 0 0 cheese", &mut context).is_err());
+        assert!(registry.execute("/setblock 0 0", &mut context).is_err());
+        assert!(registry.execute("/fly", &mut context).is_err());
+        assert!(registry.execute("/fill 0 0 0 100 100 100 air", &mut context).is_err());
+        assert!(registry.execute("/fill -2000000000 0 0 2000000000 0 0 air", &mut context).is_err());
+        assert!(registry.execute("/fill 0 0 0 3000000000 0 0 air", &mut context).is_err());
+        assert!(registry.execute("/time add 18446744073709551615", &mut context).is_err());
+
+        let world = context.get_world();
+
+        assert_eq!(block_at(world, 2, 3, 2), stone);
+        assert_eq!(block_at(world, 5, 1, 4), Some(Identifier::from_str("willekeurig:dirt").unwrap()));
+        assert_eq!(world.get_time().get_ticks() % TICKS_PER_DAY, TICKS_PER_DAY / 2);
+        assert!(world.get_time().is_frozen());
+
+        // back the way it was generated, as one replaced chunk rather than a pile of block changes
+        context.get_world_mut().set_track_block_changes(true);
+        registry.execute("/regen-chunk 2 3 2", &mut context).unwrap();
+        assert_eq!(block_at(context.get_world(), 2, 3, 2), generated);
+        assert!(context.get_world_mut().take_block_changes().is_empty());
+        assert_eq!(context.get_world_mut().take_replaced_chunks(), vec![Vector3::new(0, 0, 0)]);
+        assert!(registry.execute("/regen-chunk 1000 0 0", &mut context).is_err());
+    }
+
+    #[test]
+    fn commands_ask_for_effects() {
+        let registry = registry();
+        let mut world = world();
+
+        let mut context = CommandContext::new(&mut world, Vector3::new(10.0, 20.0, 30.0));
+
+        registry.execute("/tp ~ ~5 -3", &mut context).unwrap();
+        registry.execute("/gamemode survival", &mut context).unwrap();
+        registry.execute("/renderdistance 3", &mut context).unwrap();
+        registry.execute("/seed", &mut context).unwrap();
+        registry.execute("/help tp", &mut context).unwrap();
+
+        assert!(registry.execute("/renderdistance 0", &mut context).is_err());
+        assert!(registry.execute("/gamemode hardcore", &mut context).is_err());
+        assert!(registry.execute("/tp nan 0 0", &mut context).is_err());
+        assert!(registry.execute("/tp ~inf 0 0", &mut context).is_err());
+
+        assert_eq!(context.take_effects(), vec![
+            CommandEffect::Teleport(Vector3::new(10.0, 25.0, -3.0)),
+            CommandEffect::SetGameMode(GameMode::Survival),
+            CommandEffect::SetRenderDistance(3),
+        ]);
+
+        let output = context.take_output();
+        assert!(output.iter().any(|line| line == "Seed: 1234"));
+        assert_eq!(output.last().map(|line| line.as_str()), Some("Teleports you"));
+        assert_eq!(context.get_world().get_render_distance(), 3);
+    }
+
+    #[test]
+    fn completes_names_and_arguments() {
+        blocks::register_blocks().unwrap();
+        let registry = registry();
+
+        assert_eq!(registry.complete("/se"), vec!["/seed", "/setblock"]);
+        assert_eq!(registry.complete("/gamemode c"), vec!["/gamemode creative"]);
+        assert_eq!(registry.complete("/setblock 1 2 3 st"), vec!["/setblock 1 2 3 stone"]);
+        assert_eq!(registry.complete("/setblock 1 2 3 willekeurig:d"), vec!["/setblock 1 2 3 willekeurig:dirt"]);
+        assert_eq!(registry.complete("/tp "), vec!["/tp ~"]);
+        assert!(registry.complete("/seed 1").is_empty());
+    }
+
+    #[test]
+    fn history_goes_back_and_forth() {
+        let mut history = CommandHistory::new();
+        assert_eq!(history.back(), None);
+
+        history.push("/seed");
+        history.push("/seed");
+        history.push("  ");
+        history.push("/tp 0 0 0");
+
+        assert_eq!(history.get_lines().len(), 2);
+        assert_eq!(history.forward(), None);
+        assert_eq!(history.back(), Some("/tp 0 0 0"));
+        assert_eq!(history.back(), Some("/seed"));
+        assert_eq!(history.back(), Some("/seed"));
+        assert_eq!(history.forward(), Some("/tp 0 0 0"));
+        assert_eq!(history.forward(), Some(""));
+        assert_eq!(history.forward(), None);
+    }
+}
@@ -0,0 +1,196 @@
+use anyhow::{Result, Error};
+use cgmath::{Vector3, Zero};
+
+use common::{identifier::Identifier, serialization::{ByteReader, ByteWriter}};
+use physics::{aabb::Aabb, box_collider::BoxCollider, broadphase::ColliderHandle};
+
+use crate::chunk_manager::ChunkManager;
+
+// in blocks per second (squared)
+pub const GRAVITY: f32 = -28.;
+pub const TERMINAL_VELOCITY: f32 = -60.;
+
+/// Every player's collider, whether it's ours or someone else's over the network
+pub const PLAYER_SIZE: Vector3<f32> = Vector3::new(0.6, 1.8, 0.6);
+
+pub fn player_kind() -> Identifier {
+    Identifier::new("willekeurig", "player")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EntityId(pub u64);
+
+/// Per-entity logic (AI, despawn timers, ...). Runs every update before the
+/// entity is moved, so it can steer by changing the body's velocity.
+pub trait EntityBehaviour: Send {
+    fn update(&mut self, body: &mut EntityBody, chunk_manager: &ChunkManager, delta_time: f32);
+
+    /// Extra state written after the body when the entity is saved with its chunk
+    fn save(&self, _writer: &mut ByteWriter) { }
+}
+
+/// Reads back what `EntityBehaviour::save` wrote, registered per entity kind
+pub type EntityLoader = fn(&mut ByteReader) -> Result<Box<dyn EntityBehaviour>, Error>;
+
+/// The physical part of an entity, moved by the entity manager every update
+#[derive(Debug, Clone)]
+pub struct EntityBody {
+    pub collider: BoxCollider,
+    /// Collider position before the last update, for interpolating
+    pub previous_position: Vector3<f32>,
+    /// In blocks per second
+    pub velocity: Vector3<f32>,
+
+    pub on_ground: bool,
+    pub has_gravity: bool,
+    /// Highest ledge the entity walks up without jumping
+    pub step_height: f32,
+
+    /// Set to have the entity despawned after this update
+    pub removed: bool,
+}
+
+impl EntityBody {
+    pub fn new(position: Vector3<f32>, size: Vector3<f32>) -> Self {
+        Self {
+            collider: BoxCollider::new(position, size, Vector3::zero()),
+            previous_position: position,
+            velocity: Vector3::zero(),
+
+            on_ground: false,
+            has_gravity: true,
+            step_height: 0.,
+
+            removed: false,
+        }
+    }
+
+    pub fn get_position(&self) -> Vector3<f32> { self.collider.position }
+
+    pub fn get_size(&self) -> Vector3<f32> { self.collider.size }
+
+    pub fn get_aabb(&self) -> Aabb { Aabb::from_collider(&self.collider) }
+
+    /// Moves the entity without interpolating from where it was (teleports, respawns, ...)
+    pub fn set_position(&mut self, position: Vector3<f32>) {
+        self.collider.position = position;
+        self.previous_position = position;
+    }
+
+    pub fn get_interpolated_position(&self, alpha: f32) -> Vector3<f32> {
+        self.previous_position + (self.collider.position - self.previous_position) * alpha
+    }
+}
+
+pub struct Entity {
+    id: EntityId,
+    kind: Identifier,
+
+    pub body: EntityBody,
+    behaviour: Option<Box<dyn EntityBehaviour>>,
+
+    // persistent entities are saved with the chunk they're in, the rest
+    // (players) are the responsibility of whoever spawned them
+    persistent: bool,
+    collider_handle: Option<ColliderHandle>,
+}
+
+impl Entity {
+    pub fn new(kind: Identifier, body: EntityBody) -> Self {
+        Self {
+            id: EntityId(0),
+            kind,
+
+            body,
+            behaviour: None,
+
+            persistent: true,
+            collider_handle: None,
+        }
+    }
+
+    pub fn with_behaviour(mut self, behaviour: Box<dyn EntityBehaviour>) -> Self {
+        self.behaviour = Some(behaviour);
+        self
+    }
+
+    pub fn with_persistence(mut self, persistent: bool) -> Self {
+        self.persistent = persistent;
+        self
+    }
+
+    pub fn get_id(&self) -> EntityId { self.id }
+
+    pub fn get_kind(&self) -> &Identifier { &self.kind }
+
+    pub fn is_persistent(&self) -> bool { self.persistent }
+
+    pub(crate) fn set_id(&mut self, id: EntityId) { self.id = id; }
+
+    pub(crate) fn get_collider_handle(&self) -> Option<ColliderHandle> { self.collider_handle }
+
+    pub(crate) fn set_collider_handle(&mut self, handle: Option<ColliderHandle>) { self.collider_handle = handle; }
+
+    pub(crate) fn run_behaviour(&mut self, chunk_manager: &ChunkManager, delta_time: f32) {
+        if let Some(behaviour) = &mut self.behaviour {
+            behaviour.update(&mut self.body, chunk_manager, delta_time);
+        }
+    }
+}
+
+// persistence
+impl Entity {
+    pub fn write_to(&self, writer: &mut ByteWriter) {
+        writer.write_identifier(&self.kind);
+
+        for value in &[self.body.collider.position, self.body.collider.size, self.body.velocity] {
+            writer.write_f32(value.x);
+            writer.write_f32(value.y);
+            writer.write_f32(value.z);
+        }
+
+        writer.write_bool(self.body.has_gravity);
+        writer.write_f32(self.body.step_height);
+
+        let mut behaviour_writer = ByteWriter::new();
+
+        if let Some(behaviour) = &self.behaviour {
+            behaviour.save(&mut behaviour_writer);
+        }
+
+        writer.write_bool(self.behaviour.is_some());
+        writer.write_bytes(&behaviour_writer.into_bytes());
+    }
+
+    /// `loader` is whatever was registered for the entity's kind, if anything
+    pub fn read_from(reader: &mut ByteReader, loader: impl Fn(&Identifier) -> Option<EntityLoader>) -> Result<Self, Error> {
+        let kind = reader.read_identifier()?;
+
+        let mut read_vec = || -> Result<Vector3<f32>, Error> {
+            Ok(Vector3::new(reader.read_f32()?, reader.read_f32()?, reader.read_f32()?))
+        };
+
+        let position = read_vec()?;
+        let size = read_vec()?;
+        let velocity = read_vec()?;
+
+        let mut body = EntityBody::new(position, size);
+        body.velocity = velocity;
+        body.has_gravity = reader.read_bool()?;
+        body.step_height = reader.read_f32()?;
+
+        let has_behaviour = reader.read_bool()?;
+        let behaviour_bytes = reader.read_bytes()?;
+
+        let mut entity = Self::new(kind, body);
+
+        if has_behaviour {
+            match loader(&entity.kind) {
+                Some(load) => entity.behaviour = Some(load(&mut ByteReader::new(behaviour_bytes))?),
+                None => eprintln!("[LOG] No loader registered for entity kind '{}', loading it without behaviour", entity.kind.as_string())
+            }
+        }
+
+        Ok(entity)
+    }
+}
@@ -0,0 +1,258 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{Result, Error};
+use cgmath::Vector3;
+
+use common::{identifier::Identifier, serialization::{ByteReader, ByteWriter}};
+use physics::{broadphase::SpatialHash, voxel_collision};
+
+use crate::{ColliderOwner, chunk, chunk_manager::ChunkManager, entity::{self, Entity, EntityId, EntityLoader}};
+
+/// Owns every loaded entity, indexed by the chunk it's standing in
+pub struct EntityManager {
+    next_id: u64,
+
+    entities: HashMap<EntityId, Entity>,
+    chunk_entities: HashMap<Vector3<i32>, HashSet<EntityId>>,
+
+    loaders: HashMap<Identifier, EntityLoader>,
+}
+
+impl EntityManager {
+    pub fn new() -> Self {
+        Self {
+            next_id: 1,
+
+            entities: HashMap::new(),
+            chunk_entities: HashMap::new(),
+
+            loaders: HashMap::new(),
+        }
+    }
+
+    /// Lets entities of the given kind get their behaviour back when their chunk is loaded
+    pub fn register_loader(&mut self, kind: Identifier, loader: EntityLoader) {
+        self.loaders.insert(kind, loader);
+    }
+
+    pub fn len(&self) -> usize { self.entities.len() }
+
+    pub fn is_empty(&self) -> bool { self.entities.is_empty() }
+
+    pub fn get(&self, id: EntityId) -> Option<&Entity> { self.entities.get(&id) }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut Entity> { self.entities.get_mut(&id) }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Entity> { self.entities.values() }
+
+    pub fn get_entities_in_chunk(&self, chunk_pos: Vector3<i32>) -> Vec<EntityId> {
+        match self.chunk_entities.get(&chunk_pos) {
+            Some(ids) => ids.iter().copied().collect(),
+            None => Vec::new()
+        }
+    }
+
+    pub fn spawn(&mut self, mut entity: Entity, colliders: &mut SpatialHash<ColliderOwner>) -> EntityId {
+        let id = EntityId(self.next_id);
+        self.next_id += 1;
+
+        entity.set_id(id);
+        entity.set_collider_handle(Some(colliders.insert(entity.body.get_aabb(), ColliderOwner::Entity(id))));
+
+        self.chunk_entities.entry(entity_chunk(&entity)).or_default().insert(id);
+        self.entities.insert(id, entity);
+
+        id
+    }
+
+    pub fn despawn(&mut self, id: EntityId, colliders: &mut SpatialHash<ColliderOwner>) -> Option<Entity> {
+        let mut entity = self.entities.remove(&id)?;
+
+        if let Some(handle) = entity.get_collider_handle() {
+            colliders.remove(handle);
+            entity.set_collider_handle(None);
+        }
+
+        let chunk_pos = entity_chunk(&entity);
+
+        if let Some(ids) = self.chunk_entities.get_mut(&chunk_pos) {
+            ids.remove(&id);
+
+            if ids.is_empty() {
+                self.chunk_entities.remove(&chunk_pos);
+            }
+        }
+
+        Some(entity)
+    }
+
+    /// Runs behaviours and moves every entity whose chunk is loaded.
+    /// Entities in chunks that are still generating wait, so they don't fall through the ground.
+    pub fn update(&mut self, chunk_manager: &ChunkManager, colliders: &mut SpatialHash<ColliderOwner>, delta_time: f32) {
+        let mut removed = Vec::new();
+        let mut moved_chunks = Vec::new();
+
+        for (id, entity) in self.entities.iter_mut() {
+            let old_chunk = entity_chunk(entity);
+
+            if chunk_manager.get_chunk(old_chunk).is_none() {
+                continue;
+            }
+
+            entity.body.previous_position = entity.body.get_position();
+            entity.run_behaviour(chunk_manager, delta_time);
+
+            let body = &mut entity.body;
+
+            if body.has_gravity {
+                body.velocity.y = (body.velocity.y + entity::GRAVITY * delta_time).max(entity::TERMINAL_VELOCITY);
+            }
+
+            let step_height = if body.has_gravity { body.step_height } else { 0. };
+
+            let result = voxel_collision::move_and_slide(
+                &body.collider, body.velocity * delta_time, step_height,
+                |x, y, z| chunk_manager.is_solid(Vector3::new(x, y, z))
+            );
+
+            body.collider.position = result.position;
+            body.velocity = result.clip_velocity(body.velocity);
+            body.on_ground = result.grounded;
+
+            if let Some(handle) = entity.get_collider_handle() {
+                colliders.move_collider(handle, entity.body.get_aabb());
+            }
+
+            let new_chunk = entity_chunk(entity);
+
+            if new_chunk != old_chunk {
+                moved_chunks.push((*id, old_chunk, new_chunk));
+            }
+
+            if entity.body.removed {
+                removed.push(*id);
+            }
+        }
+
+        for (id, old_chunk, new_chunk) in moved_chunks {
+            if let Some(ids) = self.chunk_entities.get_mut(&old_chunk) {
+                ids.remove(&id);
+
+                if ids.is_empty() {
+                    self.chunk_entities.remove(&old_chunk);
+                }
+            }
+
+            self.chunk_entities.entry(new_chunk).or_default().insert(id);
+        }
+
+        for id in removed {
+            self.despawn(id, colliders);
+        }
+    }
+}
+
+// persistence
+impl EntityManager {
+    /// Writes the persistent entities in a chunk without unloading them
+    pub fn write_chunk_entities(&self, chunk_pos: Vector3<i32>, writer: &mut ByteWriter) {
+        let entities: Vec<&Entity> = self.get_entities_in_chunk(chunk_pos).iter()
+            .filter_map(|id| self.entities.get(id))
+            .filter(|entity| entity.is_persistent())
+            .collect();
+
+        writer.write_u32(entities.len() as u32);
+
+        for entity in entities {
+            entity.write_to(writer);
+        }
+    }
+
+    /// Writes the persistent entities in a chunk and despawns them, for when the chunk unloads
+    pub fn unload_chunk_entities(&mut self, chunk_pos: Vector3<i32>, writer: &mut ByteWriter, colliders: &mut SpatialHash<ColliderOwner>) {
+        self.write_chunk_entities(chunk_pos, writer);
+
+        for id in self.get_entities_in_chunk(chunk_pos) {
+            let persistent = self.entities.get(&id).is_some_and(|entity| entity.is_persistent());
+
+            if persistent {
+                self.despawn(id, colliders);
+            }
+        }
+    }
+
+    /// Spawns the entities written by `write_chunk_entities`
+    pub fn load_chunk_entities(&mut self, reader: &mut ByteReader, colliders: &mut SpatialHash<ColliderOwner>) -> Result<Vec<EntityId>, Error> {
+        let count = reader.read_u32()?;
+        let mut ids = Vec::with_capacity(count as usize);
+
+        for _ in 0..count {
+            let entity = Entity::read_from(reader, |kind| self.loaders.get(kind).copied())?;
+
+            ids.push(self.spawn(entity, colliders));
+        }
+
+        Ok(ids)
+    }
+}
+
+impl Default for EntityManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// the chunk an entity belongs to is the one its feet are in
+fn entity_chunk(entity: &Entity) -> Vector3<i32> {
+    let position = entity.body.get_position();
+    let size = entity.body.get_size();
+
+    chunk::world_to_chunk_pos(Vector3::new(
+        (position.x + size.x * 0.5).floor() as i32,
+        position.y.floor() as i32,
+        (position.z + size.z * 0.5).floor() as i32
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use common::{identifier::Identifier, serialization::{ByteReader, ByteWriter}};
+    use physics::broadphase::SpatialHash;
+
+    use crate::entity::{Entity, EntityBody};
+    use super::EntityManager;
+
+    #[test]
+    fn persistent_entities_unload_with_their_chunk() {
+        let mut colliders = SpatialHash::default();
+        let mut entities = EntityManager::new();
+
+        let kind = Identifier::from_str("willekeurig:test").unwrap();
+        let body = EntityBody::new(Vector3::new(20., 3., -5.), Vector3::new(0.5, 0.5, 0.5));
+
+        entities.spawn(Entity::new(kind.clone(), body.clone()), &mut colliders);
+        entities.spawn(Entity::new(kind.clone(), body).with_persistence(false), &mut colliders);
+
+        let chunk_pos = Vector3::new(1, 0, -1);
+        assert_eq!(entities.get_entities_in_chunk(chunk_pos).len(), 2);
+
+        let mut writer = ByteWriter::new();
+        entities.unload_chunk_entities(chunk_pos, &mut writer, &mut colliders);
+
+        // only the non-persistent one is left behind
+        assert_eq!(entities.len(), 1);
+        assert_eq!(colliders.len(), 1);
+
+        let bytes = writer.into_bytes();
+        let ids = entities.load_chunk_entities(&mut ByteReader::new(&bytes), &mut colliders).unwrap();
+
+        assert_eq!(ids.len(), 1);
+
+        let loaded = entities.get(ids[0]).unwrap();
+        assert_eq!(loaded.get_kind(), &kind);
+        assert_eq!(loaded.body.get_position(), Vector3::new(20., 3., -5.));
+        assert!(loaded.is_persistent());
+    }
+}
@@ -1,10 +1,23 @@
-use std::{collections::HashMap, sync::{Arc, RwLock}};
+use std::collections::HashMap;
 
 use cgmath::Vector3;
 use chunk::Chunk;
-use wgpu::Device;
+use rand::{Rng, SeedableRng, rngs::StdRng};
 
-use self::chunk_manager::ChunkManager;
+use anyhow::{Result, Error, anyhow};
+use common::{block::Block, identifier::Identifier, registry::Registry, serialization::{ByteReader, ByteWriter}};
+use physics::{aabb::Aabb, broadphase::{ColliderHandle, SpatialHash}};
+
+use self::{
+    chunk::ChunkData,
+    chunk_manager::ChunkManager,
+    entity::{Entity, EntityId, EntityLoader},
+    entity_manager::EntityManager,
+    pathfinding::{NavRegion, PathRequest, PathResult, PendingPath},
+    storage::WorldStorage,
+    tick::{TickClock, WorldTickContext},
+    time::WorldTime,
+};
 
 /*  -== MODULES START ==-  */
 
@@ -13,14 +26,49 @@ pub mod chunk;
 pub mod chunk_manager;
 pub mod block_culling;
 pub mod transform;
+pub mod tick;
+pub mod time;
+pub mod entity;
+pub mod entity_manager;
+pub mod storage;
+pub mod pathfinding;
+pub mod blocks;
+pub mod commands;
 
 /*  -== MODULES END ==-  */
 
+/// What a collider in the world's broadphase belongs to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColliderOwner {
+    Chunk(Vector3<i32>),
+    Entity(EntityId),
+}
+
 pub struct World {
     chunk_manager: ChunkManager,
     seed: u32,
 
-    player_last_chunk: Vector3<i32>,
+    // chunks the areas were loaded around last time, so they're only updated when someone crosses into a new chunk
+    last_center_chunks: Vec<Vector3<i32>>,
+    // remote worlds mirror a server, which does the generating and ticking
+    is_remote: bool,
+
+    entities: EntityManager,
+    storage: WorldStorage,
+
+    tick_clock: TickClock,
+    tick_rng: StdRng,
+
+    time: WorldTime,
+    // set or frozen since the last call to take_time_changed, so it can be sent elsewhere
+    time_changed: bool,
+
+    colliders: SpatialHash<ColliderOwner>,
+    chunk_collider_handles: HashMap<Vector3<i32>, Vec<ColliderHandle>>,
+
+    // meshes that changed since whoever draws the world last asked
+    remeshed_chunks: Vec<Vector3<i32>>,
+    unloaded_chunks: Vec<Vector3<i32>>,
     
     //spawn_pos: Vector3<i32>,
     render_distance: usize,
@@ -30,47 +78,141 @@ pub const MAP_W: usize = 256;
 pub const MAP_H: usize = 64;
 
 impl World {
+    /// A world whose unloaded chunks are only kept in memory
     pub fn new(seed: u32, render_distance: usize) -> Self {
+        Self::with_storage(seed, render_distance, WorldStorage::in_memory())
+    }
+
+    /// A world mirroring a server's. Its chunks come from `insert_chunk` and it doesn't run block ticks.
+    pub fn remote(render_distance: usize) -> Self {
+        let mut world = Self::new(0, render_distance);
+        world.is_remote = true;
+
+        world
+    }
+
+    pub fn with_storage(seed: u32, render_distance: usize, storage: WorldStorage) -> Self {
         //let mut rng = StdRng::seed_from_u64(seed as u64);
 
         //let x = rng.gen_range(0..255);
         //let z = rng.gen_range(0..255);
 
+        let time = match load_time(&storage) {
+            Ok(time) => time.unwrap_or_default(),
+            Err(err) => {
+                eprintln!("[LOG] {}, starting a new day", err);
+                WorldTime::default()
+            }
+        };
+
         Self {
             chunk_manager: ChunkManager::new(),
             seed,
 
-            player_last_chunk: Vector3::new(0, 0, 0),
+            last_center_chunks: Vec::new(),
+            is_remote: false,
+
+            entities: EntityManager::new(),
+            storage,
+
+            tick_clock: TickClock::new(0),
+            tick_rng: StdRng::seed_from_u64(seed as u64),
+
+            time,
+            time_changed: false,
+
+            colliders: SpatialHash::default(),
+            chunk_collider_handles: HashMap::new(),
+
+            remeshed_chunks: Vec::new(),
+            unloaded_chunks: Vec::new(),
             //spawn_pos: Vector3::new(x, 0, z),
             render_distance,
         }
     }
 
     pub fn create_or_destroy_chunks(&mut self, player_pos: &Vector3<f32>) {
+        self.load_chunks_around(&[*player_pos]);
+    }
+
+    /// In chunks. Takes effect the next time chunks are loaded around someone.
+    pub fn set_render_distance(&mut self, render_distance: usize) {
+        self.render_distance = render_distance.max(1);
+        self.last_center_chunks.clear();
+    }
+
+    /// Keeps the chunks within render distance of any of `centers` loaded, and unloads the rest
+    pub fn load_chunks_around(&mut self, centers: &[Vector3<f32>]) {
         //println!("[create_destroy_chunks] test");
         
         let dist_min = -(self.render_distance as i32);
         let dist_max = self.render_distance as i32;
 
-        let player_chunk = self.chunk_manager.world_to_chunk_coords(player_pos);
-        
-        if player_chunk != self.player_last_chunk {
+        let center_chunks: Vec<Vector3<i32>> = centers.iter()
+            .map(|center| self.chunk_manager.world_to_chunk_coords(center))
+            .collect();
+
+        if center_chunks == self.last_center_chunks {
+            return;
+        }
+
+        for center_chunk in &center_chunks {
             for c_z in dist_min..dist_max {
                 for c_x in dist_min..dist_max {
                     for c_y in dist_min..dist_max {
                         let chunk_pos = Vector3::new(
-                            c_x + player_chunk.x,
-                            c_y + player_chunk.y,
-                            c_z + player_chunk.z
+                            c_x + center_chunk.x,
+                            c_y + center_chunk.y,
+                            c_z + center_chunk.z
                         );
 
-                        self.chunk_manager.add_chunk(Chunk::new(chunk_pos));
+                        if !self.chunk_manager.contains_chunk(chunk_pos) {
+                            let chunk = self.load_or_create_chunk(chunk_pos);
+                            self.chunk_manager.add_chunk(chunk);
+                        }
                     }
                 }
             }
         }
 
-        self.player_last_chunk = player_chunk;
+        let in_range = |pos: i32, center: i32| pos >= center + dist_min && pos < center + dist_max;
+
+        for chunk_pos in self.chunk_manager.get_all_chunk_positions() {
+            let is_needed = center_chunks.iter().any(|center| {
+                in_range(chunk_pos.x, center.x) && in_range(chunk_pos.y, center.y) && in_range(chunk_pos.z, center.z)
+            });
+
+            if !is_needed {
+                if let Err(err) = self.unload_chunk(chunk_pos) {
+                    eprintln!("[LOG] {}", err);
+                }
+            }
+        }
+
+        self.last_center_chunks = center_chunks;
+    }
+
+    /// Replaces a chunk with one received from elsewhere (a server), without generating it
+    pub fn insert_chunk(&mut self, chunk_data: ChunkData) {
+        let chunk_pos = chunk_data.get_pos();
+
+        self.remove_chunk(chunk_pos);
+        self.chunk_manager.add_chunk(Chunk::from_chunk_data(chunk_data));
+    }
+
+    /// Drops a chunk without saving it
+    pub fn remove_chunk(&mut self, chunk_pos: Vector3<i32>) -> bool {
+        if self.chunk_manager.remove_chunk(chunk_pos).is_none() {
+            return false;
+        }
+
+        self.remove_chunk_colliders(chunk_pos);
+
+        if self.chunk_manager.builds_meshes() {
+            self.unloaded_chunks.push(chunk_pos);
+        }
+
+        true
     }
 
     /*pub fn get_chunks_loading(&self) -> usize {
@@ -95,8 +237,325 @@ impl World {
         self.chunk_manager.update(device, player_pos, self.render_distance);
     }*/
 
-    pub fn update(&mut self, device: Arc<RwLock<Device>>, player_pos: Vector3<f32>) {
-        self.chunk_manager.update(device, player_pos, self.seed);
+    pub fn update(&mut self, player_pos: Vector3<f32>, delta_time: f32) {
+        let rebuilt_chunks = self.chunk_manager.update(player_pos, self.seed);
+
+        for chunk_pos in &rebuilt_chunks {
+            self.register_chunk_colliders(*chunk_pos);
+        }
+
+        if self.chunk_manager.builds_meshes() {
+            self.remeshed_chunks.extend(rebuilt_chunks);
+        }
+
+        self.entities.update(&self.chunk_manager, &mut self.colliders, delta_time);
+
+        for _ in 0..self.tick_clock.advance(delta_time) {
+            if self.is_remote {
+                // keeps going between the server's time updates
+                self.tick_clock.increment();
+                self.time.advance();
+            } else {
+                self.tick();
+            }
+        }
+    }
+
+    fn remove_chunk_colliders(&mut self, chunk_pos: Vector3<i32>) {
+        if let Some(handles) = self.chunk_collider_handles.remove(&chunk_pos) {
+            for handle in handles {
+                self.colliders.remove(handle);
+            }
+        }
+    }
+
+    // swap a chunk's old colliders in the broadphase for the ones from its latest build
+    fn register_chunk_colliders(&mut self, chunk_pos: Vector3<i32>) {
+        self.remove_chunk_colliders(chunk_pos);
+
+        if let Some(chunk) = self.chunk_manager.get_chunk(chunk_pos) {
+            let colliders = &mut self.colliders;
+
+            let handles = chunk.get_colliders().iter()
+                .map(|collider| colliders.insert(Aabb::from_collider(collider), ColliderOwner::Chunk(chunk_pos)))
+                .collect();
+
+            self.chunk_collider_handles.insert(chunk_pos, handles);
+        }
+    }
+
+    /// Advances the world by a single tick: runs due scheduled ticks, then random ticks
+    pub fn tick(&mut self) {
+        let current_tick = self.tick_clock.increment();
+        self.time.advance();
+
+        let due_ticks = self.chunk_manager.take_due_ticks(current_tick);
+
+        let mut context = WorldTickContext {
+            chunk_manager: &mut self.chunk_manager,
+            rng: &mut self.tick_rng,
+            current_tick,
+        };
+
+        for (world_pos, scheduled) in due_ticks {
+            // the block might have been replaced since the tick was scheduled
+            let handler = match context.chunk_manager.get_block(world_pos) {
+                Some(block) if *block.get_identifier() == scheduled.block => block.get_scheduled_tick_handler(),
+                _ => None
+            };
+
+            if let Some(handler) = handler {
+                handler(&mut context, world_pos);
+            }
+        }
+
+        for chunk_pos in context.chunk_manager.get_loaded_chunk_positions() {
+            let is_empty = match context.chunk_manager.get_chunk(chunk_pos) {
+                Some(chunk) => chunk.is_empty(),
+                None => true
+            };
+
+            if is_empty {
+                continue;
+            }
+
+            for _ in 0..tick::RANDOM_TICKS_PER_CHUNK {
+                let world_pos = chunk_pos * chunk::CHUNK_SIZE as i32 + Vector3::new(
+                    context.rng.gen_range(0..chunk::CHUNK_SIZE as i32),
+                    context.rng.gen_range(0..chunk::CHUNK_SIZE as i32),
+                    context.rng.gen_range(0..chunk::CHUNK_SIZE as i32)
+                );
+
+                let handler = context.chunk_manager.get_block(world_pos)
+                    .and_then(|block| block.get_random_tick_handler());
+
+                if let Some(handler) = handler {
+                    handler(&mut context, world_pos);
+                }
+            }
+        }
+    }
+
+    /// Starts or stops remembering which blocks changed, for sending the changes elsewhere
+    pub fn set_track_block_changes(&mut self, track: bool) {
+        self.chunk_manager.set_track_block_changes(track);
+    }
+
+    /// Every block changed (by edits or ticks) since the last call, if tracking is on
+    pub fn take_block_changes(&mut self) -> Vec<Vector3<i32>> {
+        self.chunk_manager.take_block_changes()
+    }
+
+    /// Chunks replaced as a whole (regenerated, ...) since the last call, if tracking is on.
+    /// Their blocks changed without showing up in `take_block_changes`.
+    pub fn take_replaced_chunks(&mut self) -> Vec<Vector3<i32>> {
+        self.chunk_manager.take_replaced_chunks()
+    }
+
+    /// Returns false if the chunk at `world_pos` isn't loaded
+    pub fn set_block(&mut self, world_pos: Vector3<i32>, block: Option<Block>) -> bool {
+        self.chunk_manager.set_block(world_pos, block)
+    }
+
+    /// Puts a loaded chunk back the way the generator made it, in one go.
+    /// Returns false if the chunk isn't loaded.
+    pub fn regenerate_chunk(&mut self, chunk_pos: Vector3<i32>) -> bool {
+        if self.chunk_manager.get_chunk(chunk_pos).is_none() {
+            return false;
+        }
+
+        let mut fresh = Chunk::new(chunk_pos);
+        generator::gen_smooth_terrain(&mut fresh, &generator::gen_height_map(self.seed, chunk_pos));
+
+        self.chunk_manager.replace_chunk(fresh)
+    }
+
+    /// Schedules a tick for the block at `world_pos`, `delay` ticks from now
+    pub fn schedule_tick(&mut self, world_pos: Vector3<i32>, delay: u64) -> bool {
+        let due_tick = self.tick_clock.current_tick() + delay.max(1);
+
+        self.chunk_manager.schedule_tick(world_pos, due_tick)
+    }
+}
+
+// time
+impl World {
+    pub fn get_time(&self) -> &WorldTime { &self.time }
+
+    /// 0 and 1 are midnight, 0.5 is noon
+    pub fn get_time_of_day(&self) -> f32 { self.time.get_time_of_day() }
+
+    pub fn set_time(&mut self, ticks: u64) {
+        self.time.set_ticks(ticks);
+        self.time_changed = true;
+    }
+
+    pub fn set_time_of_day(&mut self, time_of_day: f32) {
+        self.time.set_time_of_day(time_of_day);
+        self.time_changed = true;
+    }
+
+    /// Stops (or restarts) time moving on with the ticks
+    pub fn set_time_frozen(&mut self, frozen: bool) {
+        self.time.set_frozen(frozen);
+        self.time_changed = true;
+    }
+
+    /// Whether the time was set or frozen since the last call
+    pub fn take_time_changed(&mut self) -> bool {
+        std::mem::take(&mut self.time_changed)
+    }
+}
+
+// entities
+impl World {
+    pub fn spawn_entity(&mut self, entity: Entity) -> EntityId {
+        self.entities.spawn(entity, &mut self.colliders)
+    }
+
+    pub fn despawn_entity(&mut self, id: EntityId) -> Option<Entity> {
+        self.entities.despawn(id, &mut self.colliders)
+    }
+
+    pub fn get_entity(&self, id: EntityId) -> Option<&Entity> { self.entities.get(id) }
+
+    pub fn get_entity_mut(&mut self, id: EntityId) -> Option<&mut Entity> { self.entities.get_mut(id) }
+
+    pub fn get_entities(&self) -> &EntityManager { &self.entities }
+
+    /// Needed for entities of this kind to keep their behaviour across saving and loading
+    pub fn register_entity_loader(&mut self, kind: Identifier, loader: EntityLoader) {
+        self.entities.register_loader(kind, loader);
+    }
+}
+
+// pathfinding
+impl World {
+    // room searches get to walk around obstacles between the start and goal
+    const PATH_SEARCH_MARGIN: i32 = 16;
+
+    /// Searches right away. Only loaded chunks are walkable.
+    pub fn find_path(&self, request: &PathRequest) -> PathResult {
+        pathfinding::find_path(&self.capture_nav_region(request), request)
+    }
+
+    /// Copies the blocks around the request and searches on the worker pool
+    pub fn find_path_async(&self, request: PathRequest) -> PendingPath {
+        pathfinding::find_path_async(self.capture_nav_region(&request), request)
+    }
+
+    fn capture_nav_region(&self, request: &PathRequest) -> NavRegion {
+        let (min, max) = request.get_bounds(Self::PATH_SEARCH_MARGIN);
+
+        NavRegion::capture(&self.chunk_manager, &Registry::current(), min, max)
+    }
+}
+
+// persistence
+impl World {
+    // a saved chunk is its block data (if it was ever generated) followed by its entities
+    fn load_or_create_chunk(&mut self, chunk_pos: Vector3<i32>) -> Chunk {
+        let bytes = match self.storage.load_chunk(chunk_pos) {
+            Ok(Some(bytes)) => bytes,
+            Ok(None) => return Chunk::new(chunk_pos),
+            Err(err) => {
+                eprintln!("[LOG] {}, generating it again", err);
+                return Chunk::new(chunk_pos);
+            }
+        };
+
+        let current_tick = self.tick_clock.current_tick();
+        let mut reader = ByteReader::new(&bytes);
+
+        let chunk = match reader.read_bool() {
+            Ok(true) => ChunkData::read_from(&mut reader, current_tick).map(Chunk::from_chunk_data),
+            Ok(false) => Ok(Chunk::new(chunk_pos)),
+            Err(err) => Err(err)
+        };
+
+        let chunk = chunk.and_then(|chunk| {
+            self.entities.load_chunk_entities(&mut reader, &mut self.colliders)?;
+            Ok(chunk)
+        });
+
+        match chunk {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                eprintln!("[LOG] Saved chunk [{},{},{}] is corrupt ({}), generating it again", chunk_pos.x, chunk_pos.y, chunk_pos.z, err);
+                Chunk::new(chunk_pos)
+            }
+        }
+    }
+
+    fn write_chunk(&self, chunk: &Chunk, writer: &mut ByteWriter) {
+        // chunks that never got generated are saved without block data, so they generate when loaded again
+        writer.write_bool(!chunk.is_first_build());
+
+        if !chunk.is_first_build() {
+            chunk.get_chunk_data().write_to(writer, self.tick_clock.current_tick());
+        }
+    }
+
+    /// Saves a chunk along with the entities in it, then drops both
+    pub fn unload_chunk(&mut self, chunk_pos: Vector3<i32>) -> Result<(), Error> {
+        let chunk = match self.chunk_manager.remove_chunk(chunk_pos) {
+            Some(chunk) => chunk,
+            None => return Ok(())
+        };
+
+        self.remove_chunk_colliders(chunk_pos);
+
+        if self.chunk_manager.builds_meshes() {
+            self.unloaded_chunks.push(chunk_pos);
+        }
+
+        let mut writer = ByteWriter::new();
+        self.write_chunk(&chunk, &mut writer);
+        self.entities.unload_chunk_entities(chunk_pos, &mut writer, &mut self.colliders);
+
+        self.storage.save_chunk(chunk_pos, writer.into_bytes())
+    }
+
+    /// Saves every chunk and entity without unloading anything, and the world's time
+    pub fn save_all(&mut self) -> Result<(), Error> {
+        let mut writer = ByteWriter::new();
+        writer.write_u32(LEVEL_VERSION);
+        self.time.write_to(&mut writer);
+
+        self.storage.save_level(writer.into_bytes())?;
+
+        for chunk_pos in self.chunk_manager.get_all_chunk_positions() {
+            let mut writer = ByteWriter::new();
+
+            if let Some(chunk) = self.chunk_manager.get_any_chunk(chunk_pos) {
+                self.write_chunk(chunk, &mut writer);
+            }
+
+            self.entities.write_chunk_entities(chunk_pos, &mut writer);
+            self.storage.save_chunk(chunk_pos, writer.into_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
+// meshes
+impl World {
+    pub fn builds_meshes(&self) -> bool { self.chunk_manager.builds_meshes() }
+
+    /// Headless worlds have nothing to draw, so they only build colliders for their chunks
+    pub fn set_build_meshes(&mut self, build_meshes: bool) {
+        self.chunk_manager.set_build_meshes(build_meshes);
+    }
+
+    /// Chunks whose mesh was (re)built since the last call, for uploading to the GPU
+    pub fn take_remeshed_chunks(&mut self) -> Vec<Vector3<i32>> {
+        std::mem::take(&mut self.remeshed_chunks)
+    }
+
+    /// Chunks unloaded since the last call, whose GPU buffers can be freed
+    pub fn take_unloaded_chunks(&mut self) -> Vec<Vector3<i32>> {
+        std::mem::take(&mut self.unloaded_chunks)
     }
 }
 
@@ -110,6 +569,33 @@ impl World {
         self.chunk_manager.get_renderable_chunks()
     }
 
+    pub fn is_remote(&self) -> bool { self.is_remote }
+
+    pub fn get_seed(&self) -> u32 { self.seed }
+
+    pub fn get_render_distance(&self) -> usize { self.render_distance }
+
+    pub fn get_current_tick(&self) -> u64 { self.tick_clock.current_tick() }
+
+    /// Broadphase holding the collision boxes of every loaded chunk
+    pub fn get_colliders(&self) -> &SpatialHash<ColliderOwner> { &self.colliders }
+
+    pub fn get_colliders_mut(&mut self) -> &mut SpatialHash<ColliderOwner> { &mut self.colliders }
+
+    pub fn get_block(&self, world_pos: Vector3<i32>) -> Option<Block> {
+        self.chunk_manager.get_block(world_pos)
+    }
+
+    /// Whether the block at `world_pos` blocks movement. Unloaded chunks count as empty.
+    pub fn is_solid(&self, world_pos: Vector3<i32>) -> bool {
+        self.chunk_manager.is_solid(world_pos)
+    }
+
+    /// The loaded chunk at `chunk_pos`, in chunk coordinates
+    pub fn get_chunk(&self, chunk_pos: Vector3<i32>) -> Option<&Chunk> {
+        self.chunk_manager.get_chunk(chunk_pos)
+    }
+
     pub fn get_chunk_from_world(&self, world_pos: &Vector3<f32>) -> Option<&Chunk> {
         self.chunk_manager.get_chunk_from_world(world_pos)
     }
@@ -121,4 +607,99 @@ impl World {
     /*pub fn get_chunk_queue_count(&self) -> usize {
         self.chunk_manager.get_chunk_queue_count()
     }*/
-}
\ No newline at end of file
+}
+
+// bumped whenever what's saved in the level data changes
+const LEVEL_VERSION: u32 = 1;
+
+fn load_time(storage: &WorldStorage) -> Result<Option<WorldTime>, Error> {
+    let bytes = match storage.load_level()? {
+        Some(bytes) => bytes,
+        None => return Ok(None)
+    };
+
+    let mut reader = ByteReader::new(&bytes);
+    let version = reader.read_u32()?;
+
+    if version != LEVEL_VERSION {
+        return Err(anyhow!(format!("world data has unknown version {}", version)));
+    }
+
+    Ok(Some(WorldTime::read_from(&mut reader)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Vector3, Zero};
+
+    use common::{identifier::Identifier, registry::Registry};
+
+    use super::World;
+
+    // a headless world with the 8 chunks around the origin built
+    fn loaded_world() -> World {
+        super::blocks::register_blocks().unwrap();
+
+        let mut world = World::new(7, 1);
+        world.set_build_meshes(false);
+        load_origin(&mut world);
+
+        world
+    }
+
+    fn load_origin(world: &mut World) {
+        world.load_chunks_around(&[Vector3::new(1.0, 1.0, 1.0)]);
+
+        // a couple of chunks get built per update
+        for _ in 0..8 {
+            world.update(Vector3::zero(), 0.0);
+        }
+    }
+
+    #[test]
+    fn scheduled_ticks_survive_unloading() {
+        let mut world = loaded_world();
+        let stone = Registry::current().get_block(&Identifier::from_str("willekeurig:stone").unwrap());
+
+        let chunk_pos = Vector3::new(0, 0, 0);
+        let world_pos = Vector3::new(2, 3, 4);
+
+        assert!(world.set_block(world_pos, stone));
+        assert!(world.schedule_tick(world_pos, 40));
+        let due_tick = world.get_current_tick() + 40;
+
+        world.unload_chunk(chunk_pos).unwrap();
+        assert!(world.get_chunk(chunk_pos).is_none());
+
+        // forget the last load so the missing chunk comes back from storage
+        world.set_render_distance(1);
+        load_origin(&mut world);
+
+        let ticks = world.get_chunk(chunk_pos).unwrap().get_chunk_data().get_scheduled_ticks();
+
+        assert_eq!(ticks.len(), 1);
+        assert_eq!(ticks[0].local_pos, Vector3::new(2, 3, 4));
+        assert_eq!(ticks[0].due_tick, due_tick);
+    }
+
+    #[test]
+    fn unloading_removes_chunk_colliders() {
+        let mut world = loaded_world();
+        let stone = Registry::current().get_block(&Identifier::from_str("willekeurig:stone").unwrap());
+
+        // make sure the chunk has something to collide with, then let it rebuild
+        let chunk_pos = Vector3::new(0, 0, 0);
+        assert!(world.set_block(Vector3::new(2, 3, 4), stone));
+        world.update(Vector3::zero(), 0.0);
+
+        let handles = world.chunk_collider_handles[&chunk_pos].clone();
+        let collider_count = world.get_colliders().len();
+        assert!(!handles.is_empty());
+
+        world.unload_chunk(chunk_pos).unwrap();
+
+        assert!(!world.chunk_collider_handles.contains_key(&chunk_pos));
+        assert_eq!(world.get_colliders().len(), collider_count - handles.len());
+        assert!(handles.iter().all(|handle| world.get_colliders().get(*handle).is_none()));
+    }
+}
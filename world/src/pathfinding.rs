@@ -0,0 +1,629 @@
+use std::{cmp::Ordering, collections::{BinaryHeap, HashMap}, sync::mpsc::{self, Receiver}};
+
+use cgmath::Vector3;
+
+use common::{identifier::Identifier, registry::Registry};
+
+use crate::{chunk::{self, ChunkData, CHUNK_SIZE}, chunk_manager::ChunkManager};
+
+// costs are integers so the open list can be a plain BinaryHeap
+const WALK_COST: u32 = 10;
+const STEP_UP_COST: u32 = 5; // per block climbed
+const DROP_COST: u32 = 2; // per block fallen
+
+const DIRECTIONS: [Vector3<i32>; 4] = [
+    Vector3::new(1, 0, 0),
+    Vector3::new(-1, 0, 0),
+    Vector3::new(0, 0, 1),
+    Vector3::new(0, 0, -1),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NavCell {
+    Open,
+    Solid,
+    Fluid,
+}
+
+/// A copy of the blocks around a search. Searches only ever look at one of
+/// these, so they can run on another thread while the world keeps changing.
+/// Everything outside the region counts as solid.
+#[derive(Debug, Clone)]
+pub struct NavRegion {
+    min: Vector3<i32>,
+    size: Vector3<i32>,
+    cells: Vec<NavCell>,
+}
+
+impl NavRegion {
+    /// An empty region covering `min` up to (not including) `min + size`
+    pub fn new(min: Vector3<i32>, size: Vector3<i32>) -> Self {
+        Self {
+            min,
+            size,
+            cells: vec![NavCell::Open; (size.x.max(0) * size.y.max(0) * size.z.max(0)) as usize],
+        }
+    }
+
+    /// Copies the loaded blocks between `min` and `max` (inclusive). Unloaded chunks are solid.
+    pub fn capture(chunk_manager: &ChunkManager, registry: &Registry, min: Vector3<i32>, max: Vector3<i32>) -> Self {
+        let mut region = Self::new(min, max - min + Vector3::new(1, 1, 1));
+        let mut kinds = HashMap::new();
+
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    let world_pos = Vector3::new(x, y, z);
+                    let local = chunk::world_to_local_pos(world_pos);
+
+                    let cell = match chunk_manager.get_chunk(chunk::world_to_chunk_pos(world_pos)) {
+                        Some(chunk) => classify(chunk.get_chunk_data().get_block_id(local.x, local.y, local.z), registry, &mut kinds),
+                        None => NavCell::Solid
+                    };
+
+                    region.set_cell(world_pos, cell);
+                }
+            }
+        }
+
+        region
+    }
+
+    /// A region holding exactly one chunk's blocks
+    pub fn from_chunk_data(chunk_data: &ChunkData, registry: &Registry) -> Self {
+        let origin = chunk_data.get_pos() * CHUNK_SIZE as i32;
+        let mut region = Self::new(origin, Vector3::new(CHUNK_SIZE as i32, CHUNK_SIZE as i32, CHUNK_SIZE as i32));
+        let mut kinds = HashMap::new();
+
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    let cell = classify(chunk_data.get_block_id(x, y, z), registry, &mut kinds);
+
+                    region.set_cell(origin + Vector3::new(x as i32, y as i32, z as i32), cell);
+                }
+            }
+        }
+
+        region
+    }
+
+    pub fn get_min(&self) -> Vector3<i32> { self.min }
+
+    pub fn get_size(&self) -> Vector3<i32> { self.size }
+
+    pub fn get_cell(&self, world_pos: Vector3<i32>) -> NavCell {
+        match self.index(world_pos) {
+            Some(index) => self.cells[index],
+            None => NavCell::Solid
+        }
+    }
+
+    pub fn set_cell(&mut self, world_pos: Vector3<i32>, cell: NavCell) -> bool {
+        match self.index(world_pos) {
+            Some(index) => {
+                self.cells[index] = cell;
+                true
+            },
+            None => false
+        }
+    }
+
+    fn index(&self, world_pos: Vector3<i32>) -> Option<usize> {
+        let local = world_pos - self.min;
+
+        if local.x < 0 || local.y < 0 || local.z < 0 || local.x >= self.size.x || local.y >= self.size.y || local.z >= self.size.z {
+            return None;
+        }
+
+        Some((local.x + local.y * self.size.x + local.z * self.size.x * self.size.y) as usize)
+    }
+}
+
+// the registry lookup clones the block, so remember what each kind of block turned out to be
+fn classify(block_id: Option<&Identifier>, registry: &Registry, kinds: &mut HashMap<Identifier, NavCell>) -> NavCell {
+    let block_id = match block_id {
+        Some(block_id) => block_id,
+        None => return NavCell::Open
+    };
+
+    *kinds.entry(block_id.clone()).or_insert_with(|| {
+        match registry.get_block(block_id) {
+            Some(block) if block.is_fluid() => NavCell::Fluid,
+            Some(_) => NavCell::Solid,
+            // blocks we don't know about still have a mesh, so treat them as solid
+            None => NavCell::Solid
+        }
+    })
+}
+
+/// Where to search from and to. Positions are the block the entity's
+/// collider corner (lowest x, y and z) stands in, like `BoxCollider::position`.
+#[derive(Debug, Clone)]
+pub struct PathRequest {
+    pub start: Vector3<i32>,
+    pub goal: Vector3<i32>,
+
+    /// Collider size, in blocks
+    pub size: Vector3<f32>,
+    /// Highest ledge the entity can climb onto
+    pub step_height: i32,
+    /// Furthest the entity is willing to fall
+    pub max_drop: i32,
+    /// Most nodes expanded before giving up
+    pub max_nodes: usize,
+}
+
+impl PathRequest {
+    pub const DEFAULT_MAX_DROP: i32 = 3;
+    pub const DEFAULT_MAX_NODES: usize = 4096;
+
+    pub fn new(start: Vector3<i32>, goal: Vector3<i32>, size: Vector3<f32>) -> Self {
+        Self {
+            start,
+            goal,
+
+            size,
+            step_height: 1,
+            max_drop: Self::DEFAULT_MAX_DROP,
+            max_nodes: Self::DEFAULT_MAX_NODES,
+        }
+    }
+
+    pub fn with_step_height(mut self, step_height: i32) -> Self {
+        self.step_height = step_height;
+        self
+    }
+
+    pub fn with_max_drop(mut self, max_drop: i32) -> Self {
+        self.max_drop = max_drop;
+        self
+    }
+
+    pub fn with_node_budget(mut self, max_nodes: usize) -> Self {
+        self.max_nodes = max_nodes;
+        self
+    }
+
+    /// The blocks a search could need, with `margin` blocks of room for detours around the start and goal
+    pub fn get_bounds(&self, margin: i32) -> (Vector3<i32>, Vector3<i32>) {
+        let footprint = footprint(self.size);
+
+        let min = Vector3::new(
+            self.start.x.min(self.goal.x) - margin,
+            self.start.y.min(self.goal.y) - margin - self.max_drop - 1,
+            self.start.z.min(self.goal.z) - margin,
+        );
+
+        let max = Vector3::new(
+            self.start.x.max(self.goal.x) + margin + footprint.x,
+            self.start.y.max(self.goal.y) + margin + footprint.y + self.step_height,
+            self.start.z.max(self.goal.z) + margin + footprint.z,
+        );
+
+        (min, max)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathResult {
+    /// Every block from the start to the goal, both included
+    Found(Vec<Vector3<i32>>),
+    /// The node budget ran out. Holds the way to the node that got closest to the goal.
+    Partial(Vec<Vector3<i32>>),
+    NotFound,
+}
+
+impl PathResult {
+    pub fn get_path(&self) -> Option<&[Vector3<i32>]> {
+        match self {
+            PathResult::Found(path) | PathResult::Partial(path) => Some(path),
+            PathResult::NotFound => None
+        }
+    }
+}
+
+#[derive(PartialEq, Eq)]
+struct OpenNode {
+    estimate: u32,
+    cost: u32,
+    pos: Vector3<i32>,
+}
+
+impl Ord for OpenNode {
+    // reversed, so the heap pops the cheapest estimate first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.cmp(&self.estimate)
+            .then_with(|| self.cost.cmp(&other.cost))
+    }
+}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A* over the blocks an entity of the requested size can stand in
+pub fn find_path(region: &NavRegion, request: &PathRequest) -> PathResult {
+    let walker = Walker { region, footprint: footprint(request.size), request };
+
+    if !walker.can_stand(request.start) {
+        return PathResult::NotFound;
+    }
+
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<Vector3<i32>, Vector3<i32>> = HashMap::new();
+    let mut costs: HashMap<Vector3<i32>, u32> = HashMap::new();
+
+    let mut closest = (heuristic(request.start, request.goal), request.start);
+    let mut expanded = 0;
+
+    costs.insert(request.start, 0);
+    open.push(OpenNode { estimate: closest.0, cost: 0, pos: request.start });
+
+    while let Some(node) = open.pop() {
+        if node.pos == request.goal {
+            return PathResult::Found(rebuild_path(&came_from, node.pos));
+        }
+
+        // a cheaper way here was found after this one was queued
+        if node.cost > costs[&node.pos] {
+            continue;
+        }
+
+        if expanded >= request.max_nodes {
+            return PathResult::Partial(rebuild_path(&came_from, closest.1));
+        }
+
+        expanded += 1;
+
+        for (next, move_cost) in walker.neighbours(node.pos) {
+            let cost = node.cost + move_cost;
+
+            if costs.get(&next).is_some_and(|old_cost| *old_cost <= cost) {
+                continue;
+            }
+
+            let remaining = heuristic(next, request.goal);
+
+            if remaining < closest.0 {
+                closest = (remaining, next);
+            }
+
+            costs.insert(next, cost);
+            came_from.insert(next, node.pos);
+            open.push(OpenNode { estimate: cost + remaining, cost, pos: next });
+        }
+    }
+
+    PathResult::NotFound
+}
+
+/// A search running on the worker pool
+pub struct PendingPath {
+    receiver: Receiver<PathResult>,
+}
+
+impl PendingPath {
+    /// Returns None until the search is done
+    pub fn poll(&self) -> Option<PathResult> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Blocks until the search is done
+    pub fn wait(self) -> PathResult {
+        self.receiver.recv().unwrap_or(PathResult::NotFound)
+    }
+}
+
+pub fn find_path_async(region: NavRegion, request: PathRequest) -> PendingPath {
+    let (sender, receiver) = mpsc::channel();
+
+    rayon::spawn(move || {
+        // nobody is listening anymore if this fails, so there's nothing to do about it
+        let _ = sender.send(find_path(&region, &request));
+    });
+
+    PendingPath { receiver }
+}
+
+struct Walker<'a> {
+    region: &'a NavRegion,
+    footprint: Vector3<i32>,
+    request: &'a PathRequest,
+}
+
+impl<'a> Walker<'a> {
+    // whether every block the collider would take up at `pos` is air
+    fn is_clear(&self, pos: Vector3<i32>) -> bool {
+        for x in 0..self.footprint.x {
+            for y in 0..self.footprint.y {
+                for z in 0..self.footprint.z {
+                    if self.region.get_cell(pos + Vector3::new(x, y, z)) != NavCell::Open {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    // whether anything solid is right under the collider at `pos`
+    fn has_ground(&self, pos: Vector3<i32>) -> bool {
+        for x in 0..self.footprint.x {
+            for z in 0..self.footprint.z {
+                if self.region.get_cell(pos + Vector3::new(x, -1, z)) == NavCell::Solid {
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    fn can_stand(&self, pos: Vector3<i32>) -> bool {
+        self.is_clear(pos) && self.has_ground(pos)
+    }
+
+    fn neighbours(&self, pos: Vector3<i32>) -> Vec<(Vector3<i32>, u32)> {
+        let mut neighbours = Vec::new();
+
+        for direction in DIRECTIONS.iter() {
+            let next = pos + *direction;
+
+            if self.is_clear(next) {
+                if self.has_ground(next) {
+                    neighbours.push((next, WALK_COST));
+                } else if let Some(landing) = self.drop_from(next) {
+                    neighbours.push((landing, WALK_COST + DROP_COST * (next.y - landing.y) as u32));
+                }
+            } else if let Some(ledge) = self.step_up_to(pos, next) {
+                neighbours.push((ledge, WALK_COST + STEP_UP_COST * (ledge.y - pos.y) as u32));
+            }
+        }
+
+        neighbours
+    }
+
+    // where the entity lands walking off a ledge into `pos`, if the fall isn't too deep
+    fn drop_from(&self, pos: Vector3<i32>) -> Option<Vector3<i32>> {
+        for depth in 1..=self.request.max_drop {
+            let below = pos - Vector3::new(0, depth, 0);
+
+            // falling through fluids counts as walking into them
+            if !self.is_clear(below) {
+                return None;
+            }
+
+            if self.has_ground(below) {
+                return Some(below);
+            }
+        }
+
+        None
+    }
+
+    // the lowest spot above `next` the entity can climb onto from `pos`
+    fn step_up_to(&self, pos: Vector3<i32>, next: Vector3<i32>) -> Option<Vector3<i32>> {
+        for height in 1..=self.request.step_height {
+            let up = Vector3::new(0, height, 0);
+
+            // there has to be room to go up before going over
+            if !self.is_clear(pos + up) {
+                return None;
+            }
+
+            if self.can_stand(next + up) {
+                return Some(next + up);
+            }
+        }
+
+        None
+    }
+}
+
+// blocks the collider covers on each axis, counting from the block its corner is in
+fn footprint(size: Vector3<f32>) -> Vector3<i32> {
+    Vector3::new(
+        (size.x.ceil() as i32).max(1),
+        (size.y.ceil() as i32).max(1),
+        (size.z.ceil() as i32).max(1),
+    )
+}
+
+// every move goes one block sideways and costs at least WALK_COST, so this never overestimates
+fn heuristic(from: Vector3<i32>, to: Vector3<i32>) -> u32 {
+    ((from.x - to.x).abs() + (from.z - to.z).abs()) as u32 * WALK_COST
+}
+
+fn rebuild_path(came_from: &HashMap<Vector3<i32>, Vector3<i32>>, end: Vector3<i32>) -> Vec<Vector3<i32>> {
+    let mut path = vec![end];
+    let mut current = end;
+
+    while let Some(previous) = came_from.get(&current) {
+        path.push(*previous);
+        current = *previous;
+    }
+
+    path.reverse();
+
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use common::{block::Block, identifier::Identifier, registry::Registry};
+
+    use crate::chunk::ChunkData;
+    use super::{find_path, find_path_async, NavCell, NavRegion, PathRequest, PathResult};
+
+    const MOB_SIZE: Vector3<f32> = Vector3::new(0.6, 1.8, 0.6);
+
+    fn registry() -> Registry {
+        let mut water = Block::new(Identifier::from_str("willekeurig:water").unwrap(), 0.0, 0.0);
+        water.set_fluid(true);
+
+        let mut registry = Registry::new();
+        registry.register_block(Block::new(Identifier::from_str("willekeurig:stone").unwrap(), 0.0, 32.0)).unwrap();
+        registry.register_block(water).unwrap();
+
+        registry
+    }
+
+    // a stone floor at y = 0 with `rows` drawn on top of it, row z = 0 first.
+    // '#' is a two block high wall, '1' a single block, '~' water in the floor, ' ' a hole in it
+    fn maze(rows: &[&str]) -> NavRegion {
+        let registry = registry();
+        let stone = registry.get_block(&Identifier::from_str("willekeurig:stone").unwrap());
+        let water = registry.get_block(&Identifier::from_str("willekeurig:water").unwrap());
+
+        let mut chunk_data = ChunkData::new(Vector3::new(0, 0, 0));
+
+        for x in 0..16 {
+            for z in 0..16 {
+                chunk_data.add_block(x, 0, z, stone.clone());
+            }
+        }
+
+        for (z, row) in rows.iter().enumerate() {
+            for (x, cell) in row.chars().enumerate() {
+                match cell {
+                    '#' => {
+                        chunk_data.add_block(x, 1, z, stone.clone());
+                        chunk_data.add_block(x, 2, z, stone.clone());
+                    },
+                    '1' => { chunk_data.add_block(x, 1, z, stone.clone()); },
+                    '~' => { chunk_data.add_block(x, 0, z, water.clone()); },
+                    ' ' => { chunk_data.remove_block(x, 0, z); },
+                    _ => {}
+                }
+            }
+        }
+
+        NavRegion::from_chunk_data(&chunk_data, &registry)
+    }
+
+    fn found(result: PathResult) -> Vec<Vector3<i32>> {
+        match result {
+            PathResult::Found(path) => path,
+            other => panic!("expected a path, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn walks_around_walls() {
+        let region = maze(&[
+            "....#...",
+            "....#...",
+            "....#...",
+            "........",
+        ]);
+
+        let path = found(find_path(&region, &PathRequest::new(Vector3::new(0, 1, 0), Vector3::new(7, 1, 0), MOB_SIZE)));
+
+        assert_eq!(path.first(), Some(&Vector3::new(0, 1, 0)));
+        assert_eq!(path.last(), Some(&Vector3::new(7, 1, 0)));
+        assert!(path.iter().all(|pos| pos.x != 4 || pos.z == 3), "went through the wall: {:?}", path);
+        // 7 blocks over plus 3 down and back up around the wall
+        assert_eq!(path.len(), 14);
+    }
+
+    #[test]
+    fn steps_up_single_blocks_only() {
+        let region = maze(&[
+            "..1..",
+            "#####",
+        ]);
+
+        let request = PathRequest::new(Vector3::new(0, 1, 0), Vector3::new(4, 1, 0), MOB_SIZE);
+        let path = found(find_path(&region, &request));
+
+        assert!(path.contains(&Vector3::new(2, 2, 0)), "should climb over the block: {:?}", path);
+
+        assert_eq!(find_path(&region, &request.with_step_height(0)), PathResult::NotFound);
+    }
+
+    #[test]
+    fn respects_max_drop() {
+        let mut region = maze(&["....."]);
+
+        // a 3 block high platform to walk off of
+        for y in 1..4 {
+            region.set_cell(Vector3::new(0, y, 0), NavCell::Solid);
+        }
+
+        let request = PathRequest::new(Vector3::new(0, 4, 0), Vector3::new(4, 1, 0), MOB_SIZE);
+
+        let path = found(find_path(&region, &request.clone()));
+        assert_eq!(path[1], Vector3::new(1, 1, 0));
+
+        assert_eq!(find_path(&region, &request.with_max_drop(2)), PathResult::NotFound);
+    }
+
+    #[test]
+    fn avoids_fluids() {
+        let region = maze(&[
+            "..~..",
+            "..~..",
+            ".....",
+        ]);
+
+        let path = found(find_path(&region, &PathRequest::new(Vector3::new(0, 1, 0), Vector3::new(4, 1, 0), MOB_SIZE)));
+
+        assert!(path.contains(&Vector3::new(2, 1, 2)), "should walk around the water: {:?}", path);
+    }
+
+    #[test]
+    fn wide_colliders_need_wide_gaps() {
+        let mut region = maze(&[
+            "..#..",
+            ".....",
+            "..#..",
+            "..#..",
+            ".....",
+        ]);
+
+        // close off the rest of the wall, so the gaps are the only ways through
+        for z in 5..16 {
+            region.set_cell(Vector3::new(2, 1, z), NavCell::Solid);
+            region.set_cell(Vector3::new(2, 2, z), NavCell::Solid);
+        }
+
+        let start = Vector3::new(0, 1, 2);
+        let goal = Vector3::new(4, 1, 2);
+
+        // a block wide mob fits through the gaps, a mob two blocks wide doesn't
+        found(find_path(&region, &PathRequest::new(start, goal, MOB_SIZE)));
+
+        let wide = PathRequest::new(start, goal, Vector3::new(1.8, 1.8, 1.8)).with_node_budget(10_000);
+        assert_eq!(find_path(&region, &wide), PathResult::NotFound);
+    }
+
+    #[test]
+    fn node_budget_gives_partial_path() {
+        let region = maze(&["..............."]);
+        let request = PathRequest::new(Vector3::new(0, 1, 0), Vector3::new(14, 1, 0), MOB_SIZE).with_node_budget(4);
+
+        match find_path(&region, &request) {
+            PathResult::Partial(path) => {
+                assert_eq!(path[0], Vector3::new(0, 1, 0));
+                assert!(path.len() > 1 && path.len() < 15);
+            },
+            other => panic!("expected a partial path, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn runs_on_worker_pool() {
+        let region = maze(&["....#", "....."]);
+        let request = PathRequest::new(Vector3::new(0, 1, 0), Vector3::new(4, 1, 1), MOB_SIZE);
+
+        let expected = find_path(&region, &request);
+
+        assert_eq!(find_path_async(region, request).wait(), expected);
+    }
+}
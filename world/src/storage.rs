@@ -0,0 +1,116 @@
+use std::{collections::HashMap, fs, path::{Path, PathBuf}};
+
+use anyhow::{Result, Error, anyhow};
+use cgmath::Vector3;
+
+/// Where unloaded chunks and the world's own data (like its time) go. Without a directory
+/// everything is kept in memory, so chunks survive unloading but not restarting.
+pub struct WorldStorage {
+    directory: Option<PathBuf>,
+    chunks: HashMap<Vector3<i32>, Vec<u8>>,
+    level: Option<Vec<u8>>,
+}
+
+impl WorldStorage {
+    pub fn in_memory() -> Self {
+        Self {
+            directory: None,
+            chunks: HashMap::new(),
+            level: None,
+        }
+    }
+
+    pub fn open(directory: &Path) -> Result<Self, Error> {
+        match fs::create_dir_all(directory.join("chunks")) {
+            Ok(_) => Ok(Self {
+                directory: Some(directory.to_path_buf()),
+                chunks: HashMap::new(),
+                level: None,
+            }),
+            Err(err) => Err(anyhow!(format!("couldn't create world directory '{}': {}", directory.display(), err)))
+        }
+    }
+
+    pub fn get_directory(&self) -> Option<&Path> { self.directory.as_deref() }
+
+    pub fn save_chunk(&mut self, chunk_pos: Vector3<i32>, bytes: Vec<u8>) -> Result<(), Error> {
+        match &self.directory {
+            Some(directory) => {
+                let path = chunk_path(directory, chunk_pos);
+
+                match fs::write(&path, bytes) {
+                    Ok(_) => Ok(()),
+                    Err(err) => Err(anyhow!(format!("couldn't save chunk to '{}': {}", path.display(), err)))
+                }
+            },
+            None => {
+                self.chunks.insert(chunk_pos, bytes);
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns None if the chunk was never saved
+    pub fn load_chunk(&self, chunk_pos: Vector3<i32>) -> Result<Option<Vec<u8>>, Error> {
+        match &self.directory {
+            Some(directory) => {
+                let path = chunk_path(directory, chunk_pos);
+
+                if !path.exists() {
+                    return Ok(None);
+                }
+
+                match fs::read(&path) {
+                    Ok(bytes) => Ok(Some(bytes)),
+                    Err(err) => Err(anyhow!(format!("couldn't load chunk from '{}': {}", path.display(), err)))
+                }
+            },
+            None => Ok(self.chunks.get(&chunk_pos).cloned())
+        }
+    }
+}
+
+// level data
+impl WorldStorage {
+    pub fn save_level(&mut self, bytes: Vec<u8>) -> Result<(), Error> {
+        match &self.directory {
+            Some(directory) => {
+                let path = directory.join(LEVEL_FILE);
+
+                match fs::write(&path, bytes) {
+                    Ok(_) => Ok(()),
+                    Err(err) => Err(anyhow!(format!("couldn't save world data to '{}': {}", path.display(), err)))
+                }
+            },
+            None => {
+                self.level = Some(bytes);
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns None for a world that was never saved
+    pub fn load_level(&self) -> Result<Option<Vec<u8>>, Error> {
+        match &self.directory {
+            Some(directory) => {
+                let path = directory.join(LEVEL_FILE);
+
+                if !path.exists() {
+                    return Ok(None);
+                }
+
+                match fs::read(&path) {
+                    Ok(bytes) => Ok(Some(bytes)),
+                    Err(err) => Err(anyhow!(format!("couldn't load world data from '{}': {}", path.display(), err)))
+                }
+            },
+            None => Ok(self.level.clone())
+        }
+    }
+}
+
+const LEVEL_FILE: &str = "level.dat";
+
+fn chunk_path(directory: &Path, chunk_pos: Vector3<i32>) -> PathBuf {
+    directory.join("chunks").join(format!("{}_{}_{}.chunk", chunk_pos.x, chunk_pos.y, chunk_pos.z))
+}
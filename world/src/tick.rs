@@ -0,0 +1,127 @@
+use cgmath::Vector3;
+use rand::{Rng, rngs::StdRng};
+
+use common::{block::Block, identifier::Identifier, tick::BlockTickContext, timestep::FixedTimestep};
+
+use crate::chunk_manager::ChunkManager;
+
+pub const TICKS_PER_SECOND: u32 = 20;
+pub const TICK_DELTA: f32 = 1.0 / TICKS_PER_SECOND as f32;
+
+/// How many blocks get picked for a random tick in each loaded chunk, every tick
+pub const RANDOM_TICKS_PER_CHUNK: usize = 3;
+
+// if we fall further behind than this (stutter, breakpoint, ...) the missing
+// ticks are dropped instead of trying to catch up all at once
+const MAX_TICKS_PER_UPDATE: u32 = 10;
+
+/// Turns frame delta times into a steady number of world ticks
+#[derive(Debug, Clone)]
+pub struct TickClock {
+    timestep: FixedTimestep,
+    current_tick: u64,
+}
+
+impl TickClock {
+    pub fn new(current_tick: u64) -> Self {
+        Self {
+            timestep: FixedTimestep::new(TICK_DELTA, MAX_TICKS_PER_UPDATE),
+            current_tick,
+        }
+    }
+
+    pub fn current_tick(&self) -> u64 { self.current_tick }
+
+    /// Adds `delta_time` seconds and returns how many ticks are due
+    pub fn advance(&mut self, delta_time: f32) -> u32 {
+        let ticks = self.timestep.advance(delta_time);
+        let dropped = self.timestep.get_dropped_steps();
+
+        if dropped > 0 {
+            eprintln!("[LOG] World tick is running behind, skipping {} ticks", dropped);
+        }
+
+        ticks
+    }
+
+    pub fn increment(&mut self) -> u64 {
+        self.current_tick += 1;
+        self.current_tick
+    }
+}
+
+/// A pending tick for the block at `local_pos`, stored with the chunk it's in
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledTick {
+    pub local_pos: Vector3<usize>,
+    pub block: Identifier,
+    pub due_tick: u64,
+}
+
+/// Block tick context backed by the loaded chunks of a world
+pub struct WorldTickContext<'a> {
+    pub(crate) chunk_manager: &'a mut ChunkManager,
+    pub(crate) rng: &'a mut StdRng,
+    pub(crate) current_tick: u64,
+}
+
+impl<'a> BlockTickContext for WorldTickContext<'a> {
+    fn current_tick(&self) -> u64 { self.current_tick }
+
+    fn get_block(&self, world_pos: Vector3<i32>) -> Option<Block> {
+        self.chunk_manager.get_block(world_pos)
+    }
+
+    fn set_block(&mut self, world_pos: Vector3<i32>, block: Option<Block>) -> bool {
+        self.chunk_manager.set_block(world_pos, block)
+    }
+
+    fn schedule_tick(&mut self, world_pos: Vector3<i32>, delay: u64) {
+        let due_tick = self.current_tick + delay.max(1);
+
+        self.chunk_manager.schedule_tick(world_pos, due_tick);
+    }
+
+    fn random(&mut self) -> f32 {
+        self.rng.gen::<f32>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+    use common::{block::Block, identifier::Identifier};
+
+    use crate::chunk::ChunkData;
+    use super::{TickClock, TICK_DELTA};
+
+    #[test]
+    fn clock_runs_at_fixed_rate() {
+        let mut clock = TickClock::new(0);
+
+        assert_eq!(clock.advance(TICK_DELTA * 0.5), 0);
+        assert_eq!(clock.advance(TICK_DELTA * 0.6), 1);
+        assert_eq!(clock.advance(TICK_DELTA * 3.0), 3);
+    }
+
+    #[test]
+    fn scheduled_ticks_persist_with_chunk() {
+        let stone = Block::new(Identifier::from_str("willekeurig:stone").unwrap(), 0.0, 32.0);
+
+        let mut chunk_data = ChunkData::new(Vector3::new(1, -2, 3));
+        chunk_data.add_block(4, 5, 6, Some(stone));
+        assert!(chunk_data.schedule_tick(4, 5, 6, 110));
+        assert!(!chunk_data.schedule_tick(0, 0, 0, 110), "can't schedule ticks for air");
+
+        // saved at tick 100, loaded again at tick 500: the remaining delay of 10 is kept
+        let bytes = chunk_data.serialize(100);
+        let mut loaded = ChunkData::deserialize(&bytes, 500).unwrap();
+
+        assert_eq!(loaded.get_blocks(), chunk_data.get_blocks());
+        assert!(loaded.take_due_ticks(509).is_empty());
+
+        let due = loaded.take_due_ticks(510);
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].local_pos, Vector3::new(4, 5, 6));
+    }
+}
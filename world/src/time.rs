@@ -0,0 +1,111 @@
+use anyhow::{Result, Error};
+
+use common::serialization::{ByteReader, ByteWriter};
+
+use crate::tick::TICKS_PER_SECOND;
+
+/// A whole day and night, 20 minutes at the normal tick rate
+pub const TICKS_PER_DAY: u64 = TICKS_PER_SECOND as u64 * 60 * 20;
+
+/// Worlds start in the morning rather than the dead of night
+pub const START_TIME: u64 = TICKS_PER_DAY * 3 / 10;
+
+/// Time in a world, counted in ticks since it was created.
+/// Unlike the tick clock it's saved with the world, and it can be frozen or set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldTime {
+    ticks: u64,
+    frozen: bool,
+}
+
+impl WorldTime {
+    pub fn new(ticks: u64) -> Self {
+        Self {
+            ticks,
+            frozen: false,
+        }
+    }
+
+    /// Moves time on by one tick, unless it's frozen
+    pub fn advance(&mut self) {
+        if !self.frozen {
+            self.ticks += 1;
+        }
+    }
+
+    pub fn set_ticks(&mut self, ticks: u64) {
+        self.ticks = ticks;
+    }
+
+    /// Keeps the day count, only moving to `time_of_day` (0 to 1) in the current day
+    pub fn set_time_of_day(&mut self, time_of_day: f32) {
+        let ticks_into_day = (time_of_day.rem_euclid(1.0) * TICKS_PER_DAY as f32) as u64;
+
+        self.ticks = self.get_day() * TICKS_PER_DAY + ticks_into_day.min(TICKS_PER_DAY - 1);
+    }
+
+    pub fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+
+    pub fn write_to(&self, writer: &mut ByteWriter) {
+        writer.write_u64(self.ticks);
+        writer.write_bool(self.frozen);
+    }
+
+    pub fn read_from(reader: &mut ByteReader) -> Result<Self, Error> {
+        Ok(Self {
+            ticks: reader.read_u64()?,
+            frozen: reader.read_bool()?,
+        })
+    }
+}
+
+impl Default for WorldTime {
+    fn default() -> Self { Self::new(START_TIME) }
+}
+
+// getters
+impl WorldTime {
+    pub fn get_ticks(&self) -> u64 { self.ticks }
+
+    pub fn is_frozen(&self) -> bool { self.frozen }
+
+    /// How many whole days have passed
+    pub fn get_day(&self) -> u64 { self.ticks / TICKS_PER_DAY }
+
+    /// 0 and 1 are midnight, 0.25 sunrise, 0.5 noon and 0.75 sunset
+    pub fn get_time_of_day(&self) -> f32 {
+        (self.ticks % TICKS_PER_DAY) as f32 / TICKS_PER_DAY as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::serialization::{ByteReader, ByteWriter};
+
+    use super::{TICKS_PER_DAY, WorldTime};
+
+    #[test]
+    fn frozen_time_stands_still() {
+        let mut time = WorldTime::new(TICKS_PER_DAY * 2);
+        time.set_time_of_day(0.5);
+
+        assert_eq!(time.get_day(), 2);
+        assert!((time.get_time_of_day() - 0.5).abs() < 0.001);
+
+        time.set_frozen(true);
+        time.advance();
+        assert_eq!(time.get_ticks(), TICKS_PER_DAY * 2 + TICKS_PER_DAY / 2);
+
+        time.set_frozen(false);
+        time.advance();
+        assert_eq!(time.get_ticks(), TICKS_PER_DAY * 2 + TICKS_PER_DAY / 2 + 1);
+
+        let mut writer = ByteWriter::new();
+        time.write_to(&mut writer);
+
+        let bytes = writer.into_bytes();
+        assert_eq!(WorldTime::read_from(&mut ByteReader::new(&bytes)).unwrap(), time);
+    }
+}